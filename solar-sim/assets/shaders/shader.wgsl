@@ -0,0 +1,236 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+}
+@group(1) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LightUniform {
+    position: vec3<f32>,
+    ambient: f32,
+    color: vec3<f32>,
+    emission: f32,
+}
+@group(2) @binding(0)
+var<uniform> light: LightUniform;
+
+struct ShadowUniform {
+    // One per face of the cube around the light: +x, -x, +y, -y, +z, -z
+    faces: array<mat4x4<f32>, 6>,
+}
+@group(2) @binding(1)
+var<uniform> shadow_map: ShadowUniform;
+@group(2) @binding(2)
+var t_shadow: texture_depth_2d_array;
+@group(2) @binding(3)
+var s_shadow: sampler_comparison;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+    @location(1) tex_coords: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+}
+struct InstanceInput {
+    @location(5) model_matrix_0: vec4<f32>,
+    @location(6) model_matrix_1: vec4<f32>,
+    @location(7) model_matrix_2: vec4<f32>,
+    @location(8) model_matrix_3: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    // w is the handedness of the tangent space
+    @location(3) world_tangent: vec4<f32>,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    let model_matrix = mat4x4<f32>(
+        instance.model_matrix_0,
+        instance.model_matrix_1,
+        instance.model_matrix_2,
+        instance.model_matrix_3,
+    );
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.tex_coords = model.tex_coords;
+    out.world_position = world_position.xyz;
+    // The model matrix only scales uniformly, so it can transform the normal as well
+    out.world_normal = (model_matrix * vec4<f32>(model.normal, 0.0)).xyz;
+    out.world_tangent = vec4<f32>((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
+    out.clip_position = camera.view_proj * world_position;
+    return out;
+}
+
+// Fragment shader
+
+@group(0) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(0)@binding(1)
+var s_diffuse: sampler;
+@group(0) @binding(2)
+var t_emissive: texture_2d<f32>;
+@group(0) @binding(3)
+var t_normal: texture_2d<f32>;
+
+struct ReflectanceUniform {
+    roughness: f32,
+    metalness: f32,
+}
+@group(0) @binding(4)
+var<uniform> reflectance: ReflectanceUniform;
+
+const PI: f32 = 3.1415927;
+
+// Width and height of the shadow map's layers
+const SHADOW_SIZE: f32 = 2048.0;
+
+// Fraction of the light reaching a point, averaged over 3x3 texels
+fn shadow(world_position: vec3<f32>, normal: vec3<f32>) -> f32 {
+    let from_light = world_position - light.position;
+    let distance = abs(from_light);
+    var face: i32;
+    if distance.x >= distance.y && distance.x >= distance.z {
+        face = select(1, 0, from_light.x > 0.0);
+    } else if distance.y >= distance.z {
+        face = select(3, 2, from_light.y > 0.0);
+    } else {
+        face = select(5, 4, from_light.z > 0.0);
+    }
+
+    // Move the point off the surface by about a texel to avoid shadow acne
+    let texel = 2.0 * max(distance.x, max(distance.y, distance.z)) / SHADOW_SIZE;
+    let clip = shadow_map.faces[face] * vec4<f32>(world_position + normal * texel, 1.0);
+    let ndc = clip.xyz / clip.w;
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+
+    var lit = 0.0;
+    for (var x = -1; x <= 1; x++) {
+        for (var y = -1; y <= 1; y++) {
+            let offset = vec2<f32>(f32(x), f32(y)) / SHADOW_SIZE;
+            lit += textureSampleCompareLevel(t_shadow, s_shadow, uv + offset, face, ndc.z);
+        }
+    }
+    return lit / 9.0;
+}
+
+// Geometric normal perturbed by the normal map
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+    let normal = normalize(in.world_normal);
+    // Interpolation skews the tangent, so make it orthogonal again
+    let tangent = normalize(in.world_tangent.xyz - normal * dot(normal, in.world_tangent.xyz));
+    let bitangent = cross(normal, tangent) * in.world_tangent.w;
+    let mapped = textureSample(t_normal, s_diffuse, in.tex_coords).xyz * 2.0 - 1.0;
+    return normalize(tangent * mapped.x + bitangent * mapped.y + normal * mapped.z);
+}
+
+// Fraction of the light reaching a point on the surface (Lambert)
+//
+// The shadow is looked up using the geometric normal, since the shadow map only knows the geometry.
+fn diffuse(in: VertexOutput, normal: vec3<f32>) -> f32 {
+    let to_light = normalize(light.position - in.world_position);
+    return max(dot(normal, to_light), 0.0) * shadow(in.world_position, normalize(in.world_normal));
+}
+
+fn lighting(lit: f32) -> vec3<f32> {
+    return vec3<f32>(light.ambient) + lit * light.color;
+}
+
+// Light reflected towards the camera relative to the light reaching the surface (Cook-Torrance)
+//
+// Scaled by pi, so a white matte surface reflects as much as with plain Lambert.
+fn brdf(albedo: vec3<f32>, normal: vec3<f32>, to_light: vec3<f32>, to_camera: vec3<f32>) -> vec3<f32> {
+    let halfway = normalize(to_light + to_camera);
+    let n_dot_l = max(dot(normal, to_light), 0.0);
+    let n_dot_v = max(dot(normal, to_camera), 0.0);
+    let n_dot_h = max(dot(normal, halfway), 0.0);
+    let v_dot_h = max(dot(to_camera, halfway), 0.0);
+
+    // Fresnel (Schlick), dielectrics reflect 4% head on
+    let f0 = mix(vec3<f32>(0.04), albedo, reflectance.metalness);
+    let fresnel = f0 + (1.0 - f0) * pow(1.0 - v_dot_h, 5.0);
+
+    // Normal distribution (GGX)
+    let alpha = max(reflectance.roughness * reflectance.roughness, 0.002);
+    let alpha2 = alpha * alpha;
+    let denominator = n_dot_h * n_dot_h * (alpha2 - 1.0) + 1.0;
+    let distribution = alpha2 / (PI * denominator * denominator);
+
+    // Geometry (Smith with Schlick-GGX)
+    let k = (reflectance.roughness + 1.0) * (reflectance.roughness + 1.0) / 8.0;
+    let geometry = n_dot_l / (n_dot_l * (1.0 - k) + k) * n_dot_v / (n_dot_v * (1.0 - k) + k);
+
+    let specular = distribution * fresnel * geometry / max(4.0 * n_dot_l * n_dot_v, 0.0001);
+    let refracted = (1.0 - fresnel) * (1.0 - reflectance.metalness);
+    return refracted * albedo + specular * PI;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let normal = surface_normal(in);
+    let lit = diffuse(in, normal);
+
+    let to_light = normalize(light.position - in.world_position);
+    let to_camera = normalize(camera.position.xyz - in.world_position);
+    let reflected = brdf(color.rgb, normal, to_light, to_camera) * lit * light.color;
+    let ambient = color.rgb * light.ambient;
+
+    // Emission only shows where there is no light to outshine it
+    let emission = textureSample(t_emissive, s_diffuse, in.tex_coords).rgb;
+    let night = 1.0 - smoothstep(0.0, 0.15, lit);
+
+    return vec4<f32>(ambient + reflected + emission * night, color.a);
+}
+
+// Bodies glowing on their own, e.g. the light source which would otherwise be shaded by itself
+@fragment
+fn fs_emissive(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    return vec4<f32>(color.rgb * light.emission, color.a);
+}
+
+// Clouds are white, their texture's brightness is their opacity
+@fragment
+fn fs_clouds(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_diffuse, s_diffuse, in.tex_coords).r;
+    return vec4<f32>(lighting(diffuse(in, normalize(in.world_normal))), coverage);
+}
+
+// Debug views, see render/debug.rs
+
+@fragment
+fn fs_wireframe(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.2, 1.0, 0.4, 1.0);
+}
+
+@fragment
+fn fs_normals(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(surface_normal(in) * 0.5 + 0.5, 1.0);
+}
+
+// Distance in render space at which the depth view turns black
+const DEPTH_RANGE: f32 = 1000.0;
+
+@fragment
+fn fs_depth(in: VertexOutput) -> @location(0) vec4<f32> {
+    let distance = length(camera.position.xyz - in.world_position);
+    let brightness = 1.0 - log2(1.0 + distance) / log2(1.0 + DEPTH_RANGE);
+    return vec4<f32>(vec3<f32>(max(brightness, 0.0)), 1.0);
+}
+
+// Blended additively, so each layer drawn over a pixel heats it up
+@fragment
+fn fs_overdraw(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(0.1, 0.03, 0.01, 1.0);
+}
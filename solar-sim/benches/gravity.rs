@@ -0,0 +1,59 @@
+//! Compare the gravity system on a single thread to spreading it over every available one
+//!
+//! Run with `cargo bench --bench gravity`
+
+use std::time::{Duration, Instant};
+
+use cgmath::{Point3, Vector3, Zero};
+use specs::{Builder, RunNow, World, WorldExt};
+
+use solar_sim::physics::{Acceleration, Gravity, GravityThreads, Mass, Position};
+
+/// Runs of the system to average over
+const RUNS: u32 = 10;
+
+/// Average time the gravity of `bodies` bodies takes on `threads` threads
+fn measure(bodies: u32, threads: usize) -> Duration {
+    let mut world = World::new();
+    let mut gravity = Gravity;
+    RunNow::setup(&mut gravity, &mut world);
+    world.insert(GravityThreads(threads));
+
+    // Scatter the bodies over a cube, the same way every time
+    let mut seed = 0x2545_f491_u32;
+    let mut random = move || {
+        seed ^= seed << 13;
+        seed ^= seed >> 17;
+        seed ^= seed << 5;
+        seed as f32 / u32::MAX as f32
+    };
+    for _ in 0..bodies {
+        let position = Point3::new(random(), random(), random()).cast().unwrap() * 1e11;
+        world
+            .create_entity()
+            .with(Position(position))
+            .with(Mass(1e20 * (1.0 + random())))
+            .with(Acceleration(Vector3::zero()))
+            .build();
+    }
+
+    gravity.run_now(&world);
+    let start = Instant::now();
+    for _ in 0..RUNS {
+        gravity.run_now(&world);
+    }
+    start.elapsed() / RUNS
+}
+
+fn main() {
+    let threads = GravityThreads::default().0;
+    println!("{threads} threads available");
+    for bodies in [1_000, 4_000, 16_000] {
+        let single = measure(bodies, 1);
+        let parallel = measure(bodies, threads);
+        println!(
+            "{bodies:>6} bodies: {single:>10.2?} on one thread, {parallel:>10.2?} on {threads} ({:.1}x)",
+            single.as_secs_f64() / parallel.as_secs_f64()
+        );
+    }
+}
@@ -0,0 +1,290 @@
+//! Files loaded at runtime from the assets directory, or over HTTP on the web
+//!
+//! They can be swapped without recompiling.
+
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter};
+use std::marker::PhantomData;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{Duration, Instant, SystemTime};
+
+use image::DynamicImage;
+
+/// Error which can be handed over from the thread loading an asset
+pub type SendError = Box<dyn Error + Send + Sync>;
+
+/// Main shader drawing the bodies
+pub const MAIN_SHADER: Handle<String> = Handle::new("shaders/shader.wgsl");
+
+/// Texture of bodies whose material failed to load
+pub const FALLBACK_TEXTURE: Handle<DynamicImage> = Handle::new("textures/happy-tree.png");
+
+/// Path of an asset relative to the assets directory and the type it decodes to
+pub struct Handle<T> {
+    pub path: &'static str,
+    asset: PhantomData<fn() -> T>,
+}
+
+impl<T> Handle<T> {
+    pub const fn new(path: &'static str) -> Self {
+        Self {
+            path,
+            asset: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Handle<T> {}
+
+impl<T> Debug for Handle<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Handle").field(&self.path).finish()
+    }
+}
+
+/// Type an asset's bytes can be decoded to
+pub trait Asset: Sized {
+    fn decode(bytes: Vec<u8>) -> Result<Self, SendError>;
+}
+
+impl Asset for Vec<u8> {
+    fn decode(bytes: Vec<u8>) -> Result<Self, SendError> {
+        Ok(bytes)
+    }
+}
+
+impl Asset for String {
+    fn decode(bytes: Vec<u8>) -> Result<Self, SendError> {
+        Ok(String::from_utf8(bytes)?)
+    }
+}
+
+impl Asset for DynamicImage {
+    fn decode(bytes: Vec<u8>) -> Result<Self, SendError> {
+        Ok(image::load_from_memory(&bytes)?)
+    }
+}
+
+/// Error of an asset which couldn't be read or decoded
+#[derive(Debug)]
+pub struct AssetError {
+    pub path: PathBuf,
+    pub error: SendError,
+}
+
+impl Display for AssetError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to load asset {}: {}",
+            self.path.display(),
+            self.error
+        )
+    }
+}
+
+impl Error for AssetError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error.as_ref())
+    }
+}
+
+/// Loads [`Handle`]s from a directory, which is a url relative to the page on the web
+#[derive(Clone, Debug)]
+pub struct Assets {
+    pub root: PathBuf,
+}
+
+impl Default for Assets {
+    /// The crate's `assets` directory, or `assets` next to the page on the web
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let root = concat!(env!("CARGO_MANIFEST_DIR"), "/assets");
+        #[cfg(target_arch = "wasm32")]
+        let root = "assets";
+        Self { root: root.into() }
+    }
+}
+
+impl Assets {
+    pub async fn load<T: Asset>(&self, handle: Handle<T>) -> Result<T, AssetError> {
+        let path = self.root.join(handle.path);
+        match self.read(&path).await.and_then(T::decode) {
+            Ok(asset) => Ok(asset),
+            Err(error) => Err(AssetError { path, error }),
+        }
+    }
+
+    /// Start watching the directory for changed files
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn watch(&self) -> Watcher {
+        Watcher {
+            modified: Watcher::scan(&self.root),
+            root: self.root.clone(),
+            polled: Instant::now(),
+        }
+    }
+
+    /// Files served over HTTP can't be watched, so nothing is ever reported as changed
+    #[cfg(target_arch = "wasm32")]
+    pub fn watch(&self) -> Watcher {
+        Watcher
+    }
+
+    /// Start loading an asset in the background
+    ///
+    /// The returned [`Pending`] has to be polled until it's done.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn<T: Asset + Send + 'static>(&self, handle: Handle<T>) -> Pending<T> {
+        let pending = Pending(Arc::new(Mutex::new(None)));
+        let slot = pending.0.clone();
+        let assets = self.clone();
+        std::thread::spawn(move || {
+            let result = pollster::block_on(assets.load(handle));
+            *slot.lock().unwrap() = Some(result);
+        });
+        pending
+    }
+
+    /// Start loading an asset in the background
+    ///
+    /// The returned [`Pending`] has to be polled until it's done.
+    #[cfg(target_arch = "wasm32")]
+    pub fn spawn<T: Asset + Send + 'static>(&self, handle: Handle<T>) -> Pending<T> {
+        let pending = Pending(Arc::new(Mutex::new(None)));
+        let slot = pending.0.clone();
+        let assets = self.clone();
+        wasm_bindgen_futures::spawn_local(async move {
+            let result = assets.load(handle).await;
+            *slot.lock().unwrap() = Some(result);
+        });
+        pending
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn read(&self, path: &Path) -> Result<Vec<u8>, SendError> {
+        Ok(std::fs::read(path)?)
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn read(&self, path: &std::path::Path) -> Result<Vec<u8>, SendError> {
+        use wasm_bindgen::{JsCast, JsValue};
+        use wasm_bindgen_futures::{js_sys, JsFuture};
+
+        use crate::error::CustomError;
+
+        let js_error =
+            |value: JsValue| -> SendError { CustomError::from(format!("{value:?}")).into() };
+
+        let window = web_sys::window().ok_or(CustomError::from("No window to fetch from"))?;
+        let response: web_sys::Response =
+            JsFuture::from(window.fetch_with_str(&path.to_string_lossy()))
+                .await
+                .map_err(js_error)?
+                .dyn_into()
+                .map_err(js_error)?;
+        if !response.ok() {
+            return Err(CustomError::from(format!("HTTP status {}", response.status())).into());
+        }
+        let buffer = JsFuture::from(response.array_buffer().map_err(js_error)?)
+            .await
+            .map_err(js_error)?;
+        Ok(js_sys::Uint8Array::new(&buffer).to_vec())
+    }
+}
+
+/// Asset loaded in the background by [`Assets::spawn`]
+pub struct Pending<T>(Arc<Mutex<Option<Result<T, AssetError>>>>);
+
+impl<T> Pending<T> {
+    /// Take the asset once it's done loading
+    ///
+    /// Returns `None` while it's still loading and after it has been taken.
+    pub fn poll(&self) -> Option<Result<T, AssetError>> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Reports files in the assets directory which were created or modified, see [`Assets::watch`]
+///
+/// It polls the files' modification times, so editors replacing instead of writing files work too.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Watcher {
+    root: PathBuf,
+    modified: HashMap<PathBuf, SystemTime>,
+    polled: Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Watcher {
+    /// Time between two scans of the directory
+    const INTERVAL: Duration = Duration::from_millis(500);
+
+    /// Paths relative to the assets directory of the files changed since the last scan
+    ///
+    /// Scans the directory at most once every [`INTERVAL`](Self::INTERVAL).
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        if self.polled.elapsed() < Self::INTERVAL {
+            return Vec::new();
+        }
+        self.polled = Instant::now();
+
+        let modified = Self::scan(&self.root);
+        let changed = modified
+            .iter()
+            .filter(|(path, time)| self.modified.get(*path) != Some(time))
+            .map(|(path, _)| path.clone())
+            .collect();
+        self.modified = modified;
+        changed
+    }
+
+    /// Modification time of every file below a directory by its relative path
+    fn scan(root: &Path) -> HashMap<PathBuf, SystemTime> {
+        let mut files = HashMap::new();
+        let mut directories = vec![root.to_path_buf()];
+        while let Some(directory) = directories.pop() {
+            let Ok(entries) = std::fs::read_dir(&directory) else {
+                continue;
+            };
+            for entry in entries.flatten() {
+                let Ok(metadata) = entry.metadata() else {
+                    continue;
+                };
+                let path = entry.path();
+                if metadata.is_dir() {
+                    directories.push(path);
+                } else if let (Ok(modified), Ok(relative)) =
+                    (metadata.modified(), path.strip_prefix(root))
+                {
+                    files.insert(relative.to_path_buf(), modified);
+                }
+            }
+        }
+        files
+    }
+}
+
+/// Reports files in the assets directory which were created or modified, see [`Assets::watch`]
+#[cfg(target_arch = "wasm32")]
+pub struct Watcher;
+
+#[cfg(target_arch = "wasm32")]
+impl Watcher {
+    /// Paths relative to the assets directory of the files changed since the last scan
+    pub fn changed(&mut self) -> Vec<PathBuf> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,116 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    position: vec4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct LightUniform {
+    position: vec3<f32>,
+    ambient: f32,
+    color: vec3<f32>,
+    emission: f32,
+}
+@group(1) @binding(0)
+var<uniform> light: LightUniform;
+
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+}
+struct InstanceInput {
+    @location(5) center: vec3<f32>,
+    @location(6) radius: f32,
+    @location(7) color: vec3<f32>,
+    @location(8) outer: f32,
+    @location(9) density: f32,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) world_position: vec3<f32>,
+    @location(1) @interpolate(flat) center: vec3<f32>,
+    @location(2) @interpolate(flat) radius: f32,
+    @location(3) @interpolate(flat) color: vec3<f32>,
+    @location(4) @interpolate(flat) outer: f32,
+    @location(5) @interpolate(flat) density: f32,
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+) -> VertexOutput {
+    // The icosphere's faces lie inside the unit sphere, so enlarge it slightly to cover the whole shell
+    let world_position = instance.center + model.position * instance.outer * 1.02;
+
+    var out: VertexOutput;
+    out.world_position = world_position;
+    out.center = instance.center;
+    out.radius = instance.radius;
+    out.color = instance.color;
+    out.outer = instance.outer;
+    out.density = instance.density;
+    out.clip_position = camera.view_proj * vec4<f32>(world_position, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+// Distances along a ray to where it enters and leaves a sphere, negative if it misses
+fn intersect(origin: vec3<f32>, direction: vec3<f32>, center: vec3<f32>, radius: f32) -> vec2<f32> {
+    let to_center = center - origin;
+    let closest = dot(to_center, direction);
+    let distance2 = dot(to_center, to_center) - closest * closest;
+    let radius2 = radius * radius;
+    if distance2 > radius2 {
+        return vec2<f32>(-1.0);
+    }
+    let half_chord = sqrt(radius2 - distance2);
+    return vec2<f32>(closest - half_chord, closest + half_chord);
+}
+
+const PI: f32 = 3.14159265;
+
+// Asymmetry of the Mie phase function, how strongly haze scatters forward
+const MIE_G: f32 = 0.76;
+
+// Strength of the Mie scattering relative to the Rayleigh scattering
+const MIE_STRENGTH: f32 = 0.05;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let eye = camera.position.xyz;
+    let direction = normalize(in.world_position - eye);
+
+    let shell = intersect(eye, direction, in.center, in.outer);
+    if shell.y <= 0.0 {
+        discard;
+    }
+    var near = max(shell.x, 0.0);
+    var far = shell.y;
+    let ground = intersect(eye, direction, in.center, in.radius);
+    if ground.x > 0.0 {
+        far = min(far, ground.x);
+    }
+
+    // Compare the path through the atmosphere with the longest one, which grazes the surface
+    let longest = 2.0 * sqrt(in.outer * in.outer - in.radius * in.radius);
+    let depth = (far - near) / longest;
+
+    // Approximate the light along the whole path by the light at its middle
+    let middle = eye + direction * (near + far) * 0.5;
+    let up = normalize(middle - in.center);
+    let to_light = normalize(light.position - middle);
+    let lit = max(smoothstep(-0.2, 0.3, dot(up, to_light)), light.ambient);
+
+    let cos_theta = dot(direction, to_light);
+    let rayleigh = 0.75 * (1.0 + cos_theta * cos_theta);
+    let g2 = MIE_G * MIE_G;
+    let mie = (1.0 - g2) / (4.0 * PI * pow(1.0 + g2 - 2.0 * MIE_G * cos_theta, 1.5));
+
+    let scattered = (in.color * rayleigh + vec3<f32>(mie * MIE_STRENGTH)) * max(light.color, vec3<f32>(light.ambient));
+    let amount = 1.0 - exp(-depth * in.density);
+    return vec4<f32>(scattered * lit * amount, 0.0);
+}
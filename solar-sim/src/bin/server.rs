@@ -0,0 +1,36 @@
+//! Headless server hosting a simulation for clients started with `--connect`
+//!
+//! Takes the address to host on followed by the same options as the app,
+//! of which only the ones configuring the physics have an effect.
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use solar_sim::net::NetHost;
+use solar_sim::simulation::Simulation;
+use solar_sim::{crash, Options};
+
+/// Time between two simulation steps i.e. states sent to the clients
+const TICK: Duration = Duration::from_micros(16_667);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    crash::install();
+
+    let mut args = std::env::args().skip(1);
+    let addr = args
+        .next()
+        .ok_or("Usage: server <address> [options]")?
+        .parse()?;
+    let options = Options::parse(args)?;
+
+    let mut simulation =
+        Simulation::new(Simulation::systems().with_thread_local(NetHost::bind(addr)?));
+    options.configure_physics(&mut simulation.world)?;
+
+    let mut next = Instant::now();
+    loop {
+        simulation.step();
+        next += TICK;
+        thread::sleep(next.saturating_duration_since(Instant::now()));
+    }
+}
@@ -0,0 +1,59 @@
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shaders
+
+struct BloomUniform {
+    direction: vec2<f32>,
+    threshold: f32,
+    intensity: f32,
+}
+@group(0) @binding(0)
+var<uniform> bloom: BloomUniform;
+@group(0) @binding(1)
+var t_source: texture_2d<f32>;
+@group(0) @binding(2)
+var s_source: sampler;
+
+// Keep only the part of each color brighter than the threshold
+@fragment
+fn fs_threshold(in: VertexOutput) -> @location(0) vec4<f32> {
+    let color = textureSample(t_source, s_source, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    let contribution = max(brightness - bloom.threshold, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * contribution, 1.0);
+}
+
+// One direction of a separable gaussian blur
+@fragment
+fn fs_blur(in: VertexOutput) -> @location(0) vec4<f32> {
+    var weights = array<f32, 5>(0.227027, 0.1945946, 0.1216216, 0.054054, 0.016216);
+    let step = bloom.direction / vec2<f32>(textureDimensions(t_source));
+    var color = textureSample(t_source, s_source, in.uv).rgb * weights[0];
+    for (var i = 1; i < 5; i++) {
+        let offset = step * f32(i);
+        color += textureSample(t_source, s_source, in.uv + offset).rgb * weights[i];
+        color += textureSample(t_source, s_source, in.uv - offset).rgb * weights[i];
+    }
+    return vec4<f32>(color, 1.0);
+}
+
+// Added onto the scene
+@fragment
+fn fs_composite(in: VertexOutput) -> @location(0) vec4<f32> {
+    return vec4<f32>(textureSample(t_source, s_source, in.uv).rgb * bloom.intensity, 1.0);
+}
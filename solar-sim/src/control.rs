@@ -1,5 +1,15 @@
-use winit::dpi::PhysicalPosition;
-use winit::event::{ElementState, KeyboardInput, MouseScrollDelta, VirtualKeyCode};
+use specs::Entity;
+use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::event::{ElementState, KeyboardInput, MouseButton, MouseScrollDelta, VirtualKeyCode};
+
+use crate::render::debug::DebugView;
+
+/// Resource of the body the user selected
+///
+/// Updated by the [`Picking`](crate::render::picking::Picking) system
+/// and read by the physics and overlays acting on the selected body
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Selection(pub Option<Entity>);
 
 #[derive(Copy, Clone, Default, Debug)]
 pub struct Controls {
@@ -12,6 +22,62 @@ pub struct Controls {
     pub mouse_dx: f32,
     pub mouse_dy: f32,
     pub mouse_scroll: f32,
+    pub is_stats_pressed: bool,
+    /// Toggled by F3
+    pub show_stats: bool,
+    pub is_flare_pressed: bool,
+    /// Toggled by F4
+    pub hide_lens_flare: bool,
+    pub is_debug_pressed: bool,
+    /// Cycled by F5
+    pub debug_view: DebugView,
+    pub is_swap_pressed: bool,
+    /// Set by tab until the [`SwapCameras`](crate::render::split::SwapCameras) system handles it
+    pub swap_cameras: bool,
+    pub is_present_mode_pressed: bool,
+    /// Set by F6 until [`Render`](crate::render::Render) switches to the next present mode
+    pub cycle_present_mode: bool,
+    pub is_open_window_pressed: bool,
+    /// Set by F7 until the event loop opens a [detached view](crate::render::detached::DetachedWindows)
+    pub open_window: bool,
+    pub is_rails_pressed: bool,
+    /// Set by F8 until the [`ToggleRails`](crate::physics::kepler::ToggleRails) system handles it
+    pub toggle_rails: bool,
+    pub is_lagrange_pressed: bool,
+    /// Toggled by F9
+    pub show_lagrange_points: bool,
+    pub is_influence_pressed: bool,
+    /// Toggled by F10
+    pub show_spheres_of_influence: bool,
+    pub is_fullscreen_pressed: bool,
+    /// Set by F11 until the event loop switches between windowed, borderless and exclusive fullscreen
+    pub cycle_fullscreen: bool,
+    pub is_maneuver_pressed: bool,
+    /// Set by N until the [`PlanManeuver`](crate::physics::maneuver::PlanManeuver) system handles it
+    pub toggle_maneuver: bool,
+    /// Steps along the trajectory queued by , and .
+    pub maneuver_time_steps: i32,
+    /// Delta-v steps queued by I and K (prograde), U and O (normal), L and J (radial)
+    pub maneuver_steps: [i32; 3],
+    pub is_target_pressed: bool,
+    /// Set by T until the [`Autopilot`](crate::physics::autopilot::Autopilot) system handles it
+    pub set_target: bool,
+    pub is_circularize_pressed: bool,
+    /// Set by C until the [`Autopilot`](crate::physics::autopilot::Autopilot) system handles it
+    pub circularize: bool,
+    pub is_match_pressed: bool,
+    /// Set by V until the [`Autopilot`](crate::physics::autopilot::Autopilot) system handles it
+    pub match_velocity: bool,
+    /// Steps queued by - and = until the [`AdjustPostProcess`](crate::render::tonemap::AdjustPostProcess) system applies them
+    pub exposure_steps: i32,
+    /// Steps queued by [ and ]
+    pub gamma_steps: i32,
+    /// Steps queued by ; and '
+    pub saturation_steps: i32,
+    /// Cursor position in normalized device coordinates
+    pub cursor: Option<[f32; 2]>,
+    /// Set by a left click until the click is handled
+    pub is_select_clicked: bool,
 }
 
 impl Controls {
@@ -28,6 +94,22 @@ impl Controls {
         };
     }
 
+    pub fn process_cursor(&mut self, position: PhysicalPosition<f64>, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.cursor = Some([
+            (position.x / size.width as f64 * 2.0 - 1.0) as f32,
+            (1.0 - position.y / size.height as f64 * 2.0) as f32,
+        ]);
+    }
+
+    pub fn process_mouse_button(&mut self, button: MouseButton, state: ElementState) {
+        if button == MouseButton::Left && state == ElementState::Pressed {
+            self.is_select_clicked = true;
+        }
+    }
+
     pub fn process_keyboard(&mut self, input: &KeyboardInput) -> bool {
         let KeyboardInput {
             state,
@@ -61,7 +143,158 @@ impl Controls {
                 self.is_right_pressed = is_pressed;
                 true
             }
+            VirtualKeyCode::F3 => {
+                // Ignore the key repeating while held
+                if is_pressed && !self.is_stats_pressed {
+                    self.show_stats = !self.show_stats;
+                }
+                self.is_stats_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F4 => {
+                if is_pressed && !self.is_flare_pressed {
+                    self.hide_lens_flare = !self.hide_lens_flare;
+                }
+                self.is_flare_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F5 => {
+                if is_pressed && !self.is_debug_pressed {
+                    self.debug_view = self.debug_view.next();
+                }
+                self.is_debug_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F6 => {
+                if is_pressed && !self.is_present_mode_pressed {
+                    self.cycle_present_mode = true;
+                }
+                self.is_present_mode_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F7 => {
+                if is_pressed && !self.is_open_window_pressed {
+                    self.open_window = true;
+                }
+                self.is_open_window_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F8 => {
+                if is_pressed && !self.is_rails_pressed {
+                    self.toggle_rails = true;
+                }
+                self.is_rails_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F9 => {
+                if is_pressed && !self.is_lagrange_pressed {
+                    self.show_lagrange_points = !self.show_lagrange_points;
+                }
+                self.is_lagrange_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F10 => {
+                if is_pressed && !self.is_influence_pressed {
+                    self.show_spheres_of_influence = !self.show_spheres_of_influence;
+                }
+                self.is_influence_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::F11 => {
+                if is_pressed && !self.is_fullscreen_pressed {
+                    self.cycle_fullscreen = true;
+                }
+                self.is_fullscreen_pressed = is_pressed;
+                true
+            }
+            // Repeating while held is fine, it just keeps adjusting
+            VirtualKeyCode::Minus | VirtualKeyCode::Equals => {
+                if is_pressed {
+                    self.exposure_steps += step(*keycode == VirtualKeyCode::Equals);
+                }
+                true
+            }
+            VirtualKeyCode::LBracket | VirtualKeyCode::RBracket => {
+                if is_pressed {
+                    self.gamma_steps += step(*keycode == VirtualKeyCode::RBracket);
+                }
+                true
+            }
+            VirtualKeyCode::Semicolon | VirtualKeyCode::Apostrophe => {
+                if is_pressed {
+                    self.saturation_steps += step(*keycode == VirtualKeyCode::Apostrophe);
+                }
+                true
+            }
+            VirtualKeyCode::N => {
+                if is_pressed && !self.is_maneuver_pressed {
+                    self.toggle_maneuver = true;
+                }
+                self.is_maneuver_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::T => {
+                if is_pressed && !self.is_target_pressed {
+                    self.set_target = true;
+                }
+                self.is_target_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::C => {
+                if is_pressed && !self.is_circularize_pressed {
+                    self.circularize = true;
+                }
+                self.is_circularize_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::V => {
+                if is_pressed && !self.is_match_pressed {
+                    self.match_velocity = true;
+                }
+                self.is_match_pressed = is_pressed;
+                true
+            }
+            VirtualKeyCode::Comma | VirtualKeyCode::Period => {
+                if is_pressed {
+                    self.maneuver_time_steps += step(*keycode == VirtualKeyCode::Period);
+                }
+                true
+            }
+            VirtualKeyCode::I | VirtualKeyCode::K => {
+                if is_pressed {
+                    self.maneuver_steps[0] += step(*keycode == VirtualKeyCode::I);
+                }
+                true
+            }
+            VirtualKeyCode::U | VirtualKeyCode::O => {
+                if is_pressed {
+                    self.maneuver_steps[1] += step(*keycode == VirtualKeyCode::U);
+                }
+                true
+            }
+            VirtualKeyCode::L | VirtualKeyCode::J => {
+                if is_pressed {
+                    self.maneuver_steps[2] += step(*keycode == VirtualKeyCode::L);
+                }
+                true
+            }
+            VirtualKeyCode::Tab => {
+                if is_pressed && !self.is_swap_pressed {
+                    self.swap_cameras = true;
+                }
+                self.is_swap_pressed = is_pressed;
+                true
+            }
             _ => false,
         }
     }
 }
+
+/// A step up or down
+fn step(up: bool) -> i32 {
+    if up {
+        1
+    } else {
+        -1
+    }
+}
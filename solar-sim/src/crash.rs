@@ -0,0 +1,204 @@
+//! Panic hook writing a crash report with the last known state to disk
+//!
+//! Only available on native builds, the web has `console_error_panic_hook`
+
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt::{Display, Write as _};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError, TryLockError};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Log, Metadata, Record};
+use specs::join::MaybeJoin;
+use specs::{Entities, Join, ReadStorage, System};
+
+use crate::physics::{Mass, Position, Velocity};
+
+/// Directory crash reports are written to
+pub const CRASH_DIR: &str = "crash-reports";
+
+/// Number of log lines kept for a crash report
+const LOG_LINES: usize = 100;
+
+/// Shared by every [`CrashSnapshot`], since the panic hook is too,
+/// with each simulation's snapshot kept apart under the id of its [`CrashSnapshot`]
+static STATE: Mutex<CrashState> = Mutex::new(CrashState {
+    adapter: None,
+    snapshots: BTreeMap::new(),
+    log: VecDeque::new(),
+});
+
+/// Id handed to the next [`CrashSnapshot`]
+static NEXT_SNAPSHOT: AtomicUsize = AtomicUsize::new(0);
+
+/// Everything collected while running which ends up in a crash report
+struct CrashState {
+    adapter: Option<String>,
+    snapshots: BTreeMap<usize, Vec<BodySnapshot>>,
+    log: VecDeque<String>,
+}
+
+/// State of a single entity as copied by [`CrashSnapshot`]
+struct BodySnapshot {
+    id: u32,
+    position: Position,
+    velocity: Option<Velocity>,
+    mass: Option<Mass>,
+}
+
+impl CrashState {
+    fn write(&self, report: &mut String) {
+        let _ = writeln!(report, "\n# Adapter");
+        match &self.adapter {
+            Some(adapter) => {
+                let _ = writeln!(report, "{adapter}");
+            }
+            None => report.push_str("No adapter requested yet\n"),
+        }
+
+        for (id, snapshot) in &self.snapshots {
+            let _ = writeln!(report, "\n# World {id} ({} bodies)", snapshot.len());
+            for body in snapshot {
+                let _ = writeln!(
+                    report,
+                    "{}: position={:?} velocity={:?} mass={:?}",
+                    body.id,
+                    body.position.0,
+                    body.velocity.map(|v| v.0),
+                    body.mass.map(|m| m.0),
+                );
+            }
+        }
+
+        let _ = writeln!(report, "\n# Log");
+        for line in &self.log {
+            let _ = writeln!(report, "{line}");
+        }
+    }
+}
+
+fn state() -> MutexGuard<'static, CrashState> {
+    STATE.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Install the logger and the panic hook
+///
+/// Replaces `env_logger::init()` and keeps the recent log lines around for the report.
+/// On panic a report is written into [`CRASH_DIR`] before the process aborts.
+pub fn install() {
+    let logger = env_logger::Builder::from_default_env().build();
+    log::set_max_level(logger.filter());
+    if let Err(error) = log::set_logger(Box::leak(Box::new(RecordingLogger(logger)))) {
+        eprintln!("Failed to install logger: {error}");
+    }
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        match write_report(info) {
+            Ok(path) => eprintln!("Crash report written to {}", path.display()),
+            Err(error) => eprintln!("Failed to write crash report: {error}"),
+        }
+        default_hook(info);
+        std::process::abort();
+    }));
+}
+
+/// Remember the adapter's info for the crash report
+pub fn set_adapter_info(info: &wgpu::AdapterInfo) {
+    state().adapter = Some(format!("{info:?}"));
+}
+
+fn write_report(message: &dyn Display) -> std::io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+
+    let mut report = String::new();
+    let _ = writeln!(report, "# Panic\n{message}");
+    // The panic might have happened while the state was locked
+    match STATE.try_lock() {
+        Ok(state) => state.write(&mut report),
+        Err(TryLockError::Poisoned(state)) => state.into_inner().write(&mut report),
+        Err(TryLockError::WouldBlock) => report.push_str("\nState is unavailable\n"),
+    }
+
+    fs::create_dir_all(CRASH_DIR)?;
+    let path = PathBuf::from(CRASH_DIR).join(format!("crash-{timestamp}.txt"));
+    fs::write(&path, report)?;
+    Ok(path)
+}
+
+/// Logger forwarding to `env_logger` while remembering the last [`LOG_LINES`] lines
+struct RecordingLogger(env_logger::Logger);
+
+impl Log for RecordingLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.0.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.0.matches(record) {
+            return;
+        }
+        self.0.log(record);
+
+        let mut state = state();
+        if state.log.len() == LOG_LINES {
+            state.log.pop_front();
+        }
+        state.log.push_back(format!(
+            "[{} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        ));
+    }
+
+    fn flush(&self) {
+        self.0.flush();
+    }
+}
+
+/// System copying the bodies' state for the crash report
+///
+/// Runs thread local after the physics, see [`Simulation::systems`](crate::simulation::Simulation::systems).
+/// Each instance keeps its own snapshot, so every simulation in the process is included.
+pub struct CrashSnapshot(usize);
+
+impl Default for CrashSnapshot {
+    fn default() -> Self {
+        Self(NEXT_SNAPSHOT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+impl Drop for CrashSnapshot {
+    fn drop(&mut self) {
+        state().snapshots.remove(&self.0);
+    }
+}
+
+impl<'a> System<'a> for CrashSnapshot {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+    );
+
+    fn run(&mut self, (ent, pos, vel, mass): Self::SystemData) {
+        let mut state = state();
+        let snapshot = state.snapshots.entry(self.0).or_default();
+        snapshot.clear();
+        snapshot.extend((&ent, &pos, MaybeJoin(&vel), MaybeJoin(&mass)).join().map(
+            |(ent, pos, vel, mass)| BodySnapshot {
+                id: ent.id(),
+                position: *pos,
+                velocity: vel.copied(),
+                mass: mass.copied(),
+            },
+        ));
+    }
+}
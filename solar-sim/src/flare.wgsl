@@ -0,0 +1,82 @@
+// Vertex shader
+
+struct FlareUniform {
+    sun: vec2<f32>,
+    depth: f32,
+    intensity: f32,
+    aspect: f32,
+}
+@group(0) @binding(0)
+var<uniform> flare: FlareUniform;
+@group(0) @binding(1)
+var t_depth: texture_depth_2d;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+// Position of each sprite on the line from the sun (1) through the screen's center (0)
+var<private> OFFSETS: array<f32, 7> = array<f32, 7>(1.0, 0.6, 0.3, -0.15, -0.4, -0.7, -1.1);
+// Radius of each sprite relative to the screen's height
+var<private> SIZES: array<f32, 7> = array<f32, 7>(0.35, 0.04, 0.08, 0.03, 0.12, 0.06, 0.2);
+var<private> COLORS: array<vec3<f32>, 7> = array<vec3<f32>, 7>(
+    vec3<f32>(1.0, 0.9, 0.7),
+    vec3<f32>(0.4, 0.6, 1.0),
+    vec3<f32>(0.6, 1.0, 0.5),
+    vec3<f32>(1.0, 0.6, 0.3),
+    vec3<f32>(0.3, 0.4, 1.0),
+    vec3<f32>(1.0, 0.4, 0.6),
+    vec3<f32>(0.5, 0.8, 1.0),
+);
+
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+);
+
+// Fraction of depth buffer samples around the sun which nothing is in front of
+fn visibility() -> f32 {
+    let size = vec2<i32>(textureDimensions(t_depth));
+    let center = vec2<i32>((vec2<f32>(flare.sun.x, -flare.sun.y) * 0.5 + 0.5) * vec2<f32>(size));
+    var visible = 0.0;
+    for (var x = -2; x <= 2; x++) {
+        for (var y = -2; y <= 2; y++) {
+            let texel = center + vec2<i32>(x, y) * 3;
+            if all(texel >= vec2<i32>(0)) && all(texel < size) && textureLoad(t_depth, texel, 0) <= flare.depth {
+                visible += 1.0;
+            }
+        }
+    }
+    return visible / 25.0;
+}
+
+@vertex
+fn vs_main(
+    @builtin(vertex_index) vertex: u32,
+    @builtin(instance_index) element: u32,
+) -> VertexOutput {
+    let corner = CORNERS[vertex];
+    let center = flare.sun * OFFSETS[element];
+    // Fade out as the sun leaves the screen
+    let fade = 1.0 - smoothstep(0.9, 1.5, length(flare.sun));
+
+    var out: VertexOutput;
+    out.uv = corner;
+    out.color = COLORS[element] * flare.intensity * fade * visibility();
+    out.clip_position = vec4<f32>(center + corner * SIZES[element] * vec2<f32>(1.0 / flare.aspect, 1.0), 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = max(1.0 - length(in.uv), 0.0);
+    return vec4<f32>(in.color * falloff * falloff, 0.0);
+}
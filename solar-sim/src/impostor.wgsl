@@ -0,0 +1,49 @@
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+struct SpriteInput {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec3<f32>,
+    @location(2) size: vec2<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec3<f32>,
+}
+
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+);
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, sprite: SpriteInput) -> VertexOutput {
+    let corner = CORNERS[index];
+    let center = camera.view_proj * vec4<f32>(sprite.position, 1.0);
+
+    var out: VertexOutput;
+    out.uv = corner;
+    out.color = sprite.color;
+    // Offset in clip space, so the size stays the same in pixels regardless of the distance
+    out.clip_position = center + vec4<f32>(corner * sprite.size * center.w, 0.0, 0.0);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = exp(-4.0 * dot(in.uv, in.uv));
+    return vec4<f32>(in.color * falloff, 0.0);
+}
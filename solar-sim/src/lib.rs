@@ -1,30 +1,380 @@
+use std::net::SocketAddr;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use log::warn;
-use specs::{DispatcherBuilder, World, WorldExt};
+use specs::World;
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 use winit::event::*;
 use winit::event_loop::EventLoop;
-use winit::window::{CursorGrabMode, WindowBuilder};
+use winit::window::{CursorGrabMode, Fullscreen, Window, WindowBuilder};
 
+use crate::assets::Assets;
 use crate::control::Controls;
-use crate::error::DynError;
-use crate::physics::planets::build_planets;
-use crate::physics::{Gravity, Mechanics};
-use crate::render::camera::ControlCamera;
-use crate::render::Render;
-use crate::timer::Timer;
+use crate::error::{CustomError, DynError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::net::{NetClient, NetHost};
+use crate::physics::autopilot::Autopilot;
+use crate::physics::barycenter::Recentering;
+use crate::physics::integrator::{self, Integrator, SelectedIntegrator};
+use crate::physics::kepler::ToggleRails;
+use crate::physics::maneuver::PlanManeuver;
+use crate::physics::octree::OpeningAngle;
+use crate::physics::orbit::Osculate;
+use crate::physics::tides::Tides;
+use crate::physics::timestep::MaxStep;
+use crate::physics::{Determinism, Relativity, SimSpeed, SimTime};
+use crate::render::camera::{Camera, ControlCamera};
+use crate::render::clouds::RotateClouds;
+use crate::render::compare::{Comparison, FollowCamera};
+use crate::render::conics::PatchedConics;
+use crate::render::detached::DetachedWindows;
+use crate::render::field::GravityField;
+use crate::render::gizmos::VectorGizmos;
+use crate::render::grid::EclipticGrid;
+use crate::render::highlight::Highlight;
+use crate::render::impostor::ClassifyImpostors;
+use crate::render::influence::SpheresOfInfluence;
+use crate::render::lagrange::LagrangePoints;
+use crate::render::maneuver::ManeuverPreview;
+use crate::render::minimap::Minimap;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::observers::RemoteObservers;
+use crate::render::orbits::OrbitLines;
+use crate::render::picking::{Picking, PickingMode};
+use crate::render::potential::EffectivePotential;
+use crate::render::prediction::Prediction;
+use crate::render::present::PresentMode;
+use crate::render::recording::{Recording, FRAME_TIME};
+use crate::render::split::{SplitScreen, SwapCameras};
+use crate::render::stats::StatsOverlay;
+use crate::render::tonemap::AdjustPostProcess;
+use crate::render::trail::RecordTrails;
+use crate::render::uncertainty::TrajectoryUncertainty;
+use crate::render::{Exaggeration, Render, Resize};
+use crate::simulation::Simulation;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::timer::FrameLimiter;
+use crate::window::{parse_size, WindowConfig};
 
+pub mod assets;
 pub mod control;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod crash;
 pub mod error;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod net;
 pub mod physics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod plugin;
 pub mod render;
+pub mod simulation;
 pub mod timer;
+pub mod window;
 
-pub async fn run() -> Result<(), DynError> {
+/// Options for [`run`]
+#[derive(Clone, Debug, Default)]
+pub struct Options {
+    /// Address to host the simulation on for others to observe
+    pub host: Option<SocketAddr>,
+
+    /// Address of a hosted simulation to render instead of simulating locally
+    pub connect: Option<SocketAddr>,
+
+    /// WebAssembly plugins to load, see [`plugin`] for their abi
+    pub plugins: Vec<PathBuf>,
+
+    /// Draw arrows visualizing the gravitational field
+    pub gravity_field: bool,
+
+    /// Draw contours of the two most massive bodies' effective potential
+    pub effective_potential: bool,
+
+    /// Draw every body's predicted trajectory
+    pub prediction: bool,
+
+    /// Draw the selected body's trajectory as patched conics
+    pub patched_conics: bool,
+
+    /// Draw every body's orbit with its apoapsis and periapsis and describe the selected body's orbit
+    pub orbits: bool,
+
+    /// Draw arrows along every body's velocity and acceleration
+    pub vectors: bool,
+
+    /// Draw a polar grid on the ecliptic plane, see [`GridConfig`](render::grid::GridConfig)
+    pub grid: bool,
+
+    /// Show a map of the whole system seen from above, see [`Minimap`]
+    pub minimap: bool,
+
+    /// Show a second camera overlooking the outer planets next to the main one, see [`SplitScreen`]
+    pub split: bool,
+
+    /// Run a second simulation with this integrator and show it next to the main one, see [`Comparison`]
+    pub compare: Option<Arc<dyn Integrator>>,
+
+    /// Directory to save every frame to, advancing the simulation by a fixed step per frame
+    pub record: Option<PathBuf>,
+
+    /// Fixed step to advance the physics by per frame, scaled by the sim speed, see [`Determinism`]
+    pub deterministic: Option<Duration>,
+
+    /// Pick bodies by reading back rendered ids instead of intersecting spheres, see [`PickingMode`]
+    pub gpu_picking: bool,
+
+    /// Id of a body whose trajectory's sensitivity to initial errors to draw
+    pub uncertainty: Option<u32>,
+
+    /// Seed of the random perturbations drawn for the `uncertainty` body, see [`TrajectoryUncertainty`]
+    pub seed: Option<u64>,
+
+    /// How frames are presented, see [`PresentMode`]
+    pub present_mode: Option<PresentMode>,
+
+    /// Factor to multiply the bodies' rendered radii with, see [`Exaggeration`]
+    pub exaggeration: Option<f32>,
+
+    /// Directory to load the assets from instead of the [default](Assets::default) one
+    pub assets: Option<PathBuf>,
+
+    /// Method advancing the bodies each tick, see [`SelectedIntegrator`]
+    pub integrator: Option<Arc<dyn Integrator>>,
+
+    /// Simulated seconds a single physics step advances at most, see [`MaxStep`]
+    pub max_step: Option<f32>,
+
+    /// Approximate gravity with a Barnes-Hut octree of this opening angle, see [`OpeningAngle`]
+    pub barnes_hut: Option<f32>,
+
+    /// Keep the barycenter at rest at the origin, see [`Recentering`]
+    pub recenter: bool,
+
+    /// Add the post-Newtonian correction to gravity, see [`Relativity`]
+    pub relativity: bool,
+
+    /// Apply tides sped up by this factor, see [`Tides`]
+    pub tides: Option<f32>,
+
+    /// Simulated seconds per real second, see [`SimSpeed`]
+    pub speed: Option<f32>,
+
+    /// Frames per second to draw at most, regardless of the [`PresentMode`]
+    pub max_fps: Option<f32>,
+
+    /// Title, size and icon of the main window
+    pub window: WindowConfig,
+}
+
+impl Options {
+    /// Parse command line arguments, without the program's name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self, DynError> {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--host" => {
+                    options.host = Some(args.next().ok_or("--host requires an address")?.parse()?)
+                }
+                "--connect" => {
+                    options.connect = Some(
+                        args.next()
+                            .ok_or("--connect requires an address")?
+                            .parse()?,
+                    )
+                }
+                "--plugin" => options
+                    .plugins
+                    .push(args.next().ok_or("--plugin requires a path")?.into()),
+                "--gravity-field" => options.gravity_field = true,
+                "--effective-potential" => options.effective_potential = true,
+                "--prediction" => options.prediction = true,
+                "--patched-conics" => options.patched_conics = true,
+                "--orbits" => options.orbits = true,
+                "--vectors" => options.vectors = true,
+                "--grid" => options.grid = true,
+                "--minimap" => options.minimap = true,
+                "--split" => options.split = true,
+                "--gpu-picking" => options.gpu_picking = true,
+                "--uncertainty" => {
+                    options.uncertainty = Some(
+                        args.next()
+                            .ok_or("--uncertainty requires a body id")?
+                            .parse()?,
+                    )
+                }
+                "--speed" => {
+                    let speed: f32 = args.next().ok_or("--speed requires a factor")?.parse()?;
+                    if !speed.is_finite() {
+                        return Err(CustomError::from("--speed requires a finite factor").into());
+                    }
+                    options.speed = Some(speed);
+                }
+                "--seed" => {
+                    options.seed = Some(args.next().ok_or("--seed requires a number")?.parse()?)
+                }
+                "--exaggeration" => {
+                    let factor: f32 = args
+                        .next()
+                        .ok_or("--exaggeration requires a factor")?
+                        .parse()?;
+                    if !factor.is_finite() || factor <= 0.0 {
+                        return Err(
+                            CustomError::from("--exaggeration requires a positive factor").into(),
+                        );
+                    }
+                    options.exaggeration = Some(factor);
+                }
+                "--present-mode" => {
+                    let mode = args.next().ok_or("--present-mode requires a mode")?;
+                    options.present_mode = Some(
+                        PresentMode::parse(&mode)
+                            .ok_or("--present-mode requires one of fifo, mailbox or immediate")?,
+                    )
+                }
+                "--record" => {
+                    options.record =
+                        Some(args.next().ok_or("--record requires a directory")?.into())
+                }
+                "--integrator" => {
+                    let integrator = args.next().ok_or("--integrator requires a method")?;
+                    options.integrator = Some(integrator::by_name(&integrator).ok_or(
+                        "--integrator requires one of leapfrog, euler, rk4, rkf45, gpu or kepler",
+                    )?)
+                }
+                "--compare" => {
+                    let integrator = args.next().ok_or("--compare requires a method")?;
+                    // The gpu integrator needs the renderer, which only runs on the main simulation
+                    options.compare = Some(
+                        integrator::by_name(&integrator)
+                            .filter(|integrator| integrator.cpu_forces())
+                            .ok_or(
+                                "--compare requires one of leapfrog, euler, rk4, rkf45 or kepler",
+                            )?,
+                    )
+                }
+                "--barnes-hut" => {
+                    let theta: f32 = args
+                        .next()
+                        .ok_or("--barnes-hut requires an opening angle")?
+                        .parse()?;
+                    if !theta.is_finite() || theta < 0.0 {
+                        return Err(CustomError::from(
+                            "--barnes-hut requires a non-negative opening angle",
+                        )
+                        .into());
+                    }
+                    options.barnes_hut = Some(theta);
+                }
+                "--recenter" => options.recenter = true,
+                "--relativity" => options.relativity = true,
+                "--tides" => {
+                    let factor: f32 = args
+                        .next()
+                        .ok_or("--tides requires a speed-up factor")?
+                        .parse()?;
+                    if !factor.is_finite() || factor <= 0.0 {
+                        return Err(CustomError::from("--tides requires a positive factor").into());
+                    }
+                    options.tides = Some(factor);
+                }
+                "--max-step" => {
+                    let step: f32 = args
+                        .next()
+                        .ok_or("--max-step requires a duration in seconds")?
+                        .parse()?;
+                    if !step.is_finite() || step <= 0.0 {
+                        return Err(
+                            CustomError::from("--max-step requires a positive duration").into()
+                        );
+                    }
+                    options.max_step = Some(step);
+                }
+                "--deterministic" => {
+                    let step: f64 = args
+                        .next()
+                        .ok_or("--deterministic requires a step in seconds")?
+                        .parse()?;
+                    if !step.is_finite() || step <= 0.0 {
+                        return Err(
+                            CustomError::from("--deterministic requires a positive step").into(),
+                        );
+                    }
+                    options.deterministic = Some(Duration::from_secs_f64(step));
+                }
+                "--max-fps" => {
+                    let rate: f32 = args.next().ok_or("--max-fps requires a rate")?.parse()?;
+                    if !rate.is_finite() || rate <= 0.0 {
+                        return Err(CustomError::from("--max-fps requires a positive rate").into());
+                    }
+                    options.max_fps = Some(rate);
+                }
+                "--title" => {
+                    options.window.title = args.next().ok_or("--title requires a title")?
+                }
+                "--static-title" => options.window.dynamic_title = false,
+                "--size" => {
+                    let size = args.next().ok_or("--size requires a size")?;
+                    options.window.size =
+                        Some(parse_size(&size).ok_or("--size requires <width>x<height>")?)
+                }
+                "--min-size" => {
+                    let size = args.next().ok_or("--min-size requires a size")?;
+                    options.window.min_size =
+                        Some(parse_size(&size).ok_or("--min-size requires <width>x<height>")?)
+                }
+                "--icon" => {
+                    options.window.icon = Some(args.next().ok_or("--icon requires a path")?.into())
+                }
+                "--assets" => {
+                    options.assets =
+                        Some(args.next().ok_or("--assets requires a directory")?.into())
+                }
+                _ => return Err(CustomError::from(format!("Unknown argument: {arg}")).into()),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Load the plugins and insert the resources configuring the physics into a simulation's world
+    ///
+    /// Shared by [`run`] and the headless server, so both simulate the same way.
+    pub fn configure_physics(&self, world: &mut World) -> Result<(), DynError> {
+        #[cfg(not(target_arch = "wasm32"))]
+        plugin::load(world, &self.plugins)?;
+        if let Some(integrator) = &self.integrator {
+            world.insert(SelectedIntegrator(Arc::clone(integrator)));
+        }
+        if let Some(max_step) = self.max_step {
+            world.insert(MaxStep(max_step));
+        }
+        if let Some(theta) = self.barnes_hut {
+            world.insert(OpeningAngle(theta));
+        }
+        if self.recenter {
+            world.insert(Recentering(true));
+        }
+        if self.relativity {
+            world.insert(Relativity(true));
+        }
+        if let Some(factor) = self.tides {
+            world.insert(Tides(factor));
+        }
+        if let Some(speed) = self.speed {
+            world.insert(SimSpeed(speed));
+        }
+        if let Some(step) = self.deterministic {
+            world.insert(Determinism(Some(step)));
+        }
+        Ok(())
+    }
+}
+
+pub async fn run(options: Options) -> Result<(), DynError> {
     let event_loop = EventLoop::new();
-    let window = WindowBuilder::new().build(&event_loop).unwrap();
+    let window = options.window.builder()?.build(&event_loop)?;
     if let Err(error) = window.set_cursor_grab(CursorGrabMode::Confined) {
         warn!("Failed to grab cursor: {error}")
     }
@@ -51,21 +401,165 @@ pub async fn run() -> Result<(), DynError> {
     }
 
     let window = Arc::new(window);
-    let state = Render::new(Arc::clone(&window)).await?;
+    let assets = options
+        .assets
+        .clone()
+        .map_or_else(Assets::default, |root| Assets { root });
+    let state = Render::new(Arc::clone(&window), &assets).await?;
 
-    let mut world = World::new();
-    let mut dispatcher = DispatcherBuilder::new()
-        .with(Timer::default(), "timer", &[])
-        .with(Gravity, "gravity", &[])
-        .with(Mechanics, "mechanics", &["timer", "gravity"])
+    #[cfg(not(target_arch = "wasm32"))]
+    let systems = match (options.host, options.connect) {
+        (Some(_), Some(_)) => {
+            return Err(CustomError::from("Can't host and observe at once").into())
+        }
+        (Some(addr), None) => Simulation::systems().with_thread_local(NetHost::bind(addr)?),
+        (None, Some(addr)) => {
+            Simulation::observer_systems().with(NetClient::connect(addr)?, "net_client", &["timer"])
+        }
+        (None, None) => Simulation::systems(),
+    };
+    #[cfg(target_arch = "wasm32")]
+    let systems = {
+        if options.host.is_some() || options.connect.is_some() {
+            warn!("Networking is not supported on the web");
+        }
+        if !options.plugins.is_empty() {
+            warn!("Plugins are not supported on the web");
+        }
+        if options.max_fps.is_some() {
+            warn!("Limiting the frame rate is not supported on the web");
+        }
+        Simulation::systems()
+    };
+    let mut systems = systems
         .with(ControlCamera::default(), "camera", &["timer"])
-        .with_thread_local(state)
-        .build();
-    dispatcher.setup(&mut world);
+        .with(RecordTrails, "trails", &[])
+        .with(Osculate, "orbital_elements", &[])
+        .with(RotateClouds, "clouds", &["timer"])
+        .with(StatsOverlay, "stats_overlay", &["timer"])
+        .with(Picking, "picking", &["camera"])
+        .with(ClassifyImpostors, "impostors", &["camera"])
+        .with(Highlight, "highlight", &["picking"])
+        .with(
+            ToggleRails,
+            "toggle_rails",
+            &["picking", "orbital_elements"],
+        )
+        .with(
+            LagrangePoints,
+            "lagrange_points",
+            &["camera", "orbital_elements"],
+        )
+        .with(
+            SpheresOfInfluence,
+            "spheres_of_influence",
+            &["orbital_elements"],
+        )
+        .with(
+            PlanManeuver,
+            "plan_maneuver",
+            &["picking", "orbital_elements"],
+        )
+        .with(Autopilot, "autopilot", &["picking", "orbital_elements"])
+        .with(
+            ManeuverPreview,
+            "maneuver_preview",
+            &["camera", "plan_maneuver", "autopilot"],
+        )
+        .with(AdjustPostProcess, "post_process", &[]);
+    if options.gravity_field {
+        systems.add(GravityField::default(), "gravity_field", &[]);
+    }
+    if options.effective_potential {
+        systems.add(EffectivePotential::default(), "effective_potential", &[]);
+    }
+    if options.prediction {
+        systems.add(Prediction::default(), "prediction", &[]);
+    }
+    if options.patched_conics {
+        systems.add(
+            PatchedConics::default(),
+            "patched_conics",
+            &["picking", "orbital_elements"],
+        );
+    }
+    if options.orbits {
+        systems.add(
+            OrbitLines,
+            "orbits",
+            &["camera", "picking", "orbital_elements"],
+        );
+    }
+    if options.vectors {
+        systems.add(VectorGizmos, "vector_gizmos", &[]);
+    }
+    if options.grid {
+        systems.add(EclipticGrid, "ecliptic_grid", &["camera"]);
+    }
+    if options.split {
+        systems.add(SwapCameras, "swap_cameras", &[]);
+    }
+    if options.compare.is_some() {
+        systems.add(FollowCamera, "follow_camera", &["camera"]);
+    }
+    if let Some(body) = options.uncertainty {
+        systems.add(
+            TrajectoryUncertainty::new(body, options.seed.unwrap_or_default()),
+            "uncertainty",
+            &[],
+        );
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    if options.host.is_some() {
+        systems.add(RemoteObservers, "remote_observers", &["camera"]);
+    }
+    let mut simulation = Simulation::new(systems.with_thread_local(state));
+    options.configure_physics(&mut simulation.world)?;
+    if options.gpu_picking {
+        simulation.world.insert(PickingMode::Gpu);
+    }
+    if let Some(present_mode) = options.present_mode {
+        simulation.world.insert(present_mode);
+    }
+    if let Some(exaggeration) = options.exaggeration {
+        simulation.world.insert(Exaggeration(exaggeration));
+    }
+    if options.split {
+        simulation.world.insert(SplitScreen::overview());
+    }
+    if options.minimap {
+        simulation.world.insert(Minimap(true));
+    }
+    // Recording needs its own step to match the frames
+    if let Some(directory) = &options.record {
+        simulation.world.insert(Determinism(Some(FRAME_TIME)));
+        simulation.world.insert(Recording(Some(directory.clone())));
+    }
+    // Only advanced by the physics, which observers don't run
+    simulation
+        .world
+        .entry::<SimTime>()
+        .or_insert_with(SimTime::default);
+    // Configured like the main simulation except for its integrator
+    let mut comparison = match &options.compare {
+        Some(integrator) => {
+            let mut comparison = Simulation::new(Simulation::compared_systems());
+            options.configure_physics(&mut comparison.world)?;
+            comparison
+                .world
+                .insert(SelectedIntegrator(Arc::clone(integrator)));
+            comparison
+                .world
+                .insert(*simulation.world.fetch::<Determinism>());
+            Some(comparison)
+        }
+        None => None,
+    };
 
-    build_planets(&mut world);
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut limiter = options.max_fps.map(FrameLimiter::new);
 
-    event_loop.run(move |event, _, control_flow| {
+    event_loop.run(move |event, target, control_flow| {
         control_flow.set_poll();
 
         match event {
@@ -86,31 +580,115 @@ pub async fn run() -> Result<(), DynError> {
                         ..
                     } => control_flow.set_exit(),
                     WindowEvent::Resized(physical_size) => {
-                        // TODO: state.resize(*physical_size);
+                        simulation.world.fetch_mut::<Resize>().0 = Some(*physical_size);
                     }
                     WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
                         // new_inner_size is &mut so w have to dereference it twice
-                        // TODO: state.resize(**new_inner_size);
+                        simulation.world.fetch_mut::<Resize>().0 = Some(**new_inner_size);
                     }
                     WindowEvent::KeyboardInput { input, .. } => {
-                        world.fetch_mut::<Controls>().process_keyboard(input);
+                        simulation
+                            .world
+                            .fetch_mut::<Controls>()
+                            .process_keyboard(input);
                     }
+                    WindowEvent::CursorMoved { position, .. } => simulation
+                        .world
+                        .fetch_mut::<Controls>()
+                        .process_cursor(*position, window.inner_size()),
+                    WindowEvent::MouseInput { state, button, .. } => simulation
+                        .world
+                        .fetch_mut::<Controls>()
+                        .process_mouse_button(*button, *state),
                     _ => { /*TODO*/ }
                 }
             }
+            Event::WindowEvent { event, window_id } => match event {
+                WindowEvent::CloseRequested => simulation
+                    .world
+                    .fetch_mut::<DetachedWindows>()
+                    .closed
+                    .push(window_id),
+                // Detached views share the main window's controls
+                WindowEvent::KeyboardInput { input, .. } => {
+                    simulation
+                        .world
+                        .fetch_mut::<Controls>()
+                        .process_keyboard(&input);
+                }
+                _ => {}
+            },
             Event::DeviceEvent {
                 event: DeviceEvent::MouseMotion { delta },
                 ..
-            } => world.fetch_mut::<Controls>().process_mouse(delta),
+            } => simulation
+                .world
+                .fetch_mut::<Controls>()
+                .process_mouse(delta),
             Event::DeviceEvent {
                 event: DeviceEvent::MouseWheel { delta },
                 ..
-            } => world.fetch_mut::<Controls>().process_wheel(delta),
+            } => simulation
+                .world
+                .fetch_mut::<Controls>()
+                .process_wheel(delta),
             Event::RedrawRequested(window_id) if window_id == window.id() => {
-                dispatcher.dispatch(&world);
-                world.maintain();
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(limiter) = &mut limiter {
+                    limiter.frame();
+                }
+                // Before the main simulation, whose render shows both
+                if let Some(comparison) = &mut comparison {
+                    comparison
+                        .world
+                        .insert(*simulation.world.fetch::<SimSpeed>());
+                    comparison.step();
+                    simulation
+                        .world
+                        .insert(Comparison::capture(&comparison.world));
+                }
+                simulation.step();
             }
             Event::MainEventsCleared => {
+                let title = options.window.title(
+                    *simulation.world.fetch::<SimTime>(),
+                    *simulation.world.fetch::<SimSpeed>(),
+                );
+                if title != window.title() {
+                    window.set_title(&title);
+                }
+                if std::mem::take(&mut simulation.world.fetch_mut::<Controls>().cycle_fullscreen) {
+                    // The surface and projection follow in the resulting resize
+                    cycle_fullscreen(&window);
+                }
+                if std::mem::take(&mut simulation.world.fetch_mut::<Controls>().open_window) {
+                    #[cfg(not(target_arch = "wasm32"))]
+                    match WindowBuilder::new()
+                        .with_title("Detached View")
+                        .build(target)
+                    {
+                        Ok(detached) => {
+                            let camera = *simulation.world.fetch::<Camera>();
+                            simulation
+                                .world
+                                .fetch_mut::<DetachedWindows>()
+                                .opened
+                                .push((Arc::new(detached), camera));
+                        }
+                        Err(error) => warn!("Failed to open a window: {error}"),
+                    }
+                    #[cfg(target_arch = "wasm32")]
+                    {
+                        let _ = target;
+                        warn!("Detached views are not supported on the web");
+                    }
+                }
+                // Sleep until the next frame is due instead of polling
+                #[cfg(not(target_arch = "wasm32"))]
+                if let Some(next) = limiter.as_ref().and_then(FrameLimiter::next_frame) {
+                    control_flow.set_wait_until(next);
+                    return;
+                }
                 // RedrawRequested will only trigger once, unless we manually
                 // request it.
                 window.request_redraw();
@@ -120,10 +698,48 @@ pub async fn run() -> Result<(), DynError> {
     });
 }
 
+/// Switch from windowed to borderless to exclusive fullscreen and back to windowed
+///
+/// Exclusive fullscreen uses the monitor's largest video mode and is skipped where there is none, e.g. on the web.
+fn cycle_fullscreen(window: &Window) {
+    let next = match window.fullscreen() {
+        None => Some(Fullscreen::Borderless(None)),
+        Some(Fullscreen::Borderless(_)) => window
+            .current_monitor()
+            .and_then(|monitor| {
+                monitor.video_modes().max_by_key(|mode| {
+                    let size = mode.size();
+                    (size.width * size.height, mode.refresh_rate_millihertz())
+                })
+            })
+            .map(Fullscreen::Exclusive),
+        Some(Fullscreen::Exclusive(_)) => None,
+    };
+    #[cfg(target_arch = "wasm32")]
+    let fullscreen = next.is_some();
+    window.set_fullscreen(next);
+
+    // The canvas is sized manually on the web, see `run`
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::dpi::{LogicalSize, PhysicalSize};
+        let screen = web_sys::window().and_then(|win| {
+            Some((
+                win.inner_width().ok()?.as_f64()?,
+                win.inner_height().ok()?.as_f64()?,
+            ))
+        });
+        match screen.filter(|_| fullscreen) {
+            Some((width, height)) => window.set_inner_size(LogicalSize::new(width, height)),
+            None => window.set_inner_size(PhysicalSize::new(450, 400)),
+        }
+    }
+}
+
 #[cfg(target_arch = "wasm32")]
 #[wasm_bindgen(start)]
 pub async fn wasm_main() {
     std::panic::set_hook(Box::new(console_error_panic_hook::hook));
     console_log::init_with_level(log::Level::Warn).expect("Could't initialize logger");
-    run().await.unwrap();
+    run(Options::default()).await.unwrap();
 }
@@ -1,6 +1,8 @@
-use solar_sim::run;
+use solar_sim::{crash, run, Options};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    env_logger::init();
-    pollster::block_on(run())
+    crash::install();
+
+    let options = Options::parse(std::env::args().skip(1))?;
+    pollster::block_on(run(options))
 }
@@ -0,0 +1,95 @@
+struct Step {
+    // Simulated seconds to advance the bodies by
+    dt: f32,
+    count: u32,
+}
+@group(0) @binding(0)
+var<uniform> step: Step;
+
+struct Body {
+    // Relative to the bodies' barycenter as uploaded, w is the body's mass, zero for bodies without one
+    position: vec4<f32>,
+    // As uploaded, w is one for bodies which move
+    velocity: vec4<f32>,
+    // w is one for bodies attracted by the others
+    acceleration: vec4<f32>,
+    // Distance moved since the upload, kept apart from the position so small steps aren't rounded away
+    moved: vec4<f32>,
+    // Change of velocity since the upload, kept apart from the velocity for the same reason
+    kicked: vec4<f32>,
+}
+@group(0) @binding(1)
+var<storage, read_write> bodies: array<Body>;
+
+const G: f32 = 6.67e-11;
+
+// Must match WORKGROUP_SIZE
+const TILE: u32 = 64u;
+
+// Positions and masses of the bodies currently attracting the workgroup
+var<workgroup> tile: array<vec4<f32>, TILE>;
+
+// First half of a leapfrog step, kicking by the last acceleration and drifting
+@compute @workgroup_size(64)
+fn cs_drift(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= step.count {
+        return;
+    }
+    var body = bodies[id.x];
+    if body.velocity.w == 0.0 {
+        return;
+    }
+    let kicked = body.kicked.xyz + body.acceleration.xyz * (step.dt / 2.0);
+    body.moved = vec4<f32>(body.moved.xyz + (body.velocity.xyz + kicked) * step.dt, 0.0);
+    body.kicked = vec4<f32>(kicked, 0.0);
+    bodies[id.x] = body;
+}
+
+// Current position of a body, w is its mass
+fn current(body: Body) -> vec4<f32> {
+    return vec4<f32>(body.position.xyz + body.moved.xyz, body.position.w);
+}
+
+// Second half of a leapfrog step, summing the gravity at the drifted positions and kicking by it
+@compute @workgroup_size(64)
+fn cs_kick(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(local_invocation_index) local: u32,
+) {
+    // Every invocation has to reach the barriers, even those past the last body
+    let position = current(bodies[min(id.x, step.count - 1u)]).xyz;
+    var acceleration = vec3<f32>(0.0);
+    for (var start = 0u; start < step.count; start += TILE) {
+        let other = start + local;
+        if other < step.count {
+            tile[local] = current(bodies[other]);
+        } else {
+            tile[local] = vec4<f32>(0.0);
+        }
+        workgroupBarrier();
+        for (var index = 0u; index < TILE; index++) {
+            let r = tile[index].xyz - position;
+            let r2 = dot(r, r);
+            // Skips the body itself
+            if r2 > 0.0 {
+                // Normalized first, cubing the distance would overflow
+                let inverse = inverseSqrt(r2);
+                acceleration += r * inverse * (G * tile[index].w * inverse * inverse);
+            }
+        }
+        workgroupBarrier();
+    }
+
+    if id.x >= step.count {
+        return;
+    }
+    let body = bodies[id.x];
+    var total = body.acceleration;
+    if total.w != 0.0 {
+        total = vec4<f32>(acceleration, total.w);
+        bodies[id.x].acceleration = total;
+    }
+    if body.velocity.w != 0.0 {
+        bodies[id.x].kicked = vec4<f32>(body.kicked.xyz + total.xyz * (step.dt / 2.0), 0.0);
+    }
+}
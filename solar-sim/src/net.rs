@@ -0,0 +1,649 @@
+//! Share a simulation over the network
+//!
+//! One instance hosts the authoritative simulation and streams state deltas
+//! to any number of observers, which send their camera and selection back in return.
+//! Observers don't simulate themselves but interpolate between the received states.
+//!
+//! Only available on native builds
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, ErrorKind, Read as _, Write as _};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+
+use cgmath::{Point3, Rad, Vector3, Zero};
+use log::{info, warn};
+use specs::join::MaybeJoin;
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage};
+
+use crate::control::Selection;
+use crate::physics::{Name, Planet, Position, Radius, SimSpeed, Velocity};
+use crate::render::camera::Camera;
+use crate::timer::Delta;
+
+/// Largest frame accepted from a peer
+const MAX_FRAME: usize = 16 * 1024 * 1024;
+
+/// Amount of unsent data after which a peer is considered too slow
+const MAX_BACKLOG: usize = 4 * 1024 * 1024;
+
+/// Cameras of the connected observers
+///
+/// Updated by [`NetHost`] system
+#[derive(Clone, Debug, Default)]
+pub struct RemoteCameras(pub BTreeMap<SocketAddr, Camera>);
+
+/// Bodies selected by the connected observers
+///
+/// Updated by [`NetHost`] system
+#[derive(Clone, Debug, Default)]
+pub struct RemoteSelections(pub BTreeMap<SocketAddr, Entity>);
+
+/// Network state of a single body
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BodyState {
+    pub id: u32,
+    pub position: Point3<f64>,
+    pub velocity: Vector3<f64>,
+    /// Zero for bodies without a [`Radius`]
+    pub radius: f32,
+}
+
+/// Messages exchanged between host and observers
+#[derive(Clone, Debug)]
+pub enum Message {
+    /// Sent by the host: the bodies which changed since the last state
+    State {
+        speed: f32,
+        /// Seconds until the next state is expected
+        interval: f32,
+        bodies: Vec<BodyState>,
+        /// Names of the bodies new to the observer, to match them with its own
+        names: Vec<(u32, String)>,
+        removed: Vec<u32>,
+    },
+
+    /// Sent by an observer: its current camera
+    Camera(Camera),
+
+    /// Sent by an observer: the id of the host's body it selected
+    Selection(Option<u32>),
+}
+
+impl Message {
+    const STATE: u8 = 0;
+    const CAMERA: u8 = 1;
+    const SELECTION: u8 = 2;
+
+    /// Append the message as length prefixed frame
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        let start = buffer.len();
+        buffer.extend_from_slice(&[0; 4]);
+        match self {
+            Message::State {
+                speed,
+                interval,
+                bodies,
+                names,
+                removed,
+            } => {
+                buffer.push(Self::STATE);
+                put_floats(buffer, &[*speed, *interval]);
+                buffer.extend_from_slice(&(bodies.len() as u32).to_le_bytes());
+                for body in bodies {
+                    buffer.extend_from_slice(&body.id.to_le_bytes());
+                    let (position, velocity) = (body.position, body.velocity);
+                    put_doubles(buffer, &[position.x, position.y, position.z]);
+                    put_doubles(buffer, &[velocity.x, velocity.y, velocity.z]);
+                    put_floats(buffer, &[body.radius]);
+                }
+                buffer.extend_from_slice(&(names.len() as u32).to_le_bytes());
+                for (id, name) in names {
+                    buffer.extend_from_slice(&id.to_le_bytes());
+                    buffer.extend_from_slice(&(name.len() as u32).to_le_bytes());
+                    buffer.extend_from_slice(name.as_bytes());
+                }
+                buffer.extend_from_slice(&(removed.len() as u32).to_le_bytes());
+                for id in removed {
+                    buffer.extend_from_slice(&id.to_le_bytes());
+                }
+            }
+            Message::Camera(camera) => {
+                buffer.push(Self::CAMERA);
+                let position = camera.position;
+                put_floats(buffer, &[position.x, position.y, position.z]);
+                put_floats(buffer, &[camera.yaw.0, camera.pitch.0]);
+            }
+            Message::Selection(id) => {
+                buffer.push(Self::SELECTION);
+                buffer.push(u8::from(id.is_some()));
+                buffer.extend_from_slice(&id.unwrap_or(0).to_le_bytes());
+            }
+        }
+        let length = (buffer.len() - start - 4) as u32;
+        buffer[start..start + 4].copy_from_slice(&length.to_le_bytes());
+    }
+
+    /// Parse a frame's content i.e. without its length prefix
+    pub fn decode(frame: &[u8]) -> io::Result<Self> {
+        let mut reader = Reader(frame);
+        let message = match reader.take::<1>()?[0] {
+            Self::STATE => {
+                let [speed, interval] = reader.floats::<2>()?;
+                let bodies = (0..reader.u32()?)
+                    .map(|_| {
+                        Ok(BodyState {
+                            id: reader.u32()?,
+                            position: reader.doubles::<3>()?.into(),
+                            velocity: reader.doubles::<3>()?.into(),
+                            radius: reader.f32()?,
+                        })
+                    })
+                    .collect::<io::Result<_>>()?;
+                let names = (0..reader.u32()?)
+                    .map(|_| Ok((reader.u32()?, reader.string()?)))
+                    .collect::<io::Result<_>>()?;
+                let removed = (0..reader.u32()?)
+                    .map(|_| reader.u32())
+                    .collect::<io::Result<_>>()?;
+                Message::State {
+                    speed,
+                    interval,
+                    bodies,
+                    names,
+                    removed,
+                }
+            }
+            Self::CAMERA => {
+                let position = reader.floats::<3>()?.into();
+                let [yaw, pitch] = reader.floats::<2>()?;
+                Message::Camera(Camera {
+                    position,
+                    yaw: Rad(yaw),
+                    pitch: Rad(pitch),
+                })
+            }
+            Self::SELECTION => {
+                let selected = reader.take::<1>()?[0] != 0;
+                let id = reader.u32()?;
+                Message::Selection(selected.then_some(id))
+            }
+            tag => return Err(invalid_data(format!("Unknown message: {tag}"))),
+        };
+        if reader.0.is_empty() {
+            Ok(message)
+        } else {
+            Err(invalid_data("Trailing data after message"))
+        }
+    }
+}
+
+fn put_floats(buffer: &mut Vec<u8>, floats: &[f32]) {
+    for float in floats {
+        buffer.extend_from_slice(&float.to_le_bytes());
+    }
+}
+
+fn put_doubles(buffer: &mut Vec<u8>, doubles: &[f64]) {
+    for double in doubles {
+        buffer.extend_from_slice(&double.to_le_bytes());
+    }
+}
+
+fn invalid_data(error: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, error)
+}
+
+/// Cursor over a frame's bytes
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take<const N: usize>(&mut self) -> io::Result<[u8; N]> {
+        if self.0.len() < N {
+            return Err(invalid_data("Message is too short"));
+        }
+        let (head, tail) = self.0.split_at(N);
+        self.0 = tail;
+        Ok(head.try_into().expect("Length has been checked"))
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    /// Utf-8 string prefixed by its length
+    fn string(&mut self) -> io::Result<String> {
+        let length = self.u32()? as usize;
+        if self.0.len() < length {
+            return Err(invalid_data("Message is too short"));
+        }
+        let (head, tail) = self.0.split_at(length);
+        self.0 = tail;
+        String::from_utf8(head.to_vec()).map_err(invalid_data)
+    }
+
+    fn f32(&mut self) -> io::Result<f32> {
+        self.take().map(f32::from_le_bytes)
+    }
+
+    fn floats<const N: usize>(&mut self) -> io::Result<[f32; N]> {
+        let mut floats = [0.0; N];
+        for float in &mut floats {
+            *float = self.f32()?;
+        }
+        Ok(floats)
+    }
+
+    fn f64(&mut self) -> io::Result<f64> {
+        self.take().map(f64::from_le_bytes)
+    }
+
+    fn doubles<const N: usize>(&mut self) -> io::Result<[f64; N]> {
+        let mut doubles = [0.0; N];
+        for double in &mut doubles {
+            *double = self.f64()?;
+        }
+        Ok(doubles)
+    }
+}
+
+/// Non-blocking tcp stream exchanging [`Message`]s
+pub struct Connection {
+    stream: TcpStream,
+    incoming: Vec<u8>,
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(stream: TcpStream) -> io::Result<Self> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            incoming: Vec::new(),
+            outgoing: Vec::new(),
+        })
+    }
+
+    /// Queue a message to be sent by the next [`flush`](Connection::flush)
+    pub fn send(&mut self, message: &Message) {
+        message.encode(&mut self.outgoing);
+    }
+
+    /// Write as much of the queued messages as possible without blocking
+    pub fn flush(&mut self) -> io::Result<()> {
+        while !self.outgoing.is_empty() {
+            match self.stream.write(&self.outgoing) {
+                Ok(0) => return Err(ErrorKind::WriteZero.into()),
+                Ok(written) => {
+                    self.outgoing.drain(..written);
+                }
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+        if self.outgoing.len() > MAX_BACKLOG {
+            Err(io::Error::new(ErrorKind::TimedOut, "Peer can't keep up"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Read all messages which have arrived completely
+    pub fn receive(&mut self) -> io::Result<Vec<Message>> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(ErrorKind::UnexpectedEof.into()),
+                Ok(read) => self.incoming.extend_from_slice(&buffer[..read]),
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) if error.kind() == ErrorKind::Interrupted => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let mut messages = Vec::new();
+        let mut start = 0;
+        while let Some(length) = self.incoming.get(start..start + 4) {
+            let length =
+                u32::from_le_bytes(length.try_into().expect("Slice has length 4")) as usize;
+            if length > MAX_FRAME {
+                return Err(invalid_data("Frame is too large"));
+            }
+            let Some(frame) = self.incoming.get(start + 4..start + 4 + length) else {
+                break;
+            };
+            messages.push(Message::decode(frame)?);
+            start += 4 + length;
+        }
+        self.incoming.drain(..start);
+        Ok(messages)
+    }
+}
+
+/// System hosting the simulation for observers
+///
+/// Streams changed [`Position`]s and [`Velocity`]s and updates [`RemoteCameras`] and [`RemoteSelections`].
+/// Should run thread local after the physics, see [`Simulation::systems`],
+/// so the state sent is the one just simulated.
+///
+/// [`Simulation::systems`]: crate::simulation::Simulation::systems
+pub struct NetHost {
+    listener: TcpListener,
+    peers: Vec<(SocketAddr, Connection)>,
+    last: BTreeMap<u32, BodyState>,
+}
+
+impl NetHost {
+    pub fn bind(addr: SocketAddr) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        info!("Hosting simulation on {}", listener.local_addr()?);
+        Ok(Self {
+            listener,
+            peers: Vec::new(),
+            last: BTreeMap::new(),
+        })
+    }
+}
+
+impl<'a> System<'a> for NetHost {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimSpeed>,
+        Read<'a, Delta>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Radius>,
+        ReadStorage<'a, Name>,
+        Write<'a, RemoteCameras>,
+        Write<'a, RemoteSelections>,
+    );
+
+    fn run(
+        &mut self,
+        (ent, speed, delta, pos, vel, radius, names, mut cameras, mut selections): Self::SystemData,
+    ) {
+        // Assume the next dispatch will take as long as the last one
+        let interval = delta.as_secs_f32();
+
+        // Accept new observers and bring them up to date
+        loop {
+            match self.listener.accept() {
+                Ok((stream, addr)) => match Connection::new(stream) {
+                    Ok(mut connection) => {
+                        info!("Observer {addr} connected");
+                        connection.send(&Message::State {
+                            speed: speed.0,
+                            interval,
+                            bodies: self.last.values().copied().collect(),
+                            names: (&ent, &names)
+                                .join()
+                                .filter(|(ent, _)| self.last.contains_key(&ent.id()))
+                                .map(|(ent, name)| (ent.id(), name.0.clone()))
+                                .collect(),
+                            removed: Vec::new(),
+                        });
+                        self.peers.push((addr, connection));
+                    }
+                    Err(error) => warn!("Failed to set up connection to {addr}: {error}"),
+                },
+                Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+                Err(error) => {
+                    warn!("Failed to accept observer: {error}");
+                    break;
+                }
+            }
+        }
+
+        let mut bodies = Vec::new();
+        let mut new_names = Vec::new();
+        let mut current = BTreeMap::new();
+        for (ent, pos, vel, radius, name) in (
+            &ent,
+            &pos,
+            MaybeJoin(&vel),
+            MaybeJoin(&radius),
+            MaybeJoin(&names),
+        )
+            .join()
+        {
+            let body = BodyState {
+                id: ent.id(),
+                position: pos.0,
+                velocity: vel.map_or(Vector3::zero(), |vel| vel.0),
+                radius: radius.map_or(0.0, |radius| radius.0),
+            };
+            if self.last.get(&body.id) != Some(&body) {
+                bodies.push(body);
+            }
+            if let Some(name) = name.filter(|_| !self.last.contains_key(&body.id)) {
+                new_names.push((body.id, name.0.clone()));
+            }
+            current.insert(body.id, body);
+        }
+        let removed = self
+            .last
+            .keys()
+            .filter(|id| !current.contains_key(id))
+            .copied()
+            .collect();
+        self.last = current;
+        let state = Message::State {
+            speed: speed.0,
+            interval,
+            bodies,
+            names: new_names,
+            removed,
+        };
+
+        self.peers.retain_mut(|(addr, connection)| {
+            let result = connection.receive().and_then(|messages| {
+                for message in messages {
+                    match message {
+                        Message::Camera(camera) => {
+                            cameras.0.insert(*addr, camera);
+                        }
+                        Message::Selection(id) => {
+                            match id.map(|id| ent.entity(id)).filter(|&e| ent.is_alive(e)) {
+                                Some(entity) => selections.0.insert(*addr, entity),
+                                None => selections.0.remove(addr),
+                            };
+                        }
+                        Message::State { .. } => {
+                            return Err(invalid_data("Observers can't send state"))
+                        }
+                    }
+                }
+                connection.send(&state);
+                connection.flush()
+            });
+            if let Err(error) = &result {
+                info!("Observer {addr} disconnected: {error}");
+                cameras.0.remove(addr);
+                selections.0.remove(addr);
+            }
+            result.is_ok()
+        });
+    }
+}
+
+/// System observing a simulation hosted by [`NetHost`]
+///
+/// Takes over the local bodies on the first state by matching their [`Name`]s with the host's,
+/// so both worlds built by [`build_planets`] agree whatever entity ids they got.
+/// They keep their static components like [`Mass`](crate::physics::Mass),
+/// local bodies the host lacks are deleted and the host's other bodies get only the streamed components and their name.
+/// The host's entity ids are mapped to the local entities from then on.
+/// Afterwards their [`Position`] is interpolated between the last two states,
+/// which puts the observer one state behind the host.
+/// Meant to replace the physics systems, see [`Simulation::observer_systems`].
+///
+/// [`Simulation::observer_systems`]: crate::simulation::Simulation::observer_systems
+/// [`build_planets`]: crate::physics::planets::build_planets
+pub struct NetClient {
+    connection: Option<Connection>,
+    bodies: HashMap<u32, RemoteBody>,
+    synced: bool,
+    interval: f32,
+    elapsed: f32,
+}
+
+/// Local entity of a body hosted by [`NetHost`] and its last two positions
+struct RemoteBody {
+    entity: Entity,
+    previous: Point3<f64>,
+    latest: Point3<f64>,
+}
+
+impl NetClient {
+    pub fn connect(addr: SocketAddr) -> io::Result<Self> {
+        let connection = Connection::new(TcpStream::connect(addr)?)?;
+        info!("Connected to {addr}");
+        Ok(Self {
+            connection: Some(connection),
+            bodies: HashMap::new(),
+            synced: false,
+            interval: 0.0,
+            elapsed: 0.0,
+        })
+    }
+}
+
+impl<'a> System<'a> for NetClient {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Delta>,
+        Read<'a, Camera>,
+        Read<'a, Selection>,
+        Write<'a, SimSpeed>,
+        WriteStorage<'a, Planet>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Radius>,
+        WriteStorage<'a, Name>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            ent,
+            delta,
+            camera,
+            selection,
+            mut speed,
+            mut planets,
+            mut pos,
+            mut vel,
+            mut radius,
+            mut local_names,
+        ): Self::SystemData,
+    ) {
+        let Some(connection) = &mut self.connection else {
+            return;
+        };
+
+        let selected = selection.0.and_then(|entity| {
+            self.bodies
+                .iter()
+                .find(|(_, body)| body.entity == entity)
+                .map(|(&id, _)| id)
+        });
+        connection.send(&Message::Camera(*camera));
+        connection.send(&Message::Selection(selected));
+        let messages = match connection.flush().and_then(|_| connection.receive()) {
+            Ok(messages) => messages,
+            Err(error) => {
+                warn!("Lost connection to host: {error}");
+                self.connection = None;
+                return;
+            }
+        };
+
+        self.elapsed += delta.as_secs_f32();
+        for message in messages {
+            let Message::State {
+                speed: host_speed,
+                interval,
+                bodies,
+                names,
+                removed,
+            } = message
+            else {
+                continue;
+            };
+
+            // The host's first dispatch may not have any bodies yet
+            if !self.synced && !bodies.is_empty() {
+                let hosted: HashMap<_, _> = names
+                    .iter()
+                    .map(|(id, name)| (name.as_str(), *id))
+                    .collect();
+                let positions: HashMap<_, _> =
+                    bodies.iter().map(|body| (body.id, body.position)).collect();
+                for (local, _, name) in (&ent, &pos, MaybeJoin(&local_names)).join() {
+                    let id = name.and_then(|name| hosted.get(name.0.as_str()));
+                    match id.and_then(|id| Some((*id, *positions.get(id)?))) {
+                        Some((id, position)) => {
+                            let body = RemoteBody {
+                                entity: local,
+                                previous: position,
+                                latest: position,
+                            };
+                            self.bodies.insert(id, body);
+                        }
+                        None => {
+                            let _ = ent.delete(local);
+                        }
+                    }
+                }
+                self.synced = true;
+            }
+
+            speed.0 = host_speed;
+            self.interval = interval;
+            self.elapsed = 0.0;
+            for body in self.bodies.values_mut() {
+                body.previous = body.latest;
+            }
+            for id in removed {
+                if let Some(body) = self.bodies.remove(&id) {
+                    let _ = ent.delete(body.entity);
+                }
+            }
+            for state in bodies {
+                let body = self.bodies.entry(state.id).or_insert_with(|| RemoteBody {
+                    entity: ent.create(),
+                    previous: state.position,
+                    latest: state.position,
+                });
+                body.latest = state.position;
+                let result = planets
+                    .insert(body.entity, Planet)
+                    .and(vel.insert(body.entity, Velocity(state.velocity)))
+                    .and(match state.radius {
+                        r if r > 0.0 => radius.insert(body.entity, Radius(r)),
+                        _ => Ok(radius.remove(body.entity)),
+                    });
+                if let Err(error) = result {
+                    warn!("Failed to update body {}: {error}", state.id);
+                }
+            }
+            for (id, name) in names {
+                if let Some(body) = self.bodies.get(&id) {
+                    let _ = local_names.insert(body.entity, Name(name));
+                }
+            }
+        }
+
+        let alpha = if self.interval > 0.0 {
+            (self.elapsed / self.interval).min(1.0)
+        } else {
+            1.0
+        };
+        for body in self.bodies.values() {
+            let position = body.previous + (body.latest - body.previous) * f64::from(alpha);
+            let _ = pos.insert(body.entity, Position(position));
+        }
+    }
+}
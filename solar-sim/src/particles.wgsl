@@ -0,0 +1,135 @@
+struct SwarmUniform {
+    center: vec3<f32>,
+    gm: f32,
+    color: vec3<f32>,
+    dt: f32,
+    size: vec2<f32>,
+    count: u32,
+    frame: u32,
+    emitter: vec3<f32>,
+    lifetime: f32,
+    direction: vec3<f32>,
+    speed: f32,
+    velocity: vec3<f32>,
+    spread: f32,
+}
+@group(0) @binding(0)
+var<uniform> simulated_swarm: SwarmUniform;
+
+// Compute shaders
+
+struct Particle {
+    // Relative to the swarm's center in meters, w is the particle's age in seconds
+    position: vec4<f32>,
+    velocity: vec4<f32>,
+}
+@group(0) @binding(1)
+var<storage, read_write> particles: array<Particle>;
+
+// Semi-implicit Euler step around the swarm's center
+@compute @workgroup_size(64)
+fn cs_orbit(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= simulated_swarm.count {
+        return;
+    }
+    var particle = particles[id.x];
+    let r = particle.position.xyz;
+    let distance = length(r);
+    let acceleration = -simulated_swarm.gm / (distance * distance * distance) * r;
+    let velocity = particle.velocity.xyz + acceleration * simulated_swarm.dt;
+    particle.velocity = vec4<f32>(velocity, 0.0);
+    particle.position = vec4<f32>(r + velocity * simulated_swarm.dt, particle.position.w);
+    particles[id.x] = particle;
+}
+
+// PCG hash
+fn hash(input: u32) -> u32 {
+    let state = input * 747796405u + 2891336453u;
+    let word = ((state >> ((state >> 28u) + 4u)) ^ state) * 277803737u;
+    return (word >> 22u) ^ word;
+}
+
+// Uniformly distributed in [-1, 1]
+fn random(seed: u32) -> f32 {
+    return f32(hash(seed)) / 2147483647.5 - 1.0;
+}
+
+// Move particles in a straight line and emit them again once they have outlived their lifetime
+@compute @workgroup_size(64)
+fn cs_tail(@builtin(global_invocation_id) id: vec3<u32>) {
+    if id.x >= simulated_swarm.count {
+        return;
+    }
+    var particle = particles[id.x];
+    let before = particle.position.w;
+    var age = before + simulated_swarm.dt;
+    if (before < 0.0 && age >= 0.0) || age >= simulated_swarm.lifetime {
+        age = age % simulated_swarm.lifetime;
+        let seed = hash(id.x ^ hash(simulated_swarm.frame));
+        let jitter = vec3<f32>(random(seed), random(seed + 1u), random(seed + 2u));
+        let direction = simulated_swarm.direction + jitter * simulated_swarm.spread;
+        let velocity = simulated_swarm.velocity + direction * simulated_swarm.speed;
+        particle.velocity = vec4<f32>(velocity, 0.0);
+        particle.position = vec4<f32>(simulated_swarm.emitter + velocity * age, age);
+    } else {
+        let position = particle.position.xyz + particle.velocity.xyz * simulated_swarm.dt;
+        particle.position = vec4<f32>(position, age);
+    }
+    particles[id.x] = particle;
+}
+
+// Vertex shader
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+@group(1) @binding(0)
+var<uniform> swarm: SwarmUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) fade: f32,
+}
+
+var<private> CORNERS: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, -1.0),
+    vec2<f32>(1.0, 1.0),
+    vec2<f32>(-1.0, 1.0),
+);
+
+// Meters per unit in render space
+const SCALE: f32 = 1e10;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, @location(0) position: vec4<f32>) -> VertexOutput {
+    var fade = 1.0;
+    if swarm.lifetime > 0.0 {
+        fade = 1.0 - position.w / swarm.lifetime;
+    }
+    // Collapse particles which haven't been emitted yet
+    var corner = CORNERS[index];
+    if fade > 1.0 {
+        corner = vec2<f32>(0.0);
+    }
+    let center = camera.view_proj * vec4<f32>(swarm.center + position.xyz / SCALE, 1.0);
+
+    var out: VertexOutput;
+    out.uv = corner;
+    out.fade = clamp(fade, 0.0, 1.0);
+    out.clip_position = center + vec4<f32>(corner * swarm.size * center.w, 0.0, 0.0);
+    return out;
+}
+
+// Fragment shader
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let falloff = max(1.0 - dot(in.uv, in.uv), 0.0);
+    return vec4<f32>(swarm.color * falloff * in.fade, 0.0);
+}
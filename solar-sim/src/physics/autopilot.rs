@@ -0,0 +1,102 @@
+//! Programs computing the burns for common maneuvers
+
+use std::f64::consts::{PI, TAU};
+
+use cgmath::{InnerSpace, Vector3};
+use specs::{Entity, Read, ReadStorage, System, Write, WriteStorage};
+
+use crate::control::{Controls, Selection};
+use crate::physics::maneuver::{directions, ManeuverNode};
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{SimTime, Velocity};
+
+/// Target resource, the body the [`Autopilot`] matches velocities with
+///
+/// Set to the [`Selection`] by T
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Target(pub Option<Entity>);
+
+/// Burn at the next apoapsis turning an elliptic orbit into a circle of the apoapsis' radius
+///
+/// The velocity at the apsides is perpendicular to the primary, so the burn is purely prograde.
+/// `None` for escape orbits, which have no apoapsis.
+pub fn circularize(orbit: &OrbitalElements, now: f64) -> Option<ManeuverNode> {
+    let apoapsis = orbit.apoapsis_distance()?;
+    let until = (PI - orbit.mean_anomaly()).rem_euclid(TAU) / orbit.mean_motion();
+    let speed = (orbit.mu * (2.0 / apoapsis - 1.0 / orbit.semi_major_axis)).sqrt();
+    let circular = (orbit.mu / apoapsis).sqrt();
+    Some(ManeuverNode {
+        time: now + until,
+        delta_v: Vector3::new(circular - speed, 0.0, 0.0),
+    })
+}
+
+/// Immediate burn changing a body's velocity by `delta_v` given in simulation coordinates,
+/// with the body's `orbit` orienting the burn
+pub fn burn_now(orbit: &OrbitalElements, now: f64, delta_v: Vector3<f64>) -> Option<ManeuverNode> {
+    let (r, v) = orbit.state();
+    let [prograde, normal, radial] = directions(r, v)?;
+    Some(ManeuverNode {
+        time: now,
+        delta_v: Vector3::new(
+            delta_v.dot(prograde),
+            delta_v.dot(normal),
+            delta_v.dot(radial),
+        ),
+    })
+}
+
+/// System running the autopilot programs requested through the [`Controls`] for the selected body
+///
+/// - C circularizes the orbit at the next apoapsis, see [`circularize`]
+/// - V kills the velocity relative to the [`Target`] right away
+///
+/// The programs only plan the burn by replacing the body's [`ManeuverNode`],
+/// the [`ExecuteManeuvers`](crate::physics::maneuver::ExecuteManeuvers) system carries it out.
+pub struct Autopilot;
+impl<'a> System<'a> for Autopilot {
+    type SystemData = (
+        Write<'a, Controls>,
+        Read<'a, Selection>,
+        Write<'a, Target>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, OrbitalElements>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, ManeuverNode>,
+    );
+
+    fn run(
+        &mut self,
+        (mut controls, selection, mut target, time, elements, vel, mut nodes): Self::SystemData,
+    ) {
+        let set_target = std::mem::take(&mut controls.set_target);
+        let circularize_requested = std::mem::take(&mut controls.circularize);
+        let match_requested = std::mem::take(&mut controls.match_velocity);
+        if set_target {
+            target.0 = selection.0;
+        }
+        let Some((entity, orbit)) = selection
+            .0
+            .and_then(|entity| Some((entity, elements.get(entity)?)))
+        else {
+            return;
+        };
+
+        let mut node = None;
+        if circularize_requested {
+            node = circularize(orbit, time.0);
+        }
+        if match_requested {
+            let relative = target
+                .0
+                .filter(|target| *target != entity)
+                .and_then(|target| Some(vel.get(target)?.0 - vel.get(entity)?.0));
+            if let Some(relative) = relative {
+                node = burn_now(orbit, time.0, relative);
+            }
+        }
+        if let Some(node) = node {
+            nodes.insert(entity, node).ok();
+        }
+    }
+}
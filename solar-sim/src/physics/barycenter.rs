@@ -0,0 +1,108 @@
+//! Center of mass of the whole system
+
+use cgmath::{EuclideanSpace, Point3, Vector3, Zero};
+use specs::join::MaybeJoin;
+use specs::{Join, Read, ReadStorage, System, Write, WriteStorage};
+
+use crate::physics::{Mass, Position, Velocity};
+
+/// Barycenter resource, the center of mass of every body with a [`Mass`]
+///
+/// Updated by [`FindBarycenter`] system
+#[derive(Copy, Clone, Debug)]
+pub struct Barycenter {
+    pub position: Point3<f64>,
+    pub velocity: Vector3<f64>,
+    /// Total mass of the bodies
+    pub mass: f64,
+}
+
+impl Default for Barycenter {
+    fn default() -> Self {
+        Self {
+            position: Point3::origin(),
+            velocity: Vector3::zero(),
+            mass: 0.0,
+        }
+    }
+}
+
+impl Barycenter {
+    /// Center of mass of `bodies` given by their mass, position and velocity
+    pub fn of(bodies: impl IntoIterator<Item = (f32, Point3<f64>, Vector3<f64>)>) -> Self {
+        let (mass, moment, momentum) = bodies.into_iter().fold(
+            (0.0, Vector3::zero(), Vector3::zero()),
+            |(mass, moment, momentum), (m, position, velocity)| {
+                let m = f64::from(m);
+                (
+                    mass + m,
+                    moment + position.to_vec() * m,
+                    momentum + velocity * m,
+                )
+            },
+        );
+        if mass > 0.0 {
+            Self {
+                position: Point3::from_vec(moment / mass),
+                velocity: momentum / mass,
+                mass,
+            }
+        } else {
+            Self::default()
+        }
+    }
+}
+
+/// Recentering resource, whether the [`Recenter`] system keeps the [`Barycenter`] at rest at the origin
+///
+/// Defaults to `false`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Recentering(pub bool);
+
+/// System computing the [`Barycenter`]
+pub struct FindBarycenter;
+impl<'a> System<'a> for FindBarycenter {
+    type SystemData = (
+        Write<'a, Barycenter>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (mut barycenter, mass, pos, vel): Self::SystemData) {
+        *barycenter = Barycenter::of(
+            (&mass, &pos, MaybeJoin(&vel))
+                .join()
+                .map(|(mass, pos, vel)| (mass.0, pos.0, vel.map_or(Vector3::zero(), |vel| vel.0))),
+        );
+    }
+}
+
+/// System moving every body by the [`Barycenter`]'s offset and velocity if [`Recentering`] is enabled
+///
+/// So the system doesn't drift away from the origin over long runs.
+/// Updates the [`Barycenter`] to the origin in turn.
+pub struct Recenter;
+impl<'a> System<'a> for Recenter {
+    type SystemData = (
+        Read<'a, Recentering>,
+        Write<'a, Barycenter>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (recentering, mut barycenter, mut pos, mut vel): Self::SystemData) {
+        if !recentering.0 {
+            return;
+        }
+        let offset = barycenter.position.to_vec();
+        for pos in (&mut pos).join() {
+            pos.0 -= offset;
+        }
+        for vel in (&mut vel).join() {
+            vel.0 -= barycenter.velocity;
+        }
+        barycenter.position = Point3::origin();
+        barycenter.velocity = Vector3::zero();
+    }
+}
@@ -0,0 +1,538 @@
+//! Methods advancing the bodies by one tick
+
+use std::fmt::Debug;
+use std::sync::Arc;
+
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use specs::{
+    DispatcherBuilder, Entities, Entity, Join, Read, ReadStorage, System, Write, WriteStorage,
+};
+
+use crate::physics::kepler::Kepler;
+use crate::physics::timestep::Tick;
+use crate::physics::{
+    attraction, Acceleration, Mass, Mechanics, Position, SimSpeed, SimTime, Velocity,
+};
+
+/// Method advancing the bodies by one tick
+///
+/// The physics dispatcher is built around the forces, see [`Simulation`](crate::simulation::Simulation):
+/// the integrator's systems added before them run on the last tick's [`Acceleration`],
+/// the ones added after them on the acceleration at the current positions.
+pub trait Integrator: Debug + Send + Sync {
+    /// Name to select the integrator by, e.g. on the command line
+    fn name(&self) -> &'static str;
+
+    /// Add the systems running before the forces are computed
+    fn before_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics
+    }
+
+    /// Add the systems running after the forces are computed
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static>;
+
+    /// Whether the [`Gravity`](crate::physics::Gravity) and plugin forces are computed on the cpu,
+    /// `false` for integrators computing the gravity on their own
+    fn cpu_forces(&self) -> bool {
+        true
+    }
+}
+
+/// Every integrator which comes with the simulation
+pub fn all() -> [Arc<dyn Integrator>; 6] {
+    [
+        Arc::new(Leapfrog),
+        Arc::new(Euler),
+        Arc::new(RungeKutta4),
+        Arc::new(Rkf45::default()),
+        Arc::new(Gpu),
+        Arc::new(Kepler),
+    ]
+}
+
+/// Look up an integrator coming with the simulation by its name,
+/// one of `leapfrog`, `euler`, `rk4`, `rkf45`, `gpu` or `kepler`
+pub fn by_name(name: &str) -> Option<Arc<dyn Integrator>> {
+    all()
+        .into_iter()
+        .find(|integrator| name.eq_ignore_ascii_case(integrator.name()))
+}
+
+/// Selected integrator resource, the method advancing the bodies each tick
+///
+/// Defaults to [`Leapfrog`]. Replacing it rebuilds the physics before the next tick.
+#[derive(Clone, Debug)]
+pub struct SelectedIntegrator(pub Arc<dyn Integrator>);
+
+impl Default for SelectedIntegrator {
+    fn default() -> Self {
+        Self(Arc::new(Leapfrog))
+    }
+}
+
+/// Symplectic kick-drift-kick integration by [`LeapfrogDrift`] and [`LeapfrogKick`],
+/// which keeps the energy of orbits from drifting over long runs
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Leapfrog;
+
+impl Integrator for Leapfrog {
+    fn name(&self) -> &'static str {
+        "leapfrog"
+    }
+
+    fn before_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(LeapfrogDrift, "leapfrog_drift", &[])
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(LeapfrogKick, "leapfrog_kick", &[])
+    }
+}
+
+/// Semi-implicit euler integration by [`Mechanics`], cheap but orbits drift
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Euler;
+
+impl Integrator for Euler {
+    fn name(&self) -> &'static str {
+        "euler"
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(Mechanics, "mechanics", &[])
+    }
+}
+
+impl Integrator for RungeKutta4 {
+    fn name(&self) -> &'static str {
+        "rk4"
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(RungeKutta4, "rk4", &[])
+    }
+}
+
+impl Integrator for Rkf45 {
+    fn name(&self) -> &'static str {
+        "rkf45"
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(*self, "rkf45", &[])
+    }
+}
+
+/// Leapfrog integration of the gravity between every pair of bodies in a compute shader,
+/// for far more bodies than the cpu can handle
+///
+/// The ticks are queued as [`GpuSteps`] for the [`NBodyPipeline`](crate::render::nbody::NBodyPipeline),
+/// which only exists while rendering. Plugin forces are ignored.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Gpu;
+
+impl Integrator for Gpu {
+    fn name(&self) -> &'static str {
+        "gpu"
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(QueueGpuSteps, "queue_gpu_steps", &[])
+    }
+
+    fn cpu_forces(&self) -> bool {
+        false
+    }
+}
+
+/// Steps queued for the GPU resource, see [`Gpu`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct GpuSteps {
+    pub steps: u32,
+
+    /// Simulated seconds of all steps together
+    pub seconds: f64,
+}
+
+/// System queueing every tick as a [`GpuSteps`]
+pub struct QueueGpuSteps;
+impl<'a> System<'a> for QueueGpuSteps {
+    type SystemData = (
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
+        Write<'a, GpuSteps>,
+    );
+
+    fn run(&mut self, (speed, tick, mut time, mut steps): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
+        steps.steps += 1;
+        steps.seconds += dt;
+    }
+}
+
+/// Add `scale` times the derivatives `dx` to the positions `x`
+fn offset(x: &[Point3<f64>], dx: &[Vector3<f64>], scale: f64) -> Vec<Point3<f64>> {
+    x.iter().zip(dx).map(|(x, dx)| x + dx * scale).collect()
+}
+
+/// Add `scale` times the derivatives `dv` to the velocities `v`
+fn offset_velocity(v: &[Vector3<f64>], dv: &[Vector3<f64>], scale: f64) -> Vec<Vector3<f64>> {
+    v.iter().zip(dv).map(|(v, dv)| v + dv * scale).collect()
+}
+
+/// Weighted sum of the derivatives `ds` added to the states `s` over a step of `h`
+fn combine<T>(s: &[T], ds: &[&[Vector3<f64>]], weights: &[f64], h: f64) -> Vec<T>
+where
+    T: Copy + std::ops::Add<Vector3<f64>, Output = T>,
+{
+    (0..s.len())
+        .map(|index| {
+            let sum = ds
+                .iter()
+                .zip(weights)
+                .fold(Vector3::zero(), |sum, (ds, weight)| {
+                    sum + ds[index] * *weight
+                });
+            s[index] + sum * h
+        })
+        .collect()
+}
+
+/// Moving bodies' states at the start of a tick and what accelerates them
+///
+/// Gravity is evaluated anew for every state,
+/// while the rest of the [`Acceleration`], e.g. from plugins, is kept constant.
+struct Bodies {
+    entities: Vec<Entity>,
+    x: Vec<Point3<f64>>,
+    v: Vec<Vector3<f64>>,
+    /// Attracting bodies with the index of their state if they move
+    attractors: Vec<(Entity, f32, Point3<f64>, Option<usize>)>,
+    /// Like the `Gravity` system, only bodies with a mass and acceleration are attracted
+    attracted: Vec<bool>,
+    /// Acceleration which isn't gravity
+    external: Vec<Vector3<f64>>,
+}
+
+impl Bodies {
+    fn gather(
+        ent: &Entities,
+        mass: &ReadStorage<Mass>,
+        acc: &ReadStorage<Acceleration>,
+        pos: &WriteStorage<Position>,
+        vel: &WriteStorage<Velocity>,
+    ) -> Self {
+        let (entities, (x, v)): (Vec<_>, (Vec<_>, Vec<_>)) = (ent, pos, vel)
+            .join()
+            .map(|(entity, pos, vel)| (entity, (pos.0, vel.0)))
+            .unzip();
+        let attractors = (ent, mass, pos)
+            .join()
+            .map(|(entity, mass, pos)| {
+                let index = entities.iter().position(|body| *body == entity);
+                (entity, mass.0, pos.0, index)
+            })
+            .collect();
+        let attracted = entities
+            .iter()
+            .map(|entity| mass.contains(*entity) && acc.contains(*entity))
+            .collect();
+        let mut bodies = Self {
+            entities,
+            x,
+            v,
+            attractors,
+            attracted,
+            external: Vec::new(),
+        };
+        bodies.external = bodies
+            .entities
+            .iter()
+            .enumerate()
+            .map(|(index, entity)| match acc.get(*entity) {
+                Some(acc) if bodies.attracted[index] => acc.0 - bodies.gravity(&bodies.x, index),
+                Some(acc) => acc.0,
+                None => Vector3::zero(),
+            })
+            .collect();
+        bodies
+    }
+
+    /// Gravity on the body at `index` with every moving body at `x`
+    fn gravity(&self, x: &[Point3<f64>], index: usize) -> Vector3<f64> {
+        self.attractors
+            .iter()
+            .filter(|(entity, ..)| *entity != self.entities[index])
+            .fold(Vector3::zero(), |sum, (_, mass, fixed, other)| {
+                let other = other.map_or(*fixed, |other| x[other]);
+                sum + attraction(x[index], other, *mass)
+            })
+    }
+
+    /// Acceleration of every moving body at `x`
+    fn accelerate(&self, x: &[Point3<f64>]) -> Vec<Vector3<f64>> {
+        (0..x.len())
+            .map(|index| {
+                if self.attracted[index] {
+                    self.gravity(x, index) + self.external[index]
+                } else {
+                    self.external[index]
+                }
+            })
+            .collect()
+    }
+}
+
+/// System opening a leapfrog tick, running before the [`Gravity`](crate::physics::Gravity) system
+///
+/// Kicks every [`Velocity`] by half a tick of the [`Acceleration`] left by the last tick
+/// and drifts every [`Position`] by a whole tick of the new velocity.
+pub struct LeapfrogDrift;
+impl<'a> System<'a> for LeapfrogDrift {
+    type SystemData = (
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
+        ReadStorage<'a, Acceleration>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (speed, tick, mut time, acc, mut vel, mut pos): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
+        for (acc, vel) in (&acc, &mut vel).join() {
+            vel.0 += acc.0 * (dt / 2.0);
+        }
+        for (vel, pos) in (&vel, &mut pos).join() {
+            pos.0 += vel.0 * dt;
+        }
+    }
+}
+
+/// System closing a leapfrog tick, running after the forces at the drifted positions are known
+///
+/// Kicks every [`Velocity`] by the other half tick of the new [`Acceleration`],
+/// which the next tick's [`LeapfrogDrift`] starts with.
+pub struct LeapfrogKick;
+impl<'a> System<'a> for LeapfrogKick {
+    type SystemData = (
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        ReadStorage<'a, Acceleration>,
+        WriteStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (speed, tick, acc, mut vel): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        for (acc, vel) in (&acc, &mut vel).join() {
+            vel.0 += acc.0 * (dt / 2.0);
+        }
+    }
+}
+
+/// Integrator and system advancing every body with the classic fourth order Runge-Kutta method
+///
+/// Gravity is evaluated at the intermediate states of the tick,
+/// while the rest of the [`Acceleration`], e.g. from plugins, is kept constant.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RungeKutta4;
+impl<'a> System<'a> for RungeKutta4 {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Acceleration>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (ent, speed, tick, mut time, mass, acc, mut vel, mut pos): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
+
+        let bodies = Bodies::gather(&ent, &mass, &acc, &pos, &vel);
+        let (x0, v0) = (&bodies.x, &bodies.v);
+        let accelerate = |x: &[Point3<f64>]| bodies.accelerate(x);
+
+        let (k1x, k1v) = (v0.clone(), accelerate(x0));
+        let k2x = offset_velocity(v0, &k1v, dt / 2.0);
+        let k2v = accelerate(&offset(x0, &k1x, dt / 2.0));
+        let k3x = offset_velocity(v0, &k2v, dt / 2.0);
+        let k3v = accelerate(&offset(x0, &k2x, dt / 2.0));
+        let k4x = offset_velocity(v0, &k3v, dt);
+        let k4v = accelerate(&offset(x0, &k3x, dt));
+
+        for (index, (pos, vel)) in (&mut pos, &mut vel).join().enumerate() {
+            pos.0 += (k1x[index] + k2x[index] * 2.0 + k3x[index] * 2.0 + k4x[index]) * (dt / 6.0);
+            vel.0 += (k1v[index] + k2v[index] * 2.0 + k3v[index] * 2.0 + k4v[index]) * (dt / 6.0);
+        }
+    }
+}
+
+/// Coefficients of the Runge-Kutta-Fehlberg stages' states
+const FEHLBERG: [&[f64]; 5] = [
+    &[1.0 / 4.0],
+    &[3.0 / 32.0, 9.0 / 32.0],
+    &[1932.0 / 2197.0, -7200.0 / 2197.0, 7296.0 / 2197.0],
+    &[439.0 / 216.0, -8.0, 3680.0 / 513.0, -845.0 / 4104.0],
+    &[
+        -8.0 / 27.0,
+        2.0,
+        -3544.0 / 2565.0,
+        1859.0 / 4104.0,
+        -11.0 / 40.0,
+    ],
+];
+
+/// Weights of the stages in the fifth order solution
+const FEHLBERG_5: [f64; 6] = [
+    16.0 / 135.0,
+    0.0,
+    6656.0 / 12825.0,
+    28561.0 / 56430.0,
+    -9.0 / 50.0,
+    2.0 / 55.0,
+];
+
+/// Weights of the stages in the difference between the fifth and fourth order solution
+const FEHLBERG_ERROR: [f64; 6] = [
+    1.0 / 360.0,
+    0.0,
+    -128.0 / 4275.0,
+    -2197.0 / 75240.0,
+    1.0 / 50.0,
+    2.0 / 55.0,
+];
+
+/// Most steps a tick is split into, the step doesn't shrink any further
+const MAX_STEPS: f64 = 4096.0;
+
+/// Integrator and system advancing every body with the adaptive Runge-Kutta-Fehlberg 4(5) method
+///
+/// Every tick is split into steps, whose size follows the difference between
+/// the fourth and fifth order solution: steps shrink during close encounters and grow otherwise.
+/// Like [`RungeKutta4`], only gravity is evaluated at the intermediate states.
+#[derive(Copy, Clone, Debug)]
+pub struct Rkf45 {
+    /// Largest error allowed per step, relative to how far it moves and accelerates the bodies
+    pub tolerance: f64,
+
+    /// Size of the next step in simulated seconds, a whole tick at first
+    step: Option<f64>,
+}
+
+impl Default for Rkf45 {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-4,
+            step: None,
+        }
+    }
+}
+
+impl<'a> System<'a> for Rkf45 {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Acceleration>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (ent, speed, tick, mut time, mass, acc, mut vel, mut pos): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
+        if dt <= 0.0 {
+            return;
+        }
+
+        let mut bodies = Bodies::gather(&ent, &mass, &acc, &pos, &vel);
+        let min_step = dt / MAX_STEPS;
+        let mut step = self.step.unwrap_or(dt).clamp(min_step, dt);
+        let mut done = 0.0;
+        let zero = vec![Vector3::zero(); bodies.x.len()];
+        while done < dt {
+            let h = step.min(dt - done);
+
+            let mut kx: Vec<Vec<Vector3<f64>>> = vec![bodies.v.clone()];
+            let mut kv = vec![bodies.accelerate(&bodies.x)];
+            for weights in FEHLBERG {
+                let dx: Vec<_> = kx.iter().map(Vec::as_slice).collect();
+                let dv: Vec<_> = kv.iter().map(Vec::as_slice).collect();
+                let x = combine(&bodies.x, &dx, weights, h);
+                kx.push(combine(&bodies.v, &dv, weights, h));
+                kv.push(bodies.accelerate(&x));
+            }
+            let dx: Vec<_> = kx.iter().map(Vec::as_slice).collect();
+            let dv: Vec<_> = kv.iter().map(Vec::as_slice).collect();
+            let x_error = combine(&zero, &dx, &FEHLBERG_ERROR, h);
+            let v_error = combine(&zero, &dv, &FEHLBERG_ERROR, h);
+
+            // The error compared to how far the step moves and accelerates each body
+            let error = (0..bodies.x.len())
+                .map(|index| {
+                    let accelerated = (kv[0][index].magnitude() * h).max(f64::MIN_POSITIVE);
+                    let moved = kx[0][index].magnitude() * h + accelerated * h / 2.0;
+                    (x_error[index].magnitude() / moved)
+                        .max(v_error[index].magnitude() / accelerated)
+                })
+                .fold(0.0, f64::max)
+                / self.tolerance;
+
+            let accepted = error <= 1.0 || h <= min_step;
+            if accepted {
+                bodies.x = combine(&bodies.x, &dx, &FEHLBERG_5, h);
+                bodies.v = combine(&bodies.v, &dv, &FEHLBERG_5, h);
+                done += h;
+            }
+            // Aim for an error just within the tolerance, without changing too abruptly
+            let factor = if error > 0.0 {
+                (0.9 * error.powf(-0.2)).clamp(0.2, 5.0)
+            } else {
+                5.0
+            };
+            // The last step of a tick may have been cut short, which says nothing about the next one
+            if !(accepted && h < step && factor >= 1.0) {
+                step = (h * factor).clamp(min_step, dt);
+            }
+        }
+        self.step = Some(step);
+
+        for (index, (pos, vel)) in (&mut pos, &mut vel).join().enumerate() {
+            pos.0 = bodies.x[index];
+            vel.0 = bodies.v[index];
+        }
+    }
+}
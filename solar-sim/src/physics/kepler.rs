@@ -0,0 +1,191 @@
+//! Bodies moving analytically along Kepler orbits instead of being integrated
+
+use cgmath::{Vector3, Zero};
+use specs::join::MaybeJoin;
+use specs::{
+    Component, DispatcherBuilder, Entities, Join, Read, ReadStorage, System, VecStorage, Write,
+    WriteStorage,
+};
+
+use crate::control::{Controls, Selection};
+use crate::physics::integrator::Integrator;
+use crate::physics::orbit::{primary, OrbitalElements};
+use crate::physics::timestep::Tick;
+use crate::physics::{Mass, Position, SimSpeed, SimTime, Velocity, G};
+
+/// Rails component, keeps a body on a fixed Kepler orbit around its primary whatever the integrator does
+///
+/// Toggled for the selected body by F8, see [`ToggleRails`].
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Rails {
+    pub orbit: OrbitalElements,
+
+    /// [`SimTime`] the body was at the orbit's true anomaly
+    pub epoch: f64,
+}
+
+/// System moving every body with [`Rails`] to where its orbit puts it at the current [`SimTime`]
+///
+/// Runs after the integrator, overwriting whatever it did to those bodies.
+/// Primaries are heavier than their bodies, so the heaviest bodies are moved first
+/// and bodies on rails around bodies on rails follow their updated primary.
+pub struct FollowRails;
+impl<'a> System<'a> for FollowRails {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, Rails>,
+        ReadStorage<'a, Mass>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (ent, time, rails, mass, mut pos, mut vel): Self::SystemData) {
+        let mut railed: Vec<_> = (&ent, &rails, MaybeJoin(&mass))
+            .join()
+            .map(|(entity, rails, mass)| (entity, *rails, mass.map_or(0.0, |mass| mass.0)))
+            .collect();
+        railed.sort_by(|(.., a), (.., b)| b.total_cmp(a));
+        for (entity, rails, _) in railed {
+            let Some(primary_pos) = pos.get(rails.orbit.primary).map(|pos| pos.0) else {
+                continue;
+            };
+            let primary_vel = vel
+                .get(rails.orbit.primary)
+                .map_or(Vector3::zero(), |vel| vel.0);
+            let (r, v) = rails.orbit.advance(time.0 - rails.epoch).state();
+            if let Some(pos) = pos.get_mut(entity) {
+                pos.0 = primary_pos + r;
+            }
+            if let Some(vel) = vel.get_mut(entity) {
+                vel.0 = primary_vel + v;
+            }
+        }
+    }
+}
+
+/// System putting the selected body on [`Rails`] or taking it off them when F8 was pressed
+///
+/// The body follows the osculating orbit it was on when it was put on rails.
+pub struct ToggleRails;
+impl<'a> System<'a> for ToggleRails {
+    type SystemData = (
+        Write<'a, Controls>,
+        Read<'a, Selection>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, OrbitalElements>,
+        WriteStorage<'a, Rails>,
+    );
+
+    fn run(&mut self, (mut controls, selection, time, elements, mut rails): Self::SystemData) {
+        if !std::mem::take(&mut controls.toggle_rails) {
+            return;
+        }
+        let Some(entity) = selection.0 else {
+            return;
+        };
+        if rails.remove(entity).is_none() {
+            if let Some(orbit) = elements.get(entity) {
+                rails
+                    .insert(
+                        entity,
+                        Rails {
+                            orbit: *orbit,
+                            epoch: time.0,
+                        },
+                    )
+                    .ok();
+            }
+        }
+    }
+}
+
+/// Analytical propagation by [`Kepler`]
+///
+/// Moves every body along the Kepler orbit around its primary it is on at the start of the tick.
+/// Bodies without a primary, i.e. the sun, move in a straight line.
+/// Primaries move before their bodies, which are placed relative to the primary's new state.
+pub struct KeplerStep;
+impl<'a> System<'a> for KeplerStep {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
+        ReadStorage<'a, Mass>,
+        WriteStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (ent, speed, tick, mut time, mass, mut pos, mut vel): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
+
+        let attractors: Vec<_> = (&ent, &mass, &pos)
+            .join()
+            .map(|(entity, mass, pos)| (entity, mass.0, pos.0))
+            .collect();
+        let mut bodies: Vec<_> = (&ent, &pos, &vel, MaybeJoin(&mass))
+            .join()
+            .map(|(entity, this_pos, this_vel, mass)| {
+                let mass = mass.map_or(0.0, |mass| mass.0);
+                let orbit = primary(mass, this_pos.0, attractors.iter().copied()).and_then(
+                    |(primary, primary_mass, primary_pos)| {
+                        let primary_vel = vel.get(primary).map_or(Vector3::zero(), |vel| vel.0);
+                        OrbitalElements::from_state(
+                            primary,
+                            this_pos.0 - primary_pos,
+                            this_vel.0 - primary_vel,
+                            f64::from(G) * (f64::from(primary_mass) + f64::from(mass)),
+                        )
+                    },
+                );
+                (entity, mass, this_pos.0, this_vel.0, orbit)
+            })
+            .collect();
+
+        // Heaviest first, so every primary already moved when its bodies are placed around it
+        bodies.sort_by(|(_, a, ..), (_, b, ..)| b.total_cmp(a));
+        for (entity, _, position, velocity, orbit) in bodies {
+            let state = match orbit {
+                Some(orbit) => {
+                    let (r, v) = orbit.advance(dt).state();
+                    pos.get(orbit.primary)
+                        .map(|pos| pos.0)
+                        .zip(vel.get(orbit.primary).map(|vel| vel.0))
+                        .map(|(primary_pos, primary_vel)| (primary_pos + r, primary_vel + v))
+                }
+                None => None,
+            };
+            let (position, velocity) = state.unwrap_or((position + velocity * dt, velocity));
+            if let Some(pos) = pos.get_mut(entity) {
+                pos.0 = position;
+            }
+            if let Some(vel) = vel.get_mut(entity) {
+                vel.0 = velocity;
+            }
+        }
+    }
+}
+
+/// Propagation along Kepler orbits by [`KeplerStep`], fast and free of drift but ignoring every body except the primary
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Kepler;
+
+impl Integrator for Kepler {
+    fn name(&self) -> &'static str {
+        "kepler"
+    }
+
+    fn after_forces(
+        &self,
+        physics: DispatcherBuilder<'static, 'static>,
+    ) -> DispatcherBuilder<'static, 'static> {
+        physics.with(KeplerStep, "kepler", &[])
+    }
+
+    fn cpu_forces(&self) -> bool {
+        false
+    }
+}
@@ -0,0 +1,188 @@
+//! Planned burns changing a body's velocity at a set time
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3, Zero};
+use specs::{
+    Component, Entities, Join, Read, ReadStorage, System, VecStorage, Write, WriteStorage,
+};
+
+use crate::control::{Controls, Selection};
+use crate::physics::kepler::Rails;
+use crate::physics::orbit::{primary, OrbitalElements};
+use crate::physics::{Mass, Position, SimTime, Velocity};
+
+/// Meters per second a single key press changes a node's delta-v by
+const DELTA_V_STEP: f64 = 10.0;
+
+/// Seconds a single key press moves a node by along an escape orbit,
+/// elliptic orbits are split into [`ORBIT_STEPS`] instead
+const TIME_STEP: f64 = 60.0 * 60.0;
+
+/// Number of key presses moving a node once around an elliptic orbit
+const ORBIT_STEPS: f64 = 72.0;
+
+/// Maneuver node component, a burn the body makes once the [`SimTime`] reaches the node's
+///
+/// Executed and removed by the [`ExecuteManeuvers`] system
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct ManeuverNode {
+    /// [`SimTime`] of the burn
+    pub time: f64,
+
+    /// Change of velocity in meters per second along the prograde, normal and radial directions
+    pub delta_v: Vector3<f64>,
+}
+
+impl ManeuverNode {
+    /// Change of velocity of a body at `r` moving at `v` relative to its primary, see [`directions`]
+    ///
+    /// Zero for bodies without directions.
+    pub fn burn(&self, r: Vector3<f64>, v: Vector3<f64>) -> Vector3<f64> {
+        directions(r, v).map_or(Vector3::zero(), |[prograde, normal, radial]| {
+            prograde * self.delta_v.x + normal * self.delta_v.y + radial * self.delta_v.z
+        })
+    }
+}
+
+/// Prograde, normal and radial direction of a body at `r` moving at `v` relative to its primary
+///
+/// Prograde is along the velocity, normal along the orbit's angular momentum
+/// and radial away from the primary, perpendicular to the other two.
+/// `None` for bodies falling straight at their primary, which have no orbit to orient them by.
+pub fn directions(r: Vector3<f64>, v: Vector3<f64>) -> Option<[Vector3<f64>; 3]> {
+    let normal = r.cross(v);
+    if normal.magnitude2() == 0.0 {
+        return None;
+    }
+    let prograde = v.normalize();
+    let normal = normal.normalize();
+    Some([prograde, normal, prograde.cross(normal)])
+}
+
+/// System applying every [`ManeuverNode`] which is due to its body's [`Velocity`] and removing it
+///
+/// The burn is oriented relative to the body's primary at that moment.
+/// Bodies on [`Rails`] continue on the orbit the burn put them on,
+/// or are taken off them if it has none.
+pub struct ExecuteManeuvers;
+impl<'a> System<'a> for ExecuteManeuvers {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Velocity>,
+        WriteStorage<'a, ManeuverNode>,
+        WriteStorage<'a, Rails>,
+    );
+
+    fn run(&mut self, (ent, time, mass, pos, mut vel, mut nodes, mut rails): Self::SystemData) {
+        let due: Vec<_> = (&ent, &nodes)
+            .join()
+            .filter(|(_, node)| node.time <= time.0)
+            .map(|(entity, node)| (entity, *node))
+            .collect();
+        if due.is_empty() {
+            return;
+        }
+        let attractors: Vec<_> = (&ent, &mass, &pos)
+            .join()
+            .map(|(entity, mass, pos)| (entity, mass.0, pos.0))
+            .collect();
+
+        for (entity, node) in due {
+            nodes.remove(entity);
+            let Some(position) = pos.get(entity).map(|pos| pos.0) else {
+                continue;
+            };
+            let this_mass = mass.get(entity).map_or(0.0, |mass| mass.0);
+            let (primary_pos, primary_vel) =
+                primary(this_mass, position, attractors.iter().copied()).map_or(
+                    (Point3::origin(), Vector3::zero()),
+                    |(primary, _, primary_pos)| {
+                        let primary_vel = vel.get(primary).map_or(Vector3::zero(), |vel| vel.0);
+                        (primary_pos, primary_vel)
+                    },
+                );
+            if let Some(vel) = vel.get_mut(entity) {
+                vel.0 += node.burn(position - primary_pos, vel.0 - primary_vel);
+            }
+
+            let Some(orbit) = rails.get(entity).map(|rails| rails.orbit) else {
+                continue;
+            };
+            let primary_vel = vel.get(orbit.primary).map_or(Vector3::zero(), |vel| vel.0);
+            let burned =
+                pos.get(orbit.primary)
+                    .zip(vel.get(entity))
+                    .and_then(|(primary_pos, vel)| {
+                        OrbitalElements::from_state(
+                            orbit.primary,
+                            position - primary_pos.0,
+                            vel.0 - primary_vel,
+                            orbit.mu,
+                        )
+                    });
+            match burned {
+                Some(orbit) => {
+                    rails
+                        .insert(
+                            entity,
+                            Rails {
+                                orbit,
+                                epoch: time.0,
+                            },
+                        )
+                        .ok();
+                }
+                None => {
+                    rails.remove(entity);
+                }
+            }
+        }
+    }
+}
+
+/// System placing, moving and tuning a [`ManeuverNode`] on the selected body's trajectory
+/// as requested through the [`Controls`]
+///
+/// New nodes are placed a step ahead on the body's orbit and can't be moved into the past.
+pub struct PlanManeuver;
+impl<'a> System<'a> for PlanManeuver {
+    type SystemData = (
+        Write<'a, Controls>,
+        Read<'a, Selection>,
+        Read<'a, SimTime>,
+        ReadStorage<'a, OrbitalElements>,
+        WriteStorage<'a, ManeuverNode>,
+    );
+
+    fn run(&mut self, (mut controls, selection, time, elements, mut nodes): Self::SystemData) {
+        let toggle = std::mem::take(&mut controls.toggle_maneuver);
+        let time_steps = std::mem::take(&mut controls.maneuver_time_steps);
+        let delta_v_steps = std::mem::take(&mut controls.maneuver_steps);
+        let Some(entity) = selection.0 else {
+            return;
+        };
+
+        let step = elements
+            .get(entity)
+            .and_then(OrbitalElements::period)
+            .map_or(TIME_STEP, |period| period / ORBIT_STEPS);
+        if toggle && nodes.remove(entity).is_none() {
+            nodes
+                .insert(
+                    entity,
+                    ManeuverNode {
+                        time: time.0 + step,
+                        delta_v: Vector3::zero(),
+                    },
+                )
+                .ok();
+        }
+        if let Some(node) = nodes.get_mut(entity) {
+            node.time = (node.time + step * f64::from(time_steps)).max(time.0);
+            node.delta_v += Vector3::from(delta_v_steps).map(f64::from) * DELTA_V_STEP;
+        }
+    }
+}
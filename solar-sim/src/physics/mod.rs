@@ -1,34 +1,56 @@
 //! Collection of components and system to simulate physics
 
+pub mod autopilot;
+pub mod barycenter;
+pub mod integrator;
+pub mod kepler;
+pub mod maneuver;
+pub mod octree;
+pub mod orbit;
 pub mod planets;
+pub mod radiation;
+pub mod strict;
+pub mod tides;
+pub mod timestep;
 
+use std::f64::consts::TAU;
 use std::fmt::Debug;
+#[cfg(not(target_arch = "wasm32"))]
+use std::num::NonZeroUsize;
+use std::panic::resume_unwind;
+use std::time::Duration;
 
-use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use cgmath::{InnerSpace, Point3, Quaternion, Rad, Rotation3, Vector3, Zero};
+use specs::join::MaybeJoin;
 use specs::{
-    Component, Entities, Join, NullStorage, Read, ReadStorage, System, VecStorage, WriteStorage,
+    Component, Entities, Join, NullStorage, Read, ReadStorage, System, VecStorage, Write,
+    WriteStorage,
 };
 
-use crate::timer::Delta;
+use crate::physics::octree::{Octree, OpeningAngle};
+use crate::physics::timestep::Tick;
 
-/// Position component
+/// Position component in meters
+///
+/// Double precision, since `f32` is only accurate to about half a million meters at the outer planets.
+/// Converted to `f32` relative to the camera for rendering, see [`Origin`](crate::render::Origin).
 #[derive(Copy, Clone, Debug, Component)]
 #[storage(VecStorage)]
-pub struct Position(pub Point3<f32>);
+pub struct Position(pub Point3<f64>);
 
 /// Velocity component
 ///
 /// Requires a [`Position`] component to affect anything
 #[derive(Copy, Clone, Debug, Component)]
 #[storage(VecStorage)]
-pub struct Velocity(pub Vector3<f32>);
+pub struct Velocity(pub Vector3<f64>);
 
 /// Acceleration component
 ///
 /// Requires a [`Velocity`] component to affect anything
 #[derive(Copy, Clone, Debug, Component)]
 #[storage(VecStorage)]
-pub struct Acceleration(pub Vector3<f32>);
+pub struct Acceleration(pub Vector3<f64>);
 
 /// Mass component
 ///
@@ -38,6 +60,69 @@ pub struct Acceleration(pub Vector3<f32>);
 #[storage(VecStorage)]
 pub struct Mass(pub f32);
 
+/// Radius component in meters
+///
+/// Used to scale a body when rendering it
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Radius(pub f32);
+
+/// Rotation component, the body's spin around its axis
+///
+/// Folded into the orientation the body is rendered with.
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Rotation {
+    /// Seconds of simulated time for one rotation, negative for retrograde spins
+    pub period: f32,
+
+    /// Current rotation around the axis
+    ///
+    /// Updated by [`Spin`] system
+    pub angle: Rad<f32>,
+
+    /// Obliquity, the angle between the axis and the normal of the orbital plane
+    pub tilt: Rad<f32>,
+}
+
+impl Rotation {
+    /// Orientation of the body, spun around its axis tilted towards the x axis
+    pub fn orientation(&self) -> Quaternion<f32> {
+        Quaternion::from_angle_x(self.tilt) * Quaternion::from_angle_y(self.angle)
+    }
+
+    /// Direction of the spin axis, the y axis tilted towards the z axis like in [`orientation`](Self::orientation)
+    ///
+    /// Computed with [`strict`] functions, since the forces depend on it.
+    pub fn axis(&self) -> Vector3<f64> {
+        let (sin, cos) = strict::sin_cos(f64::from(self.tilt.0));
+        Vector3::new(0.0, cos, sin)
+    }
+}
+
+/// Relativity resource, whether the [`Gravity`] system adds the first post-Newtonian correction
+///
+/// Only noticeable over long runs close to massive bodies, e.g. in the precession of Mercury's perihelion
+/// by 43 arcseconds per century.
+///
+/// Defaults to `false`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Relativity(pub bool);
+
+/// Oblateness component, the `J2` coefficient of a body flattened by its spin
+///
+/// Requires a [`Radius`] to affect others, the bulge lies around the [`Rotation`]'s axis
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Oblateness(pub f32);
+
+/// Name component
+///
+/// Shown as a label above the body
+#[derive(Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Name(pub String);
+
 /// Marker component for things to render
 #[derive(Copy, Clone, Debug, Default, Component)]
 #[storage(NullStorage)]
@@ -55,53 +140,264 @@ impl Default for SimSpeed {
     }
 }
 
+/// Simulated time resource, seconds since the simulation started
+///
+/// Updated by [`Mechanics`] system
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SimTime(pub f64);
+
+/// Determinism mode resource
+///
+/// If set, the physics advances by one tick of this step per dispatch
+/// instead of the measured [`Delta`](crate::timer::Delta),
+/// so the same scenario produces identical trajectories on every platform.
+/// Like the measured delta, the step is scaled by the [`SimSpeed`].
+///
+/// The forces and the [`Euler`](integrator::Euler), [`Leapfrog`](integrator::Leapfrog)
+/// and [`RungeKutta4`](integrator::RungeKutta4) integrators only use IEEE 754 operations
+/// with exactly specified results (`+`, `-`, `*`, `/` and `sqrt`, no fused multiply-adds) in a fixed order
+/// and the [`strict`] functions instead of the platform's transcendental ones,
+/// so with them the fixed step is all that is left to make a run reproducible across platforms.
+/// The step control of [`Rkf45`](integrator::Rkf45), the [`Kepler`](kepler::Kepler) integrator
+/// and bodies on [`Rails`](kepler::Rails) use the platform's `powf` and trigonometric functions
+/// and the [`Gpu`](integrator::Gpu) integrator the GPU's arithmetic, so they only reproduce on the same machine.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Determinism(pub Option<Duration>);
+
 /// System for **basic** mechanics
 ///
 /// Applies [`Acceleration`] to [`Velocity`]
-/// and [`Velocity`] to [`Position`].
+/// and [`Velocity`] to [`Position`] over one [`Tick`].
+/// Run by the [`Euler`](integrator::Euler) integrator.
 pub struct Mechanics;
 impl<'a> System<'a> for Mechanics {
     type SystemData = (
         Read<'a, SimSpeed>,
-        Read<'a, Delta>,
+        Read<'a, Tick>,
+        Write<'a, SimTime>,
         ReadStorage<'a, Acceleration>,
         WriteStorage<'a, Velocity>,
         WriteStorage<'a, Position>,
     );
 
-    fn run(&mut self, (speed, delta, acc, mut vel, mut pos): Self::SystemData) {
+    fn run(&mut self, (speed, tick, mut time, acc, mut vel, mut pos): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        time.0 += dt;
         for (acc, vel) in (&acc, &mut vel).join() {
-            vel.0 += acc.0 * delta.as_secs_f32() * speed.0;
+            vel.0 += acc.0 * dt;
         }
         for (vel, pos) in (&vel, &mut pos).join() {
-            pos.0 += vel.0 * delta.as_secs_f32() * speed.0;
+            pos.0 += vel.0 * dt;
+        }
+    }
+}
+
+/// System advancing every [`Rotation`] over one [`Tick`]
+pub struct Spin;
+impl<'a> System<'a> for Spin {
+    type SystemData = (
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        WriteStorage<'a, Rotation>,
+    );
+
+    fn run(&mut self, (speed, tick, mut rotation): Self::SystemData) {
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        for rotation in (&mut rotation).join() {
+            // Only the fraction of a turn, so huge steps don't lose the angle's precision
+            let turns = (dt / f64::from(rotation.period)).fract();
+            let angle = (f64::from(rotation.angle.0) + TAU * turns).rem_euclid(TAU);
+            rotation.angle = Rad(angle as f32);
         }
     }
 }
 
+/// Fewest bodies worth spawning another thread of the [`Gravity`] system for
+const BODIES_PER_THREAD: usize = 64;
+
+/// Gravity threads resource, how many threads the [`Gravity`] system spreads the bodies over
+///
+/// Defaults to the available parallelism, or a single thread on the web
+#[derive(Copy, Clone, Debug)]
+pub struct GravityThreads(pub usize);
+
+impl Default for GravityThreads {
+    fn default() -> Self {
+        #[cfg(not(target_arch = "wasm32"))]
+        let threads = std::thread::available_parallelism().map_or(1, NonZeroUsize::get);
+        #[cfg(target_arch = "wasm32")]
+        let threads = 1;
+        Self(threads)
+    }
+}
+
+/// Map every item with `f`, spreading them over up to `threads` scoped threads
+fn par_map<T: Sync, R: Send>(items: &[T], threads: usize, f: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let chunk = items.len().div_ceil(threads.max(1)).max(BODIES_PER_THREAD);
+    if chunk >= items.len() {
+        return items.iter().map(f).collect();
+    }
+    let f = &f;
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(f).collect::<Vec<_>>()))
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|panic| resume_unwind(panic)))
+            .collect()
+    })
+}
+
 /// System for gravity
+///
+/// Sums over every pair of bodies, or uses an [`Octree`] with a non-zero [`OpeningAngle`].
+/// Bodies with an [`Oblateness`] add the pull of their bulge on top,
+/// and [`Relativity`] adds a correction depending on the bodies' relative [`Velocity`].
+/// The attracted bodies are spread over [`GravityThreads`] threads.
 pub struct Gravity;
 impl<'a> System<'a> for Gravity {
     type SystemData = (
         Entities<'a>,
+        Read<'a, GravityThreads>,
+        Read<'a, OpeningAngle>,
+        Read<'a, Relativity>,
         ReadStorage<'a, Mass>,
         ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Oblateness>,
+        ReadStorage<'a, Radius>,
+        ReadStorage<'a, Rotation>,
         WriteStorage<'a, Acceleration>,
     );
 
-    fn run(&mut self, (ent, mass, pos, mut acc): Self::SystemData) {
-        for (this, _, this_pos, this_acc) in (&ent, &mass, &pos, &mut acc).join() {
-            this_acc.0 = Vector3::zero();
-            for (other, other_mass, other_pos) in (&ent, &mass, &pos).join() {
-                if this != other {
-                    let r = other_pos.0 - this_pos.0;
-                    let acc = G * other_mass.0 / r.magnitude().powi(2);
-                    this_acc.0 += acc * r.normalize();
-                }
-            }
+    fn run(
+        &mut self,
+        (
+            ent,
+            threads,
+            theta,
+            relativity,
+            mass,
+            pos,
+            vel,
+            oblateness,
+            radius,
+            rotation,
+            mut acc,
+        ): Self::SystemData,
+    ) {
+        let bodies: Vec<_> = (&ent, &mass, &pos)
+            .join()
+            .map(|(entity, mass, pos)| (entity, pos.0, mass.0))
+            .collect();
+        let oblate: Vec<_> = (
+            &ent,
+            &mass,
+            &pos,
+            &oblateness,
+            &radius,
+            MaybeJoin(&rotation),
+        )
+            .join()
+            .filter(|(_, _, _, oblateness, ..)| oblateness.0 != 0.0)
+            .map(|(entity, mass, pos, oblateness, radius, rotation)| {
+                let radius = f64::from(radius.0);
+                let j2 = f64::from(oblateness.0) * radius * radius;
+                let pole = rotation.map_or(Vector3::unit_y(), Rotation::axis);
+                (entity, pos.0, mass.0, j2, pole)
+            })
+            .collect();
+        let relativistic: Vec<_> = if relativity.0 {
+            (&ent, &mass, &pos, MaybeJoin(&vel))
+                .join()
+                .map(|(entity, mass, pos, vel)| {
+                    (
+                        entity,
+                        pos.0,
+                        vel.map_or(Vector3::zero(), |vel| vel.0),
+                        mass.0,
+                    )
+                })
+                .collect()
+        } else {
+            Vec::new()
+        };
+        let theta = theta.0;
+        let tree = (theta > 0.0).then(|| Octree::new(&bodies));
+        let attracted: Vec<_> = (&ent, &mass, &pos, MaybeJoin(&vel), &acc)
+            .join()
+            .map(|(entity, _, pos, vel, _)| {
+                (entity, pos.0, vel.map_or(Vector3::zero(), |vel| vel.0))
+            })
+            .collect();
+
+        let accelerations = par_map(&attracted, threads.0, |&(this, this_pos, this_vel)| {
+            let point_masses = match &tree {
+                Some(tree) => tree.acceleration(this, this_pos, theta),
+                None => bodies.iter().filter(|(other, ..)| *other != this).fold(
+                    Vector3::zero(),
+                    |sum, (_, other_pos, other_mass)| {
+                        sum + attraction(this_pos, *other_pos, *other_mass)
+                    },
+                ),
+            };
+            let bulges = oblate.iter().filter(|(other, ..)| *other != this).fold(
+                point_masses,
+                |sum, &(_, other_pos, other_mass, j2, pole)| {
+                    sum + bulge(this_pos, other_pos, other_mass, j2, pole)
+                },
+            );
+            relativistic
+                .iter()
+                .filter(|(other, ..)| *other != this)
+                .fold(bulges, |sum, &(_, other_pos, other_vel, other_mass)| {
+                    sum + post_newtonian(this_pos - other_pos, this_vel - other_vel, other_mass)
+                })
+        });
+        for ((_, _, this_acc), acceleration) in (&mass, &pos, &mut acc).join().zip(accelerations) {
+            this_acc.0 = acceleration;
         }
     }
 }
 
+/// Acceleration of a body at `this` towards a `mass` at `other`
+pub fn attraction(this: Point3<f64>, other: Point3<f64>, mass: f32) -> Vector3<f64> {
+    let r = other - this;
+    f64::from(G) * f64::from(mass) / r.magnitude2() * r.normalize()
+}
+
+/// Acceleration of a body at `this` by the equatorial bulge of a `mass` at `other`, on top of its [`attraction`]
+///
+/// `j2` is the [`Oblateness`] times the squared [`Radius`], `pole` the direction of the bulging body's axis.
+pub fn bulge(
+    this: Point3<f64>,
+    other: Point3<f64>,
+    mass: f32,
+    j2: f64,
+    pole: Vector3<f64>,
+) -> Vector3<f64> {
+    let r = this - other;
+    let r2 = r.magnitude2();
+    let z = r.dot(pole);
+    let factor = -1.5 * j2 * f64::from(G) * f64::from(mass) / (r2 * r2 * r2.sqrt());
+    (r * (1.0 - 5.0 * z * z / r2) + pole * (2.0 * z)) * factor
+}
+
+/// First post-Newtonian correction to the [`attraction`] of a body at `r` moving at `v` relative to a `mass`
+///
+/// The correction for a test particle in the mass' Schwarzschild metric.
+pub fn post_newtonian(r: Vector3<f64>, v: Vector3<f64>, mass: f32) -> Vector3<f64> {
+    let gm = f64::from(G) * f64::from(mass);
+    let r2 = r.magnitude2();
+    let distance = r2.sqrt();
+    let factor = gm / (C * C * r2 * distance);
+    (r * (4.0 * gm / distance - v.magnitude2()) + v * (4.0 * r.dot(v))) * factor
+}
+
 /// Gravitational constant
 pub const G: f32 = 6.67e-11;
+
+/// Speed of light in meters per second
+pub const C: f64 = 299_792_458.0;
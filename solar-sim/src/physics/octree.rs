@@ -0,0 +1,176 @@
+//! Barnes-Hut octree approximating the gravity of distant groups of bodies
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3, Zero};
+use specs::Entity;
+
+use crate::physics::attraction;
+
+/// Deepest level a node is split to, bodies closer together than that share a leaf
+const MAX_DEPTH: u32 = 32;
+
+/// Opening angle resource, the θ of the Barnes-Hut approximation used by [`Gravity`](crate::physics::Gravity)
+///
+/// Groups of bodies whose size seen from a body is smaller than it are attracting as one.
+/// Larger angles are faster but less accurate, `0.0` sums over every pair of bodies exactly.
+///
+/// Defaults to `0.0`
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OpeningAngle(pub f32);
+
+/// Cube of space with the total mass and center of mass of the bodies in it
+#[derive(Copy, Clone, Debug)]
+struct Node {
+    center: Point3<f64>,
+    /// Half the length of the cube's edges
+    half: f64,
+    mass: f32,
+    center_of_mass: Point3<f64>,
+    /// Index of the first of eight consecutive children, `0` for leaves
+    children: usize,
+    /// Only body in the leaf, `None` if it's empty or holds several bodies at [`MAX_DEPTH`]
+    body: Option<Entity>,
+}
+
+impl Node {
+    fn new(center: Point3<f64>, half: f64) -> Self {
+        Self {
+            center,
+            half,
+            mass: 0.0,
+            center_of_mass: center,
+            children: 0,
+            body: None,
+        }
+    }
+
+    fn add(&mut self, position: Point3<f64>, mass: f32) {
+        let total = self.mass + mass;
+        if total > 0.0 {
+            self.center_of_mass = Point3::from_vec(
+                (self.center_of_mass.to_vec() * f64::from(self.mass)
+                    + position.to_vec() * f64::from(mass))
+                    / f64::from(total),
+            );
+        }
+        self.mass = total;
+    }
+
+    /// Index of the octant `position` lies in, relative to the first child
+    fn octant(&self, position: Point3<f64>) -> usize {
+        (position.x >= self.center.x) as usize
+            | ((position.y >= self.center.y) as usize) << 1
+            | ((position.z >= self.center.z) as usize) << 2
+    }
+}
+
+/// Octree over a snapshot of massive bodies
+pub struct Octree {
+    nodes: Vec<Node>,
+}
+
+impl Octree {
+    /// Build the tree over the `bodies`' positions and masses
+    pub fn new(bodies: &[(Entity, Point3<f64>, f32)]) -> Self {
+        let (min, max) = bodies.iter().fold(
+            (
+                Point3::new(f64::INFINITY, f64::INFINITY, f64::INFINITY),
+                Point3::new(f64::NEG_INFINITY, f64::NEG_INFINITY, f64::NEG_INFINITY),
+            ),
+            |(min, max), (_, position, _)| {
+                (
+                    Point3::new(
+                        min.x.min(position.x),
+                        min.y.min(position.y),
+                        min.z.min(position.z),
+                    ),
+                    Point3::new(
+                        max.x.max(position.x),
+                        max.y.max(position.y),
+                        max.z.max(position.z),
+                    ),
+                )
+            },
+        );
+        let mut tree = if min.x.is_finite() {
+            let size = max - min;
+            Self {
+                nodes: vec![Node::new(
+                    min.midpoint(max),
+                    size.x.max(size.y).max(size.z) / 2.0 + 1.0,
+                )],
+            }
+        } else {
+            Self {
+                nodes: vec![Node::new(Point3::origin(), 1.0)],
+            }
+        };
+        for &(entity, position, mass) in bodies {
+            tree.insert(entity, position, mass);
+        }
+        tree
+    }
+
+    fn insert(&mut self, entity: Entity, position: Point3<f64>, mass: f32) {
+        let mut index = 0;
+        for depth in 0.. {
+            let node = self.nodes[index];
+            if node.children == 0 {
+                if node.mass == 0.0 && node.body.is_none() {
+                    self.nodes[index].body = Some(entity);
+                    self.nodes[index].add(position, mass);
+                    return;
+                }
+                if depth >= MAX_DEPTH || node.body.is_none() {
+                    self.nodes[index].body = None;
+                    self.nodes[index].add(position, mass);
+                    return;
+                }
+
+                // Split the leaf, moving its body down into one of the children
+                let children = self.nodes.len();
+                let half = node.half / 2.0;
+                for octant in 0..8 {
+                    let offset = Vector3::new(
+                        if octant & 1 != 0 { half } else { -half },
+                        if octant & 2 != 0 { half } else { -half },
+                        if octant & 4 != 0 { half } else { -half },
+                    );
+                    self.nodes.push(Node::new(node.center + offset, half));
+                }
+                let child = &mut self.nodes[children + node.octant(node.center_of_mass)];
+                child.body = node.body;
+                child.add(node.center_of_mass, node.mass);
+                self.nodes[index].children = children;
+                self.nodes[index].body = None;
+            }
+
+            let node = &mut self.nodes[index];
+            node.add(position, mass);
+            index = node.children + node.octant(position);
+        }
+    }
+
+    /// Acceleration of the `body` at `position` by every other body in the tree
+    pub fn acceleration(&self, body: Entity, position: Point3<f64>, theta: f32) -> Vector3<f64> {
+        let mut acceleration = Vector3::zero();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let node = &self.nodes[index];
+            if node.mass == 0.0 || node.body == Some(body) {
+                continue;
+            }
+            let distance = (node.center_of_mass - position).magnitude();
+            if node.children == 0 {
+                // Leaves at the deepest level may hold `body` together with others
+                if distance > 0.0 {
+                    acceleration += attraction(position, node.center_of_mass, node.mass);
+                }
+            } else if 2.0 * node.half < f64::from(theta) * distance {
+                acceleration += attraction(position, node.center_of_mass, node.mass);
+            } else {
+                stack.extend(node.children..node.children + 8);
+            }
+        }
+        acceleration
+    }
+}
@@ -0,0 +1,311 @@
+//! Kepler orbits of the bodies around their primaries
+
+use std::f64::consts::{PI, TAU};
+
+use cgmath::{InnerSpace, Matrix3, Point3, Rad, Vector3, Zero};
+use specs::join::MaybeJoin;
+use specs::{Component, Entities, Entity, Join, ReadStorage, System, VecStorage, WriteStorage};
+
+use crate::physics::{Mass, Position, Velocity, G};
+
+/// Eccentricities and node vectors shorter than this are treated as zero
+const EPSILON: f64 = 1e-10;
+
+/// Eccentricities closer than this to one are moved away from it,
+/// since parabolas have an infinite semi-major axis which none of the formulas handle
+const PARABOLIC: f64 = 1e-6;
+
+/// Newton iterations solving Kepler's equation at most
+const KEPLER_ITERATIONS: usize = 50;
+
+/// Osculating orbital elements component,
+/// the Kepler orbit a body would follow around its primary if nothing else attracted them
+///
+/// Angles are relative to the ecliptic, the plane the planets orbit in,
+/// with the x axis as reference direction.
+///
+/// Updated by [`Osculate`] system
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct OrbitalElements {
+    /// Body the orbit is around
+    pub primary: Entity,
+
+    /// Gravitational parameter `μ = G (M + m)` of the body and its primary
+    pub mu: f64,
+
+    /// Semi-major axis `a` in meters, negative for hyperbolic orbits
+    pub semi_major_axis: f64,
+
+    /// Eccentricity `e`, zero for circles and at least one for escape orbits
+    pub eccentricity: f64,
+
+    /// Inclination `i` to the ecliptic, more than 90° for retrograde orbits
+    pub inclination: Rad<f64>,
+
+    /// Longitude of the ascending node `Ω`
+    pub ascending_node: Rad<f64>,
+
+    /// Argument of periapsis `ω`, measured from the ascending node
+    pub periapsis: Rad<f64>,
+
+    /// True anomaly `ν`, the body's current angle from the periapsis
+    pub true_anomaly: Rad<f64>,
+}
+
+impl OrbitalElements {
+    /// Elements of a body at `r` moving at `v` relative to its primary,
+    /// with `mu` being the gravitational parameter `G (M + m)` of both
+    ///
+    /// `None` if the state doesn't describe an orbit, i.e. the body is at the primary or falls straight into it.
+    pub fn from_state(primary: Entity, r: Vector3<f64>, v: Vector3<f64>, mu: f64) -> Option<Self> {
+        let (r, v) = (ecliptic(r), ecliptic(v));
+        let distance = r.magnitude();
+        let h = r.cross(v);
+        if distance == 0.0 || h.magnitude2() == 0.0 || mu <= 0.0 {
+            return None;
+        }
+        let node = Vector3::new(-h.y, h.x, 0.0);
+        let e = (r * (v.magnitude2() - mu / distance) - v * r.dot(v)) / mu;
+        let (eccentricity, semi_major_axis) = if (e.magnitude() - 1.0).abs() < PARABOLIC {
+            // The nearest ellipse or hyperbola, with the parabola's semi-latus rectum `h² / μ`
+            let escaping = v.magnitude2() >= 2.0 * mu / distance;
+            let eccentricity = if escaping {
+                1.0 + PARABOLIC
+            } else {
+                1.0 - PARABOLIC
+            };
+            let semi_latus_rectum = h.magnitude2() / mu;
+            (
+                eccentricity,
+                semi_latus_rectum / (1.0 - eccentricity * eccentricity),
+            )
+        } else {
+            (e.magnitude(), 1.0 / (2.0 / distance - v.magnitude2() / mu))
+        };
+
+        let inclination = (h.z / h.magnitude()).clamp(-1.0, 1.0).acos();
+        let ascending_node = if node.magnitude() > EPSILON * h.magnitude() {
+            node.y.atan2(node.x)
+        } else {
+            0.0
+        };
+        // Measured from the node, or the reference direction for orbits in the ecliptic
+        let reference = if node.magnitude() > EPSILON * h.magnitude() {
+            node.normalize()
+        } else {
+            Vector3::unit_x()
+        };
+        let normal = h.normalize();
+        let angle = |to: Vector3<f64>| normal.cross(reference).dot(to).atan2(reference.dot(to));
+        let (periapsis, true_anomaly) = if eccentricity > EPSILON {
+            let periapsis = angle(e);
+            (periapsis, angle(r) - periapsis)
+        } else {
+            // Circles have no periapsis, so the anomaly is measured from the reference instead
+            (0.0, angle(r))
+        };
+
+        Some(Self {
+            primary,
+            mu,
+            semi_major_axis,
+            eccentricity,
+            inclination: Rad(inclination),
+            ascending_node: Rad(ascending_node.rem_euclid(TAU)),
+            periapsis: Rad(periapsis.rem_euclid(TAU)),
+            true_anomaly: Rad(true_anomaly.rem_euclid(TAU)),
+        })
+    }
+
+    /// Position and velocity relative to the primary
+    pub fn state(&self) -> (Vector3<f64>, Vector3<f64>) {
+        let e = self.eccentricity;
+        let (sin, cos) = self.true_anomaly.0.sin_cos();
+        let p = self.semi_major_axis * (1.0 - e * e);
+        let r = p / (1.0 + e * cos);
+        let speed = (self.mu / p).sqrt();
+        let rotation = Matrix3::from_angle_z(self.ascending_node)
+            * Matrix3::from_angle_x(self.inclination)
+            * Matrix3::from_angle_z(self.periapsis);
+        (
+            simulation(rotation * Vector3::new(r * cos, r * sin, 0.0)),
+            simulation(rotation * Vector3::new(-sin * speed, (e + cos) * speed, 0.0)),
+        )
+    }
+
+    /// The same orbit with the body at another true anomaly
+    pub fn at(&self, true_anomaly: Rad<f64>) -> Self {
+        Self {
+            true_anomaly,
+            ..*self
+        }
+    }
+
+    /// `count + 1` positions relative to the primary evenly spaced in true anomaly along the whole orbit
+    ///
+    /// Escape orbits are followed until they almost run along their asymptotes.
+    pub fn path(&self, count: usize) -> impl Iterator<Item = Vector3<f64>> + '_ {
+        let (from, to) = if self.eccentricity < 1.0 {
+            (-PI, PI)
+        } else {
+            let asymptote = (-1.0 / self.eccentricity).acos();
+            (-asymptote * 0.95, asymptote * 0.95)
+        };
+        (0..=count).map(move |i| {
+            let true_anomaly = from + (to - from) * i as f64 / count as f64;
+            self.at(Rad(true_anomaly)).state().0
+        })
+    }
+
+    /// Distance from the primary at the periapsis, the closest point of the orbit
+    pub fn periapsis_distance(&self) -> f64 {
+        self.semi_major_axis * (1.0 - self.eccentricity)
+    }
+
+    /// Distance from the primary at the apoapsis, the farthest point of the orbit,
+    /// `None` for escape orbits which have none
+    pub fn apoapsis_distance(&self) -> Option<f64> {
+        (self.eccentricity < 1.0).then_some(self.semi_major_axis * (1.0 + self.eccentricity))
+    }
+
+    /// Seconds per revolution, `None` for escape orbits
+    pub fn period(&self) -> Option<f64> {
+        (self.eccentricity < 1.0).then(|| TAU / self.mean_motion())
+    }
+
+    /// Mean anomaly `M`, which grows uniformly with time unlike the true anomaly
+    pub fn mean_anomaly(&self) -> f64 {
+        let e = self.eccentricity;
+        let half = self.true_anomaly.0 / 2.0;
+        if e < 1.0 {
+            let eccentric =
+                2.0 * ((1.0 - e).sqrt() * half.sin()).atan2((1.0 + e).sqrt() * half.cos());
+            eccentric - e * eccentric.sin()
+        } else {
+            let hyperbolic = 2.0 * (((e - 1.0) / (e + 1.0)).sqrt() * half.tan()).atanh();
+            e * hyperbolic.sinh() - hyperbolic
+        }
+    }
+
+    /// Mean motion `n` in radians per second
+    pub fn mean_motion(&self) -> f64 {
+        let a = self.semi_major_axis.abs();
+        (self.mu / (a * a * a)).sqrt()
+    }
+
+    /// Radius `a (m / M)^(2/5)` of the body's sphere of influence,
+    /// in which it rather than its primary dominates the motion of other bodies
+    pub fn sphere_of_influence(&self, mass: f32, primary_mass: f32) -> f64 {
+        let ratio = f64::from(mass) / f64::from(primary_mass);
+        self.semi_major_axis.abs() * ratio.powf(0.4)
+    }
+
+    /// The same orbit `dt` seconds later, solving Kepler's equation for the new true anomaly
+    pub fn advance(&self, dt: f64) -> Self {
+        let e = self.eccentricity;
+        let mean = self.mean_anomaly() + self.mean_motion() * dt;
+        let true_anomaly = if e < 1.0 {
+            let mean = mean.rem_euclid(TAU);
+            let mut eccentric = if e > 0.8 { PI } else { mean };
+            for _ in 0..KEPLER_ITERATIONS {
+                let step = (eccentric - e * eccentric.sin() - mean) / (1.0 - e * eccentric.cos());
+                eccentric -= step;
+                if step.abs() < 1e-12 {
+                    break;
+                }
+            }
+            let half = eccentric / 2.0;
+            2.0 * ((1.0 + e).sqrt() * half.sin()).atan2((1.0 - e).sqrt() * half.cos())
+        } else {
+            let mut hyperbolic = (mean / e).asinh();
+            for _ in 0..KEPLER_ITERATIONS {
+                let step =
+                    (e * hyperbolic.sinh() - hyperbolic - mean) / (e * hyperbolic.cosh() - 1.0);
+                hyperbolic -= step;
+                if step.abs() < 1e-12 {
+                    break;
+                }
+            }
+            2.0 * (((e + 1.0) / (e - 1.0)).sqrt() * (hyperbolic / 2.0).tanh()).atan()
+        };
+        Self {
+            true_anomaly: Rad(true_anomaly.rem_euclid(TAU)),
+            ..*self
+        }
+    }
+}
+
+/// Simulation vector in ecliptic coordinates, whose z axis the planets orbit counterclockwise around
+fn ecliptic(v: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(v.x, v.z, -v.y)
+}
+
+/// Ecliptic vector back in simulation coordinates, see [`ecliptic`]
+fn simulation(v: Vector3<f64>) -> Vector3<f64> {
+    Vector3::new(v.x, -v.z, v.y)
+}
+
+/// Primary of a body at `position` with some `mass`, out of bodies given by their entity, mass and position
+///
+/// The more massive body with the strongest tide on it, i.e. the largest `M / r³`.
+pub fn primary(
+    mass: f32,
+    position: Point3<f64>,
+    bodies: impl IntoIterator<Item = (Entity, f32, Point3<f64>)>,
+) -> Option<(Entity, f32, Point3<f64>)> {
+    bodies
+        .into_iter()
+        .filter(|(_, other, other_position)| *other > mass && *other_position != position)
+        .map(|body @ (_, other, other_position)| {
+            let r2 = (other_position - position).magnitude2();
+            (body, f64::from(other) / (r2 * r2.sqrt()))
+        })
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(body, _)| body)
+}
+
+/// System updating every moving body's [`OrbitalElements`]
+///
+/// Bodies without a primary, i.e. the sun, have their elements removed.
+pub struct Osculate;
+impl<'a> System<'a> for Osculate {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, OrbitalElements>,
+    );
+
+    fn run(&mut self, (ent, mass, pos, vel, mut elements): Self::SystemData) {
+        let attractors: Vec<_> = (&ent, &mass, &pos)
+            .join()
+            .map(|(entity, mass, pos)| (entity, mass.0, pos.0))
+            .collect();
+        for (entity, this_pos, this_vel, mass) in (&ent, &pos, &vel, MaybeJoin(&mass)).join() {
+            let mass = mass.map_or(0.0, |mass| mass.0);
+            let orbit = primary(mass, this_pos.0, attractors.iter().copied()).and_then(
+                |(primary, primary_mass, primary_pos)| {
+                    let primary_vel = vel.get(primary).map_or(Vector3::zero(), |vel| vel.0);
+                    OrbitalElements::from_state(
+                        primary,
+                        this_pos.0 - primary_pos,
+                        this_vel.0 - primary_vel,
+                        f64::from(G) * (f64::from(primary_mass) + f64::from(mass)),
+                    )
+                },
+            );
+            match orbit {
+                Some(orbit) => {
+                    elements
+                        .insert(entity, orbit)
+                        .expect("The entity was just joined so it is alive");
+                }
+                None => {
+                    elements.remove(entity);
+                }
+            }
+        }
+    }
+}
@@ -1,86 +1,269 @@
 //! Populate the world with our planets based on some data copied from wikipedia
 
-use cgmath::{Point3, Vector3, Zero};
+use cgmath::{Deg, EuclideanSpace, Point3, Rad, Vector3, Zero};
 use specs::{Builder, World, WorldExt};
 
-use crate::physics::{Acceleration, Mass, Planet, Position, Velocity};
+use crate::physics::barycenter::Barycenter;
+use crate::physics::radiation::{Area, Reflectivity};
+use crate::physics::{
+    Acceleration, Mass, Name, Oblateness, Planet, Position, Radius, Rotation, Velocity,
+};
+use crate::render::atmosphere::Atmosphere;
+use crate::render::clouds::Clouds;
+use crate::render::light::{Emissive, LightSource};
+use crate::render::material::Material;
+use crate::render::particles::{Belt, Comet};
+use crate::render::registry::{MaterialHandle, MeshHandle};
+use crate::render::rings::{RingTexture, Rings};
 
 /// Populate the world with our planets
+///
+/// Their barycenter starts at rest at the origin,
+/// since the data gives the sun no velocity to balance the planets' momentum and the system would drift away otherwise.
 pub fn build_planets(world: &mut World) {
+    world.register::<Planet>();
+    world.register::<Position>();
+    world.register::<Velocity>();
+    world.register::<Acceleration>();
     world.register::<Mass>();
+    world.register::<Radius>();
+    world.register::<Name>();
+    world.register::<Rotation>();
+    world.register::<Oblateness>();
+    world.register::<Area>();
+    world.register::<Reflectivity>();
+    world.register::<Material>();
+    world.register::<LightSource>();
+    world.register::<Emissive>();
+    world.register::<Rings>();
+    world.register::<Atmosphere>();
+    world.register::<Clouds>();
+    world.register::<Belt>();
+    world.register::<Comet>();
+    world.register::<MaterialHandle>();
+    world.register::<MeshHandle>();
+    let barycenter = Barycenter::of(
+        PLANETS
+            .iter()
+            .map(|planet| (planet.mass, planet.position, planet.velocity)),
+    );
     for planet in &PLANETS[..] {
-        world
-            .create_entity()
+        let builder = world.create_entity();
+        let builder = match planet.name {
+            "sun" => builder.with(LightSource).with(Emissive).with(Belt {
+                count: 20000,
+                inner: 329e9,
+                outer: 494e9,
+                thickness: 30e9,
+                seed: 13,
+                color: [0.02, 0.018, 0.015],
+            }),
+            "venus" => builder.with(Atmosphere {
+                height: 250e3,
+                color: [0.9, 0.75, 0.45],
+                density: 4.0,
+            }),
+            "earth" => builder
+                .with(Atmosphere {
+                    height: 100e3,
+                    color: [0.3, 0.55, 1.0],
+                    density: 1.5,
+                })
+                .with(Clouds {
+                    height: 50e3,
+                    period: 5.0 * 24.0 * 3600.0,
+                    seed: 12,
+                    opacity: 0.9,
+                    angle: Rad(0.0),
+                }),
+            "saturn" => builder.with(Rings {
+                inner: 74.658e6,
+                outer: 136.775e6,
+                tilt: Deg(26.73).into(),
+                texture: RingTexture {
+                    seed: 10,
+                    color: [0.85, 0.78, 0.62],
+                    opacity: 0.9,
+                },
+            }),
+            "uranus" => builder.with(Rings {
+                inner: 41.837e6,
+                outer: 51.149e6,
+                tilt: Deg(97.77).into(),
+                texture: RingTexture {
+                    seed: 11,
+                    color: [0.35, 0.35, 0.38],
+                    opacity: 0.5,
+                },
+            }),
+            "halley" => builder
+                .with(Comet {
+                    count: 4096,
+                    lifetime: 20.0 * 24.0 * 3600.0,
+                    dust_speed: 5e3,
+                    ion_speed: 50e3,
+                })
+                .with(Area(95e6))
+                .with(Reflectivity(0.04)),
+            _ => builder,
+        };
+        builder
             .with(Planet)
-            .with(Position(planet.position))
-            .with(Velocity(planet.velocity))
+            .with(Position(planet.position - barycenter.position.to_vec()))
+            .with(Velocity(planet.velocity - barycenter.velocity))
             .with(Acceleration(Vector3::zero()))
             .with(Mass(planet.mass))
+            .with(Radius(planet.radius))
+            .with(Rotation {
+                period: planet.period,
+                angle: Rad(0.0),
+                tilt: planet.obliquity.into(),
+            })
+            .with(Oblateness(planet.j2))
+            .with(Material(planet.name))
+            .with(Name(planet.name.to_string()))
             .build();
     }
 }
 
 /// Data of our planets copied from wikipedia
-const PLANETS: [PlanetData; 9] = [
+///
+/// New bodies are appended, so the entity ids `--uncertainty` selects bodies by stay the same.
+const PLANETS: [PlanetData; 12] = [
     PlanetData {
         name: "sun",
         position: Point3::new(0.0, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 0.0),
         mass: 1.989e30,
+        radius: 695.7e6,
+        period: 2192832.0,
+        obliquity: Deg(7.25),
+        j2: 2e-7,
     },
     PlanetData {
         name: "mercury",
         position: Point3::new(57.909e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 47.36e3),
         mass: 0.33011e24,
+        radius: 2.4397e6,
+        period: 5067014.0,
+        obliquity: Deg(0.034),
+        j2: 5.03e-5,
     },
     PlanetData {
         name: "venus",
         position: Point3::new(108.209e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 35.02e3),
         mass: 4.8675e24,
+        radius: 6.0518e6,
+        period: 20997360.0,
+        obliquity: Deg(177.36),
+        j2: 4.458e-6,
     },
     PlanetData {
         name: "earth",
         position: Point3::new(149.596e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 29.78e3),
         mass: 5.9724e24,
+        radius: 6.371e6,
+        period: 86164.1,
+        obliquity: Deg(23.44),
+        j2: 1.08263e-3,
     },
     PlanetData {
         name: "mars",
         position: Point3::new(227.923e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 24.07e3),
         mass: 0.64171e24,
+        radius: 3.3895e6,
+        period: 88642.7,
+        obliquity: Deg(25.19),
+        j2: 1.96045e-3,
     },
     PlanetData {
         name: "jupiter",
         position: Point3::new(778.570e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 13e3),
         mass: 1898.19e24,
+        radius: 69.911e6,
+        period: 35730.0,
+        obliquity: Deg(3.13),
+        j2: 14.736e-3,
     },
     PlanetData {
         name: "saturn",
         position: Point3::new(1433.529e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 9.68e3),
         mass: 568.34e24,
+        radius: 58.232e6,
+        period: 38018.0,
+        obliquity: Deg(26.73),
+        j2: 16.298e-3,
     },
     PlanetData {
         name: "uranus",
         position: Point3::new(2872.463e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 6.80e3),
         mass: 86.813e24,
+        radius: 25.362e6,
+        period: 62064.0,
+        obliquity: Deg(97.77),
+        j2: 3.343e-3,
     },
     PlanetData {
         name: "neptune",
         position: Point3::new(4495.060e9, 0.0, 0.0),
         velocity: Vector3::new(0.0, 0.0, 5.43e3),
         mass: 102.413e24,
+        radius: 24.622e6,
+        period: 57996.0,
+        obliquity: Deg(28.32),
+        j2: 3.411e-3,
+    },
+    PlanetData {
+        name: "halley",
+        position: Point3::new(-87.66e9, 0.0, 0.0),
+        velocity: Vector3::new(0.0, 16.8e3, -51.8e3),
+        mass: 2.2e14,
+        radius: 5.5e3,
+        period: 190080.0,
+        obliquity: Deg(0.0),
+        j2: 0.0,
+    },
+    // Tidally locked, so its sidereal day is its month. Orbits earth inclined by 5.145° to the ecliptic.
+    PlanetData {
+        name: "moon",
+        position: Point3::new(149.596e9 + 384.4e6, 0.0, 0.0),
+        velocity: Vector3::new(0.0, 91.65, 29.78e3 + 1.01788e3),
+        mass: 7.342e22,
+        radius: 1.7374e6,
+        period: 2360591.5,
+        obliquity: Deg(6.68),
+        j2: 2.03e-4,
+    },
+    // 400 km above earth, inclined by 51.6° so earth's oblateness turns its node back by about 5° a day
+    PlanetData {
+        name: "iss",
+        position: Point3::new(149.596e9 + 6.771e6, 0.0, 0.0),
+        velocity: Vector3::new(0.0, 6.0133e3, 29.78e3 + 4.7656e3),
+        mass: 4.2e5,
+        radius: 50.0,
+        period: 5544.0,
+        obliquity: Deg(0.0),
+        j2: 0.0,
     },
 ];
 
 struct PlanetData {
     name: &'static str,
-    position: Point3<f32>,
-    velocity: Vector3<f32>,
+    position: Point3<f64>,
+    velocity: Vector3<f64>,
     mass: f32,
+    radius: f32,
+    /// Sidereal rotation period in seconds
+    period: f32,
+    /// Axial tilt, retrograde spins are tilted by more than 90°
+    obliquity: Deg<f32>,
+    /// Second zonal harmonic of the gravity field, see [`Oblateness`]
+    j2: f32,
 }
@@ -0,0 +1,65 @@
+//! Radiation pressure of the sun's light on small bodies
+
+use std::f64::consts::PI;
+
+use cgmath::InnerSpace;
+use specs::join::MaybeJoin;
+use specs::{Component, Join, ReadStorage, System, VecStorage, WriteStorage};
+
+use crate::physics::{Acceleration, Mass, Position, C};
+use crate::render::light::LightSource;
+
+/// Luminosity of every [`LightSource`] in watts, the sun's
+pub const SOLAR_LUMINOSITY: f64 = 3.828e26;
+
+/// Area component, the cross-section in square meters a body exposes to sunlight
+///
+/// Requires a [`Mass`] and [`Acceleration`] to be pushed by the [`RadiationPressure`] system
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Area(pub f32);
+
+/// Reflectivity component, the fraction of the sunlight hitting an [`Area`] which is reflected
+///
+/// Reflected light pushes twice as hard as absorbed light.
+/// Without it, bodies absorb all of the light.
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Reflectivity(pub f32);
+
+/// System pushing every body with an [`Area`] away from the [`LightSource`]s
+///
+/// Adds to the [`Acceleration`], so it runs after the [`Gravity`](crate::physics::Gravity) system.
+pub struct RadiationPressure;
+impl<'a> System<'a> for RadiationPressure {
+    type SystemData = (
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Area>,
+        ReadStorage<'a, Reflectivity>,
+        WriteStorage<'a, Acceleration>,
+    );
+
+    fn run(&mut self, (light, pos, mass, area, reflectivity, mut acc): Self::SystemData) {
+        let lights: Vec<_> = (&light, &pos).join().map(|(_, pos)| pos.0).collect();
+        if lights.is_empty() {
+            return;
+        }
+        for (pos, mass, area, reflectivity, acc) in
+            (&pos, &mass, &area, MaybeJoin(&reflectivity), &mut acc).join()
+        {
+            let push = f64::from(area.0)
+                * (1.0 + reflectivity.map_or(0.0, |reflectivity| f64::from(reflectivity.0)))
+                / f64::from(mass.0);
+            for light in &lights {
+                let r = pos.0 - light;
+                let r2 = r.magnitude2();
+                if r2 > 0.0 {
+                    let pressure = SOLAR_LUMINOSITY / (4.0 * PI * r2 * C);
+                    acc.0 += r / r2.sqrt() * (pressure * push);
+                }
+            }
+        }
+    }
+}
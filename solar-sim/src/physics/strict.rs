@@ -0,0 +1,56 @@
+//! Transcendental functions built from exactly rounded operations only
+//!
+//! The platform's `sin` and `cos` may differ in their last bits between x86, ARM and wasm.
+//! These only use `+`, `-`, `*` and rounding in a fixed order, so they give identical results everywhere,
+//! see [`Determinism`](super::Determinism).
+//!
+//! The polynomials are the ones of fdlibm's kernels, accurate to about one ulp on `[-π/4, π/4]`.
+
+/// First 33 bits of π/2
+const FRAC_PI_2_HI: f64 = 1.570_796_326_734_125_614_17;
+
+/// π/2 minus [`FRAC_PI_2_HI`]
+const FRAC_PI_2_LO: f64 = 6.077_100_506_506_192_249_32e-11;
+
+const SIN: [f64; 6] = [
+    -1.666_666_666_666_663_243_48e-1,
+    8.333_333_333_322_489_461_24e-3,
+    -1.984_126_982_985_794_931_34e-4,
+    2.755_731_370_707_006_767_89e-6,
+    -2.505_076_025_340_686_341_95e-8,
+    1.589_690_995_211_550_102_21e-10,
+];
+
+const COS: [f64; 6] = [
+    4.166_666_666_666_660_190_37e-2,
+    -1.388_888_888_887_410_957_49e-3,
+    2.480_158_728_947_672_941_78e-5,
+    -2.755_731_435_139_066_330_35e-7,
+    2.087_572_321_298_174_827_90e-9,
+    -1.135_964_755_778_819_482_65e-11,
+];
+
+/// Evaluate a polynomial in `z` with Horner's method, highest coefficient last
+fn horner(coefficients: &[f64; 6], z: f64) -> f64 {
+    coefficients
+        .iter()
+        .rev()
+        .fold(0.0, |sum, coefficient| sum * z + coefficient)
+}
+
+/// Sine and cosine of an angle in radians
+///
+/// Accurate for angles up to about a million turns, beyond that the reduction to `[-π/4, π/4]` loses precision.
+pub fn sin_cos(x: f64) -> (f64, f64) {
+    let quadrant = (x * std::f64::consts::FRAC_2_PI).round();
+    let r = x - quadrant * FRAC_PI_2_HI - quadrant * FRAC_PI_2_LO;
+    let z = r * r;
+    let sin = r + r * z * horner(&SIN, z);
+    let cos = 1.0 - 0.5 * z + z * z * horner(&COS, z);
+    match quadrant.rem_euclid(4.0) as u8 {
+        0 => (sin, cos),
+        1 => (cos, -sin),
+        2 => (-sin, -cos),
+        _ => (-cos, sin),
+    }
+}
@@ -0,0 +1,151 @@
+//! Tidal torques slowly locking spins to orbits and moving the orbits
+
+use std::f64::consts::TAU;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, WriteStorage};
+
+use crate::physics::timestep::Tick;
+use crate::physics::{Acceleration, Mass, Position, Radius, Rotation, SimSpeed, Velocity, G};
+
+/// Love number `k2` of every body, how much its shape yields to a tide
+const LOVE_NUMBER: f64 = 0.3;
+
+/// Tidal quality factor `Q` of every body, how little of a tide's energy it dissipates
+const QUALITY: f64 = 100.0;
+
+/// Moment of inertia of every body relative to `m R²`, `0.4` for a uniform sphere
+const INERTIA: f64 = 0.4;
+
+/// Tides resource, how many times faster than the rest of the simulation the tides act
+///
+/// Locking a moon takes millions of years, so the effect is only visible sped up by many orders of magnitude.
+///
+/// Defaults to `0.0`, which disables the [`TidalTorque`] system
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tides(pub f32);
+
+/// Snapshot of a body taking part in the tides
+#[derive(Copy, Clone, Debug)]
+struct Body {
+    entity: Entity,
+    mass: f64,
+    radius: f64,
+    position: Point3<f64>,
+    velocity: Vector3<f64>,
+}
+
+/// System applying the tides between every spinning body and its primary over one [`Tick`]
+///
+/// The primary is the more massive body raising the strongest tide, i.e. with the largest `M / r³`.
+/// The bulge the primary raises on the body drags its [`Rotation`] towards the orbital period until it's locked.
+/// The bulge the body raises on a spinning primary pushes it along its orbit,
+/// raising it while the primary spins faster than the body orbits and lowering it otherwise,
+/// while despinning the primary in turn.
+///
+/// Adds to the [`Acceleration`], so it runs after the [`Gravity`](crate::physics::Gravity) system.
+pub struct TidalTorque;
+impl<'a> System<'a> for TidalTorque {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Tides>,
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Radius>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        WriteStorage<'a, Rotation>,
+        WriteStorage<'a, Acceleration>,
+    );
+
+    fn run(
+        &mut self,
+        (ent, tides, speed, tick, mass, radius, pos, vel, mut rotation, mut acc): Self::SystemData,
+    ) {
+        if tides.0 <= 0.0 {
+            return;
+        }
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0) * f64::from(tides.0);
+        let g = f64::from(G);
+        let k = 1.5 * LOVE_NUMBER / QUALITY * g;
+
+        let bodies: Vec<_> = (&ent, &mass, &radius, &pos, &vel)
+            .join()
+            .map(|(entity, mass, radius, pos, vel)| Body {
+                entity,
+                mass: f64::from(mass.0),
+                radius: f64::from(radius.0),
+                position: pos.0,
+                velocity: vel.0,
+            })
+            .collect();
+
+        // Change of each body's angular velocity, applied after every pair saw the same spins
+        let mut spin_changes = Vec::new();
+        for body in &bodies {
+            let Some(spin) = rotation.get(body.entity) else {
+                continue;
+            };
+            let Some(primary) =
+                bodies
+                    .iter()
+                    .filter(|other| other.mass > body.mass)
+                    .max_by(|a, b| {
+                        let tide = |other: &Body| {
+                            let r2 = (other.position - body.position).magnitude2();
+                            other.mass / (r2 * r2.sqrt())
+                        };
+                        tide(a).total_cmp(&tide(b))
+                    })
+            else {
+                continue;
+            };
+
+            let r = body.position - primary.position;
+            let v = body.velocity - primary.velocity;
+            let a2 = r.magnitude2();
+            let a6 = a2 * a2 * a2;
+            if a6 == 0.0 {
+                continue;
+            }
+
+            // Despinning by the primary's tide, signed around the body's own axis
+            let axis = spin.axis();
+            let n = r.cross(v).dot(axis) / a2;
+            let omega = angular_velocity(spin);
+            let rate = k * primary.mass * primary.mass * body.radius * body.radius * body.radius
+                / (INERTIA * body.mass * a6);
+            let locked = omega + (n - omega).clamp(-rate * dt, rate * dt);
+            spin_changes.push((body.entity, locked - omega));
+
+            // Orbit pushed by the body's tide on the spinning primary
+            let Some(primary_spin) = rotation.get(primary.entity) else {
+                continue;
+            };
+            let axis = primary_spin.axis();
+            let n = r.cross(v).dot(axis) / a2;
+            let lag = (angular_velocity(primary_spin) - n).signum();
+            let r2 = primary.radius * primary.radius;
+            let torque = lag * k * body.mass * body.mass * r2 * r2 * primary.radius / a6;
+            if let Some(acc) = acc.get_mut(body.entity) {
+                let along = axis.cross(r).normalize();
+                acc.0 += along * (torque / (body.mass * a2.sqrt()) * f64::from(tides.0));
+            }
+            let despin = torque / (INERTIA * primary.mass * r2);
+            spin_changes.push((primary.entity, -despin * dt));
+        }
+
+        for (entity, change) in spin_changes {
+            if let Some(spin) = rotation.get_mut(entity) {
+                let omega = angular_velocity(spin) + change;
+                spin.period = (TAU / omega) as f32;
+            }
+        }
+    }
+}
+
+/// Radians per second a body spins at, negative for retrograde spins
+fn angular_velocity(rotation: &Rotation) -> f64 {
+    TAU / f64::from(rotation.period)
+}
@@ -0,0 +1,204 @@
+//! Running the physics at a fixed rate independent of the frame rate
+
+use std::time::Duration;
+
+use cgmath::Point3;
+use log::info;
+use specs::{
+    Component, Dispatcher, DispatcherBuilder, Entities, Join, Read, ReadStorage, RunNow,
+    SystemData, VecStorage, World, WorldExt, Write, WriteStorage,
+};
+
+use crate::physics::integrator::{self, Integrator, SelectedIntegrator};
+use crate::physics::{Determinism, Position, SimSpeed};
+use crate::timer::Delta;
+
+/// Most ticks run in one dispatch, the physics falls behind instead of taking ever longer
+const MAX_TICKS: u32 = 8;
+
+/// Most sub-steps a tick is split into, beyond which the steps grow with the speed again
+const MAX_SUBSTEPS: u32 = 64;
+
+/// Fixed tick resource, the real time advanced by every tick of the physics
+///
+/// Defaults to 120 ticks per second
+#[derive(Copy, Clone, Debug)]
+pub struct FixedTick(pub Duration);
+
+impl Default for FixedTick {
+    fn default() -> Self {
+        Self(Duration::from_secs(1) / 120)
+    }
+}
+
+/// Max step resource, the most simulated seconds a single step of the physics should advance
+///
+/// Ticks advancing further at the current [`SimSpeed`] are split into equal sub-steps,
+/// so orbits stay as accurate at high speeds as at low ones.
+///
+/// Defaults to a minute
+#[derive(Copy, Clone, Debug)]
+pub struct MaxStep(pub f32);
+
+impl Default for MaxStep {
+    fn default() -> Self {
+        Self(60.0)
+    }
+}
+
+/// Tick resource, the real time advanced by the tick or sub-step currently running
+///
+/// Set by [`FixedTimestep`] for the physics systems, which multiply it with the [`SimSpeed`].
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Tick(pub Duration);
+
+/// Component of a [`Position`] at the last two ticks
+///
+/// Added by [`FixedTimestep`] to every entity with a position
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Ticked {
+    previous: Point3<f64>,
+    current: Point3<f64>,
+    /// Position written for rendering, which is replaced by `current` before the next tick
+    shown: Point3<f64>,
+}
+
+/// Thread local system running the physics systems in fixed [`Tick`]s
+///
+/// Runs as many ticks as fit into the time passed, carrying the rest over to the next dispatch.
+/// Afterwards every [`Position`] is interpolated between the last two ticks,
+/// so bodies move smoothly regardless of the frame rate.
+/// With [`Determinism`] set, exactly one tick of its step is run per dispatch instead.
+/// Either way, ticks are split into sub-steps as long as the [`MaxStep`].
+///
+/// Positions changed by other systems between dispatches are kept as they are.
+///
+/// The physics is built around the [`SelectedIntegrator`] and rebuilt whenever another one is selected.
+/// Only the resources and components of the [built-in](integrator::all) integrators are set up.
+pub struct FixedTimestep {
+    /// Builds the physics systems around an integrator
+    build: fn(&dyn Integrator) -> DispatcherBuilder<'static, 'static>,
+    physics: Dispatcher<'static, 'static>,
+    /// Name of the integrator `physics` was built around
+    integrator: &'static str,
+    /// Time passed which hasn't been ticked yet
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    pub fn new(build: fn(&dyn Integrator) -> DispatcherBuilder<'static, 'static>) -> Self {
+        let integrator = SelectedIntegrator::default().0;
+        Self {
+            build,
+            physics: build(&*integrator).build(),
+            integrator: integrator.name(),
+            accumulator: Duration::ZERO,
+        }
+    }
+}
+
+impl<'a> RunNow<'a> for FixedTimestep {
+    fn run_now(&mut self, world: &'a World) {
+        // Ticks continue from where the last one left off, not from the interpolated positions
+        {
+            let (mut positions, ticked) =
+                <(WriteStorage<'a, Position>, ReadStorage<'a, Ticked>)>::fetch(world);
+            for (position, ticked) in (&mut positions, &ticked).join() {
+                if position.0 == ticked.shown {
+                    position.0 = ticked.current;
+                }
+            }
+        }
+
+        let (tick, ticks, alpha) = match world.fetch::<Determinism>().0 {
+            Some(step) => (step, 1, 1.0),
+            None => {
+                let tick = world.fetch::<FixedTick>().0;
+                self.accumulator += **world.fetch::<Delta>();
+                let ticks = (self.accumulator.as_nanos() / tick.as_nanos().max(1)) as u32;
+                let ticks = if ticks > MAX_TICKS {
+                    self.accumulator = tick * MAX_TICKS;
+                    MAX_TICKS
+                } else {
+                    ticks
+                };
+                self.accumulator -= tick * ticks;
+                (
+                    tick,
+                    ticks,
+                    self.accumulator.as_secs_f32() / tick.as_secs_f32(),
+                )
+            }
+        };
+
+        let integrator = world.fetch::<SelectedIntegrator>().0.clone();
+        if integrator.name() != self.integrator {
+            info!("Integrating with {}", integrator.name());
+            self.physics = (self.build)(&*integrator).build();
+            self.integrator = integrator.name();
+        }
+
+        let step = tick.as_secs_f32() * world.fetch::<SimSpeed>().0.abs();
+        let substeps = ((step / world.fetch::<MaxStep>().0).ceil() as u32).clamp(1, MAX_SUBSTEPS);
+        world.fetch_mut::<Tick>().0 = tick / substeps;
+        for _ in 0..ticks {
+            {
+                let (entities, positions, mut ticked) = <(
+                    Entities<'a>,
+                    ReadStorage<'a, Position>,
+                    WriteStorage<'a, Ticked>,
+                )>::fetch(world);
+                for (entity, position) in (&entities, &positions).join() {
+                    let previous = position.0;
+                    let _ = ticked.insert(
+                        entity,
+                        Ticked {
+                            previous,
+                            current: previous,
+                            shown: previous,
+                        },
+                    );
+                }
+            }
+            for _ in 0..substeps {
+                self.physics.dispatch(world);
+            }
+        }
+
+        let (entities, mut positions, mut ticked) = <(
+            Entities<'a>,
+            WriteStorage<'a, Position>,
+            WriteStorage<'a, Ticked>,
+        )>::fetch(world);
+        for (entity, position) in (&entities, &mut positions).join() {
+            let Ok(entry) = ticked.entry(entity) else {
+                continue;
+            };
+            // Bodies added since the last tick stay where they are
+            let ticked = entry.or_insert(Ticked {
+                previous: position.0,
+                current: position.0,
+                shown: position.0,
+            });
+            ticked.current = position.0;
+            ticked.shown = ticked.previous + (ticked.current - ticked.previous) * f64::from(alpha);
+            position.0 = ticked.shown;
+        }
+    }
+
+    fn setup(&mut self, world: &mut World) {
+        for integrator in integrator::all() {
+            (self.build)(&*integrator).build().setup(world);
+        }
+        self.physics.setup(world);
+        world.register::<Ticked>();
+        <Read<'a, Delta> as SystemData>::setup(world);
+        <Read<'a, Determinism> as SystemData>::setup(world);
+        <Read<'a, FixedTick> as SystemData>::setup(world);
+        <Read<'a, MaxStep> as SystemData>::setup(world);
+        <Read<'a, SimSpeed> as SystemData>::setup(world);
+        <Write<'a, Tick> as SystemData>::setup(world);
+        <Read<'a, SelectedIntegrator> as SystemData>::setup(world);
+    }
+}
@@ -0,0 +1,337 @@
+//! Extend the simulation with sandboxed WebAssembly plugins
+//!
+//! Only available on native builds
+//!
+//! # ABI version 1
+//!
+//! A plugin is a WebAssembly module exporting its `memory`
+//! and `plugin_abi_version() -> i32` returning [`ABI_VERSION`].
+//! Its memory may grow up to [`MAX_MEMORY`] bytes.
+//!
+//! Bodies are passed as consecutive records of 7 little endian `f64`s:
+//! position, velocity and mass.
+//! Bodies without a velocity or mass have them set to zero.
+//!
+//! All further exports are optional:
+//! - `alloc(size: i32) -> i32` returns a pointer to `size` bytes the host may use to pass data,
+//!   required by all of the following
+//! - `spawn(out: i32, max: i32) -> i32` writes up to `max` bodies to `out` and returns their count,
+//!   called once after loading
+//! - `force(bodies: i32, count: i32, out: i32)` writes an acceleration (3 `f64`s) for every body to `out`,
+//!   called every tick after [`Gravity`](crate::physics::Gravity)
+//! - `analyze(bodies: i32, count: i32, dt: f64)` inspects the bodies after every tick
+//!
+//! Plugins may import `env.log(ptr: i32, len: i32)` to log a utf-8 string.
+
+use std::path::{Path, PathBuf};
+
+use cgmath::{Point3, Vector3, Zero};
+use log::{info, warn};
+use specs::join::MaybeJoin;
+use specs::{Builder, Join, Read, ReadStorage, System, World, WorldExt, Write, WriteStorage};
+use wasmtime::{
+    Caller, Config, Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder,
+    TypedFunc,
+};
+
+use crate::error::{CustomError, DynError};
+use crate::physics::timestep::Tick;
+use crate::physics::{Acceleration, Mass, Planet, Position, SimSpeed, Velocity};
+use crate::render::procedural::Surface;
+
+/// Version of the ABI described in the [module's docs](self)
+pub const ABI_VERSION: i32 = 1;
+
+/// Most bodies a plugin may spawn
+const MAX_SPAWN: usize = 1024;
+
+/// Instructions a plugin may execute per call before it is aborted
+const FUEL: u64 = 100_000_000;
+
+/// Bytes of memory a plugin may grow to
+pub const MAX_MEMORY: usize = 64 * 1024 * 1024;
+
+/// Elements a plugin's table may grow to
+const MAX_TABLE_ELEMENTS: usize = 10_000;
+
+/// Number of `f64`s per body
+const BODY_LEN: usize = 7;
+
+/// Loaded plugins
+#[derive(Default)]
+pub struct Plugins(pub Vec<Plugin>);
+
+/// A single loaded plugin
+pub struct Plugin {
+    name: String,
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: Option<TypedFunc<i32, i32>>,
+    spawn: Option<TypedFunc<(i32, i32), i32>>,
+    force: Option<TypedFunc<(i32, i32, i32), ()>>,
+    analyze: Option<TypedFunc<(i32, i32, f64), ()>>,
+    buffer: Option<(i32, usize)>,
+}
+
+/// Load plugins into a world and let them spawn their bodies
+pub fn load(world: &mut World, paths: &[PathBuf]) -> Result<(), DynError> {
+    let engine = Engine::new(Config::new().consume_fuel(true))?;
+    let mut linker = Linker::new(&engine);
+    linker.func_wrap(
+        "env",
+        "log",
+        |mut caller: Caller<'_, StoreLimits>, ptr: i32, len: i32| {
+            let Some(memory) = caller.get_export("memory").and_then(|e| e.into_memory()) else {
+                return;
+            };
+            // Longer than the memory would fail to read anyway, without allocating it first
+            if len < 0 || len as usize > memory.data_size(&caller) {
+                return;
+            }
+            let mut bytes = vec![0; len as usize];
+            if memory.read(&caller, ptr as usize, &mut bytes).is_ok() {
+                info!("[plugin] {}", String::from_utf8_lossy(&bytes));
+            }
+        },
+    )?;
+
+    world.register::<Surface>();
+    let mut plugins = Vec::new();
+    let mut seed = 0;
+    for path in paths {
+        let mut plugin = Plugin::load(&engine, &linker, path)?;
+        for body in plugin.spawn()? {
+            world
+                .create_entity()
+                .with(Planet)
+                .with(body.0)
+                .with(body.1)
+                .with(Acceleration(Vector3::zero()))
+                .with(body.2)
+                .with(Surface::rocky(seed))
+                .build();
+            seed += 1;
+        }
+        info!("Loaded plugin {}", plugin.name);
+        plugins.push(plugin);
+    }
+    world.insert(Plugins(plugins));
+    Ok(())
+}
+
+impl Plugin {
+    fn load(engine: &Engine, linker: &Linker<StoreLimits>, path: &Path) -> Result<Self, DynError> {
+        let module = Module::from_file(engine, path)?;
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(MAX_MEMORY)
+            .table_elements(MAX_TABLE_ELEMENTS)
+            .instances(1)
+            .build();
+        let mut store = Store::new(engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(FUEL)?;
+        let instance = linker.instantiate(&mut store, &module)?;
+
+        let name = path.display().to_string();
+        let version = instance
+            .get_typed_func::<(), i32>(&mut store, "plugin_abi_version")?
+            .call(&mut store, ())?;
+        if version != ABI_VERSION {
+            return Err(CustomError::from(format!(
+                "{name} uses abi version {version} instead of {ABI_VERSION}"
+            ))
+            .into());
+        }
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| CustomError::from(format!("{name} doesn't export its memory")))?;
+
+        Ok(Self {
+            alloc: instance.get_typed_func(&mut store, "alloc").ok(),
+            spawn: instance.get_typed_func(&mut store, "spawn").ok(),
+            force: instance.get_typed_func(&mut store, "force").ok(),
+            analyze: instance.get_typed_func(&mut store, "analyze").ok(),
+            buffer: None,
+            name,
+            store,
+            memory,
+        })
+    }
+
+    /// Get a pointer to at least `size` bytes in the plugin's memory
+    fn buffer(&mut self, size: usize) -> Result<i32, DynError> {
+        match self.buffer {
+            Some((ptr, capacity)) if capacity >= size => Ok(ptr),
+            _ => {
+                let alloc = self.alloc.as_ref().ok_or_else(|| {
+                    CustomError::from(format!("{} doesn't export alloc", self.name))
+                })?;
+                let len = i32::try_from(size).map_err(|_| {
+                    CustomError::from(format!("{} bytes don't fit into {}", size, self.name))
+                })?;
+                self.store.set_fuel(FUEL)?;
+                let ptr = alloc.call(&mut self.store, len)?;
+                self.buffer = Some((ptr, size));
+                Ok(ptr)
+            }
+        }
+    }
+
+    fn write_doubles(&mut self, ptr: i32, doubles: &[f64]) -> Result<(), DynError> {
+        let bytes: Vec<u8> = doubles.iter().flat_map(|f| f.to_le_bytes()).collect();
+        self.memory.write(&mut self.store, ptr as usize, &bytes)?;
+        Ok(())
+    }
+
+    fn read_doubles(&self, ptr: i32, len: usize) -> Result<Vec<f64>, DynError> {
+        let mut bytes = vec![0; len * 8];
+        self.memory.read(&self.store, ptr as usize, &mut bytes)?;
+        Ok(bytes
+            .chunks_exact(8)
+            .map(|chunk| f64::from_le_bytes(chunk.try_into().expect("Chunk has length 8")))
+            .collect())
+    }
+
+    fn spawn(&mut self) -> Result<Vec<(Position, Velocity, Mass)>, DynError> {
+        let Some(spawn) = self.spawn.clone() else {
+            return Ok(Vec::new());
+        };
+        let ptr = self.buffer(MAX_SPAWN * BODY_LEN * 8)?;
+        self.store.set_fuel(FUEL)?;
+        let count = spawn.call(&mut self.store, (ptr, MAX_SPAWN as i32))?;
+        let count = (count.max(0) as usize).min(MAX_SPAWN);
+        Ok(self
+            .read_doubles(ptr, count * BODY_LEN)?
+            .chunks_exact(BODY_LEN)
+            .map(|body| {
+                (
+                    Position(Point3::new(body[0], body[1], body[2])),
+                    Velocity(Vector3::new(body[3], body[4], body[5])),
+                    Mass(body[6] as f32),
+                )
+            })
+            .collect())
+    }
+
+    /// Write the bodies into the plugin's memory and return a pointer to them
+    fn write_bodies(&mut self, bodies: &[f64], extra: usize) -> Result<i32, DynError> {
+        let ptr = self.buffer((bodies.len() + extra) * 8)?;
+        self.write_doubles(ptr, bodies)?;
+        Ok(ptr)
+    }
+
+    fn force(&mut self, bodies: &[f64]) -> Result<Option<Vec<f64>>, DynError> {
+        let Some(force) = self.force.clone() else {
+            return Ok(None);
+        };
+        let count = bodies.len() / BODY_LEN;
+        let ptr = self.write_bodies(bodies, count * 3)?;
+        // The pointer comes from the plugin, so it might be anywhere
+        let out = i32::try_from(bodies.len() * 8)
+            .ok()
+            .and_then(|len| ptr.checked_add(len))
+            .ok_or_else(|| {
+                CustomError::from(format!("{} returned a buffer out of bounds", self.name))
+            })?;
+        self.store.set_fuel(FUEL)?;
+        force.call(&mut self.store, (ptr, count as i32, out))?;
+        self.read_doubles(out, count * 3).map(Some)
+    }
+
+    fn analyze(&mut self, bodies: &[f64], dt: f64) -> Result<(), DynError> {
+        let Some(analyze) = self.analyze.clone() else {
+            return Ok(());
+        };
+        let count = bodies.len() / BODY_LEN;
+        let ptr = self.write_bodies(bodies, 0)?;
+        self.store.set_fuel(FUEL)?;
+        analyze.call(&mut self.store, (ptr, count as i32, dt))?;
+        Ok(())
+    }
+}
+
+/// Flatten the bodies into the ABI's layout
+fn bodies(
+    pos: &ReadStorage<Position>,
+    vel: &ReadStorage<Velocity>,
+    mass: &ReadStorage<Mass>,
+) -> Vec<f64> {
+    (pos, MaybeJoin(vel), MaybeJoin(mass))
+        .join()
+        .flat_map(|(pos, vel, mass)| {
+            let pos = pos.0;
+            let vel = vel.map_or(Vector3::zero(), |vel| vel.0);
+            let mass = mass.map_or(0.0, |mass| f64::from(mass.0));
+            [pos.x, pos.y, pos.z, vel.x, vel.y, vel.z, mass]
+        })
+        .collect()
+}
+
+/// Drop plugins which failed, so a broken plugin doesn't spam the log
+fn retain_working(
+    plugins: &mut Plugins,
+    mut call: impl FnMut(&mut Plugin) -> Result<(), DynError>,
+) {
+    plugins.0.retain_mut(|plugin| match call(plugin) {
+        Ok(()) => true,
+        Err(error) => {
+            warn!("Unloading plugin {}: {error}", plugin.name);
+            false
+        }
+    });
+}
+
+/// System adding the plugins' forces to the [`Acceleration`]
+///
+/// Should run between [`Gravity`](crate::physics::Gravity) and [`Mechanics`](crate::physics::Mechanics)
+pub struct PluginForces;
+impl<'a> System<'a> for PluginForces {
+    type SystemData = (
+        Write<'a, Plugins>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+        WriteStorage<'a, Acceleration>,
+    );
+
+    fn run(&mut self, (mut plugins, pos, vel, mass, mut acc): Self::SystemData) {
+        if plugins.0.is_empty() {
+            return;
+        }
+        let bodies = bodies(&pos, &vel, &mass);
+        retain_working(&mut plugins, |plugin| {
+            let Some(forces) = plugin.force(&bodies)? else {
+                return Ok(());
+            };
+            let forces = forces.chunks_exact(3);
+            for ((_, acc), force) in (&pos, MaybeJoin(&mut acc)).join().zip(forces) {
+                if let Some(acc) = acc {
+                    acc.0 += Vector3::new(force[0], force[1], force[2]);
+                }
+            }
+            Ok(())
+        });
+    }
+}
+
+/// System letting the plugins analyze the bodies after each tick
+pub struct PluginAnalysis;
+impl<'a> System<'a> for PluginAnalysis {
+    type SystemData = (
+        Write<'a, Plugins>,
+        Read<'a, SimSpeed>,
+        Read<'a, Tick>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Mass>,
+    );
+
+    fn run(&mut self, (mut plugins, speed, tick, pos, vel, mass): Self::SystemData) {
+        if plugins.0.is_empty() {
+            return;
+        }
+        let bodies = bodies(&pos, &vel, &mass);
+        let dt = tick.0.as_secs_f64() * f64::from(speed.0);
+        retain_working(&mut plugins, |plugin| plugin.analyze(&bodies, dt));
+    }
+}
@@ -0,0 +1,126 @@
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+struct SurfaceUniform {
+    palette: array<vec4<f32>, 4>,
+    seed: u32,
+    // 0 for terrain, 1 for bands
+    pattern: u32,
+}
+@group(0) @binding(0)
+var<uniform> surface: SurfaceUniform;
+
+fn hash(p: vec3<f32>) -> f32 {
+    var q = fract(p * 0.3183099 + vec3<f32>(0.71, 0.113, 0.419));
+    q *= 17.0;
+    return fract(q.x * q.y * q.z * (q.x + q.y + q.z));
+}
+
+// Value noise in [0, 1]
+fn noise(p: vec3<f32>) -> f32 {
+    let i = floor(p);
+    let f = fract(p);
+    let u = f * f * (3.0 - 2.0 * f);
+    return mix(
+        mix(
+            mix(hash(i + vec3<f32>(0.0, 0.0, 0.0)), hash(i + vec3<f32>(1.0, 0.0, 0.0)), u.x),
+            mix(hash(i + vec3<f32>(0.0, 1.0, 0.0)), hash(i + vec3<f32>(1.0, 1.0, 0.0)), u.x),
+            u.y,
+        ),
+        mix(
+            mix(hash(i + vec3<f32>(0.0, 0.0, 1.0)), hash(i + vec3<f32>(1.0, 0.0, 1.0)), u.x),
+            mix(hash(i + vec3<f32>(0.0, 1.0, 1.0)), hash(i + vec3<f32>(1.0, 1.0, 1.0)), u.x),
+            u.y,
+        ),
+        u.z,
+    );
+}
+
+// Fractional brownian motion
+fn fbm(p: vec3<f32>) -> f32 {
+    var value = 0.0;
+    var amplitude = 0.5;
+    var q = p;
+    for (var octave = 0; octave < 6; octave++) {
+        value += amplitude * noise(q);
+        q = q * 2.03 + vec3<f32>(1.7, 9.2, 5.3);
+        amplitude *= 0.5;
+    }
+    return value;
+}
+
+// Noise in [0, 3] at a texture coordinate
+fn height(uv: vec2<f32>) -> f32 {
+    // Sample on the sphere, so the texture wraps without seams
+    let longitude = uv.x * 6.2831853;
+    let latitude = (0.5 - uv.y) * 3.1415927;
+    let direction = vec3<f32>(cos(latitude) * cos(longitude), sin(latitude), cos(latitude) * sin(longitude));
+
+    let offset = vec3<f32>(f32(surface.seed % 97u), f32(surface.seed % 89u), f32(surface.seed % 83u)) * 7.3;
+    let p = direction * 2.5 + offset;
+
+    if surface.pattern == 1u {
+        return bands(direction, p, offset);
+    }
+
+    // Domain warping
+    let warp = vec3<f32>(fbm(p), fbm(p + vec3<f32>(5.2, 1.3, 2.8)), fbm(p + vec3<f32>(1.7, 9.2, 3.4)));
+    return clamp(fbm(p + 4.0 * warp), 0.0, 1.0) * 3.0;
+}
+
+// Noise in [0, 3] varying mostly with the latitude
+fn bands(direction: vec3<f32>, p: vec3<f32>, offset: vec3<f32>) -> f32 {
+    // Turbulence bending the bands' edges into swirls
+    let turbulence = fbm(p * 2.0) - 0.5;
+    let latitude = direction.y + 0.08 * turbulence;
+
+    let value = fbm(vec3<f32>(latitude * 7.0, offset.xy));
+    // The sum of octaves rarely strays far from its mean, so stretch it for more contrast
+    return clamp((value - 0.5) * 2.0 + 0.5, 0.0, 1.0) * 3.0;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let t = height(in.uv);
+
+    let index = min(u32(t), 2u);
+    let color = mix(surface.palette[index], surface.palette[index + 1u], t - f32(index));
+    return vec4<f32>(color.rgb, 1.0);
+}
+
+// Height of the noise's peaks relative to the sphere's radius
+const BUMPINESS: f32 = 0.02;
+
+// Tangent space normals of the noise used as height,
+// the tangent follows u and the bitangent v
+@fragment
+fn fs_normal(in: VertexOutput) -> @location(0) vec4<f32> {
+    // One texel of the generated textures
+    let e = vec2<f32>(1.0 / 512.0, 1.0 / 256.0);
+    let latitude = (0.5 - in.uv.y) * 3.1415927;
+
+    // Arc length on the unit sphere covered by the differences
+    let du = 2.0 * e.x * 6.2831853 * max(cos(latitude), 0.05);
+    let dv = 2.0 * e.y * 3.1415927;
+    let slope_u = (height(in.uv + vec2<f32>(e.x, 0.0)) - height(in.uv - vec2<f32>(e.x, 0.0))) / du;
+    let slope_v = (height(in.uv + vec2<f32>(0.0, e.y)) - height(in.uv - vec2<f32>(0.0, e.y))) / dv;
+
+    let normal = normalize(vec3<f32>(-slope_u * BUMPINESS, -slope_v * BUMPINESS, 1.0));
+    return vec4<f32>(normal * 0.5 + 0.5, 1.0);
+}
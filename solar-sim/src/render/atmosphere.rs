@@ -0,0 +1,199 @@
+//! Hazy shells of air around planets
+
+use std::mem::size_of;
+
+use cgmath::Point3;
+use specs::{Component, VecStorage};
+use wgpu::{
+    vertex_attr_array, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation, BlendState,
+    Buffer, BufferAddress, BufferDescriptor, BufferUsages, ColorTargetState, DepthStencilState,
+    Device, FragmentState, PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::render::texture::Texture;
+use crate::render::transparent::{Shared, Transparent};
+use crate::render::Vertex;
+
+/// Atmosphere component of a planet
+///
+/// Rendered as a shell around the planet scattering the light passing through it.
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Atmosphere {
+    /// Thickness above the surface in meters
+    pub height: f32,
+
+    /// Color of the scattered light i.e. the sky's color
+    pub color: [f32; 3],
+
+    /// How opaque the atmosphere is when looking straight down
+    pub density: f32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AtmosphereRaw {
+    center: [f32; 3],
+    radius: f32,
+    color: [f32; 3],
+    outer: f32,
+    density: f32,
+}
+
+/// Draws every planet's [`Atmosphere`]
+///
+/// The scattered light is added on top of whatever is behind the shell.
+pub struct AtmospherePipeline {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    capacity: usize,
+    centers: Vec<Point3<f32>>,
+}
+
+impl AtmospherePipeline {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_layout: &BindGroupLayout,
+        light_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Atmosphere Shader"),
+            source: ShaderSource::Wgsl(include_str!("../atmosphere.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Atmosphere Pipeline Layout"),
+            bind_group_layouts: &[camera_layout, light_layout],
+            push_constant_ranges: &[],
+        });
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Atmosphere Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    Vertex::desc(),
+                    VertexBufferLayout {
+                        array_stride: size_of::<AtmosphereRaw>() as BufferAddress,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &vertex_attr_array![
+                            5 => Float32x3,
+                            6 => Float32,
+                            7 => Float32x3,
+                            8 => Float32,
+                            9 => Float32,
+                        ],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let capacity = 16;
+        Self {
+            pipeline,
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            centers: Vec::new(),
+        }
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Atmosphere Buffer"),
+            size: (capacity * size_of::<AtmosphereRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Upload the atmospheres of the current frame
+    ///
+    /// `atmospheres` yields each planet's atmosphere with its position and rendered radius in render space.
+    /// `scale` converts meters to render space including any exaggeration.
+    pub fn update<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        scale: f32,
+        atmospheres: impl Iterator<Item = (&'a Atmosphere, Point3<f32>, f32)>,
+    ) {
+        let data: Vec<_> = atmospheres
+            .map(|(atmosphere, center, radius)| AtmosphereRaw {
+                center: center.into(),
+                radius,
+                color: atmosphere.color,
+                outer: radius + atmosphere.height * scale,
+                density: atmosphere.density,
+            })
+            .collect();
+        if data.len() > self.capacity {
+            self.capacity = data.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+        self.centers = data.iter().map(|raw| raw.center.into()).collect();
+    }
+}
+
+/// Atmospheres are drawn using the planets' sphere mesh
+impl Transparent for AtmospherePipeline {
+    fn centers(&self) -> &[Point3<f32>] {
+        &self.centers
+    }
+
+    fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>) {
+        let (vertex_buffer, index_buffer, _) = shared.sphere;
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, shared.camera, &[]);
+        render_pass.set_bind_group(1, shared.light, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    }
+
+    fn draw_item<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        shared: &Shared<'a>,
+        index: usize,
+    ) {
+        let (_, _, num_indices) = shared.sphere;
+        let index = index as u32;
+        render_pass.draw_indexed(0..num_indices, 0, index..index + 1);
+    }
+}
@@ -0,0 +1,213 @@
+//! Axes in a corner of the screen turning with the camera
+
+use std::mem::size_of;
+
+use cgmath::{Matrix4, Point3, SquareMatrix, Vector3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BlendState,
+    Buffer, BufferAddress, BufferUsages, ColorTargetState, CommandEncoder, Device, FragmentState,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat, TextureView,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::render::camera::{Camera, CameraUniform, OPENGL_TO_WGPU_MATRIX};
+use crate::render::text::{Align, Text};
+
+/// Width and height of the viewport the axes are drawn into in pixels
+const SIZE: f32 = 96.0;
+
+/// Pixels between the viewport and the screen's edges
+const MARGIN: f32 = 8.0;
+
+/// Half the width of the viewport in units of the axes' length, leaving room for their labels
+const EXTENT: f32 = 1.4;
+
+/// Height of the axes' labels in pixels
+const LABEL_SIZE: f32 = 14.0;
+
+/// Direction, color and name of each axis
+///
+/// Y is the ecliptic plane's normal.
+const AXES: [([f32; 3], [f32; 3], &str); 3] = [
+    ([1.0, 0.0, 0.0], [1.0, 0.3, 0.3], "X"),
+    ([0.0, 1.0, 0.0], [0.3, 1.0, 0.3], "Y"),
+    ([0.0, 0.0, 1.0], [0.4, 0.5, 1.0], "Z"),
+];
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct AxisVertexRaw {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+/// Draws the world's axes into the bottom right corner, rotated like the camera sees them
+///
+/// Keeps the orientation of the ecliptic plane visible while flying freely.
+pub struct AxesGizmo {
+    pipeline: RenderPipeline,
+    vertex_buffer: Buffer,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    /// Top left corner of the viewport, `None` if the screen is too small for it
+    viewport: Option<[f32; 2]>,
+}
+
+impl AxesGizmo {
+    /// Create the pipeline drawing to the surface, which is of `format`
+    pub fn new(device: &Device, format: TextureFormat, camera_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Axes Shader"),
+            source: ShaderSource::Wgsl(include_str!("../lines.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Axes Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Axes Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<AxisVertexRaw>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let vertices: Vec<_> = AXES
+            .iter()
+            .flat_map(|&(direction, color, _)| {
+                [
+                    AxisVertexRaw {
+                        position: [0.0; 3],
+                        color,
+                    },
+                    AxisVertexRaw {
+                        position: direction,
+                        color,
+                    },
+                ]
+            })
+            .collect();
+        let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Axes Vertex Buffer"),
+            contents: bytemuck::cast_slice(&vertices),
+            usage: BufferUsages::VERTEX,
+        });
+
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Axes Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
+                Matrix4::identity(),
+                Point3::new(0.0, 0.0, 0.0),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("axes_camera_bind_group"),
+        });
+
+        Self {
+            pipeline,
+            vertex_buffer,
+            camera_buffer,
+            camera_bind_group,
+            viewport: None,
+        }
+    }
+
+    /// Turn the axes with the camera and place them on a screen of some size in pixels
+    ///
+    /// Returns the labels at the axes' tips.
+    pub fn update(&mut self, queue: &Queue, camera: &Camera, screen: [f32; 2]) -> Vec<Text> {
+        self.viewport = (screen[0] >= SIZE + 2.0 * MARGIN && screen[1] >= SIZE + 2.0 * MARGIN)
+            .then(|| [screen[0] - SIZE - MARGIN, screen[1] - SIZE - MARGIN]);
+        let Some([left, top]) = self.viewport else {
+            return Vec::new();
+        };
+
+        let rotation = Matrix4::look_to_rh(
+            Point3::new(0.0, 0.0, 0.0),
+            camera.direction(),
+            Vector3::unit_y(),
+        );
+        let view_proj = OPENGL_TO_WGPU_MATRIX
+            * cgmath::ortho(-EXTENT, EXTENT, -EXTENT, EXTENT, -EXTENT, EXTENT)
+            * rotation;
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform::new(view_proj, Point3::new(0.0, 0.0, 0.0))]),
+        );
+
+        AXES.iter()
+            .map(|&([x, y, z], color, name)| {
+                let clip = view_proj * (Vector3::new(x, y, z) * 1.15).extend(1.0);
+                Text {
+                    content: name.to_string(),
+                    position: [
+                        left + (clip.x / clip.w + 1.0) / 2.0 * SIZE,
+                        top + (1.0 - clip.y / clip.w) / 2.0 * SIZE + LABEL_SIZE / 2.0,
+                    ],
+                    size: LABEL_SIZE,
+                    color: [color[0], color[1], color[2], 1.0],
+                    align: Align::Center,
+                }
+            })
+            .collect()
+    }
+
+    /// Draw the axes on top of the frame
+    pub fn draw(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        let Some([left, top]) = self.viewport else {
+            return;
+        };
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Axes Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_viewport(left, top, SIZE, SIZE, 0.0, 1.0);
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.camera_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.draw(0..(AXES.len() * 2) as u32, 0..1);
+    }
+}
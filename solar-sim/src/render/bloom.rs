@@ -0,0 +1,339 @@
+//! Glow around bright parts of the scene
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent, BlendFactor,
+    BlendOperation, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState,
+    CommandEncoder, Device, Extent3d, FragmentState, PipelineLayoutDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, ShaderModule, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages, SurfaceConfiguration, TextureDescriptor, TextureDimension,
+    TextureUsages, TextureView, TextureViewDescriptor, VertexState,
+};
+
+use crate::render::tonemap::Tonemap;
+
+/// Resource configuring the glow
+///
+/// Defaults to an [`intensity`](Bloom::intensity) of `0.6` and a [`threshold`](Bloom::threshold) of `1.0`
+#[derive(Copy, Clone, Debug)]
+pub struct Bloom {
+    /// Factor of the blurred colors added onto the scene, zero disables bloom
+    pub intensity: f32,
+
+    /// Brightness above which a color starts glowing
+    pub threshold: f32,
+}
+
+impl Default for Bloom {
+    fn default() -> Self {
+        Self {
+            intensity: 0.6,
+            threshold: 1.0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BloomUniform {
+    direction: [f32; 2],
+    threshold: f32,
+    intensity: f32,
+}
+
+/// Number of horizontal and vertical blur pass pairs
+const BLUR_PASSES: usize = 3;
+
+/// Extracts the bright parts of the HDR target, blurs them at half resolution and adds them back
+pub struct BloomPipeline {
+    threshold: RenderPipeline,
+    blur: RenderPipeline,
+    composite: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    /// Uniforms for blurring horizontally and vertically,
+    /// the former is also used by the threshold and composite passes
+    horizontal: Buffer,
+    vertical: Buffer,
+    targets: Targets,
+    intensity: f32,
+}
+
+/// The textures which depend on the surface's size
+struct Targets {
+    /// Receives the threshold and vertical blur passes
+    first: TextureView,
+    /// Receives the horizontal blur passes
+    second: TextureView,
+    threshold: BindGroup,
+    horizontal: BindGroup,
+    vertical: BindGroup,
+}
+
+impl BloomPipeline {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, hdr: &TextureView) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Bloom Shader"),
+            source: ShaderSource::Wgsl(include_str!("../bloom.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("bloom_bind_group_layout"),
+        });
+
+        let bloom = Bloom::default();
+        let uniform = |direction| {
+            device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Bloom Buffer"),
+                contents: bytemuck::cast_slice(&[BloomUniform {
+                    direction,
+                    threshold: bloom.threshold,
+                    intensity: bloom.intensity,
+                }]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            })
+        };
+        let horizontal = uniform([1.0, 0.0]);
+        let vertical = uniform([0.0, 1.0]);
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Bloom Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |entry_point, blend| {
+            create_pipeline(device, &pipeline_layout, &shader, entry_point, blend)
+        };
+        let threshold = pipeline("fs_threshold", None);
+        let blur = pipeline("fs_blur", None);
+        let composite = pipeline(
+            "fs_composite",
+            Some(BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::One,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent::OVER,
+            }),
+        );
+
+        let targets = Targets::new(
+            device,
+            &layout,
+            &horizontal,
+            &vertical,
+            &sampler,
+            config,
+            hdr,
+        );
+
+        Self {
+            threshold,
+            blur,
+            composite,
+            layout,
+            sampler,
+            horizontal,
+            vertical,
+            targets,
+            intensity: bloom.intensity,
+        }
+    }
+
+    /// Match the targets to the surface's new size
+    ///
+    /// Requires the HDR target to be resized already
+    pub fn resize(&mut self, device: &Device, config: &SurfaceConfiguration, hdr: &TextureView) {
+        self.targets = Targets::new(
+            device,
+            &self.layout,
+            &self.horizontal,
+            &self.vertical,
+            &self.sampler,
+            config,
+            hdr,
+        );
+    }
+
+    pub fn update(&mut self, queue: &Queue, bloom: &Bloom) {
+        for (buffer, direction) in [(&self.horizontal, [1.0, 0.0]), (&self.vertical, [0.0, 1.0])] {
+            let uniform = BloomUniform {
+                direction,
+                threshold: bloom.threshold,
+                intensity: bloom.intensity,
+            };
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[uniform]));
+        }
+        self.intensity = bloom.intensity;
+    }
+
+    /// Add the glow onto the HDR target
+    pub fn draw(&self, encoder: &mut CommandEncoder, hdr: &TextureView) {
+        if self.intensity <= 0.0 {
+            return;
+        }
+
+        let targets = &self.targets;
+        let mut pass = |pipeline, bind_group, target, load| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Bloom Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: target,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        };
+        let clear = wgpu::LoadOp::Clear(wgpu::Color::BLACK);
+
+        pass(&self.threshold, &targets.threshold, &targets.first, clear);
+        for _ in 0..BLUR_PASSES {
+            pass(&self.blur, &targets.horizontal, &targets.second, clear);
+            pass(&self.blur, &targets.vertical, &targets.first, clear);
+        }
+        pass(
+            &self.composite,
+            &targets.horizontal,
+            hdr,
+            wgpu::LoadOp::Load,
+        );
+    }
+}
+
+impl Targets {
+    fn new(
+        device: &Device,
+        layout: &BindGroupLayout,
+        horizontal: &Buffer,
+        vertical: &Buffer,
+        sampler: &Sampler,
+        config: &SurfaceConfiguration,
+        hdr: &TextureView,
+    ) -> Self {
+        let create_view = || {
+            device
+                .create_texture(&TextureDescriptor {
+                    label: Some("Bloom Target"),
+                    size: Extent3d {
+                        width: (config.width / 2).max(1),
+                        height: (config.height / 2).max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: TextureDimension::D2,
+                    format: Tonemap::FORMAT,
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+                    view_formats: &[],
+                })
+                .create_view(&TextureViewDescriptor::default())
+        };
+        let first = create_view();
+        let second = create_view();
+
+        let bind = |buffer: &Buffer, source| {
+            device.create_bind_group(&BindGroupDescriptor {
+                layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(source),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(sampler),
+                    },
+                ],
+                label: Some("bloom_bind_group"),
+            })
+        };
+
+        Self {
+            threshold: bind(horizontal, hdr),
+            // The horizontal pass reads what the vertical one wrote and the other way around
+            horizontal: bind(horizontal, &first),
+            vertical: bind(vertical, &second),
+            first,
+            second,
+        }
+    }
+}
+
+fn create_pipeline(
+    device: &Device,
+    layout: &wgpu::PipelineLayout,
+    shader: &ShaderModule,
+    entry_point: &str,
+    blend: Option<BlendState>,
+) -> RenderPipeline {
+    device.create_render_pipeline(&RenderPipelineDescriptor {
+        label: Some("Bloom Pipeline"),
+        layout: Some(layout),
+        vertex: VertexState {
+            module: shader,
+            entry_point: "vs_main",
+            buffers: &[],
+        },
+        fragment: Some(FragmentState {
+            module: shader,
+            entry_point,
+            targets: &[Some(ColorTargetState {
+                format: Tonemap::FORMAT,
+                blend,
+                write_mask: Default::default(),
+            })],
+        }),
+        primitive: Default::default(),
+        depth_stencil: None,
+        multisample: Default::default(),
+        multiview: Default::default(),
+    })
+}
@@ -0,0 +1,176 @@
+//! Reuse bind groups and pipelines created from the same layout and resources
+//!
+//! Resources are told apart by their wgpu ids, so e.g. two textures with the same contents
+//! still get two bind groups.
+
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupLayout, BindingResource, Buffer, BufferAddress,
+    BufferSize, BufferUsages, ColorTargetState, DepthStencilState, Device, Id, MultisampleState,
+    PipelineLayout, PrimitiveState, RenderPipeline, RenderPipelineDescriptor, Sampler,
+    ShaderModule, TextureView, VertexAttribute, VertexStepMode,
+};
+
+/// Resource bound to a bind group entry
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum ResourceKey {
+    Buffer(Id<Buffer>, BufferAddress, Option<BufferSize>),
+    Sampler(Id<Sampler>),
+    TextureView(Id<TextureView>),
+}
+
+impl ResourceKey {
+    /// `None` for arrays of resources, which aren't cached
+    fn new(resource: &BindingResource) -> Option<Self> {
+        Some(match resource {
+            BindingResource::Buffer(binding) => {
+                Self::Buffer(binding.buffer.global_id(), binding.offset, binding.size)
+            }
+            BindingResource::Sampler(sampler) => Self::Sampler(sampler.global_id()),
+            BindingResource::TextureView(view) => Self::TextureView(view.global_id()),
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct BindGroupKey {
+    layout: Id<BindGroupLayout>,
+    entries: Vec<(u32, ResourceKey)>,
+}
+
+/// Hands out the same bind group for the same layout and resources
+///
+/// Also keeps the buffers of constant uniforms, so bind groups using equal ones can be shared.
+#[derive(Default)]
+pub struct BindGroupCache {
+    bind_groups: Mutex<HashMap<BindGroupKey, Arc<BindGroup>>>,
+    uniforms: Mutex<HashMap<Vec<u8>, Arc<Buffer>>>,
+}
+
+impl BindGroupCache {
+    /// Get the bind group of a descriptor, creating it if none was created with the same layout and resources
+    pub fn get_or_create(
+        &self,
+        device: &Device,
+        descriptor: &BindGroupDescriptor,
+    ) -> Arc<BindGroup> {
+        let entries = descriptor
+            .entries
+            .iter()
+            .map(|entry| Some((entry.binding, ResourceKey::new(&entry.resource)?)))
+            .collect::<Option<Vec<_>>>();
+        let Some(entries) = entries else {
+            return Arc::new(device.create_bind_group(descriptor));
+        };
+        let key = BindGroupKey {
+            layout: descriptor.layout.global_id(),
+            entries,
+        };
+        self.bind_groups
+            .lock()
+            .unwrap()
+            .entry(key)
+            .or_insert_with(|| Arc::new(device.create_bind_group(descriptor)))
+            .clone()
+    }
+
+    /// Get a uniform buffer which is never written to, creating it if none has the same contents
+    pub fn uniform(&self, device: &Device, label: &str, contents: &[u8]) -> Arc<Buffer> {
+        self.uniforms
+            .lock()
+            .unwrap()
+            .entry(contents.to_vec())
+            .or_insert_with(|| {
+                Arc::new(device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some(label),
+                    contents,
+                    usage: BufferUsages::UNIFORM,
+                }))
+            })
+            .clone()
+    }
+
+    /// Drop the bind groups nobody uses anymore, so the resources they bind can be freed
+    ///
+    /// Uniform buffers are kept, there are only a few of them.
+    pub fn collect(&self) {
+        self.bind_groups
+            .lock()
+            .unwrap()
+            .retain(|_, bind_group| Arc::strong_count(bind_group) > 1);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct PipelineKey {
+    layout: Option<Id<PipelineLayout>>,
+    vertex: (Id<ShaderModule>, String),
+    buffers: Vec<(BufferAddress, VertexStepMode, Vec<VertexAttribute>)>,
+    fragment: Option<(Id<ShaderModule>, String, Vec<Option<ColorTargetState>>)>,
+    primitive: PrimitiveState,
+    depth_stencil: Option<DepthStencilState>,
+    multisample: MultisampleState,
+    multiview: Option<NonZeroU32>,
+}
+
+impl PipelineKey {
+    fn new(descriptor: &RenderPipelineDescriptor) -> Self {
+        Self {
+            layout: descriptor.layout.map(PipelineLayout::global_id),
+            vertex: (
+                descriptor.vertex.module.global_id(),
+                descriptor.vertex.entry_point.to_string(),
+            ),
+            buffers: descriptor
+                .vertex
+                .buffers
+                .iter()
+                .map(|buffer| {
+                    (
+                        buffer.array_stride,
+                        buffer.step_mode,
+                        buffer.attributes.to_vec(),
+                    )
+                })
+                .collect(),
+            fragment: descriptor.fragment.as_ref().map(|fragment| {
+                (
+                    fragment.module.global_id(),
+                    fragment.entry_point.to_string(),
+                    fragment.targets.to_vec(),
+                )
+            }),
+            primitive: descriptor.primitive,
+            depth_stencil: descriptor.depth_stencil.clone(),
+            multisample: descriptor.multisample,
+            multiview: descriptor.multiview,
+        }
+    }
+}
+
+/// Hands out the same render pipeline for descriptors equal in everything but their label
+#[derive(Default)]
+pub struct PipelineCache {
+    pipelines: Mutex<HashMap<PipelineKey, Arc<RenderPipeline>>>,
+}
+
+impl PipelineCache {
+    /// Get the pipeline of a descriptor, creating it if none was created from an equal one
+    pub fn get_or_create(
+        &self,
+        device: &Device,
+        descriptor: &RenderPipelineDescriptor,
+    ) -> Arc<RenderPipeline> {
+        self.pipelines
+            .lock()
+            .unwrap()
+            .entry(PipelineKey::new(descriptor))
+            .or_insert_with(|| Arc::new(device.create_render_pipeline(descriptor)))
+            .clone()
+    }
+}
@@ -1,4 +1,4 @@
-use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, Rad, Vector3};
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix, Matrix4, Point3, Rad, Vector3, Vector4};
 use specs::shred::PanicHandler;
 use specs::{Read, System, Write};
 
@@ -35,9 +35,32 @@ impl Camera {
     }
 }
 
+/// Camera data as seen by the shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    pub view_proj: [[f32; 4]; 4],
+    /// Position in render space, `w` is unused
+    pub position: [f32; 4],
+}
+
+impl CameraUniform {
+    pub fn new(view_proj: Matrix4<f32>, position: Point3<f32>) -> Self {
+        Self {
+            view_proj: view_proj.into(),
+            position: position.to_homogeneous().into(),
+        }
+    }
+}
+
+/// Resource of the camera's projection
+///
+/// Inserted by [`Render`](crate::render::Render)
 #[derive(Copy, Clone, Debug)]
 pub struct Projection {
     pub aspect: f32,
+    /// Height of the screen in pixels
+    pub height: u32,
     pub fovy: Rad<f32>,
     pub znear: f32,
     pub zfar: f32,
@@ -47,6 +70,7 @@ impl Projection {
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             aspect: width as f32 / height as f32,
+            height,
             fovy: Deg(45.0).into(),
             znear: 0.1,
             zfar: 100.0,
@@ -55,11 +79,82 @@ impl Projection {
 
     pub fn resize(&mut self, width: u32, height: u32) {
         self.aspect = width as f32 / height as f32;
+        self.height = height;
+    }
+
+    /// Approximate radius in pixels of a sphere at some distance
+    pub fn screen_radius(&self, radius: f32, distance: f32) -> f32 {
+        if distance <= radius {
+            return f32::INFINITY;
+        }
+        radius / (distance * (self.fovy.0 / 2.0).tan()) * self.height as f32 / 2.0
     }
 
     pub fn matrix(&self) -> Matrix4<f32> {
         cgmath::perspective(self.fovy, self.aspect, self.znear, self.zfar)
     }
+
+    /// Projection into wgpu's clip space with reversed-Z and an infinite far plane
+    ///
+    /// The near plane maps to a depth of 1 and infinity to 0.
+    /// Floats are densest around 0, so distant bodies keep their precision
+    /// instead of z-fighting as with [`matrix`](Self::matrix), whose depth goes from 0 to 1.
+    pub fn reversed_z(&self) -> Matrix4<f32> {
+        let focal = 1.0 / (self.fovy.0 / 2.0).tan();
+        #[rustfmt::skip]
+        let matrix = Matrix4::new(
+            focal / self.aspect, 0.0, 0.0, 0.0,
+            0.0, focal, 0.0, 0.0,
+            0.0, 0.0, 0.0, -1.0,
+            0.0, 0.0, self.znear, 0.0,
+        );
+        matrix
+    }
+}
+
+/// Orthographic projection into wgpu's clip space with reversed-Z like [`Projection::reversed_z`]
+///
+/// Sees a box of `width` by `height` units centered in front of the camera,
+/// from its position at a depth of 1 up to `depth` units away at a depth of 0.
+pub fn orthographic(width: f32, height: f32, depth: f32) -> Matrix4<f32> {
+    #[rustfmt::skip]
+    let matrix = Matrix4::new(
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, 2.0 / height, 0.0, 0.0,
+        0.0, 0.0, 1.0 / depth, 0.0,
+        0.0, 0.0, 1.0, 1.0,
+    );
+    matrix
+}
+
+/// Planes bounding what the camera sees
+///
+/// Each plane's normal points inwards with the plane's distance to the origin stored in `w`.
+#[derive(Copy, Clone, Debug)]
+pub struct Frustum([Vector4<f32>; 5]);
+
+impl Frustum {
+    /// Extract the planes from a view projection using [`Projection::reversed_z`]
+    ///
+    /// The far plane is at infinity, which leaves the near plane and the four sides.
+    pub fn new(view_proj: Matrix4<f32>) -> Self {
+        let row = |index| view_proj.row(index);
+        let planes = [
+            row(3) + row(0),
+            row(3) - row(0),
+            row(3) + row(1),
+            row(3) - row(1),
+            row(3) - row(2),
+        ];
+        Self(planes.map(|plane| plane / plane.truncate().magnitude()))
+    }
+
+    /// Whether any part of a sphere might be visible
+    pub fn contains_sphere(&self, center: Point3<f32>, radius: f32) -> bool {
+        self.0
+            .iter()
+            .all(|plane| plane.truncate().dot(center.to_vec()) + plane.w >= -radius)
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -0,0 +1,258 @@
+//! Translucent cloud layers drifting above planets' surfaces
+
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::mem::size_of;
+use std::sync::Arc;
+
+use cgmath::{Point3, Quaternion, Rad, Rotation3};
+use specs::{Component, Entity, Join, Read, System, VecStorage, WriteStorage};
+use wgpu::{
+    BindGroup, BindGroupLayout, BlendState, Buffer, BufferAddress, BufferDescriptor, BufferUsages,
+    ColorTargetState, DepthStencilState, Device, FragmentState, PipelineLayout, PrimitiveState,
+    Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor, ShaderModule, TextureFormat,
+    VertexState,
+};
+
+use crate::physics::{Determinism, SimSpeed};
+use crate::render::cache::BindGroupCache;
+use crate::render::instance::{Instance, InstanceRaw};
+use crate::render::material::{bind_material, FallbackTextures, Reflectance};
+use crate::render::procedural::{Pattern, Surface, TextureGenerator};
+use crate::render::texture::Texture;
+use crate::render::transparent::{Shared, Transparent};
+use crate::render::Vertex;
+use crate::timer::Delta;
+
+/// Clouds component of a planet
+///
+/// Rendered as a sphere slightly above the surface, rotating independently of it.
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Clouds {
+    /// Altitude above the surface in meters
+    pub height: f32,
+
+    /// Seconds of simulated time for one rotation
+    pub period: f32,
+
+    /// Seed of the generated cloud texture
+    pub seed: u32,
+
+    /// Opacity of the thickest clouds
+    pub opacity: f32,
+
+    /// Current rotation around the planet's axis
+    ///
+    /// Updated by [`RotateClouds`] system
+    pub angle: Rad<f32>,
+}
+
+impl Clouds {
+    /// Noise the cloud texture is generated from, its brightness is the clouds' opacity
+    fn surface(&self) -> Surface {
+        Surface {
+            seed: self.seed,
+            palette: [
+                [0.0; 3],
+                [0.0; 3],
+                [self.opacity * 0.6; 3],
+                [self.opacity; 3],
+            ],
+            pattern: Pattern::Terrain,
+        }
+    }
+}
+
+/// System rotating [`Clouds`] with the simulated time
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RotateClouds;
+
+impl<'a> System<'a> for RotateClouds {
+    type SystemData = (
+        Read<'a, Delta>,
+        Read<'a, SimSpeed>,
+        Read<'a, Determinism>,
+        WriteStorage<'a, Clouds>,
+    );
+
+    fn run(&mut self, (delta, speed, determinism, mut clouds): Self::SystemData) {
+        let dt = determinism.0.unwrap_or(**delta).as_secs_f32() * speed.0;
+        for clouds in (&mut clouds).join() {
+            clouds.angle = Rad((clouds.angle.0 + TAU * dt / clouds.period) % TAU);
+        }
+    }
+}
+
+/// Seed and opacity a cloud texture was generated with
+type CloudKey = (u32, f32);
+
+/// Draws every planet's [`Clouds`]
+///
+/// Uses the main shader, so clouds are lit and shadowed like the surfaces below them.
+pub struct CloudPipeline {
+    pipeline: RenderPipeline,
+    textures: HashMap<Entity, (CloudKey, Texture, Arc<BindGroup>)>,
+    order: Vec<Entity>,
+    centers: Vec<Point3<f32>>,
+    buffer: Buffer,
+    capacity: usize,
+}
+
+impl CloudPipeline {
+    /// Regenerate every texture in the next update
+    pub fn clear(&mut self) {
+        self.textures.clear();
+    }
+
+    /// Create the pipeline from the main shader and its layout
+    /// `pipeline` is created by [`create_pipeline`](Self::create_pipeline)
+    pub fn new(device: &Device, pipeline: RenderPipeline) -> Self {
+        let capacity = 16;
+        Self {
+            pipeline,
+            textures: HashMap::new(),
+            order: Vec::new(),
+            centers: Vec::new(),
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+        }
+    }
+
+    /// Create the pipeline drawing clouds with the main shader
+    pub fn create_pipeline(
+        device: &Device,
+        format: TextureFormat,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+    ) -> RenderPipeline {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Cloud Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: shader,
+                entry_point: "fs_clouds",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        })
+    }
+
+    /// Replace the pipeline, e.g. after the main shader was reloaded
+    pub fn set_pipeline(&mut self, pipeline: RenderPipeline) {
+        self.pipeline = pipeline;
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Cloud Instance Buffer"),
+            size: (capacity * size_of::<InstanceRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Upload the clouds of the current frame
+    ///
+    /// `clouds` yields each planet's clouds with its position and rendered radius in render space.
+    /// `scale` converts meters to render space including any exaggeration.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        generator: &TextureGenerator,
+        texture_layout: &BindGroupLayout,
+        fallback: &FallbackTextures,
+        cache: &BindGroupCache,
+        scale: f32,
+        clouds: impl Iterator<Item = (Entity, &'a Clouds, Point3<f32>, f32, Rad<f32>)>,
+    ) {
+        self.order.clear();
+        self.centers.clear();
+        let mut instances = Vec::new();
+        for (entity, clouds, position, radius, tilt) in clouds {
+            let key = (clouds.seed, clouds.opacity);
+            if self.textures.get(&entity).map(|(key, ..)| *key) != Some(key) {
+                let texture = generator.generate(device, queue, &clouds.surface());
+                let bind_group = bind_material(
+                    device,
+                    cache,
+                    texture_layout,
+                    fallback,
+                    &texture,
+                    None,
+                    None,
+                    Reflectance::default(),
+                );
+                self.textures.insert(entity, (key, texture, bind_group));
+            }
+            let instance = Instance {
+                rotation: Quaternion::from_angle_x(tilt) * Quaternion::from_angle_y(clouds.angle),
+                ..Instance::from_position(position, radius + clouds.height * scale)
+            };
+            instances.push(instance.to_raw());
+            self.order.push(entity);
+            self.centers.push(position);
+        }
+        self.textures
+            .retain(|entity, _| self.order.contains(entity));
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&instances));
+    }
+}
+
+/// Clouds are drawn using the planets' sphere mesh
+impl Transparent for CloudPipeline {
+    fn centers(&self) -> &[Point3<f32>] {
+        &self.centers
+    }
+
+    fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>) {
+        let (vertex_buffer, index_buffer, _) = shared.sphere;
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, shared.camera, &[]);
+        render_pass.set_bind_group(2, shared.light, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, self.buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    }
+
+    fn draw_item<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        shared: &Shared<'a>,
+        index: usize,
+    ) {
+        let (_, _, bind_group) = &self.textures[&self.order[index]];
+        let (_, _, num_indices) = shared.sphere;
+        let index = index as u32;
+        render_pass.set_bind_group(0, bind_group, &[]);
+        render_pass.draw_indexed(0..num_indices, 0, index..index + 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! Second simulation drawn next to the main one, e.g. to compare integrators
+
+use cgmath::{InnerSpace, One, Point3, Quaternion};
+use specs::join::MaybeJoin;
+use specs::{Join, Read, System, World, WorldExt, Write};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroupLayout, Buffer, BufferUsages, Device, Queue};
+
+use crate::physics::{Planet, Position, Radius, Rotation};
+use crate::render::camera::{Camera, Frustum, Projection};
+use crate::render::impostor::{ImpostorPipeline, Sprite, MAX_SIZE};
+use crate::render::instance::Instance;
+use crate::render::lod::SphereLods;
+use crate::render::registry::MeshHandle;
+use crate::render::split::SplitScreen;
+use crate::render::tonemap::Tonemap;
+use crate::render::{sprite_color, Exaggeration, Origin, TextureKey};
+
+/// Comparison resource, the bodies of a second simulation
+///
+/// While set, the right half of a [`SplitScreen`] shows these bodies instead of the main simulation's,
+/// seen through the same camera, see [`FollowCamera`].
+/// Only the bodies, their impostors and the skybox are drawn there,
+/// lines, labels, particles and transparent layers such as rings are the main simulation's only.
+///
+/// Bodies are matched to the main simulation's by entity id for their textures, meshes and light,
+/// which holds as long as both are populated by [`build_planets`](crate::physics::planets::build_planets).
+///
+/// Updated by [`Comparison::capture`] whenever the second simulation stepped
+#[derive(Clone, Debug, Default)]
+pub struct Comparison(pub Option<Vec<ComparedBody>>);
+
+/// A body of the compared simulation
+#[derive(Copy, Clone, Debug)]
+pub struct ComparedBody {
+    /// Id of its entity in both simulations
+    pub id: u32,
+    pub position: Point3<f64>,
+    pub radius: Option<Radius>,
+    pub rotation: Option<Rotation>,
+}
+
+impl Comparison {
+    /// Copy the bodies of the second simulation's world
+    pub fn capture(world: &World) -> Self {
+        let entities = world.entities();
+        let planets = world.read_storage::<Planet>();
+        let positions = world.read_storage::<Position>();
+        let radii = world.read_storage::<Radius>();
+        let rotations = world.read_storage::<Rotation>();
+        let bodies = (
+            &entities,
+            &planets,
+            &positions,
+            MaybeJoin(&radii),
+            MaybeJoin(&rotations),
+        )
+            .join()
+            .map(|(entity, _, pos, radius, rotation)| ComparedBody {
+                id: entity.id(),
+                position: pos.0,
+                radius: radius.copied(),
+                rotation: rotation.copied(),
+            })
+            .collect();
+        Self(Some(bodies))
+    }
+}
+
+/// System keeping the [`SplitScreen`]'s camera on the main [`Camera`] while comparing,
+/// so both halves show the same place
+#[derive(Copy, Clone, Debug, Default)]
+pub struct FollowCamera;
+
+impl<'a> System<'a> for FollowCamera {
+    type SystemData = (
+        Read<'a, Camera>,
+        Read<'a, Comparison>,
+        Write<'a, SplitScreen>,
+    );
+
+    fn run(&mut self, (camera, comparison, mut split): Self::SystemData) {
+        if comparison.0.is_some() {
+            split.0 = Some(*camera);
+        }
+    }
+}
+
+/// How the main simulation draws a body, copied by the compared body with the same id
+pub(super) struct Appearance {
+    pub texture: Option<TextureKey>,
+    pub mesh: Option<MeshHandle>,
+    pub emissive: bool,
+    pub light: bool,
+    pub impostor: bool,
+}
+
+/// Instances and impostors of the [`Comparison`]'s bodies visible in the second view
+pub(super) struct ComparedView {
+    pub instance_buffer: Buffer,
+    instances: usize,
+    /// Texture, level of detail, mesh and whether it's emissive of each instance
+    pub draws: Vec<(Option<TextureKey>, usize, Option<MeshHandle>, bool)>,
+    pub impostors: ImpostorPipeline,
+    /// Whether the second view shows the comparison at all
+    pub active: bool,
+}
+
+impl ComparedView {
+    pub fn new(device: &Device, camera_layout: &BindGroupLayout) -> Self {
+        Self {
+            instance_buffer: Self::buffer(device, &[]),
+            instances: 0,
+            draws: Vec::new(),
+            impostors: ImpostorPipeline::new(device, Tonemap::FORMAT, camera_layout),
+            active: false,
+        }
+    }
+
+    fn buffer(device: &Device, contents: &[u8]) -> Buffer {
+        device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Compared Instance Buffer"),
+            // Never empty, since binding an empty buffer isn't allowed
+            contents: if contents.is_empty() {
+                &[0; 4]
+            } else {
+                contents
+            },
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+        })
+    }
+
+    /// Cull and classify the bodies seen by the second view's camera and upload them
+    ///
+    /// Pass `None` as `view` while not comparing, the second view then shows the main simulation.
+    #[allow(clippy::too_many_arguments)]
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        viewport: [f32; 2],
+        view: Option<(&[ComparedBody], Camera, Frustum)>,
+        origin: &Origin,
+        projection: &Projection,
+        exaggeration: &Exaggeration,
+        appearance: impl Fn(u32) -> Appearance,
+    ) {
+        self.active = view.is_some();
+        self.draws.clear();
+        let Some((bodies, camera, frustum)) = view else {
+            return;
+        };
+        let bodies: Vec<_> = bodies
+            .iter()
+            .map(|body| (body, appearance(body.id)))
+            .collect();
+        let sun = bodies
+            .iter()
+            .find(|(_, appearance)| appearance.light)
+            .map(|(body, _)| {
+                (
+                    origin.render(body.position),
+                    exaggeration.render_radius(body.radius.as_ref()),
+                )
+            });
+        let mut instances = Vec::new();
+        let mut sprites = Vec::new();
+        for (body, appearance) in bodies {
+            let center = origin.render(body.position);
+            let scale = exaggeration.render_radius(body.radius.as_ref());
+            if !frustum.contains_sphere(center, scale) {
+                continue;
+            }
+            let screen_radius =
+                projection.screen_radius(scale, (center - camera.position).magnitude());
+            if appearance.impostor && 2.0 * screen_radius < MAX_SIZE {
+                sprites.push(Sprite {
+                    position: center,
+                    color: sprite_color(appearance.light, center, camera.position, sun),
+                });
+                continue;
+            }
+            self.draws.push((
+                appearance.texture,
+                SphereLods::level(screen_radius),
+                appearance.mesh,
+                appearance.emissive,
+            ));
+            instances.push(
+                Instance {
+                    rotation: body
+                        .rotation
+                        .as_ref()
+                        .map_or(Quaternion::one(), Rotation::orientation),
+                    ..Instance::from_position(center, scale)
+                }
+                .to_raw(),
+            );
+        }
+
+        if instances.len() > self.instances {
+            self.instance_buffer = Self::buffer(device, bytemuck::cast_slice(&instances));
+            self.instances = instances.len();
+        } else {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        }
+        self.impostors.update(device, queue, viewport, &sprites);
+    }
+}
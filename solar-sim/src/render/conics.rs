@@ -0,0 +1,238 @@
+//! Predict the selected body's trajectory as a chain of Kepler orbits
+
+use std::collections::HashMap;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, Write};
+
+use crate::control::Selection;
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{Mass, Position, Velocity, G};
+use crate::render::lines::{LineVertex, Lines};
+
+/// Layer in [`Lines`] the conics are drawn to
+const LAYER: &str = "patched_conics";
+
+/// Colors the successive conics cycle through
+const COLORS: [[f32; 3]; 4] = [
+    [0.3, 0.6, 1.0],
+    [1.0, 0.6, 0.2],
+    [0.8, 0.4, 1.0],
+    [0.3, 1.0, 0.7],
+];
+
+/// Snapshot of a body which can be a primary
+#[derive(Copy, Clone, Debug)]
+struct Body {
+    mass: f32,
+    position: Point3<f64>,
+    velocity: Vector3<f64>,
+    orbit: Option<OrbitalElements>,
+}
+
+/// The bodies heavier than the predicted one, each moving along its own Kepler orbit
+///
+/// Every primary is heavier than its bodies, so it's always part of them as well.
+struct Attractors(HashMap<Entity, Body>);
+
+impl Attractors {
+    /// Position and velocity of a body `t` seconds from now
+    fn state(&self, entity: Entity, t: f64) -> (Point3<f64>, Vector3<f64>) {
+        let body = &self.0[&entity];
+        match body
+            .orbit
+            .filter(|orbit| self.0.contains_key(&orbit.primary))
+        {
+            Some(orbit) => {
+                let (position, velocity) = self.state(orbit.primary, t);
+                let (r, v) = orbit.advance(t).state();
+                (position + r, velocity + v)
+            }
+            None => (body.position + body.velocity * t, body.velocity),
+        }
+    }
+
+    /// Radius of a body's sphere of influence, `None` for bodies without a primary
+    fn sphere_of_influence(&self, entity: Entity) -> Option<f64> {
+        let body = &self.0[&entity];
+        let orbit = body.orbit?;
+        let primary = self.0.get(&orbit.primary)?;
+        Some(orbit.sphere_of_influence(body.mass, primary.mass))
+    }
+
+    /// Body whose sphere of influence contains `position` `t` seconds from now
+    ///
+    /// The smallest sphere wins, falling back to the heaviest body outside of all of them.
+    fn primary_at(&self, position: Point3<f64>, t: f64) -> Option<Entity> {
+        self.0
+            .keys()
+            .filter_map(|&entity| {
+                let radius = self.sphere_of_influence(entity)?;
+                let distance = (self.state(entity, t).0 - position).magnitude();
+                (distance < radius).then_some((entity, radius))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity)
+            .or_else(|| {
+                self.0
+                    .iter()
+                    .max_by(|(_, a), (_, b)| a.mass.total_cmp(&b.mass))
+                    .map(|(entity, _)| *entity)
+            })
+    }
+
+    /// Conic of a body with `mass` at `position` moving at `velocity` around `primary` `t` seconds from now
+    fn conic(
+        &self,
+        primary: Entity,
+        mass: f32,
+        position: Point3<f64>,
+        velocity: Vector3<f64>,
+        t: f64,
+    ) -> Option<OrbitalElements> {
+        let (primary_pos, primary_vel) = self.state(primary, t);
+        OrbitalElements::from_state(
+            primary,
+            position - primary_pos,
+            velocity - primary_vel,
+            f64::from(G) * (f64::from(self.0[&primary].mass) + f64::from(mass)),
+        )
+    }
+}
+
+/// System drawing the selected body's trajectory as patched conics
+///
+/// The body follows a Kepler orbit around the body whose sphere of influence it is in,
+/// switching to a new orbit whenever it leaves that sphere or enters a smaller one.
+/// The heavier bodies move along their own Kepler orbits meanwhile.
+/// Each conic is drawn around its primary's current position in its own color,
+/// so orbits around a moving planet close on themselves.
+///
+/// Updates [`Lines`] resource
+#[derive(Clone, Debug)]
+pub struct PatchedConics {
+    /// Simulated seconds to predict
+    pub horizon: f64,
+
+    /// Number of steps to reach the horizon
+    pub steps: usize,
+
+    /// Number of conics after which the prediction stops
+    pub max_patches: usize,
+}
+
+impl Default for PatchedConics {
+    fn default() -> Self {
+        Self {
+            horizon: 365.25 * 24.0 * 60.0 * 60.0,
+            steps: 1000,
+            max_patches: 4,
+        }
+    }
+}
+
+impl PatchedConics {
+    /// Points of every conic, relative to its primary's current position
+    fn predict(
+        &self,
+        attractors: &Attractors,
+        mass: f32,
+        position: Point3<f64>,
+        velocity: Vector3<f64>,
+    ) -> Vec<Vec<Point3<f64>>> {
+        let Some(mut primary) = attractors.primary_at(position, 0.0) else {
+            return Vec::new();
+        };
+        let Some(mut conic) = attractors.conic(primary, mass, position, velocity, 0.0) else {
+            return Vec::new();
+        };
+        let mut epoch = 0.0;
+        let mut origin = attractors.0[&primary].position;
+        let mut patches = vec![vec![position]];
+
+        let dt = self.horizon / self.steps as f64;
+        for i in 1..=self.steps {
+            let t = dt * i as f64;
+            let (primary_pos, primary_vel) = attractors.state(primary, t);
+            let (r, v) = conic.advance(t - epoch).state();
+            let (position, velocity) = (primary_pos + r, primary_vel + v);
+            if let Some(patch) = patches.last_mut() {
+                patch.push(origin + r);
+            }
+
+            let Some(next) = attractors.primary_at(position, t) else {
+                break;
+            };
+            if next != primary {
+                if patches.len() >= self.max_patches {
+                    break;
+                }
+                let Some(next_conic) = attractors.conic(next, mass, position, velocity, t) else {
+                    break;
+                };
+                primary = next;
+                conic = next_conic;
+                epoch = t;
+                origin = attractors.0[&primary].position;
+                let (primary_pos, _) = attractors.state(primary, t);
+                patches.push(vec![origin + (position - primary_pos)]);
+            }
+        }
+        patches
+    }
+}
+
+impl<'a> System<'a> for PatchedConics {
+    type SystemData = (
+        Write<'a, Lines>,
+        Read<'a, Selection>,
+        Entities<'a>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, OrbitalElements>,
+    );
+
+    fn run(&mut self, (mut lines, selection, ent, mass, pos, vel, elements): Self::SystemData) {
+        let Some((this_mass, this_pos, this_vel)) = selection.0.and_then(|entity| {
+            let mass = mass.get(entity).map_or(0.0, |mass| mass.0);
+            Some((mass, pos.get(entity)?.0, vel.get(entity)?.0))
+        }) else {
+            lines.clear(LAYER);
+            return;
+        };
+
+        let attractors = Attractors(
+            (&ent, &mass, &pos, &vel)
+                .join()
+                .filter(|(_, mass, ..)| mass.0 > this_mass)
+                .map(|(entity, mass, pos, vel)| {
+                    let body = Body {
+                        mass: mass.0,
+                        position: pos.0,
+                        velocity: vel.0,
+                        orbit: elements.get(entity).copied(),
+                    };
+                    (entity, body)
+                })
+                .collect(),
+        );
+
+        let mut vertices = Vec::new();
+        for (patch, color) in self
+            .predict(&attractors, this_mass, this_pos, this_vel)
+            .iter()
+            .zip(COLORS.iter().cycle())
+        {
+            for pair in patch.windows(2) {
+                for position in pair {
+                    vertices.push(LineVertex {
+                        position: *position,
+                        color: *color,
+                    });
+                }
+            }
+        }
+        lines.set(LAYER, vertices);
+    }
+}
@@ -0,0 +1,174 @@
+//! Views showing how the bodies are drawn instead of what they look like
+
+use wgpu::{
+    BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, CompareFunction,
+    DepthStencilState, Device, Features, FragmentState, PipelineLayout, PolygonMode,
+    PrimitiveState, RenderPipeline, RenderPipelineDescriptor, ShaderModule, TextureFormat,
+    VertexState,
+};
+
+use crate::render::instance::InstanceRaw;
+use crate::render::text::Text;
+use crate::render::texture::Texture;
+use crate::render::Vertex;
+
+/// What the bodies show instead of their shaded surface
+///
+/// Cycled by F5, see [`Controls::debug_view`](crate::control::Controls::debug_view)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Off,
+    /// Edges of the triangles, only available with [`Features::POLYGON_MODE_LINE`]
+    Wireframe,
+    /// Surface normals including the normal maps, mapped from -1..1 to 0..1
+    Normals,
+    /// Distance to the camera on a logarithmic scale, brighter is nearer
+    Depth,
+    /// Every fragment drawn regardless of depth, so pixels drawn more often glow brighter
+    Overdraw,
+}
+
+impl DebugView {
+    /// The view following this one, wrapping around to [`Off`](Self::Off)
+    pub fn next(self) -> Self {
+        match self {
+            Self::Off => Self::Wireframe,
+            Self::Wireframe => Self::Normals,
+            Self::Normals => Self::Depth,
+            Self::Depth => Self::Overdraw,
+            Self::Overdraw => Self::Off,
+        }
+    }
+
+    /// Name of the view in the bottom left corner while it's on
+    pub fn label(self, screen: [f32; 2]) -> Option<Text> {
+        let name = match self {
+            Self::Off => return None,
+            Self::Wireframe => "wireframe",
+            Self::Normals => "normals",
+            Self::Depth => "depth",
+            Self::Overdraw => "overdraw",
+        };
+        Some(Text::new(
+            format!("debug view: {name}"),
+            [8.0, screen[1] - 8.0],
+            16.0,
+        ))
+    }
+}
+
+/// Pipelines replacing the main pipelines while a [`DebugView`] is on
+pub struct DebugPipelines {
+    wireframe: Option<RenderPipeline>,
+    normals: RenderPipeline,
+    depth: RenderPipeline,
+    overdraw: RenderPipeline,
+}
+
+impl DebugPipelines {
+    /// Create the pipelines from the main shader and its layout
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+    ) -> Self {
+        let create_pipeline = |label, entry_point, polygon_mode, blend, depth_stencil| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some(label),
+                layout: Some(layout),
+                vertex: VertexState {
+                    module: shader,
+                    entry_point: "vs_main",
+                    buffers: &[Vertex::desc(), InstanceRaw::desc()],
+                },
+                fragment: Some(FragmentState {
+                    module: shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: Some(blend),
+                        write_mask: Default::default(),
+                    })],
+                }),
+                primitive: PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode,
+                    ..Default::default()
+                },
+                depth_stencil: Some(depth_stencil),
+                multisample: Default::default(),
+                multiview: Default::default(),
+            })
+        };
+        let depth_tested = DepthStencilState {
+            format: Texture::DEPTH_FORMAT,
+            depth_write_enabled: true,
+            depth_compare: Texture::DEPTH_COMPARE,
+            stencil: Default::default(),
+            bias: Default::default(),
+        };
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+
+        Self {
+            wireframe: device
+                .features()
+                .contains(Features::POLYGON_MODE_LINE)
+                .then(|| {
+                    create_pipeline(
+                        "Wireframe Pipeline",
+                        "fs_wireframe",
+                        PolygonMode::Line,
+                        BlendState::REPLACE,
+                        depth_tested.clone(),
+                    )
+                }),
+            normals: create_pipeline(
+                "Normals Pipeline",
+                "fs_normals",
+                PolygonMode::Fill,
+                BlendState::REPLACE,
+                depth_tested.clone(),
+            ),
+            depth: create_pipeline(
+                "Depth View Pipeline",
+                "fs_depth",
+                PolygonMode::Fill,
+                BlendState::REPLACE,
+                depth_tested,
+            ),
+            overdraw: create_pipeline(
+                "Overdraw Pipeline",
+                "fs_overdraw",
+                PolygonMode::Fill,
+                BlendState {
+                    color: additive,
+                    alpha: additive,
+                },
+                DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: CompareFunction::Always,
+                    stencil: Default::default(),
+                    bias: Default::default(),
+                },
+            ),
+        }
+    }
+
+    /// Pipeline to draw every body with, `None` if the view is off or unsupported
+    pub fn get(&self, view: DebugView) -> Option<&RenderPipeline> {
+        match view {
+            DebugView::Off => None,
+            DebugView::Wireframe => self.wireframe.as_ref(),
+            DebugView::Normals => Some(&self.normals),
+            DebugView::Depth => Some(&self.depth),
+            DebugView::Overdraw => Some(&self.overdraw),
+        }
+    }
+}
@@ -0,0 +1,208 @@
+//! Additional windows showing the simulation from their own camera
+
+use std::sync::Arc;
+
+use cgmath::{Matrix4, Point3, SquareMatrix};
+use log::warn;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages,
+    CommandEncoder, Device, Queue, RenderPass, Surface, SurfaceConfiguration, SurfaceTexture,
+    TextureUsages,
+};
+use winit::window::{Window, WindowId};
+
+use crate::render::camera::{Camera, CameraUniform, Projection};
+use crate::render::texture::Texture;
+use crate::render::tonemap::{PostProcess, Tonemap};
+use crate::render::{Origin, BLACK};
+
+/// Detached windows resource
+///
+/// The event loop opens windows and queues them in `opened` together with their camera,
+/// [`Render`](crate::render::Render) then creates their surfaces and draws into them every frame.
+/// Closed windows are queued in `closed` until they are dropped.
+#[derive(Default)]
+pub struct DetachedWindows {
+    pub opened: Vec<(Arc<Window>, Camera)>,
+    pub closed: Vec<WindowId>,
+}
+
+/// Window with its own surface and camera, which is drawn from the same scene as the main one
+///
+/// Only the bodies, impostors and lines are drawn and the camera stays where it was opened.
+pub struct DetachedView {
+    /// Dropped before the window it was created from
+    surface: Surface,
+    window: Arc<Window>,
+    config: SurfaceConfiguration,
+    camera: Camera,
+    tonemap: Tonemap,
+    depth: Texture,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    /// Whether the window is shown at all, i.e. not minimized
+    active: bool,
+}
+
+impl DetachedView {
+    pub fn new(
+        device: &Device,
+        adapter: &Adapter,
+        (surface, window): (Surface, Arc<Window>),
+        camera: Camera,
+        camera_layout: &BindGroupLayout,
+    ) -> Self {
+        let caps = surface.get_capabilities(adapter);
+        let size = window.inner_size();
+        let config = SurfaceConfiguration {
+            usage: TextureUsages::RENDER_ATTACHMENT,
+            format: caps
+                .formats
+                .iter()
+                .copied()
+                .find(|f| f.is_srgb())
+                .unwrap_or(caps.formats[0]),
+            width: size.width.max(1),
+            height: size.height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: caps.alpha_modes[0],
+            view_formats: vec![],
+        };
+        surface.configure(device, &config);
+
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Detached Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
+                Matrix4::identity(),
+                camera.position,
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("detached_camera_bind_group"),
+        });
+
+        Self {
+            tonemap: Tonemap::new(device, &config),
+            depth: Texture::create_depth_texture(device, &config, "detached_depth_texture"),
+            window,
+            surface,
+            config,
+            camera,
+            camera_buffer,
+            camera_bind_group,
+            active: false,
+        }
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Window and camera to open the view again with, e.g. on a new device
+    pub fn into_parts(self) -> (Arc<Window>, Camera) {
+        (self.window, self.camera)
+    }
+
+    /// Follow the window's size and upload the camera, returning its view projection and position
+    ///
+    /// `projection` is the main camera's, whose field of view and clipping planes are kept.
+    /// Returns `None` while the window is minimized.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        origin: &Origin,
+        projection: &Projection,
+        post_process: &PostProcess,
+    ) -> Option<(Matrix4<f32>, Point3<f32>)> {
+        let size = self.window.inner_size();
+        self.active = size.width > 0 && size.height > 0;
+        if !self.active {
+            return None;
+        }
+        if size.width != self.config.width || size.height != self.config.height {
+            self.config.width = size.width;
+            self.config.height = size.height;
+            self.surface.configure(device, &self.config);
+            self.tonemap.resize(device, &self.config);
+            self.depth =
+                Texture::create_depth_texture(device, &self.config, "detached_depth_texture");
+        }
+
+        let projection = Projection {
+            aspect: size.width as f32 / size.height as f32,
+            height: size.height,
+            ..*projection
+        };
+        let camera = origin.camera(&self.camera);
+        let view_proj = projection.reversed_z() * camera.matrix();
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform::new(view_proj, camera.position)]),
+        );
+        self.tonemap.update(queue, post_process);
+        Some((view_proj, camera.position))
+    }
+
+    pub fn camera(&self) -> &BindGroup {
+        &self.camera_bind_group
+    }
+
+    /// Begin the pass drawing the scene into the view's offscreen target, `None` while it's minimized
+    pub fn begin<'a>(&'a self, encoder: &'a mut CommandEncoder) -> Option<RenderPass<'a>> {
+        self.active.then(|| {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Detached Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.tonemap.view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            })
+        })
+    }
+
+    /// Tonemap the drawn scene onto the window's surface, which is presented once it's submitted
+    ///
+    /// Returns `None` while it's minimized or if the surface isn't available this frame.
+    pub fn finish(&self, device: &Device, encoder: &mut CommandEncoder) -> Option<SurfaceTexture> {
+        if !self.active {
+            return None;
+        }
+        let output = match self.surface.get_current_texture() {
+            Ok(output) => output,
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
+                self.surface.configure(device, &self.config);
+                return None;
+            }
+            Err(error) => {
+                warn!("Failed to draw detached view: {error}");
+                return None;
+            }
+        };
+        let view = output
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        self.tonemap.draw(encoder, &view);
+        Some(output)
+    }
+}
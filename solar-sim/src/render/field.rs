@@ -0,0 +1,104 @@
+//! Visualize the gravitational field as a grid of arrows
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use specs::{Join, ReadStorage, System, Write};
+
+use crate::physics::{Mass, Position, G};
+use crate::render::lines::{LineVertex, Lines};
+
+/// Layer in [`Lines`] the arrows are drawn to
+const LAYER: &str = "gravity_field";
+
+/// System drawing the local gravitational acceleration on a planar grid
+///
+/// The grid lies in the orbital plane (y = 0) and is centered around the origin.
+/// Each arrow points along the acceleration and its length and color scale logarithmically with its magnitude.
+///
+/// Updates [`Lines`] resource every few dispatches
+#[derive(Copy, Clone, Debug)]
+pub struct GravityField {
+    /// Distance from the origin to the grid's edges
+    pub extent: f32,
+
+    /// Number of arrows along each of the grid's sides
+    pub resolution: usize,
+
+    /// Number of dispatches between updates
+    pub every: u32,
+
+    frame: u32,
+}
+
+impl Default for GravityField {
+    fn default() -> Self {
+        Self {
+            extent: 3e11,
+            resolution: 32,
+            every: 10,
+            frame: 0,
+        }
+    }
+}
+
+impl<'a> System<'a> for GravityField {
+    type SystemData = (
+        Write<'a, Lines>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (mut lines, mass, pos): Self::SystemData) {
+        self.frame += 1;
+        if self.frame < self.every {
+            return;
+        }
+        self.frame = 0;
+
+        let cell = 2.0 * self.extent / self.resolution as f32;
+        let points: Vec<_> = (0..self.resolution)
+            .flat_map(|x| (0..self.resolution).map(move |z| (x, z)))
+            .map(|(x, z)| {
+                Point3::new(
+                    (x as f32 + 0.5) * cell - self.extent,
+                    0.0,
+                    (z as f32 + 0.5) * cell - self.extent,
+                )
+            })
+            .filter_map(|point| {
+                let mut acc = Vector3::new(0.0, 0.0, 0.0);
+                for (mass, pos) in (&mass, &pos).join() {
+                    let r = pos.0.map(|c| c as f32) - point;
+                    if r.magnitude2() > 0.0 {
+                        acc += G * mass.0 / r.magnitude2() * r.normalize();
+                    }
+                }
+                let magnitude: f32 = acc.magnitude();
+                (magnitude > 0.0).then(|| (point, acc / magnitude, magnitude.log10()))
+            })
+            .collect();
+
+        let (min, max) = points.iter().fold(
+            (f32::INFINITY, f32::NEG_INFINITY),
+            |(min, max), (_, _, m)| (min.min(*m), max.max(*m)),
+        );
+        let range = (max - min).max(f32::EPSILON);
+
+        let mut arrows = Vec::with_capacity(points.len() * 6);
+        for (point, direction, magnitude) in points {
+            let t = (magnitude - min) / range;
+            let color = [t, 0.2, 1.0 - t];
+            let length = cell * 0.9 * (0.2 + 0.8 * t);
+
+            let tip = point + direction * length;
+            let back = tip - direction * length * 0.3;
+            let side = Vector3::new(-direction.z, 0.0, direction.x) * length * 0.15;
+            for position in [point, tip, tip, back + side, tip, back - side] {
+                arrows.push(LineVertex {
+                    position: position.cast().unwrap(),
+                    color,
+                });
+            }
+        }
+        lines.set(LAYER, arrows);
+    }
+}
@@ -0,0 +1,229 @@
+//! Lens flare when looking towards the sun
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendComponent, BlendFactor,
+    BlendOperation, BlendState, Buffer, BufferBindingType, BufferUsages, ColorTargetState,
+    CommandEncoder, Device, FragmentState, PipelineLayoutDescriptor, Queue, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureView,
+    VertexState,
+};
+
+use crate::render::tonemap::Tonemap;
+
+/// Resource configuring the lens flare
+///
+/// Defaults to an [`intensity`](LensFlare::intensity) of `0.4`
+#[derive(Copy, Clone, Debug)]
+pub struct LensFlare {
+    /// Brightness of the flare when the sun is fully visible, zero disables the flare
+    pub intensity: f32,
+}
+
+impl Default for LensFlare {
+    fn default() -> Self {
+        Self { intensity: 0.4 }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct FlareUniform {
+    /// The sun's position in normalized device coordinates
+    sun: [f32; 2],
+    /// Depth of the sun's point closest to the camera
+    depth: f32,
+    intensity: f32,
+    aspect: f32,
+    _padding: [f32; 3],
+}
+
+/// Number of sprites the flare is made of, must match the shader
+const ELEMENTS: u32 = 7;
+
+/// Draws sprites along the line from the sun through the screen's center
+///
+/// The depth buffer is read around the sun's position to find how much of it is hidden behind other bodies.
+pub struct LensFlarePipeline {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    buffer: Buffer,
+    bind_group: BindGroup,
+    /// Whether the sun is in front of the camera and the flare is enabled
+    visible: bool,
+}
+
+impl LensFlarePipeline {
+    pub fn new(device: &Device, depth: &TextureView) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Lens Flare Shader"),
+            source: ShaderSource::Wgsl(include_str!("../flare.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("lens_flare_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Lens Flare Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Lens Flare Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: Tonemap::FORMAT,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Lens Flare Buffer"),
+            contents: bytemuck::cast_slice(&[FlareUniform::default()]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let bind_group = Self::bind(device, &layout, &buffer, depth);
+
+        Self {
+            pipeline,
+            layout,
+            buffer,
+            bind_group,
+            visible: false,
+        }
+    }
+
+    fn bind(
+        device: &Device,
+        layout: &BindGroupLayout,
+        buffer: &Buffer,
+        depth: &TextureView,
+    ) -> BindGroup {
+        device.create_bind_group(&BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(depth),
+                },
+            ],
+            label: Some("lens_flare_bind_group"),
+        })
+    }
+
+    /// Use the new depth buffer after the surface was resized
+    pub fn resize(&mut self, device: &Device, depth: &TextureView) {
+        self.bind_group = Self::bind(device, &self.layout, &self.buffer, depth);
+    }
+
+    /// Project the sun onto the screen
+    ///
+    /// `sun` is the sun's center and rendered radius in render space, `None` hides the flare.
+    pub fn update(
+        &mut self,
+        queue: &Queue,
+        flare: &LensFlare,
+        view_proj: Matrix4<f32>,
+        aspect: f32,
+        camera: Point3<f32>,
+        sun: Option<(Point3<f32>, f32)>,
+    ) {
+        self.visible = false;
+        let Some((center, radius)) = sun else {
+            return;
+        };
+        let to_camera = camera - center;
+        if flare.intensity <= 0.0 || to_camera.magnitude() <= radius {
+            return;
+        }
+        // The sun's own surface covers its center in the depth buffer
+        let closest = center + to_camera.normalize_to(radius);
+        let clip = view_proj * closest.to_vec().extend(1.0);
+        if clip.w <= 0.0 {
+            return;
+        }
+        let uniform = FlareUniform {
+            sun: [clip.x / clip.w, clip.y / clip.w],
+            depth: clip.z / clip.w,
+            intensity: flare.intensity,
+            aspect,
+            _padding: [0.0; 3],
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.visible = true;
+    }
+
+    /// Add the flare onto the HDR target
+    pub fn draw(&self, encoder: &mut CommandEncoder, hdr: &TextureView) {
+        if !self.visible {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Lens Flare Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: hdr,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..ELEMENTS);
+    }
+}
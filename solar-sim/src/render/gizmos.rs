@@ -0,0 +1,95 @@
+//! Arrows showing which way bodies move and are pulled
+
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use specs::{Join, ReadStorage, System, Write};
+
+use crate::physics::{Acceleration, Position, Velocity};
+use crate::render::lines::{LineVertex, Lines};
+
+/// Layer in [`Lines`] the arrows are drawn to
+const LAYER: &str = "vector_gizmos";
+
+/// Length in meters an arrow grows by for every tenfold increase of its magnitude
+const LENGTH: f32 = 5e9;
+
+/// Velocity in m/s whose arrow has a length of [`LENGTH`] times log10(2)
+const VELOCITY_REFERENCE: f32 = 1e2;
+
+/// Acceleration in m/s² whose arrow has a length of [`LENGTH`] times log10(2)
+const ACCELERATION_REFERENCE: f32 = 1e-6;
+
+const VELOCITY_COLOR: [f32; 3] = [0.2, 1.0, 0.3];
+
+const ACCELERATION_COLOR: [f32; 3] = [1.0, 0.3, 0.2];
+
+/// System drawing an arrow for each body's [`Velocity`] and [`Acceleration`]
+///
+/// The arrows start at the body and their lengths scale logarithmically with the magnitudes,
+/// so both the fast and slow bodies' arrows are visible at once.
+///
+/// Updates [`Lines`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct VectorGizmos;
+
+impl<'a> System<'a> for VectorGizmos {
+    type SystemData = (
+        Write<'a, Lines>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+        ReadStorage<'a, Acceleration>,
+    );
+
+    fn run(&mut self, (mut lines, pos, vel, acc): Self::SystemData) {
+        let mut arrows = Vec::new();
+        for (pos, vel) in (&pos, &vel).join() {
+            arrow(
+                &mut arrows,
+                pos.0,
+                vel.0.map(|c| c as f32),
+                VELOCITY_REFERENCE,
+                VELOCITY_COLOR,
+            );
+        }
+        for (pos, acc) in (&pos, &acc).join() {
+            arrow(
+                &mut arrows,
+                pos.0,
+                acc.0.map(|c| c as f32),
+                ACCELERATION_REFERENCE,
+                ACCELERATION_COLOR,
+            );
+        }
+        lines.set(LAYER, arrows);
+    }
+}
+
+/// Add the lines of an arrow starting at `start` pointing along `vector`
+fn arrow(
+    lines: &mut Vec<LineVertex>,
+    start: Point3<f64>,
+    vector: Vector3<f32>,
+    reference: f32,
+    color: [f32; 3],
+) {
+    let magnitude = vector.magnitude();
+    if !magnitude.is_normal() {
+        return;
+    }
+    let direction = vector / magnitude;
+    let length = LENGTH * (1.0 + magnitude / reference).log10();
+
+    let tip = direction * length;
+    let back = tip - direction * length * 0.2;
+    let up = if direction.y.abs() < 0.99 {
+        Vector3::unit_y()
+    } else {
+        Vector3::unit_x()
+    };
+    let side = direction.cross(up).normalize() * length * 0.1;
+    for offset in [Vector3::zero(), tip, tip, back + side, tip, back - side] {
+        lines.push(LineVertex {
+            position: start + offset.cast().unwrap(),
+            color,
+        });
+    }
+}
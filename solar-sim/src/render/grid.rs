@@ -0,0 +1,108 @@
+//! Polar grid on the ecliptic plane for judging distances
+
+use std::f32::consts::TAU;
+
+use cgmath::Point3;
+use specs::{Read, ReadExpect, System, Write};
+
+use crate::render::camera::{Camera, Projection};
+use crate::render::label::project;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::text::{Align, Text, TextQueue};
+use crate::render::SCALE;
+
+/// Astronomical unit in meters
+pub const AU: f32 = 1.495_978_7e11;
+
+/// Layer in [`Lines`] the grid is drawn to
+const LAYER: &str = "ecliptic_grid";
+
+/// Number of lines each ring is made of
+const SEGMENTS: usize = 128;
+
+/// Height of the distance labels in pixels
+const SIZE: f32 = 14.0;
+
+/// Grid resource configuring the [`EclipticGrid`]
+///
+/// Defaults to rings at 1, 5, 10 and 30 AU with a spoke every 30°
+#[derive(Clone, Debug)]
+pub struct GridConfig {
+    /// Radii of the rings in AU
+    pub rings: Vec<f32>,
+
+    /// Number of spokes from the center to the outermost ring
+    pub spokes: usize,
+
+    pub color: [f32; 3],
+}
+
+impl Default for GridConfig {
+    fn default() -> Self {
+        Self {
+            rings: vec![1.0, 5.0, 10.0, 30.0],
+            spokes: 12,
+            color: [0.25, 0.3, 0.4],
+        }
+    }
+}
+
+/// System drawing rings and spokes on the ecliptic plane (y = 0) around the origin,
+/// labeling each ring with its radius
+///
+/// Updates [`Lines`] and [`TextQueue`] resources
+#[derive(Copy, Clone, Debug, Default)]
+pub struct EclipticGrid;
+
+impl<'a> System<'a> for EclipticGrid {
+    type SystemData = (
+        Write<'a, Lines>,
+        Write<'a, TextQueue>,
+        Read<'a, GridConfig>,
+        Read<'a, Camera>,
+        ReadExpect<'a, Projection>,
+    );
+
+    fn run(&mut self, (mut lines, mut texts, config, camera, projection): Self::SystemData) {
+        let point = |radius: f32, angle: f32| {
+            Point3::new(angle.cos() * radius, 0.0, angle.sin() * radius) * AU
+        };
+        let vertex = |position: Point3<f32>| LineVertex {
+            position: position.cast().unwrap(),
+            color: config.color,
+        };
+
+        let mut grid = Vec::new();
+        for &radius in &config.rings {
+            for segment in 0..SEGMENTS {
+                let angle = |segment: usize| segment as f32 / SEGMENTS as f32 * TAU;
+                grid.push(vertex(point(radius, angle(segment))));
+                grid.push(vertex(point(radius, angle(segment + 1))));
+            }
+        }
+        let outer = config.rings.iter().copied().fold(0.0, f32::max);
+        for spoke in 0..config.spokes {
+            let angle = spoke as f32 / config.spokes as f32 * TAU;
+            grid.push(vertex(Point3::new(0.0, 0.0, 0.0)));
+            grid.push(vertex(point(outer, angle)));
+        }
+        lines.set(LAYER, grid);
+
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let height = projection.height as f32;
+        let screen = [projection.aspect * height, height];
+        let [r, g, b] = config.color;
+        for &radius in &config.rings {
+            let Some(position) = project(view_proj, screen, point(radius, 0.0) / SCALE) else {
+                continue;
+            };
+            texts.push(Text {
+                content: format!("{radius} AU"),
+                position,
+                size: SIZE,
+                color: [r * 2.0, g * 2.0, b * 2.0, 1.0],
+                align: Align::Left,
+            });
+        }
+    }
+}
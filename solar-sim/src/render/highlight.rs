@@ -0,0 +1,86 @@
+//! Ring marking the selected body
+
+use std::f32::consts::TAU;
+
+use cgmath::{InnerSpace, Vector3};
+use specs::{Read, ReadStorage, System, Write};
+
+use crate::control::Selection;
+use crate::physics::{Position, Radius};
+use crate::render::camera::Camera;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::{Exaggeration, SCALE};
+
+/// Layer in [`Lines`] the highlight is drawn to
+pub const LAYER: &str = "highlight";
+
+/// Color of the ring
+const COLOR: [f32; 3] = [1.0, 0.8, 0.2];
+
+/// Number of lines the ring is made of
+const SEGMENTS: usize = 64;
+
+/// Radius of the ring relative to the body's rendered radius
+const MARGIN: f32 = 1.4;
+
+/// Smallest radius of the ring relative to its distance to the camera,
+/// so bodies smaller than a pixel are still marked visibly
+const MIN_SIZE: f32 = 0.02;
+
+/// System drawing a ring facing the camera around the [`Selection`]
+///
+/// Updates [`Lines`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Highlight;
+
+impl<'a> System<'a> for Highlight {
+    type SystemData = (
+        Write<'a, Lines>,
+        Read<'a, Selection>,
+        Read<'a, Camera>,
+        Read<'a, Exaggeration>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Radius>,
+    );
+
+    fn run(&mut self, (mut lines, selection, camera, exaggeration, pos, radius): Self::SystemData) {
+        let Some(anchor) = selection.0.and_then(|entity| pos.get(entity)) else {
+            lines.clear(LAYER);
+            return;
+        };
+        let anchor = anchor.0;
+        let center = anchor.map(|c| c as f32) / SCALE;
+        let radius = exaggeration.render_radius(selection.0.and_then(|entity| radius.get(entity)));
+
+        let forward = center - camera.position;
+        let distance = forward.magnitude();
+        if distance <= f32::EPSILON {
+            lines.clear(LAYER);
+            return;
+        }
+        let forward = forward / distance;
+        let up = if forward.y.abs() < 0.99 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_x()
+        };
+        let right = forward.cross(up).normalize();
+        let up = right.cross(forward);
+        let size = (radius * MARGIN).max(distance * MIN_SIZE);
+
+        let point = |segment: usize| {
+            let angle = segment as f32 / SEGMENTS as f32 * TAU;
+            let offset = (right * angle.cos() + up * angle.sin()) * (size * SCALE);
+            LineVertex {
+                position: anchor + offset.cast().unwrap(),
+                color: COLOR,
+            }
+        };
+        lines.set(
+            LAYER,
+            (0..SEGMENTS)
+                .flat_map(|segment| [point(segment), point(segment + 1)])
+                .collect(),
+        );
+    }
+}
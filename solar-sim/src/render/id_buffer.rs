@@ -0,0 +1,295 @@
+//! Pixel accurate picking by rendering entity ids and reading them back
+
+use std::mem::size_of;
+use std::sync::mpsc::{channel, Receiver};
+
+use log::warn;
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupLayout, Buffer, BufferAddress, BufferAsyncError,
+    BufferDescriptor, BufferUsages, ColorTargetState, CommandEncoder, DepthStencilState, Device,
+    Extent3d, FragmentState, PipelineLayoutDescriptor, PrimitiveState, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, SurfaceConfiguration,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::render::instance::InstanceRaw;
+use crate::render::texture::Texture;
+use crate::render::Vertex;
+
+/// Id written where no body is
+pub const NONE: u32 = 0;
+
+/// Where the id under the cursor is in its way back to the cpu
+#[derive(Debug)]
+enum Readback {
+    /// Free to pick
+    Idle,
+
+    /// Copy into the readback buffer is recorded but not submitted yet
+    Copied,
+
+    /// Waiting for the readback buffer to be mapped
+    Mapping(Receiver<Result<(), BufferAsyncError>>),
+}
+
+/// Renders every body's id into an offscreen target and reads back single pixels
+///
+/// Ids are entity ids plus one, so [`NONE`] can mark the background.
+pub struct IdBuffer {
+    pipeline: RenderPipeline,
+    target: wgpu::Texture,
+    target_view: TextureView,
+    depth: Texture,
+    size: (u32, u32),
+    ids: Buffer,
+    capacity: usize,
+    readback: Buffer,
+    state: Readback,
+}
+
+impl IdBuffer {
+    const FORMAT: TextureFormat = TextureFormat::R32Uint;
+
+    pub fn new(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        camera_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Id Shader"),
+            source: ShaderSource::Wgsl(include_str!("../id.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Id Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Id Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[
+                    Vertex::desc(),
+                    InstanceRaw::desc(),
+                    VertexBufferLayout {
+                        array_stride: size_of::<u32>() as BufferAddress,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: &vertex_attr_array![9 => Uint32],
+                    },
+                ],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: Self::FORMAT,
+                    blend: None,
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let (target, target_view) = Self::create_target(device, config);
+        let capacity = 64;
+
+        Self {
+            pipeline,
+            target,
+            target_view,
+            depth: Texture::create_depth_texture(device, config, "id_depth_texture"),
+            size: (config.width, config.height),
+            ids: Self::create_buffer(device, capacity),
+            capacity,
+            readback: device.create_buffer(&BufferDescriptor {
+                label: Some("Id Readback Buffer"),
+                size: wgpu::COPY_BYTES_PER_ROW_ALIGNMENT as BufferAddress,
+                usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            }),
+            state: Readback::Idle,
+        }
+    }
+
+    fn create_target(
+        device: &Device,
+        config: &SurfaceConfiguration,
+    ) -> (wgpu::Texture, TextureView) {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Id Target"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Id Buffer"),
+            size: (capacity * size_of::<u32>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Whether a new pick can be started
+    pub fn is_idle(&self) -> bool {
+        matches!(self.state, Readback::Idle)
+    }
+
+    /// Record rendering the instances' ids and copying the pixel under the cursor for readback
+    ///
+    /// `ids` holds one id per instance.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &wgpu::Queue,
+        config: &SurfaceConfiguration,
+        encoder: &mut CommandEncoder,
+        camera: &BindGroup,
+        vertices: (&Buffer, &Buffer, u32),
+        instances: &Buffer,
+        ids: &[u32],
+        (x, y): (u32, u32),
+    ) {
+        if !self.is_idle() {
+            return;
+        }
+        if self.size != (config.width, config.height) {
+            (self.target, self.target_view) = Self::create_target(device, config);
+            self.depth = Texture::create_depth_texture(device, config, "id_depth_texture");
+            self.size = (config.width, config.height);
+        }
+        if ids.len() > self.capacity {
+            self.capacity = ids.len().next_power_of_two();
+            self.ids = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.ids, 0, bytemuck::cast_slice(ids));
+
+        let (vertex_buffer, index_buffer, num_indices) = vertices;
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Id Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.target_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            if !ids.is_empty() {
+                render_pass.set_pipeline(&self.pipeline);
+                render_pass.set_bind_group(0, camera, &[]);
+                render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                render_pass.set_vertex_buffer(1, instances.slice(..));
+                render_pass.set_vertex_buffer(2, self.ids.slice(..));
+                render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+                render_pass.draw_indexed(0..num_indices, 0, 0..ids.len() as u32);
+            }
+        }
+
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.target,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: x.min(self.size.0 - 1),
+                    y: y.min(self.size.1 - 1),
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT),
+                    rows_per_image: Some(1),
+                },
+            },
+            Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        self.state = Readback::Copied;
+    }
+
+    /// Start reading back the copied pixel
+    ///
+    /// Requires the encoder passed to [`IdBuffer::render`] to be submitted
+    pub fn request(&mut self) {
+        if let Readback::Copied = self.state {
+            let (sender, receiver) = channel();
+            self.readback
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            self.state = Readback::Mapping(receiver);
+        }
+    }
+
+    /// The picked id once it arrived
+    pub fn poll(&mut self, device: &Device) -> Option<u32> {
+        let Readback::Mapping(receiver) = &self.state else {
+            return None;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let result = receiver.try_recv().ok()?;
+        self.state = Readback::Idle;
+        match result {
+            Ok(()) => {
+                let id = {
+                    let view = self.readback.slice(..).get_mapped_range();
+                    u32::from_ne_bytes([view[0], view[1], view[2], view[3]])
+                };
+                self.readback.unmap();
+                Some(id)
+            }
+            Err(error) => {
+                warn!("Failed to read back picked id: {error}");
+                Some(NONE)
+            }
+        }
+    }
+}
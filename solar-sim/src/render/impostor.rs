@@ -0,0 +1,212 @@
+//! Point sprites standing in for bodies too small on screen to be drawn as a mesh
+
+use std::mem::size_of;
+
+use cgmath::{InnerSpace, Point3};
+use specs::shred::PanicHandler;
+use specs::{Component, Entities, Join, NullStorage, Read, ReadStorage, System, WriteStorage};
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupLayout, BlendComponent, BlendFactor, BlendOperation,
+    BlendState, Buffer, BufferAddress, BufferDescriptor, BufferUsages, ColorTargetState,
+    DepthStencilState, Device, FragmentState, PipelineLayoutDescriptor, Queue, RenderPass,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::physics::{Planet, Position, Radius};
+use crate::render::camera::{Camera, Projection};
+use crate::render::texture::Texture;
+use crate::render::{Exaggeration, SCALE};
+
+/// Diameter in pixels below which bodies become impostors
+pub const MAX_SIZE: f32 = 2.0;
+
+/// Radius in pixels of the sprites, their falloff makes them look smaller
+const SPRITE_RADIUS: f32 = 2.0;
+
+/// Marker component for bodies drawn as a point sprite instead of a mesh
+///
+/// Updated by [`ClassifyImpostors`] system
+#[derive(Copy, Clone, Debug, Default, Component)]
+#[storage(NullStorage)]
+pub struct Impostor;
+
+/// System marking every body smaller than [`MAX_SIZE`] on screen as an [`Impostor`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ClassifyImpostors;
+
+impl<'a> System<'a> for ClassifyImpostors {
+    type SystemData = (
+        Entities<'a>,
+        Read<'a, Camera>,
+        Read<'a, Projection, PanicHandler>,
+        Read<'a, Exaggeration>,
+        ReadStorage<'a, Planet>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Radius>,
+        WriteStorage<'a, Impostor>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            ent,
+            camera,
+            projection,
+            exaggeration,
+            planet,
+            pos,
+            radius,
+            mut impostors,
+        ): Self::SystemData,
+    ) {
+        for (e, _, pos) in (&ent, &planet, &pos).join() {
+            let center = pos.0.map(|c| c as f32) / SCALE;
+            let size = 2.0
+                * projection.screen_radius(
+                    exaggeration.render_radius(radius.get(e)),
+                    (center - camera.position).magnitude(),
+                );
+            if (size < MAX_SIZE) != impostors.contains(e) {
+                if size < MAX_SIZE {
+                    impostors.insert(e, Impostor).ok();
+                } else {
+                    impostors.remove(e);
+                }
+            }
+        }
+    }
+}
+
+/// A single sprite in render space
+#[derive(Copy, Clone, Debug)]
+pub struct Sprite {
+    pub position: Point3<f32>,
+    pub color: [f32; 3],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteRaw {
+    position: [f32; 3],
+    color: [f32; 3],
+    /// Half of the sprite's width and height in normalized device coordinates
+    size: [f32; 2],
+}
+
+/// Draws [`Sprite`]s facing the camera with a soft falloff
+///
+/// They are tested against the depth buffer but don't write to it.
+pub struct ImpostorPipeline {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    capacity: usize,
+    len: u32,
+}
+
+impl ImpostorPipeline {
+    pub fn new(device: &Device, format: TextureFormat, camera_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Impostor Shader"),
+            source: ShaderSource::Wgsl(include_str!("../impostor.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Impostor Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Impostor Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<SpriteRaw>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![
+                        0 => Float32x3,
+                        1 => Float32x3,
+                        2 => Float32x2,
+                    ],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let capacity = 64;
+        Self {
+            pipeline,
+            buffer: Self::create_buffer(device, capacity),
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Impostor Buffer"),
+            size: (capacity * size_of::<SpriteRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Upload the sprites of the current frame
+    ///
+    /// `size` is the screen's width and height in pixels.
+    pub fn update(&mut self, device: &Device, queue: &Queue, size: [f32; 2], sprites: &[Sprite]) {
+        let data: Vec<_> = sprites
+            .iter()
+            .map(|sprite| SpriteRaw {
+                position: sprite.position.into(),
+                color: sprite.color,
+                size: size.map(|pixels| 2.0 * SPRITE_RADIUS / pixels),
+            })
+            .collect();
+        if data.len() > self.capacity {
+            self.capacity = data.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&data));
+        self.len = data.len() as u32;
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, camera: &'a BindGroup) {
+        if self.len == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..6, 0..self.len);
+    }
+}
@@ -0,0 +1,90 @@
+//! Spheres of influence, the regions in which a body captures others from its primary
+
+use std::f64::consts::{PI, TAU};
+
+use cgmath::{Point3, Vector3};
+use specs::{Join, Read, ReadStorage, System, Write};
+
+use crate::control::Controls;
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{Mass, Position};
+use crate::render::lines::{LineVertex, Lines};
+
+/// Layer in [`Lines`] the spheres are drawn to
+const LAYER: &str = "spheres_of_influence";
+
+/// Number of lines each circle is made of
+const SEGMENTS: usize = 48;
+
+/// Number of circles of longitude and of latitude each
+const CIRCLES: usize = 6;
+
+/// Dim, so the spheres don't hide what's inside them
+const COLOR: [f32; 3] = [0.15, 0.25, 0.45];
+
+/// System drawing a wireframe sphere around every body with [`OrbitalElements`] and a [`Mass`]
+/// while [`Controls::show_spheres_of_influence`] is set
+///
+/// The radius is given by [`OrbitalElements::sphere_of_influence`].
+/// A small body inside the sphere orbits the body, one outside of it orbits the body's primary.
+///
+/// Updates [`Lines`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SpheresOfInfluence;
+
+impl<'a> System<'a> for SpheresOfInfluence {
+    type SystemData = (
+        Read<'a, Controls>,
+        Write<'a, Lines>,
+        ReadStorage<'a, OrbitalElements>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(&mut self, (controls, mut lines, elements, mass, pos): Self::SystemData) {
+        if !controls.show_spheres_of_influence {
+            lines.clear(LAYER);
+            return;
+        }
+
+        let mut spheres = Vec::new();
+        for (orbit, this_mass, pos) in (&elements, &mass, &pos).join() {
+            let Some(primary_mass) = mass.get(orbit.primary) else {
+                continue;
+            };
+            let radius = orbit.sphere_of_influence(this_mass.0, primary_mass.0);
+            sphere(&mut spheres, pos.0, radius);
+        }
+        lines.set(LAYER, spheres);
+    }
+}
+
+/// Push the circles of longitude and latitude of a sphere
+fn sphere(lines: &mut Vec<LineVertex>, center: Point3<f64>, radius: f64) {
+    let mut circle = |point: &dyn Fn(f64) -> Vector3<f64>| {
+        for segment in 0..SEGMENTS {
+            for end in [segment, segment + 1] {
+                let angle = end as f64 / SEGMENTS as f64 * TAU;
+                lines.push(LineVertex {
+                    position: center + point(angle) * radius,
+                    color: COLOR,
+                });
+            }
+        }
+    };
+    for i in 0..CIRCLES {
+        // Half turns suffice, every meridian circle covers both sides
+        let (sin, cos) = (i as f64 / CIRCLES as f64 * PI).sin_cos();
+        circle(&|angle| {
+            let (y, horizontal) = angle.sin_cos();
+            Vector3::new(horizontal * cos, y, horizontal * sin)
+        });
+    }
+    for i in 1..CIRCLES {
+        let (y, width) = (i as f64 / CIRCLES as f64 * PI - PI / 2.0).sin_cos();
+        circle(&|angle| {
+            let (sin, cos) = angle.sin_cos();
+            Vector3::new(cos * width, y, sin * width)
+        });
+    }
+}
@@ -0,0 +1,185 @@
+//! Close-up of the selected body in a corner of the screen
+
+use cgmath::{Deg, EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages,
+    CommandEncoder, Device, Extent3d, Queue, RenderPass, SurfaceConfiguration, TextureDescriptor,
+    TextureDimension, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+use crate::render::camera::{CameraUniform, Projection};
+use crate::render::texture::Texture;
+use crate::render::tonemap::Tonemap;
+use crate::render::BLACK;
+
+/// Width and height of the inset in pixels
+const SIZE: u32 = 256;
+
+/// Pixels between the inset and the screen's edges
+const MARGIN: u32 = 8;
+
+/// Distance of the inset's camera to the body's center in units of its radius
+const DISTANCE: f32 = 4.0;
+
+/// Second view locked onto a body, which is copied into the top right corner of the scene
+///
+/// The body is seen from the same side as from the main camera.
+pub struct Inset {
+    color: wgpu::Texture,
+    color_view: TextureView,
+    depth: Texture,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    /// Whether a body is shown at all
+    active: bool,
+}
+
+impl Inset {
+    pub fn new(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        camera_layout: &BindGroupLayout,
+    ) -> Self {
+        let color = device.create_texture(&TextureDescriptor {
+            label: Some("Inset Target"),
+            size: Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Tonemap::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let color_view = color.create_view(&TextureViewDescriptor::default());
+        let depth = Texture::create_depth_texture(
+            device,
+            &SurfaceConfiguration {
+                width: SIZE,
+                height: SIZE,
+                ..config.clone()
+            },
+            "inset_depth_texture",
+        );
+
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Inset Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
+                Matrix4::identity(),
+                Point3::origin(),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("inset_camera_bind_group"),
+        });
+
+        Self {
+            color,
+            color_view,
+            depth,
+            camera_buffer,
+            camera_bind_group,
+            active: false,
+        }
+    }
+
+    /// Point the camera at a body given by its center and radius in render space, or hide the inset
+    ///
+    /// `camera` is the main camera's position.
+    pub fn update(&mut self, queue: &Queue, body: Option<(Point3<f32>, f32)>, camera: Point3<f32>) {
+        self.active = body.is_some();
+        let Some((center, radius)) = body else {
+            return;
+        };
+        let offset = camera - center;
+        let direction = if offset.magnitude2() > 0.0 {
+            offset.normalize()
+        } else {
+            Vector3::unit_z()
+        };
+        let position = center + direction * radius * DISTANCE;
+        let up = if direction.y.abs() < 0.99 {
+            Vector3::unit_y()
+        } else {
+            Vector3::unit_x()
+        };
+        let projection = Projection {
+            fovy: Deg(30.0).into(),
+            znear: radius * (DISTANCE - 1.0) / 2.0,
+            ..Projection::new(SIZE, SIZE)
+        };
+        let view_proj = projection.reversed_z() * Matrix4::look_at_rh(position, center, up);
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform::new(view_proj, position)]),
+        );
+    }
+
+    /// Camera looking at the body
+    pub fn camera(&self) -> &BindGroup {
+        &self.camera_bind_group
+    }
+
+    /// Begin the pass drawing the inset, `None` while it's hidden
+    pub fn begin<'a>(&'a self, encoder: &'a mut CommandEncoder) -> Option<RenderPass<'a>> {
+        self.active.then(|| {
+            encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Inset Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &self.color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth.view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
+                        store: false,
+                    }),
+                    stencil_ops: None,
+                }),
+            })
+        })
+    }
+
+    /// Copy the inset into the top right corner of the scene, unless it's hidden or doesn't fit
+    pub fn composite(&self, encoder: &mut CommandEncoder, tonemap: &Tonemap) {
+        let target = tonemap.texture();
+        if !self.active || target.width() < SIZE + 2 * MARGIN || target.height() < SIZE + 2 * MARGIN
+        {
+            return;
+        }
+        encoder.copy_texture_to_texture(
+            self.color.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: target,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: target.width() - SIZE - MARGIN,
+                    y: MARGIN,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
@@ -1,9 +1,10 @@
-use cgmath::{EuclideanSpace, InnerSpace, One, Point3, Quaternion, Rotation3, Vector3, Zero};
+use cgmath::{EuclideanSpace, One, Point3, Quaternion, Vector3, Zero};
 
 #[derive(Debug)]
 pub struct Instance {
     pub position: Vector3<f32>,
     pub rotation: Quaternion<f32>,
+    pub scale: f32,
 }
 
 impl Default for Instance {
@@ -11,46 +12,25 @@ impl Default for Instance {
         Self {
             position: Vector3::zero(),
             rotation: Quaternion::one(),
+            scale: 1.0,
         }
     }
 }
 
 impl Instance {
-    pub fn from_position(position: Point3<f32>) -> Self {
+    pub fn from_position(position: Point3<f32>, scale: f32) -> Self {
         Self {
             position: position - Point3::origin(),
             rotation: Quaternion::one(),
+            scale,
         }
     }
 
-    pub fn generate(instance_per_row: usize, instance_displacement: Vector3<f32>) -> Vec<Self> {
-        (0..instance_per_row)
-            .flat_map(|z| {
-                (0..instance_per_row).map(move |x| {
-                    let position = cgmath::Vector3 {
-                        x: x as f32,
-                        y: 0.0,
-                        z: z as f32,
-                    } - instance_displacement;
-
-                    let rotation = if position.is_zero() {
-                        // this is needed so an object at (0, 0, 0) won't get scaled to zero
-                        // as Quaternions can effect scale if they're not created correctly
-                        Quaternion::from_axis_angle(Vector3::unit_z(), cgmath::Deg(0.0))
-                    } else {
-                        Quaternion::from_axis_angle(position.normalize(), cgmath::Deg(45.0))
-                    };
-
-                    Self { position, rotation }
-                })
-            })
-            .collect()
-    }
-
     pub fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
             model: (cgmath::Matrix4::from_translation(self.position)
-                * cgmath::Matrix4::from(self.rotation))
+                * cgmath::Matrix4::from(self.rotation)
+                * cgmath::Matrix4::from_scale(self.scale))
             .into(),
         }
     }
@@ -0,0 +1,58 @@
+//! Names floating above the bodies
+
+use cgmath::{InnerSpace, Matrix4, Point3, Vector3, Vector4};
+
+use crate::render::text::{Align, Text};
+
+/// Height of the labels in pixels
+const SIZE: f32 = 16.0;
+
+/// Distance in render space from which on labels start to fade out
+const FADE_START: f32 = 30.0;
+
+/// Distance in render space from which on labels are invisible
+const FADE_END: f32 = 90.0;
+
+/// Pixels between a body's top and its label
+const MARGIN: f32 = 4.0;
+
+/// Pixel coordinates of a point in render space, `None` if it's behind the camera
+pub fn project(view_proj: Matrix4<f32>, screen: [f32; 2], point: Point3<f32>) -> Option<[f32; 2]> {
+    let clip = view_proj * Vector4::new(point.x, point.y, point.z, 1.0);
+    (clip.w > 0.0).then(|| {
+        [
+            (clip.x / clip.w + 1.0) / 2.0 * screen[0],
+            (1.0 - clip.y / clip.w) / 2.0 * screen[1],
+        ]
+    })
+}
+
+/// Create a label above each body facing the screen
+///
+/// Bodies are given by their name, center and radius in render space.
+pub fn labels<'a>(
+    view_proj: Matrix4<f32>,
+    camera: Point3<f32>,
+    screen: [f32; 2],
+    bodies: impl IntoIterator<Item = (&'a str, Point3<f32>, f32)>,
+) -> Vec<Text> {
+    let mut labels = Vec::new();
+    for (name, center, radius) in bodies {
+        let distance = (center - camera).magnitude();
+        let fade = ((distance - FADE_START) / (FADE_END - FADE_START)).clamp(0.0, 1.0);
+        if fade >= 1.0 {
+            continue;
+        }
+        let Some(top) = project(view_proj, screen, center + Vector3::unit_y() * radius) else {
+            continue;
+        };
+        labels.push(Text {
+            content: name.to_string(),
+            position: [top[0], top[1] - MARGIN],
+            size: SIZE,
+            color: [1.0, 1.0, 1.0, 1.0 - fade],
+            align: Align::Center,
+        });
+    }
+    labels
+}
@@ -0,0 +1,142 @@
+//! Lagrange points of the planets, where a small body can keep its place relative to a planet and the sun
+
+use cgmath::{InnerSpace, Point3, Quaternion, Rad, Rotation, Rotation3, Vector3};
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::control::Controls;
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{Mass, Name, Position, Velocity};
+use crate::render::camera::{Camera, Projection};
+use crate::render::label::project;
+use crate::render::light::LightSource;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::text::{Align, Text, TextQueue};
+use crate::render::SCALE;
+
+/// Layer in [`Lines`] the markers are drawn to
+const LAYER: &str = "lagrange_points";
+
+/// Length of a marker's arms relative to the planet's distance from the sun
+const MARKER: f64 = 0.01;
+
+/// Color of the unstable points L1 to L3 on the line through the sun and the planet
+const COLLINEAR: [f32; 3] = [1.0, 0.5, 0.3];
+
+/// Color of the stable points L4 and L5 leading and trailing the planet
+const TRIANGULAR: [f32; 3] = [0.3, 1.0, 0.5];
+
+/// Height of the labels in pixels
+const SIZE: f32 = 12.0;
+
+/// Lagrange points L1 to L5 of a body at `r` moving at `v` relative to its primary,
+/// with `ratio` being the body's share `m / (M + m)` of their mass
+///
+/// L1 to L3 use the usual approximations for small ratios.
+/// Returned relative to the primary.
+pub fn lagrange_points(r: Vector3<f64>, v: Vector3<f64>, ratio: f64) -> [Vector3<f64>; 5] {
+    let hill = (ratio / 3.0).cbrt();
+    let normal = r.cross(v).normalize();
+    let turn = |angle: f64| Quaternion::from_axis_angle(normal, Rad(angle)).rotate_vector(r);
+    [
+        r * (1.0 - hill),
+        r * (1.0 + hill),
+        -r * (1.0 + 5.0 / 12.0 * ratio),
+        turn(std::f64::consts::FRAC_PI_3),
+        turn(-std::f64::consts::FRAC_PI_3),
+    ]
+}
+
+/// System marking the Lagrange points of every body orbiting a [`LightSource`] with a cross and a label
+/// while [`Controls::show_lagrange_points`] is set
+///
+/// Updates [`Lines`] and [`TextQueue`] resources
+#[derive(Copy, Clone, Debug, Default)]
+pub struct LagrangePoints;
+
+impl<'a> System<'a> for LagrangePoints {
+    type SystemData = (
+        Read<'a, Controls>,
+        Write<'a, Lines>,
+        Write<'a, TextQueue>,
+        Read<'a, Camera>,
+        ReadExpect<'a, Projection>,
+        ReadStorage<'a, LightSource>,
+        ReadStorage<'a, OrbitalElements>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            controls,
+            mut lines,
+            mut texts,
+            camera,
+            projection,
+            light,
+            elements,
+            name,
+            mass,
+            pos,
+            vel,
+        ): Self::SystemData,
+    ) {
+        if !controls.show_lagrange_points {
+            lines.clear(LAYER);
+            return;
+        }
+
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let height = projection.height as f32;
+        let screen = [projection.aspect * height, height];
+
+        let mut markers = Vec::new();
+        for (orbit, name, this_mass, this_pos, this_vel) in
+            (&elements, &name, &mass, &pos, &vel).join()
+        {
+            if !light.contains(orbit.primary) {
+                continue;
+            }
+            let (Some(sun_mass), Some(sun_pos), Some(sun_vel)) = (
+                mass.get(orbit.primary),
+                pos.get(orbit.primary),
+                vel.get(orbit.primary),
+            ) else {
+                continue;
+            };
+            let r = this_pos.0 - sun_pos.0;
+            let ratio = f64::from(this_mass.0) / (f64::from(sun_mass.0) + f64::from(this_mass.0));
+            let arm = r.magnitude() * MARKER;
+            let points = lagrange_points(r, this_vel.0 - sun_vel.0, ratio);
+            for (i, point) in points.into_iter().enumerate() {
+                let center = sun_pos.0 + point;
+                let color = if i < 3 { COLLINEAR } else { TRIANGULAR };
+                for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+                    for end in [center - axis * arm, center + axis * arm] {
+                        markers.push(LineVertex {
+                            position: end,
+                            color,
+                        });
+                    }
+                }
+
+                let center: Point3<f32> = center.map(|c| c as f32);
+                let Some(position) = project(view_proj, screen, center / SCALE) else {
+                    continue;
+                };
+                let [r, g, b] = color;
+                texts.push(Text {
+                    content: format!("{} L{}", name.0, i + 1),
+                    position,
+                    size: SIZE,
+                    color: [r, g, b, 1.0],
+                    align: Align::Left,
+                });
+            }
+        }
+        lines.set(LAYER, markers);
+    }
+}
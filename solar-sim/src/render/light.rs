@@ -0,0 +1,55 @@
+//! Light emitted by the sun
+
+use specs::{Component, NullStorage};
+
+/// Marker component for the body lighting all others
+///
+/// Only the first one found is used.
+/// Without any, every body is rendered fully lit.
+/// It should be [`Emissive`] as well, since it would be shaded by itself otherwise.
+#[derive(Copy, Clone, Debug, Default, Component)]
+#[storage(NullStorage)]
+pub struct LightSource;
+
+/// Marker component for bodies drawn unlit, glowing with their texture times [`EMISSION`]
+#[derive(Copy, Clone, Debug, Default, Component)]
+#[storage(NullStorage)]
+pub struct Emissive;
+
+/// Brightness of the sides facing away from the light
+pub const AMBIENT: f32 = 0.05;
+
+/// Brightness of the light source's own surface, beyond what the display can show
+pub const EMISSION: f32 = 4.0;
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    /// Position in render space
+    pub position: [f32; 3],
+    pub ambient: f32,
+    pub color: [f32; 3],
+    pub emission: f32,
+}
+
+impl LightUniform {
+    /// White light at a position in render space
+    pub fn at(position: [f32; 3]) -> Self {
+        Self {
+            position,
+            ambient: AMBIENT,
+            color: [1.0; 3],
+            emission: EMISSION,
+        }
+    }
+
+    /// No light at all, so everything is lit by the ambient term alone
+    pub fn unlit() -> Self {
+        Self {
+            position: [0.0; 3],
+            ambient: 1.0,
+            color: [0.0; 3],
+            emission: 1.0,
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Pipeline and resource for drawing colored lines in world space
+
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use cgmath::Point3;
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupLayout, BlendState, Buffer, BufferAddress,
+    BufferDescriptor, BufferUsages, ColorTargetState, DepthStencilState, Device, FragmentState,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::render::texture::Texture;
+use crate::render::Origin;
+
+/// A line's end in world space
+///
+/// In double precision, so lines near bodies far from the origin keep up with them
+/// until [`Origin::render`] moves them next to the camera.
+#[derive(Copy, Clone, Debug)]
+pub struct LineVertex {
+    pub position: Point3<f64>,
+    pub color: [f32; 3],
+}
+
+/// Resource of lines to draw
+///
+/// Every layer is a list of vertex pairs, each pair forming one line.
+/// Systems own their layer and replace it whenever they have something new to show.
+#[derive(Clone, Debug, Default)]
+pub struct Lines(pub BTreeMap<&'static str, Vec<LineVertex>>);
+
+impl Lines {
+    /// Replace a layer's lines
+    pub fn set(&mut self, layer: &'static str, lines: Vec<LineVertex>) {
+        self.0.insert(layer, lines);
+    }
+
+    /// Remove a layer's lines
+    pub fn clear(&mut self, layer: &'static str) {
+        self.0.remove(layer);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertexRaw {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+/// Draws the [`Lines`] resource
+pub struct LinePipeline {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    capacity: usize,
+    len: u32,
+}
+
+impl LinePipeline {
+    pub fn new(device: &Device, format: TextureFormat, camera_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Line Shader"),
+            source: ShaderSource::Wgsl(include_str!("../lines.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Line Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Line Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<LineVertexRaw>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x3],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let capacity = 1024;
+        let buffer = Self::create_buffer(device, capacity);
+
+        Self {
+            pipeline,
+            buffer,
+            capacity,
+            len: 0,
+        }
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Line Buffer"),
+            size: (capacity * size_of::<LineVertexRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Upload all layers' lines, growing the buffer if necessary
+    pub fn update(&mut self, device: &Device, queue: &Queue, origin: &Origin, lines: &Lines) {
+        let vertices: Vec<_> = lines
+            .0
+            .values()
+            .flatten()
+            .map(|vertex| LineVertexRaw {
+                position: origin.render(vertex.position).into(),
+                color: vertex.color,
+            })
+            .collect();
+
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&vertices));
+        self.len = vertices.len() as u32;
+    }
+
+    /// Draw the lines
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, camera: &'a BindGroup) {
+        if self.len == 0 {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, camera, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+        render_pass.draw(0..self.len, 0..1);
+    }
+}
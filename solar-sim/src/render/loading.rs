@@ -0,0 +1,61 @@
+//! Progress bar shown while the scene's textures are streamed in
+
+use crate::render::text::{Align, Text};
+
+/// Characters in the progress bar
+const BAR_WIDTH: usize = 32;
+
+/// Height of the progress bar's text in pixels
+const SIZE: f32 = 20.0;
+
+/// Loading progress resource
+///
+/// Updated by [`Render`](crate::render::Render) which streams a limited number of textures each frame,
+/// drawing bodies with a placeholder until theirs is ready.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Loading {
+    /// Textures and assets created so far
+    pub loaded: usize,
+
+    /// Textures and assets still waiting to be created
+    pub pending: usize,
+
+    /// Whether nothing was pending once, after which the progress bar stays hidden
+    pub ready: bool,
+}
+
+impl Loading {
+    /// Fraction of everything known so far which is loaded
+    pub fn progress(&self) -> f32 {
+        match self.loaded + self.pending {
+            0 => 1.0,
+            total => self.loaded as f32 / total as f32,
+        }
+    }
+}
+
+/// Create a progress bar in the middle of the screen until the scene is [`ready`](Loading::ready)
+pub fn progress_bar(loading: &Loading, screen: [f32; 2]) -> Vec<Text> {
+    if loading.ready {
+        return Vec::new();
+    }
+    let progress = loading.progress();
+    let filled = (progress * BAR_WIDTH as f32) as usize;
+    let center = [screen[0] / 2.0, screen[1] / 2.0];
+    vec![
+        Text {
+            content: format!("Loading {:.0}%", progress * 100.0),
+            position: [center[0], center[1] - SIZE],
+            size: SIZE,
+            color: [1.0; 4],
+            align: Align::Center,
+        },
+        Text {
+            content: format!("[{}{}]", "#".repeat(filled), "-".repeat(BAR_WIDTH - filled)),
+            position: [center[0], center[1] + SIZE / 2.0],
+            size: SIZE,
+            color: [1.0; 4],
+            align: Align::Center,
+        },
+    ]
+}
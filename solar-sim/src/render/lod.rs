@@ -0,0 +1,71 @@
+//! Spheres of varying detail for bodies of varying size on screen
+
+use std::ops::Range;
+
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{Buffer, BufferUsages, Device, RenderPass};
+
+use crate::render::shapes::icosphere;
+
+/// Subdivisions of each level of detail and the radius in pixels from which on it is used
+const LEVELS: [(u8, f32); 5] = [(0, 0.0), (1, 4.0), (2, 16.0), (3, 64.0), (5, 256.0)];
+
+/// Every level of detail of the unit sphere packed into one vertex and one index buffer
+pub struct SphereLods {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    /// Indexes and offset of the first vertex of each level
+    levels: Vec<(Range<u32>, i32)>,
+}
+
+impl SphereLods {
+    pub fn new(device: &Device) -> Self {
+        let mut vertexes = Vec::new();
+        let mut indexes = Vec::new();
+        let mut levels = Vec::new();
+        for (subdivisions, _) in LEVELS {
+            let (level_vertexes, level_indexes) = icosphere(subdivisions);
+            let first = indexes.len() as u32;
+            levels.push((
+                first..first + level_indexes.len() as u32,
+                vertexes.len() as i32,
+            ));
+            vertexes.extend(level_vertexes);
+            indexes.extend(level_indexes);
+        }
+
+        Self {
+            vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Sphere LOD Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertexes),
+                usage: BufferUsages::VERTEX,
+            }),
+            index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Sphere LOD Index Buffer"),
+                contents: bytemuck::cast_slice(&indexes),
+                usage: BufferUsages::INDEX,
+            }),
+            levels,
+        }
+    }
+
+    /// Most detailed level worth using for a sphere with a radius of `pixels` on screen
+    pub fn level(pixels: f32) -> usize {
+        LEVELS
+            .iter()
+            .rposition(|(_, from)| pixels >= *from)
+            .unwrap_or(0)
+    }
+
+    /// Bind the buffers, the instances are expected in vertex buffer slot 1
+    pub fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+    }
+
+    /// Draw instances at a level of detail, requires [`bind`](Self::bind)
+    pub fn draw(&self, render_pass: &mut RenderPass, level: usize, instances: Range<u32>) {
+        let (indexes, base_vertex) = &self.levels[level];
+        render_pass.draw_indexed(indexes.clone(), *base_vertex, instances);
+    }
+}
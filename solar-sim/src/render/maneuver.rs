@@ -0,0 +1,116 @@
+//! Preview of the orbit a planned burn leads to
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use specs::{Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::control::Selection;
+use crate::physics::maneuver::ManeuverNode;
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{Position, SimTime};
+use crate::render::camera::{Camera, Projection};
+use crate::render::label::project;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::text::{Align, Text, TextQueue};
+use crate::render::SCALE;
+
+/// Layer in [`Lines`] the preview is drawn to
+const LAYER: &str = "maneuver";
+
+/// Number of lines the resulting orbit is made of
+const SEGMENTS: usize = 128;
+
+/// Length of the node marker's arms relative to its distance from the primary
+const MARKER: f64 = 0.03;
+
+/// Color of the node and the resulting orbit
+const COLOR: [f32; 3] = [1.0, 0.4, 0.8];
+
+/// Height of the node's label in pixels
+const SIZE: f32 = 14.0;
+
+/// System drawing the [`Selection`]'s [`ManeuverNode`] and the orbit the burn puts it on
+///
+/// The node sits where the body's current [`OrbitalElements`] put it at the node's time.
+/// Like the orbit lines, the resulting orbit is drawn around the primary's current position.
+///
+/// Updates [`Lines`] and [`TextQueue`] resources
+#[derive(Copy, Clone, Debug, Default)]
+pub struct ManeuverPreview;
+
+impl<'a> System<'a> for ManeuverPreview {
+    type SystemData = (
+        Write<'a, Lines>,
+        Write<'a, TextQueue>,
+        Read<'a, Selection>,
+        Read<'a, SimTime>,
+        Read<'a, Camera>,
+        ReadExpect<'a, Projection>,
+        ReadStorage<'a, OrbitalElements>,
+        ReadStorage<'a, ManeuverNode>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut lines,
+            mut texts,
+            selection,
+            time,
+            camera,
+            projection,
+            elements,
+            nodes,
+            pos,
+        ): Self::SystemData,
+    ) {
+        let Some((orbit, node, primary)) = selection.0.and_then(|entity| {
+            let orbit = elements.get(entity)?;
+            Some((orbit, nodes.get(entity)?, pos.get(orbit.primary)?.0))
+        }) else {
+            lines.clear(LAYER);
+            return;
+        };
+
+        let (r, v) = orbit.advance(node.time - time.0).state();
+        let vertex = |position: Point3<f64>| LineVertex {
+            position,
+            color: COLOR,
+        };
+        let mut vertices = Vec::new();
+        let arm = r.magnitude() * MARKER;
+        for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+            vertices.push(vertex(primary + r - axis * arm));
+            vertices.push(vertex(primary + r + axis * arm));
+        }
+        let burned = OrbitalElements::from_state(orbit.primary, r, v + node.burn(r, v), orbit.mu);
+        if let Some(burned) = burned {
+            let path: Vec<_> = burned.path(SEGMENTS).collect();
+            for pair in path.windows(2) {
+                for r in pair {
+                    vertices.push(vertex(primary + r));
+                }
+            }
+        }
+        lines.set(LAYER, vertices);
+
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let height = projection.height as f32;
+        let screen = [projection.aspect * height, height];
+        let center: Point3<f32> = (primary + r).map(|c| c as f32);
+        if let Some(position) = project(view_proj, screen, center / SCALE) {
+            let [r, g, b] = COLOR;
+            texts.push(Text {
+                content: format!(
+                    "burn {:.0} m/s in {:.1} h",
+                    node.delta_v.magnitude(),
+                    (node.time - time.0) / (60.0 * 60.0)
+                ),
+                position,
+                size: SIZE,
+                color: [r, g, b, 1.0],
+                align: Align::Left,
+            });
+        }
+    }
+}
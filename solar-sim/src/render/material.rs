@@ -0,0 +1,437 @@
+//! Textures shared between bodies
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use image::{DynamicImage, Rgba, RgbaImage};
+use specs::{Component, VecStorage};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindingResource, Device,
+    Queue, TextureFormat,
+};
+
+use crate::assets::Handle;
+use crate::error::DynError;
+use crate::render::cache::BindGroupCache;
+use crate::render::procedural::{Pattern, Surface};
+use crate::render::texture::Texture;
+
+/// Material component naming a body's entry in the [`Materials`] registry
+///
+/// Takes precedence over a [`Surface`] component
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Component)]
+#[storage(VecStorage)]
+pub struct Material(pub &'static str);
+
+/// Where a material's texture comes from
+#[derive(Clone, Debug)]
+pub enum MaterialSource {
+    /// Encoded image, e.g. `include_bytes!` of a png
+    Image(&'static [u8]),
+
+    /// Image in the assets directory
+    ///
+    /// It is fetched in the background and reloaded whenever the file changes.
+    Asset(Handle<DynamicImage>),
+
+    /// Texture generated from noise
+    Procedural(Surface),
+
+    /// KTX2 container, see [`Texture::from_ktx2`]
+    ///
+    /// The encoded image is used instead when the adapter can't sample the container's format,
+    /// e.g. BCn on most mobile GPUs.
+    Compressed {
+        ktx2: &'static [u8],
+        fallback: &'static [u8],
+    },
+}
+
+/// How a material's surface reflects light
+///
+/// Defaults to a completely matte, non metallic surface
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Reflectance {
+    /// Spread of the specular highlight, from a mirror at 0 to no highlight at all at 1
+    pub roughness: f32,
+
+    /// Whether the surface reflects like a metal at 1 or like rock, ice and gas at 0
+    pub metalness: f32,
+}
+
+impl Default for Reflectance {
+    fn default() -> Self {
+        Self {
+            roughness: 1.0,
+            metalness: 0.0,
+        }
+    }
+}
+
+/// Reflectance as seen by the shaders
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ReflectanceUniform {
+    roughness: f32,
+    metalness: f32,
+    _padding: [f32; 2],
+}
+
+/// Textures making up a material
+#[derive(Clone, Debug)]
+pub struct MaterialTextures {
+    /// Color of the lit surface
+    pub albedo: MaterialSource,
+
+    /// Light the surface emits where it isn't lit, e.g. city lights on the night side
+    pub emissive: Option<MaterialSource>,
+
+    /// Tangent space normals, e.g. of craters and mountains
+    ///
+    /// Images are read as linear, procedural sources produce the bumps of their noise.
+    pub normal: Option<MaterialSource>,
+
+    pub reflectance: Reflectance,
+}
+
+impl MaterialTextures {
+    /// Every source the material's textures come from
+    pub fn sources(&self) -> impl Iterator<Item = &MaterialSource> {
+        [
+            Some(&self.albedo),
+            self.emissive.as_ref(),
+            self.normal.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// The images in the assets directory the material's textures come from
+    pub fn assets(&self) -> impl Iterator<Item = Handle<DynamicImage>> + '_ {
+        self.sources().filter_map(|source| match source {
+            MaterialSource::Asset(handle) => Some(*handle),
+            _ => None,
+        })
+    }
+}
+
+impl From<MaterialSource> for MaterialTextures {
+    fn from(albedo: MaterialSource) -> Self {
+        Self {
+            albedo,
+            emissive: None,
+            normal: None,
+            reflectance: Reflectance::default(),
+        }
+    }
+}
+
+/// Registry of textures available to [`Material`] components
+///
+/// Each texture is loaded once when it is first used.
+///
+/// Defaults to [`Materials::solar_system`]
+#[derive(Clone, Debug)]
+pub struct Materials(pub HashMap<&'static str, MaterialTextures>);
+
+impl Default for Materials {
+    fn default() -> Self {
+        Self::solar_system()
+    }
+}
+
+impl Materials {
+    /// A material for each of our planets
+    pub fn solar_system() -> Self {
+        let procedural = |seed, palette| {
+            MaterialSource::Procedural(Surface {
+                seed,
+                palette,
+                pattern: Pattern::Terrain,
+            })
+        };
+        let material = |seed, palette, roughness| MaterialTextures {
+            reflectance: Reflectance {
+                roughness,
+                metalness: 0.0,
+            },
+            ..MaterialTextures::from(procedural(seed, palette))
+        };
+        let bumpy = |seed, palette, roughness| MaterialTextures {
+            normal: Some(procedural(seed, palette)),
+            ..material(seed, palette, roughness)
+        };
+        let banded = |seed, palette, roughness| MaterialTextures {
+            albedo: MaterialSource::Procedural(Surface {
+                seed,
+                palette,
+                pattern: Pattern::Bands,
+            }),
+            ..material(seed, palette, roughness)
+        };
+        Self(HashMap::from([
+            (
+                "sun",
+                material(
+                    1,
+                    [
+                        [0.9, 0.35, 0.02],
+                        [1.0, 0.55, 0.05],
+                        [1.0, 0.75, 0.2],
+                        [1.0, 0.95, 0.6],
+                    ],
+                    1.0,
+                ),
+            ),
+            (
+                "mercury",
+                bumpy(
+                    2,
+                    [
+                        [0.15, 0.14, 0.13],
+                        [0.3, 0.28, 0.26],
+                        [0.45, 0.43, 0.4],
+                        [0.6, 0.58, 0.55],
+                    ],
+                    0.9,
+                ),
+            ),
+            (
+                "venus",
+                material(
+                    3,
+                    [
+                        [0.6, 0.45, 0.2],
+                        [0.8, 0.65, 0.35],
+                        [0.9, 0.8, 0.55],
+                        [0.95, 0.9, 0.75],
+                    ],
+                    0.7,
+                ),
+            ),
+            (
+                "earth",
+                MaterialTextures {
+                    albedo: procedural(
+                        4,
+                        [
+                            [0.02, 0.08, 0.3],
+                            [0.05, 0.25, 0.55],
+                            [0.15, 0.4, 0.1],
+                            [0.9, 0.9, 0.9],
+                        ],
+                    ),
+                    // Same noise as the albedo, so the lights are on land but not on ice
+                    emissive: Some(procedural(
+                        4,
+                        [[0.0; 3], [0.0; 3], [0.6, 0.45, 0.2], [0.0; 3]],
+                    )),
+                    normal: None,
+                    // Somewhere between the shiny oceans and the rough continents
+                    reflectance: Reflectance {
+                        roughness: 0.6,
+                        metalness: 0.0,
+                    },
+                },
+            ),
+            (
+                "moon",
+                bumpy(
+                    15,
+                    [
+                        [0.2, 0.2, 0.2],
+                        [0.35, 0.35, 0.34],
+                        [0.5, 0.5, 0.48],
+                        [0.65, 0.64, 0.62],
+                    ],
+                    0.9,
+                ),
+            ),
+            (
+                "iss",
+                material(
+                    16,
+                    [
+                        [0.55, 0.55, 0.55],
+                        [0.7, 0.7, 0.68],
+                        [0.8, 0.78, 0.7],
+                        [0.9, 0.9, 0.9],
+                    ],
+                    0.4,
+                ),
+            ),
+            (
+                "mars",
+                bumpy(
+                    5,
+                    [
+                        [0.3, 0.1, 0.04],
+                        [0.55, 0.2, 0.07],
+                        [0.75, 0.35, 0.15],
+                        [0.85, 0.6, 0.4],
+                    ],
+                    0.95,
+                ),
+            ),
+            (
+                "jupiter",
+                banded(
+                    6,
+                    [
+                        [0.45, 0.3, 0.2],
+                        [0.7, 0.55, 0.4],
+                        [0.85, 0.75, 0.6],
+                        [0.95, 0.9, 0.85],
+                    ],
+                    0.5,
+                ),
+            ),
+            (
+                "saturn",
+                banded(
+                    7,
+                    [
+                        [0.6, 0.5, 0.3],
+                        [0.75, 0.65, 0.45],
+                        [0.85, 0.78, 0.6],
+                        [0.95, 0.9, 0.75],
+                    ],
+                    0.5,
+                ),
+            ),
+            (
+                "uranus",
+                banded(
+                    8,
+                    [
+                        [0.4, 0.7, 0.75],
+                        [0.5, 0.8, 0.85],
+                        [0.6, 0.87, 0.9],
+                        [0.75, 0.93, 0.95],
+                    ],
+                    0.35,
+                ),
+            ),
+            (
+                "neptune",
+                banded(
+                    9,
+                    [
+                        [0.05, 0.1, 0.4],
+                        [0.1, 0.2, 0.65],
+                        [0.2, 0.35, 0.8],
+                        [0.5, 0.6, 0.9],
+                    ],
+                    0.35,
+                ),
+            ),
+            (
+                "halley",
+                bumpy(
+                    14,
+                    [
+                        [0.02, 0.02, 0.02],
+                        [0.06, 0.05, 0.05],
+                        [0.12, 0.11, 0.1],
+                        [0.3, 0.28, 0.26],
+                    ],
+                    0.95,
+                ),
+            ),
+        ]))
+    }
+}
+
+/// Textures standing in for a material's missing optional ones
+pub struct FallbackTextures {
+    /// Gray albedo of bodies whose texture isn't loaded yet
+    pub placeholder: Texture,
+
+    /// Black, so nothing is emitted
+    pub emissive: Texture,
+
+    /// Pointing straight out of the surface
+    pub normal: Texture,
+}
+
+impl FallbackTextures {
+    pub fn new(device: &Device, queue: &Queue) -> Result<Self, DynError> {
+        let pixel = |color| DynamicImage::ImageRgba8(RgbaImage::from_pixel(1, 1, Rgba(color)));
+        Ok(Self {
+            placeholder: Texture::from_image(
+                device,
+                queue,
+                &pixel([128, 128, 128, 255]),
+                Some("placeholder"),
+            )?,
+            emissive: Texture::from_image(
+                device,
+                queue,
+                &pixel([0, 0, 0, 255]),
+                Some("no_emission"),
+            )?,
+            normal: Texture::from_image_with_format(
+                device,
+                queue,
+                &pixel([128, 128, 255, 255]),
+                Some("flat_normals"),
+                TextureFormat::Rgba8Unorm,
+            )?,
+        })
+    }
+}
+
+/// Create a bind group for sampling a material's textures in the main pipeline
+#[allow(clippy::too_many_arguments)]
+pub fn bind_material(
+    device: &Device,
+    cache: &BindGroupCache,
+    layout: &BindGroupLayout,
+    fallback: &FallbackTextures,
+    albedo: &Texture,
+    emissive: Option<&Texture>,
+    normal: Option<&Texture>,
+    reflectance: Reflectance,
+) -> Arc<BindGroup> {
+    let emissive = emissive.unwrap_or(&fallback.emissive);
+    let normal = normal.unwrap_or(&fallback.normal);
+    // Shared by every material reflecting the same way
+    let reflectance = cache.uniform(
+        device,
+        "Reflectance Buffer",
+        bytemuck::cast_slice(&[ReflectanceUniform {
+            roughness: reflectance.roughness,
+            metalness: reflectance.metalness,
+            _padding: [0.0; 2],
+        }]),
+    );
+    cache.get_or_create(
+        device,
+        &BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&albedo.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&albedo.sampler),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&emissive.view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::TextureView(&normal.view),
+                },
+                BindGroupEntry {
+                    binding: 4,
+                    resource: reflectance.as_entire_binding(),
+                },
+            ],
+            label: Some("material_bind_group"),
+        },
+    )
+}
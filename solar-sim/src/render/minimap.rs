@@ -0,0 +1,239 @@
+//! Map of the whole system seen from above in a corner of the screen
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages, Color,
+    CommandEncoder, Device, Extent3d, Queue, SurfaceConfiguration, TextureDescriptor,
+    TextureDimension, TextureUsages, TextureView, TextureViewDescriptor,
+};
+
+use crate::render::camera::{orthographic, Camera, CameraUniform, Projection};
+use crate::render::impostor::{ImpostorPipeline, Sprite};
+use crate::render::light::EMISSION;
+use crate::render::lines::{LinePipeline, LineVertex, Lines};
+use crate::render::texture::Texture;
+use crate::render::tonemap::Tonemap;
+use crate::render::{Origin, SCALE};
+
+/// Width and height of the map in pixels
+const SIZE: u32 = 192;
+
+/// Pixels between the map and the screen's edges
+const MARGIN: u32 = 8;
+
+/// Half the map's width in units of the outermost body's distance to the origin
+const PADDING: f32 = 1.1;
+
+/// Color of the lines outlining what the main camera sees
+const FRUSTUM_COLOR: [f32; 3] = [1.0, 0.8, 0.2];
+
+/// Background of the map, barely lighter than space so its border shows
+const BACKGROUND: Color = Color {
+    r: 0.01,
+    g: 0.01,
+    b: 0.02,
+    a: 1.0,
+};
+
+/// Minimap resource, whether the [`MinimapPipeline`] shows a map in the bottom left corner
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Minimap(pub bool);
+
+/// Draws every body as a dot on an orthographic view down onto the ecliptic plane,
+/// which is copied into the bottom left corner of the scene
+///
+/// The edges of the main camera's view are drawn from its position,
+/// so it's clear which part of the system is on screen.
+pub struct MinimapPipeline {
+    color: wgpu::Texture,
+    color_view: TextureView,
+    depth: Texture,
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    sprites: ImpostorPipeline,
+    lines: LinePipeline,
+    /// Whether the map is shown at all
+    active: bool,
+}
+
+impl MinimapPipeline {
+    pub fn new(
+        device: &Device,
+        config: &SurfaceConfiguration,
+        camera_layout: &BindGroupLayout,
+    ) -> Self {
+        let color = device.create_texture(&TextureDescriptor {
+            label: Some("Minimap Target"),
+            size: Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Tonemap::FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let color_view = color.create_view(&TextureViewDescriptor::default());
+        let depth = Texture::create_depth_texture(
+            device,
+            &SurfaceConfiguration {
+                width: SIZE,
+                height: SIZE,
+                ..config.clone()
+            },
+            "minimap_depth_texture",
+        );
+
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Minimap Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
+                Matrix4::identity(),
+                Point3::origin(),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("minimap_camera_bind_group"),
+        });
+
+        Self {
+            color,
+            color_view,
+            depth,
+            camera_buffer,
+            camera_bind_group,
+            sprites: ImpostorPipeline::new(device, Tonemap::FORMAT, camera_layout),
+            lines: LinePipeline::new(device, Tonemap::FORMAT, camera_layout),
+            active: false,
+        }
+    }
+
+    /// Fit the map around the bodies and outline what the main camera sees, or hide the map
+    ///
+    /// Bodies are given by their position in render space and whether they are a light source.
+    pub fn update(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        camera: Option<(&Camera, &Projection)>,
+        bodies: impl IntoIterator<Item = (Point3<f32>, bool)>,
+    ) {
+        self.active = camera.is_some();
+        let Some((camera, projection)) = camera else {
+            return;
+        };
+
+        let sprites: Vec<_> = bodies
+            .into_iter()
+            .map(|(position, is_light)| Sprite {
+                position,
+                color: if is_light { [EMISSION; 3] } else { [1.0; 3] },
+            })
+            .collect();
+        let extent = sprites
+            .iter()
+            .map(|sprite| sprite.position)
+            .chain([camera.position])
+            .map(|position| position.to_vec().magnitude())
+            .fold(0.0, f32::max)
+            * PADDING;
+        let extent = if extent > 0.0 { extent } else { 1.0 };
+
+        // Looking down with -z being up on the map
+        let position = Point3::new(0.0, extent, 0.0);
+        let view = Matrix4::look_to_rh(position, -Vector3::unit_y(), -Vector3::unit_z());
+        let view_proj = orthographic(2.0 * extent, 2.0 * extent, 2.0 * extent) * view;
+        queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[CameraUniform::new(view_proj, position)]),
+        );
+        self.sprites
+            .update(device, queue, [SIZE as f32; 2], &sprites);
+
+        // Long enough to leave the map from anywhere on it
+        let length = 4.0 * extent;
+        let forward = camera.direction();
+        let right = forward.cross(Vector3::unit_y()).normalize();
+        let up = right.cross(forward);
+        let tan = (projection.fovy.0 / 2.0).tan();
+        let mut frustum = Vec::new();
+        for (x, y) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let edge = forward + right * (x * tan * projection.aspect) + up * (y * tan);
+            for point in [camera.position, camera.position + edge.normalize() * length] {
+                frustum.push(LineVertex {
+                    position: (point * SCALE).cast().unwrap(),
+                    color: FRUSTUM_COLOR,
+                });
+            }
+        }
+        let mut lines = Lines::default();
+        lines.set("frustum", frustum);
+        self.lines
+            .update(device, queue, &Origin(Point3::origin()), &lines);
+    }
+
+    /// Draw the map, unless it's hidden
+    pub fn draw(&self, encoder: &mut CommandEncoder) {
+        if !self.active {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Minimap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.color_view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(BACKGROUND),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: &self.depth.view,
+                depth_ops: Some(wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
+                    store: false,
+                }),
+                stencil_ops: None,
+            }),
+        });
+        self.lines.draw(&mut render_pass, &self.camera_bind_group);
+        self.sprites.draw(&mut render_pass, &self.camera_bind_group);
+    }
+
+    /// Copy the map into the bottom left corner of the scene, unless it's hidden or doesn't fit
+    pub fn composite(&self, encoder: &mut CommandEncoder, tonemap: &Tonemap) {
+        let target = tonemap.texture();
+        if !self.active || target.width() < SIZE + 2 * MARGIN || target.height() < SIZE + 2 * MARGIN
+        {
+            return;
+        }
+        encoder.copy_texture_to_texture(
+            self.color.as_image_copy(),
+            wgpu::ImageCopyTexture {
+                texture: target,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: MARGIN,
+                    y: target.height() - SIZE - MARGIN,
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            Extent3d {
+                width: SIZE,
+                height: SIZE,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+}
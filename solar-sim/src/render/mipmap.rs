@@ -0,0 +1,207 @@
+//! Mip chains and anisotropic filtering for textures wrapped around bodies
+
+use std::sync::Arc;
+
+use wgpu::{
+    AddressMode, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, ColorTargetState, CommandEncoderDescriptor,
+    Device, FilterMode, FragmentState, PipelineLayout, PipelineLayoutDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, Sampler, SamplerBindingType, SamplerDescriptor,
+    ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat,
+    TextureSampleType, TextureViewDescriptor, TextureViewDimension, VertexState,
+};
+
+use crate::render::cache::PipelineCache;
+
+/// Anisotropic filtering resource
+///
+/// Maximum number of samples taken along a texture seen at a grazing angle,
+/// between 1 which turns it off and 16.
+/// Changing it regenerates every body's textures.
+///
+/// Defaults to [`16`](Anisotropy::default)
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Anisotropy(pub u16);
+
+impl Default for Anisotropy {
+    fn default() -> Self {
+        Self(16)
+    }
+}
+
+/// Fills the mip levels below the first one by repeatedly halving the previous level
+///
+/// Also creates the samplers for mipmapped textures.
+pub struct MipmapGenerator {
+    shader: ShaderModule,
+    layout: BindGroupLayout,
+    pipeline_layout: PipelineLayout,
+    /// Downsamples linearly, the anisotropy only applies to the samplers handed out
+    sampler: Sampler,
+    /// A pipeline for each format of the textures mipmaps were generated for
+    pipelines: PipelineCache,
+
+    /// Anisotropy of the samplers created by [`sampler`](Self::sampler)
+    pub anisotropy: Anisotropy,
+}
+
+impl MipmapGenerator {
+    /// Formats of the textures wrapped around bodies, others get their pipeline created when first used
+    const FORMATS: [TextureFormat; 2] = [TextureFormat::Rgba8UnormSrgb, TextureFormat::Rgba8Unorm];
+
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Mipmap Shader"),
+            source: ShaderSource::Wgsl(include_str!("../mipmap.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("mipmap_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Mipmap Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Mipmap Sampler"),
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let generator = Self {
+            shader,
+            layout,
+            pipeline_layout,
+            sampler,
+            pipelines: PipelineCache::default(),
+            anisotropy: Anisotropy::default(),
+        };
+        for format in Self::FORMATS {
+            generator.pipeline(device, format);
+        }
+        generator
+    }
+
+    fn pipeline(&self, device: &Device, format: TextureFormat) -> Arc<RenderPipeline> {
+        self.pipelines.get_or_create(
+            device,
+            &RenderPipelineDescriptor {
+                label: Some("Mipmap Pipeline"),
+                layout: Some(&self.pipeline_layout),
+                vertex: VertexState {
+                    module: &self.shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &self.shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: Default::default(),
+                    })],
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: Default::default(),
+            },
+        )
+    }
+
+    /// Number of mip levels down to a single texel
+    pub fn level_count(width: u32, height: u32) -> u32 {
+        u32::BITS - width.max(height).max(1).leading_zeros()
+    }
+
+    /// Fill every mip level of a texture from its first one
+    ///
+    /// The texture needs the `RENDER_ATTACHMENT` and `TEXTURE_BINDING` usages.
+    pub fn generate(&self, device: &Device, queue: &Queue, texture: &wgpu::Texture) {
+        let pipeline = self.pipeline(device, texture.format());
+
+        let level = |mip| {
+            texture.create_view(&TextureViewDescriptor {
+                label: Some("Mip Level"),
+                base_mip_level: mip,
+                mip_level_count: Some(1),
+                ..Default::default()
+            })
+        };
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Mipmap Encoder"),
+        });
+        for mip in 1..texture.mip_level_count() {
+            let source = level(mip - 1);
+            let target = level(mip);
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                layout: &self.layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&source),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::Sampler(&self.sampler),
+                    },
+                ],
+                label: Some("mipmap_bind_group"),
+            });
+
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Mipmap Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(&pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+    }
+
+    /// Trilinear sampler with the current [`Anisotropy`], wrapping around the longitude
+    pub fn sampler(&self, device: &Device) -> Sampler {
+        device.create_sampler(&SamplerDescriptor {
+            address_mode_u: AddressMode::Repeat,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Linear,
+            anisotropy_clamp: self.anisotropy.0.clamp(1, 16),
+            ..Default::default()
+        })
+    }
+}
@@ -1,74 +1,732 @@
+pub mod atmosphere;
+pub mod axes;
+pub mod bloom;
+pub mod cache;
 pub mod camera;
+pub mod clouds;
+pub mod compare;
+pub mod conics;
+pub mod debug;
+pub mod detached;
+pub mod field;
+pub mod flare;
+pub mod gizmos;
+pub mod grid;
+pub mod highlight;
+pub mod id_buffer;
+pub mod impostor;
+pub mod influence;
+pub mod inset;
 pub mod instance;
+pub mod label;
+pub mod lagrange;
+pub mod light;
+pub mod lines;
+pub mod loading;
+pub mod lod;
+pub mod maneuver;
+pub mod material;
+pub mod minimap;
+pub mod mipmap;
+pub mod nbody;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod observers;
+pub mod orbits;
+pub mod particles;
+pub mod picking;
+pub mod potential;
+pub mod prediction;
+pub mod present;
+pub mod procedural;
+pub mod recording;
+pub mod registry;
+pub mod rings;
+pub mod shadow;
 pub mod shapes;
+pub mod skybox;
+pub mod split;
+pub mod stats;
+pub mod text;
 pub mod texture;
+pub mod timestamps;
+pub mod tonemap;
+pub mod trail;
+pub mod transparent;
+pub mod uncertainty;
 
+use std::collections::{HashMap, HashSet};
 use std::mem::size_of;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use cgmath::{Matrix4, SquareMatrix};
-use specs::{Join, Read, ReadStorage, RunNow, SystemData, World};
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, One, Point3, Quaternion, Rad, SquareMatrix};
+use image::DynamicImage;
+use log::{info, warn};
+use specs::join::MaybeJoin;
+use specs::{Entities, Entity, Join, Read, ReadStorage, RunNow, SystemData, World, Write};
 use wgpu::util::{BufferInitDescriptor, DeviceExt};
 use wgpu::{
-    vertex_attr_array, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
-    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, BufferAddress,
-    BufferBindingType, BufferUsages, Color, ColorTargetState, CompareFunction, DepthStencilState,
-    DeviceDescriptor, Features, FragmentState, Limits, PipelineLayoutDescriptor, PrimitiveState,
-    PrimitiveTopology, RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor,
-    ShaderSource, ShaderStages, SurfaceConfiguration, TextureUsages, VertexState, VertexStepMode,
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
+    BufferAddress, BufferBindingType, BufferUsages, Color, ColorTargetState, DepthStencilState,
+    DeviceDescriptor, DownlevelFlags, Features, FragmentState, Limits, PipelineLayout,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, RenderPass,
+    RenderPipelineDescriptor, RequestAdapterOptions, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, SurfaceConfiguration, TextureUsages, VertexState, VertexStepMode,
 };
 use winit::window::Window;
 
+use crate::assets::{AssetError, Assets, Handle, Pending, Watcher, FALLBACK_TEXTURE, MAIN_SHADER};
+use crate::control::{Controls, Selection};
 use crate::error::{CustomError, DynError};
-use crate::physics::{Planet, Position};
-use crate::render::camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX};
+use crate::physics::integrator::{GpuSteps, SelectedIntegrator};
+use crate::physics::{
+    Determinism, Mass, Name, Planet, Position, Radius, Rotation, SimSpeed, Velocity, G,
+};
+use crate::render::atmosphere::{Atmosphere, AtmospherePipeline};
+use crate::render::axes::AxesGizmo;
+use crate::render::bloom::{Bloom, BloomPipeline};
+use crate::render::cache::BindGroupCache;
+use crate::render::camera::{Camera, CameraUniform, Frustum, Projection, OPENGL_TO_WGPU_MATRIX};
+use crate::render::clouds::{CloudPipeline, Clouds};
+use crate::render::compare::{Appearance, ComparedView, Comparison};
+use crate::render::debug::{DebugPipelines, DebugView};
+use crate::render::detached::{DetachedView, DetachedWindows};
+use crate::render::flare::{LensFlare, LensFlarePipeline};
+use crate::render::id_buffer::IdBuffer;
+use crate::render::impostor::{Impostor, ImpostorPipeline, Sprite, MAX_SIZE};
+use crate::render::inset::Inset;
 use crate::render::instance::{Instance, InstanceRaw};
-use crate::render::shapes::octahedron;
+use crate::render::label::labels;
+use crate::render::light::{Emissive, LightSource, LightUniform, AMBIENT, EMISSION};
+use crate::render::lines::{LinePipeline, Lines};
+use crate::render::loading::{progress_bar, Loading};
+use crate::render::lod::SphereLods;
+use crate::render::material::{
+    bind_material, FallbackTextures, Material, MaterialSource, MaterialTextures, Materials,
+    Reflectance,
+};
+use crate::render::minimap::{Minimap, MinimapPipeline};
+use crate::render::mipmap::Anisotropy;
+use crate::render::nbody::NBodyPipeline;
+use crate::render::particles::{Belt, Comet, ParticlePipeline};
+use crate::render::picking::PickingMode;
+use crate::render::present::PresentMode;
+use crate::render::procedural::{Surface, TextureGenerator};
+use crate::render::recording::{Recorder, Recording};
+use crate::render::registry::{MaterialHandle, MaterialRegistry, MeshHandle, MeshRegistry};
+use crate::render::rings::{RingPipeline, Rings};
+use crate::render::shadow::ShadowMap;
+use crate::render::shapes::icosphere;
+use crate::render::skybox::{starfield, Skybox};
+use crate::render::split::{SplitScreen, SplitView, VIEWS};
+use crate::render::text::{TextQueue, TextRenderer};
 use crate::render::texture::Texture;
+use crate::render::timestamps::{GpuStats, GpuTimer};
+use crate::render::tonemap::{PostProcess, Tonemap};
+use crate::render::trail::{Trail, TrailPipeline};
+use crate::render::transparent::{Shared, Transparent, TransparentDraw};
+use crate::timer::{Delta, FrameStats};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
     pub position: [f32; 3],
     pub tex_coords: [f32; 2],
+    pub normal: [f32; 3],
+    /// Direction of increasing u, `w` is the handedness i.e. the bitangent is `cross(normal, tangent) * w`
+    pub tangent: [f32; 4],
+}
+
+impl Vertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = vertex_attr_array![
+        0 => Float32x3,
+        1 => Float32x2,
+        2 => Float32x3,
+        3 => Float32x4,
+    ];
+
+    pub fn desc() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: size_of::<Vertex>() as BufferAddress,
+            step_mode: VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// What [`Render`] draws its frames to
+enum Target {
+    /// A window's surface, presented after every frame
+    Window {
+        surface: wgpu::Surface,
+        window: Arc<Window>,
+    },
+    /// Texture without any window, e.g. on servers without a display
+    Offscreen(wgpu::Texture),
 }
 
 pub struct Render {
-    surface: wgpu::Surface,
+    target: Target,
+    /// Kept to create the surfaces of [`DetachedView`]s
+    instance: wgpu::Instance,
+    adapter: wgpu::Adapter,
     device: wgpu::Device,
     queue: wgpu::Queue,
     config: wgpu::SurfaceConfiguration,
     pub size: winit::dpi::PhysicalSize<u32>,
     render_pipeline: wgpu::RenderPipeline,
+    /// Draws [`Emissive`] bodies unlit
+    emissive_pipeline: wgpu::RenderPipeline,
+    /// Replace the two above while [`debug_view`](Self::debug_view) is on
+    debug_pipelines: DebugPipelines,
+    debug_view: DebugView,
+    render_pipeline_layout: PipelineLayout,
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
+    /// Drawn on bodies whose material is broken, missing until it's loaded
     #[allow(dead_code)]
-    diffuse_texture: Texture,
-    diffuse_bind_group: wgpu::BindGroup,
+    diffuse_texture: Option<Texture>,
+    diffuse_bind_group: Arc<wgpu::BindGroup>,
+    pending_diffuse: Option<Pending<DynamicImage>>,
+    /// Drawn on bodies whose texture is still waiting to be created
+    placeholder_bind_group: Arc<wgpu::BindGroup>,
+    loading: Loading,
+    fallback_textures: FallbackTextures,
+    texture_bind_group_layout: BindGroupLayout,
+    bind_groups: BindGroupCache,
+    texture_generator: TextureGenerator,
+    /// Loaded materials and generated surfaces
+    textures: HashMap<TextureKey, (Vec<Texture>, Arc<wgpu::BindGroup>)>,
+    /// Texture used by each visible instance, the diffuse texture if `None`, the placeholder until it's loaded
+    instance_textures: Vec<Option<TextureKey>>,
+    /// Level of detail of each visible instance's sphere
+    instance_lods: Vec<usize>,
+    /// Mesh drawn instead of the sphere for each visible instance
+    instance_meshes: Vec<Option<MeshHandle>>,
+    /// Whether each visible instance is drawn by the emissive pipeline
+    instance_emissive: Vec<bool>,
+    sphere_lods: SphereLods,
+    /// Materials which failed to load and shouldn't be retried
+    broken_materials: HashSet<&'static str>,
+    broken_handles: HashSet<MaterialHandle>,
+    /// Images of [`MaterialSource::Asset`]s, fetched before their material is loaded
+    images: HashMap<&'static str, Result<DynamicImage, AssetError>>,
+    pending_images: HashMap<&'static str, Pending<DynamicImage>>,
+    assets: Assets,
+    /// Reloads textures whose files changed
+    watcher: Watcher,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: wgpu::BindGroup,
-    camera_config: Projection,
+    camera_bind_group_layout: BindGroupLayout,
+    light_buffer: wgpu::Buffer,
+    light_bind_group: wgpu::BindGroup,
+    shadow_map: ShadowMap,
+    skybox: Skybox,
+    tonemap: Tonemap,
+    bloom: BloomPipeline,
+    /// Visible instances followed by the ones outside the camera's frustum
     instances: Vec<Instance>,
-    #[allow(dead_code)]
     instance_buffer: wgpu::Buffer,
+    /// Id of each visible instance's entity for the [`IdBuffer`]
+    instance_ids: Vec<u32>,
+    /// Created once [`PickingMode::Gpu`] is used
+    id_buffer: Option<IdBuffer>,
+    /// Missing without timestamp queries
+    gpu_timer: Option<GpuTimer>,
+    /// Created once a [`Recording`] directory is set
+    recorder: Option<Recorder>,
+    /// Set by the device's error handler once the device is lost, see [`Render::recover`]
+    lost: Arc<AtomicBool>,
+    /// Supported by the surface, only [`Fifo`](wgpu::PresentMode::Fifo) when rendering offscreen
+    present_modes: Vec<wgpu::PresentMode>,
+    /// Pixel to read from the [`IdBuffer`] in the next frame
+    pending_pick: Option<(u32, u32)>,
+    line_pipeline: LinePipeline,
+    trail_pipeline: TrailPipeline,
+    ring_pipeline: RingPipeline,
+    atmosphere_pipeline: AtmospherePipeline,
+    cloud_pipeline: CloudPipeline,
+    /// Items of the transparent pipelines sorted back to front for each view's camera,
+    /// see [`transparent_layers`](Self::transparent_layers)
+    transparent_draws: [Vec<TransparentDraw>; VIEWS],
+    split: SplitView,
+    /// Drawn in the second view instead while there's a [`Comparison`]
+    compared: ComparedView,
+    impostor_pipeline: ImpostorPipeline,
+    /// Missing without compute shaders, e.g. on WebGL
+    particle_pipeline: Option<ParticlePipeline>,
+    /// Missing without compute shaders, see [`Gpu`](crate::physics::integrator::Gpu)
+    nbody: Option<NBodyPipeline>,
+    lens_flare: LensFlarePipeline,
+    axes: AxesGizmo,
+    /// Close-up of the [`Selection`]
+    inset: Inset,
+    minimap: MinimapPipeline,
+    /// Windows opened through [`DetachedWindows`]
+    detached: Vec<DetachedView>,
+    text_renderer: TextRenderer,
     // NEW!
     depth_texture: Texture,
-    window: Arc<Window>,
+}
+
+/// Key of a texture cached by [`Render`]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum TextureKey {
+    Material(&'static str),
+    Surface(Entity),
+    /// Stored in the [`MaterialRegistry`] instead
+    Handle(MaterialHandle),
+}
+
+/// Color of a body drawn as an [`Impostor`]
+///
+/// The light source glows, the others are as bright as the part of them lit from the camera's view.
+fn sprite_color(
+    is_light: bool,
+    position: Point3<f32>,
+    camera: Point3<f32>,
+    light: Option<(Point3<f32>, f32)>,
+) -> [f32; 3] {
+    if is_light {
+        return [EMISSION; 3];
+    }
+    let Some((light, _)) = light else {
+        return [1.0; 3];
+    };
+    let to_light = (light - position).normalize();
+    let to_camera = (camera - position).normalize();
+    // Fraction of the disc seen lit, from full at opposition to new at conjunction
+    let phase = (1.0 + to_light.dot(to_camera)) / 2.0;
+    [AMBIENT + (1.0 - AMBIENT) * phase; 3]
+}
+
+/// Whether an error was caused by losing the device
+///
+/// wgpu doesn't expose the cause's type, so this goes by its message.
+fn is_device_lost(error: &wgpu::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(error) = source {
+        if error.to_string().contains("device is lost") {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// Debug group of each view of a [`SplitScreen`] in gpu captures
+const VIEW_NAMES: [&str; VIEWS] = ["Main View", "Split View"];
+
+/// Textures created per frame at most, so streaming them in doesn't stall the window
+const TEXTURES_PER_FRAME: usize = 1;
+
+/// Seed of the stars in the background
+const STARFIELD_SEED: u64 = 0x5eed_57a2;
+
+/// Meters per unit in render space
+pub const SCALE: f32 = 1e10;
+
+/// Floating origin of render space, the main camera's position in meters
+///
+/// Everything uploaded to the gpu is relative to it,
+/// so single precision stays accurate around the camera however far it is from the sun.
+#[derive(Copy, Clone, Debug)]
+pub struct Origin(pub Point3<f64>);
+
+impl Origin {
+    pub fn new(camera: &Camera) -> Self {
+        Self(camera.position.cast::<f64>().unwrap() * f64::from(SCALE))
+    }
+
+    /// Position in meters converted to render space
+    pub fn render(&self, meters: Point3<f64>) -> Point3<f32> {
+        Point3::from_vec(((meters - self.0) / f64::from(SCALE)).map(|c| c as f32))
+    }
+
+    /// Position in render space moved into the origin's render space
+    pub fn relative(&self, position: Point3<f32>) -> Point3<f32> {
+        self.render(position.cast::<f64>().unwrap() * f64::from(SCALE))
+    }
+
+    /// Camera moved into the origin's render space
+    pub fn camera(&self, camera: &Camera) -> Camera {
+        Camera {
+            position: self.relative(camera.position),
+            ..*camera
+        }
+    }
+}
+
+/// Resize resource
+///
+/// Set to the window's new size by [`run`](crate::run) and applied by [`Render`] before the next frame,
+/// which also updates the [`Projection`]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Resize(pub Option<winit::dpi::PhysicalSize<u32>>);
+
+/// Resource multiplying the rendered [`Radius`] of every body
+///
+/// At solar system distances real sizes are far below a pixel.
+/// Bodies without a radius are rendered with a radius of one unit regardless.
+///
+/// Defaults to [`50.0`](Exaggeration::default)
+#[derive(Copy, Clone, Debug)]
+pub struct Exaggeration(pub f32);
+
+impl Default for Exaggeration {
+    fn default() -> Self {
+        Self(50.0)
+    }
+}
+
+impl Exaggeration {
+    /// Radius a body is rendered with in render space
+    pub fn render_radius(&self, radius: Option<&Radius>) -> f32 {
+        radius.map_or(1.0, |radius| radius.0 * self.0 / SCALE)
+    }
 }
 
 impl<'a> RunNow<'a> for Render {
     fn run_now(&mut self, world: &'a World) {
+        world.fetch_mut::<FrameStats>().begin_render();
+        if self.lost.load(Ordering::Relaxed) {
+            #[cfg(target_arch = "wasm32")]
+            panic!("The device was lost");
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                match self.recover(world) {
+                    Ok(()) => info!("Recovered from losing the device"),
+                    Err(error) => panic!("Failed to recover from losing the device: {error}"),
+                }
+                world.fetch_mut::<FrameStats>().end_render();
+                return;
+            }
+        }
+
+        if let Some(size) = world.fetch_mut::<Resize>().0.take() {
+            self.resize(size);
+        }
+
+        match &mut self.nbody {
+            Some(nbody) => nbody.run(&self.device, &self.queue, world),
+            None if world.fetch::<GpuSteps>().steps > 0 => {
+                warn!("Compute shaders aren't supported, integrating on the cpu instead");
+                *world.fetch_mut::<SelectedIntegrator>() = SelectedIntegrator::default();
+                *world.fetch_mut::<GpuSteps>() = GpuSteps::default();
+            }
+            None => {}
+        }
+
+        let entities = Entities::<'a>::fetch(world);
         let planets = ReadStorage::<'a, Planet>::fetch(world);
         let positions = ReadStorage::<'a, Position>::fetch(world);
-        let instances: Vec<_> = (&planets, &positions)
-            .join()
-            .map(|(_, pos)| Instance::from_position(pos.0 / 1e10))
+        let surfaces = ReadStorage::<'a, Surface>::fetch(world);
+        let materials = ReadStorage::<'a, Material>::fetch(world);
+        let registry = Read::<'a, Materials>::fetch(world);
+        let lights = ReadStorage::<'a, LightSource>::fetch(world);
+        let emissives = ReadStorage::<'a, Emissive>::fetch(world);
+        let rotations = ReadStorage::<'a, Rotation>::fetch(world);
+        let impostors = ReadStorage::<'a, Impostor>::fetch(world);
+        let radii = ReadStorage::<'a, Radius>::fetch(world);
+        let names = ReadStorage::<'a, Name>::fetch(world);
+        let trails = ReadStorage::<'a, Trail>::fetch(world);
+        let rings = ReadStorage::<'a, Rings>::fetch(world);
+        let atmospheres = ReadStorage::<'a, Atmosphere>::fetch(world);
+        let clouds = ReadStorage::<'a, Clouds>::fetch(world);
+        let belts = ReadStorage::<'a, Belt>::fetch(world);
+        let comets = ReadStorage::<'a, Comet>::fetch(world);
+        let velocities = ReadStorage::<'a, Velocity>::fetch(world);
+        let masses = ReadStorage::<'a, Mass>::fetch(world);
+        let exaggeration = Read::<'a, Exaggeration>::fetch(world);
+        let post_process = Read::<'a, PostProcess>::fetch(world);
+        let bloom = Read::<'a, Bloom>::fetch(world);
+        let lens_flare = Read::<'a, LensFlare>::fetch(world);
+        let anisotropy = Read::<'a, Anisotropy>::fetch(world);
+        let material_handles = ReadStorage::<'a, MaterialHandle>::fetch(world);
+        let mesh_handles = ReadStorage::<'a, MeshHandle>::fetch(world);
+        let mut material_registry = Write::<'a, MaterialRegistry>::fetch(world);
+        let mut mesh_registry = Write::<'a, MeshRegistry>::fetch(world);
+
+        mesh_registry.upload(&self.device);
+
+        for path in self.watcher.changed() {
+            info!("Asset {} changed", path.display());
+            let changed = |asset: &str| path == Path::new(asset);
+            if changed(FALLBACK_TEXTURE.path) {
+                self.pending_diffuse = Some(self.assets.spawn(FALLBACK_TEXTURE));
+            }
+            if changed(MAIN_SHADER.path) {
+                self.reload_shader();
+            }
+            self.images.retain(|asset, _| !changed(asset));
+            self.pending_images.retain(|asset, _| !changed(asset));
+            for (name, textures) in &registry.0 {
+                if textures.assets().any(|handle| changed(handle.path)) {
+                    self.textures.remove(&TextureKey::Material(name));
+                    self.broken_materials.remove(name);
+                }
+            }
+            let handles: Vec<_> = material_registry
+                .iter()
+                .filter(|(_, textures)| textures.assets().any(|handle| changed(handle.path)))
+                .map(|(handle, _)| handle)
+                .collect();
+            for handle in handles {
+                material_registry.unbind(handle);
+                self.broken_handles.remove(&handle);
+            }
+        }
+
+        if let Some(result) = self.pending_diffuse.as_ref().and_then(Pending::poll) {
+            self.pending_diffuse = None;
+            self.loading.loaded += 1;
+            let texture = result.map_err(DynError::from).and_then(|image| {
+                Texture::from_image(
+                    &self.device,
+                    &self.queue,
+                    &image,
+                    Some(FALLBACK_TEXTURE.path),
+                )
+            });
+            match texture {
+                Ok(texture) => {
+                    self.diffuse_bind_group =
+                        self.bind_texture(&texture, None, None, Reflectance::default());
+                    self.diffuse_texture = Some(texture);
+                }
+                Err(error) => warn!("Failed to load the fallback texture: {error}"),
+            }
+        }
+        self.loading.pending = usize::from(self.pending_diffuse.is_some());
+
+        if *anisotropy != self.texture_generator.mipmaps.anisotropy {
+            self.texture_generator.mipmaps.anisotropy = *anisotropy;
+            // The samplers are part of the textures
+            self.textures.clear();
+            material_registry.unbind_all();
+            self.cloud_pipeline.clear();
+        }
+
+        self.textures.retain(|key, _| match key {
+            TextureKey::Material(_) | TextureKey::Handle(_) => true,
+            TextureKey::Surface(entity) => entities.is_alive(*entity) && surfaces.contains(*entity),
+        });
+        self.bind_groups.collect();
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.enabled = world.fetch::<Controls>().show_stats;
+            if let Some(stats) = timer.poll(&self.device) {
+                *world.fetch_mut::<GpuStats>() = stats;
+            }
+        }
+        let camera = world.fetch::<Camera>();
+        let origin = Origin::new(&camera);
+        let camera = origin.camera(&camera);
+        let second = world
+            .fetch::<SplitScreen>()
+            .0
+            .map(|second| origin.camera(&second));
+        let second_position = second.map(|second| second.position);
+        let size = [self.config.width as f32, self.config.height as f32];
+        let viewport = if second.is_some() {
+            [size[0] / 2.0, size[1]]
+        } else {
+            size
+        };
+        world
+            .fetch_mut::<Projection>()
+            .resize(viewport[0] as u32, viewport[1] as u32);
+        let projection = world.fetch::<Projection>();
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let second_view_proj = second.map(|second| projection.reversed_z() * second.matrix());
+        self.split
+            .update(&self.queue, second_view_proj.zip(second_position));
+        let frustum = Frustum::new(view_proj);
+        let second_frustum = second_view_proj.map(Frustum::new);
+        {
+            let mut windows = world.fetch_mut::<DetachedWindows>();
+            for (window, camera) in windows.opened.drain(..) {
+                // # Safety
+                //
+                // The view owns the arced window and drops its surface first
+                match unsafe { self.instance.create_surface(window.as_ref()) } {
+                    Ok(surface) => self.detached.push(DetachedView::new(
+                        &self.device,
+                        &self.adapter,
+                        (surface, window),
+                        camera,
+                        &self.camera_bind_group_layout,
+                    )),
+                    Err(error) => warn!("Failed to create a surface for a detached view: {error}"),
+                }
+            }
+            let closed = std::mem::take(&mut windows.closed);
+            self.detached.retain(|view| !closed.contains(&view.id()));
+        }
+        let detached: Vec<_> = self
+            .detached
+            .iter_mut()
+            .filter_map(|view| {
+                view.update(
+                    &self.device,
+                    &self.queue,
+                    &origin,
+                    &projection,
+                    &post_process,
+                )
+            })
+            .map(|(view_proj, position)| (Frustum::new(view_proj), position))
             .collect();
+        let selection = world.fetch::<Selection>().0;
+        let sun = (&lights, &positions, MaybeJoin(&radii))
+            .join()
+            .next()
+            .map(|(_, pos, radius)| (origin.render(pos.0), exaggeration.render_radius(radius)));
+
+        self.instance_textures.clear();
+        self.instance_lods.clear();
+        self.instance_meshes.clear();
+        self.instance_emissive.clear();
+        self.instance_ids.clear();
+        let mut instances = Vec::new();
+        // Still needed by the shadow map, since they might cast shadows into the view
+        let mut culled = Vec::new();
+        let mut sprites = Vec::new();
+        let mut budget = TEXTURES_PER_FRAME;
+        let mut inset = None;
+        for (
+            entity,
+            _,
+            pos,
+            handle,
+            mesh,
+            material,
+            surface,
+            radius,
+            impostor,
+            light,
+            emissive,
+            rotation,
+        ) in (
+            &entities,
+            &planets,
+            &positions,
+            MaybeJoin(&material_handles),
+            MaybeJoin(&mesh_handles),
+            MaybeJoin(&materials),
+            MaybeJoin(&surfaces),
+            MaybeJoin(&radii),
+            MaybeJoin(&impostors),
+            MaybeJoin(&lights),
+            MaybeJoin(&emissives),
+            MaybeJoin(&rotations),
+        )
+            .join()
+        {
+            let center = origin.render(pos.0);
+            let scale = exaggeration.render_radius(radius);
+            // Never culled, since the inset shows it up close
+            let selected = selection == Some(entity);
+            if selected {
+                inset = Some((center, scale));
+            }
+            let visible = frustum.contains_sphere(center, scale)
+                || second_frustum.is_some_and(|frustum| frustum.contains_sphere(center, scale))
+                || detached
+                    .iter()
+                    .any(|(frustum, _)| frustum.contains_sphere(center, scale));
+            if !selected && !visible {
+                culled.push(Instance::from_position(center, scale));
+                continue;
+            }
+            // Distance to the closest camera, which decides the level of detail
+            let distance = second_position
+                .into_iter()
+                .chain(detached.iter().map(|(_, position)| *position))
+                .chain([camera.position])
+                .map(|position| (center - position).magnitude())
+                .fold(f32::INFINITY, f32::min);
+            // Classified by the main camera, so it might still be large in the second view
+            let impostor =
+                impostor.is_some() && 2.0 * projection.screen_radius(scale, distance) < MAX_SIZE;
+            if impostor && !selected {
+                culled.push(Instance::from_position(center, scale));
+                sprites.push(Sprite {
+                    position: center,
+                    color: sprite_color(light.is_some(), center, camera.position, sun),
+                });
+                continue;
+            }
+
+            let key = match (handle, material, surface) {
+                (Some(handle), ..) => Some(TextureKey::Handle(*handle)),
+                (None, Some(material), _) => Some(TextureKey::Material(material.0)),
+                (None, None, Some(_)) => Some(TextureKey::Surface(entity)),
+                (None, None, None) => None,
+            };
+            let loaded = match key {
+                Some(TextureKey::Handle(handle)) => {
+                    material_registry.bind_group(handle).is_some()
+                        || self.broken_handles.contains(&handle)
+                }
+                Some(key @ TextureKey::Material(name)) => {
+                    self.textures.contains_key(&key) || self.broken_materials.contains(name)
+                }
+                Some(key) => self.textures.contains_key(&key),
+                None => true,
+            };
+            let textures = match key {
+                Some(TextureKey::Handle(handle)) => material_registry.get(handle),
+                Some(TextureKey::Material(name)) => registry.0.get(name),
+                _ => None,
+            };
+            let fetching = match textures {
+                Some(textures) if !loaded => !self.fetch_images(textures),
+                _ => false,
+            };
+            let deferred = !loaded && (fetching || budget == 0);
+            if deferred {
+                self.loading.pending += 1;
+            } else if !loaded {
+                budget -= 1;
+                self.loading.loaded += 1;
+            }
+
+            let texture = match (handle, material, surface) {
+                _ if deferred => key,
+                (Some(handle), ..) => self.load_handle(*handle, &mut material_registry),
+                (None, Some(material), _) => self.load_material(material, &registry),
+                (None, None, Some(surface)) => {
+                    let key = TextureKey::Surface(entity);
+                    if !self.textures.contains_key(&key) {
+                        let texture =
+                            self.texture_generator
+                                .generate(&self.device, &self.queue, surface);
+                        let bind_group =
+                            self.bind_texture(&texture, None, None, Reflectance::default());
+                        self.textures.insert(key, (vec![texture], bind_group));
+                    }
+                    Some(key)
+                }
+                (None, None, None) => None,
+            };
+            self.instance_textures.push(texture);
+            self.instance_meshes.push(mesh.copied());
+            self.instance_emissive.push(emissive.is_some());
+            self.instance_lods
+                .push(SphereLods::level(projection.screen_radius(scale, distance)));
+            self.instance_ids.push(entity.id() + 1);
+            instances.push(Instance {
+                rotation: rotation.map_or(Quaternion::one(), Rotation::orientation),
+                ..Instance::from_position(center, scale)
+            });
+        }
+        instances.append(&mut culled);
         let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
 
         if self.instances.len() != instances.len() {
-            self.instances = instances;
             self.instance_buffer = self.device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("Instance Buffer"),
                 contents: bytemuck::cast_slice(&instance_data),
@@ -81,29 +739,419 @@ impl<'a> RunNow<'a> for Render {
                 bytemuck::cast_slice(instance_data.as_slice()),
             );
         }
+        self.instances = instances;
+        self.inset.update(&self.queue, inset, camera.position);
+        let comparison = world.fetch::<Comparison>();
+        self.compared.update(
+            &self.device,
+            &self.queue,
+            viewport,
+            comparison
+                .0
+                .as_deref()
+                .zip(second)
+                .zip(second_frustum)
+                .map(|((bodies, second), frustum)| (bodies, second, frustum)),
+            &origin,
+            &projection,
+            &exaggeration,
+            |id| {
+                let entity = entities.entity(id);
+                let texture = match (
+                    material_handles.get(entity),
+                    materials.get(entity),
+                    surfaces.get(entity),
+                ) {
+                    (Some(handle), ..) => Some(TextureKey::Handle(*handle)),
+                    (None, Some(material), _) => Some(TextureKey::Material(material.0)),
+                    (None, None, Some(_)) => Some(TextureKey::Surface(entity)),
+                    (None, None, None) => None,
+                };
+                Appearance {
+                    texture,
+                    mesh: mesh_handles.get(entity).copied(),
+                    emissive: emissives.contains(entity),
+                    light: lights.contains(entity),
+                    impostor: impostors.contains(entity),
+                }
+            },
+        );
+
+        match &world.fetch::<Recording>().0 {
+            Some(directory) => {
+                if self.recorder.as_ref().map(Recorder::directory) != Some(directory) {
+                    info!("Recording frames to {}", directory.display());
+                    self.recorder =
+                        Some(Recorder::new(&self.device, &self.config, directory.clone()));
+                }
+            }
+            None => self.recorder = None,
+        }
 
-        let matrix: [[f32; 4]; 4] = (OPENGL_TO_WGPU_MATRIX
-            * self.camera_config.matrix()
-            * world.fetch::<Camera>().matrix())
-        .into();
+        if *world.fetch::<PickingMode>() == PickingMode::Gpu {
+            let id_buffer = self.id_buffer.get_or_insert_with(|| {
+                IdBuffer::new(&self.device, &self.config, &self.camera_bind_group_layout)
+            });
+            if let Some(id) = id_buffer.poll(&self.device) {
+                world.fetch_mut::<Selection>().0 = id
+                    .checked_sub(1)
+                    .map(|id| entities.entity(id))
+                    .filter(|entity| entities.is_alive(*entity));
+            }
+            let mut controls = world.fetch_mut::<Controls>();
+            if controls.is_select_clicked {
+                controls.is_select_clicked = false;
+                // The cursor is hidden, so fall back to the screen's center
+                let [x, y] = match (controls.cursor, second) {
+                    // The id buffer is rendered by the main camera only, stretched across the screen
+                    (Some([x, y]), Some(_)) => [x * 2.0 + 1.0, y],
+                    (cursor, _) => cursor.unwrap_or([0.0, 0.0]),
+                };
+                self.pending_pick = Some((
+                    ((x + 1.0) / 2.0 * self.config.width as f32) as u32,
+                    ((1.0 - y) / 2.0 * self.config.height as f32) as u32,
+                ));
+            }
+        }
+        self.debug_view = world.fetch::<Controls>().debug_view;
+        {
+            let mut controls = world.fetch_mut::<Controls>();
+            let mut present_mode = world.fetch_mut::<PresentMode>();
+            if controls.cycle_present_mode {
+                controls.cycle_present_mode = false;
+                *present_mode = present_mode.next(&self.present_modes);
+            }
+            if present_mode.0 != self.config.present_mode {
+                if self.present_modes.contains(&present_mode.0) {
+                    info!("Presenting with {:?}", present_mode.0);
+                    self.config.present_mode = present_mode.0;
+                    if let Target::Window { surface, .. } = &self.target {
+                        surface.configure(&self.device, &self.config);
+                    }
+                } else {
+                    warn!("The surface doesn't support {:?}", present_mode.0);
+                    present_mode.0 = self.config.present_mode;
+                }
+            }
+        }
+
+        let uniform = CameraUniform::new(view_proj, camera.position);
         self.queue
-            .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[matrix]));
+            .write_buffer(&self.camera_buffer, 0, bytemuck::cast_slice(&[uniform]));
+        self.skybox.update(&self.queue, 0, &camera, &projection);
+        if let Some(second) = &second {
+            self.skybox.update(&self.queue, 1, second, &projection);
+        }
+
+        let light = match sun {
+            Some((pos, _)) => {
+                self.shadow_map.update(&self.queue, pos);
+                LightUniform::at([pos.x, pos.y, pos.z])
+            }
+            None => LightUniform::unlit(),
+        };
+        self.queue
+            .write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&[light]));
+        self.lens_flare.update(
+            &self.queue,
+            &lens_flare,
+            view_proj,
+            projection.aspect,
+            camera.position,
+            // Drawn across the whole screen, which would misplace it in a split one
+            sun.filter(|_| !world.fetch::<Controls>().hide_lens_flare && second.is_none()),
+        );
+
+        self.tonemap.update(&self.queue, &post_process);
+
+        self.impostor_pipeline
+            .update(&self.device, &self.queue, viewport, &sprites);
+        self.minimap.update(
+            &self.device,
+            &self.queue,
+            world
+                .fetch::<Minimap>()
+                .0
+                .then_some((&*world.fetch::<Camera>(), &*projection)),
+            // The map has its own view of the whole system, centered on the sun instead
+            (&planets, &positions, MaybeJoin(&lights))
+                .join()
+                .map(|(_, pos, light)| (pos.0.map(|c| c as f32) / SCALE, light.is_some())),
+        );
+        let dt = world
+            .fetch::<Determinism>()
+            .0
+            .unwrap_or(**world.fetch::<Delta>())
+            .as_secs_f32()
+            * world.fetch::<SimSpeed>().0;
+        if let Some(particle_pipeline) = &mut self.particle_pipeline {
+            particle_pipeline.begin(viewport, dt);
+            particle_pipeline.update_belts(
+                &self.device,
+                &self.queue,
+                (&entities, &belts, &positions, &masses)
+                    .join()
+                    .map(|(entity, belt, pos, mass)| {
+                        (entity, belt, origin.render(pos.0), G * mass.0)
+                    }),
+            );
+            if let Some((_, light)) = (&lights, &positions).join().next() {
+                particle_pipeline.update_comets(
+                    &self.device,
+                    &self.queue,
+                    origin.render(light.0),
+                    (&entities, &comets, &positions, &velocities).join().map(
+                        |(entity, comet, pos, vel)| {
+                            let emitter = (pos.0 - light.0).map(|c| c as f32);
+                            (entity, comet, emitter, vel.0.map(|c| c as f32))
+                        },
+                    ),
+                );
+            }
+        }
+        let bodies = (&names, &positions, MaybeJoin(&radii))
+            .join()
+            .map(|(name, pos, radius)| {
+                (
+                    name.0.as_str(),
+                    origin.render(pos.0),
+                    exaggeration.render_radius(radius),
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut texts = labels(view_proj, camera.position, viewport, bodies.iter().copied());
+        // Named after the main simulation's bodies, which a comparison doesn't show there
+        let second_labels = second_view_proj
+            .zip(second_position)
+            .filter(|_| !self.compared.active);
+        if let Some((second_view_proj, second_position)) = second_labels {
+            for mut text in labels(second_view_proj, second_position, viewport, bodies) {
+                text.position[0] += viewport[0];
+                texts.push(text);
+            }
+        }
+        texts.append(&mut world.fetch_mut::<TextQueue>().0);
+        self.loading.ready |= self.loading.pending == 0;
+        *world.fetch_mut::<Loading>() = self.loading;
+        texts.append(&mut progress_bar(&self.loading, size));
+        texts.extend(self.debug_view.label(size));
+        texts.append(&mut self.axes.update(&self.queue, &camera, viewport));
+        self.text_renderer
+            .prepare(&self.device, &self.queue, size, &texts);
+        self.bloom.update(&self.queue, &bloom);
+
+        self.line_pipeline
+            .update(&self.device, &self.queue, &origin, &world.fetch::<Lines>());
+        self.trail_pipeline
+            .update(&self.device, &self.queue, &origin, trails.join());
+        self.cloud_pipeline.update(
+            &self.device,
+            &self.queue,
+            &self.texture_generator,
+            &self.texture_bind_group_layout,
+            &self.fallback_textures,
+            &self.bind_groups,
+            exaggeration.0 / SCALE,
+            (
+                &entities,
+                &clouds,
+                &positions,
+                MaybeJoin(&radii),
+                MaybeJoin(&rotations),
+            )
+                .join()
+                .map(|(entity, clouds, pos, radius, rotation)| {
+                    (
+                        entity,
+                        clouds,
+                        origin.render(pos.0),
+                        exaggeration.render_radius(radius),
+                        rotation.map_or(Rad(0.0), |rotation| rotation.tilt),
+                    )
+                }),
+        );
+        self.atmosphere_pipeline.update(
+            &self.device,
+            &self.queue,
+            exaggeration.0 / SCALE,
+            (&atmospheres, &positions, MaybeJoin(&radii)).join().map(
+                |(atmosphere, pos, radius)| {
+                    (
+                        atmosphere,
+                        origin.render(pos.0),
+                        exaggeration.render_radius(radius),
+                    )
+                },
+            ),
+        );
+        self.ring_pipeline.update(
+            &self.device,
+            &self.queue,
+            exaggeration.0 / SCALE,
+            (&entities, &rings, &positions)
+                .join()
+                .map(|(entity, rings, pos)| (entity, rings, origin.render(pos.0))),
+        );
+        let layers = self.transparent_layers();
+        let second_position = second_position.filter(|_| !self.compared.active);
+        let transparent_draws = [Some(camera.position), second_position].map(|position| {
+            position.map_or_else(Vec::new, |position| transparent::sort(&layers, position))
+        });
+        self.transparent_draws = transparent_draws;
 
-        match self.render() {
+        match self.render(&mesh_registry, &material_registry) {
             Ok(_) => {}
-            Err(error) => panic!("Unhandled surface error: {error:?}"),
+            // E.g. after alt-tabbing or resizing, the next frame is drawn to the new surface
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated)
+                if !self.lost.load(Ordering::Relaxed) =>
+            {
+                info!("Reconfiguring the lost or outdated surface");
+                self.reconfigure();
+            }
+            Err(wgpu::SurfaceError::Timeout) => warn!("Skipped a frame, the surface timed out"),
+            // Along with the device, which is recreated at the start of the next frame
+            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {}
+            Err(error @ wgpu::SurfaceError::OutOfMemory) => {
+                panic!("Unhandled surface error: {error:?}")
+            }
         }
+
+        world.fetch_mut::<FrameStats>().end_render();
     }
 
     fn setup(&mut self, world: &mut World) {
+        world.insert(Projection::new(self.config.width, self.config.height));
         <Read<'a, Camera> as SystemData>::setup(world);
+        <Read<'a, Lines> as SystemData>::setup(world);
+        <Write<'a, TextQueue> as SystemData>::setup(world);
+        <Write<'a, DetachedWindows> as SystemData>::setup(world);
+        <Write<'a, GpuSteps> as SystemData>::setup(world);
+        <Write<'a, SelectedIntegrator> as SystemData>::setup(world);
+        <Write<'a, FrameStats> as SystemData>::setup(world);
+        <Read<'a, Exaggeration> as SystemData>::setup(world);
+        <Read<'a, PickingMode> as SystemData>::setup(world);
+        <Read<'a, Recording> as SystemData>::setup(world);
+        <Read<'a, SplitScreen> as SystemData>::setup(world);
+        <Read<'a, Comparison> as SystemData>::setup(world);
+        <Read<'a, Minimap> as SystemData>::setup(world);
+        <Write<'a, GpuStats> as SystemData>::setup(world);
+        <Write<'a, PresentMode> as SystemData>::setup(world);
+        <Write<'a, Resize> as SystemData>::setup(world);
+        <Write<'a, Selection> as SystemData>::setup(world);
+        <Write<'a, Controls> as SystemData>::setup(world);
+        <Read<'a, PostProcess> as SystemData>::setup(world);
+        <Read<'a, Bloom> as SystemData>::setup(world);
+        <Read<'a, LensFlare> as SystemData>::setup(world);
+        <Read<'a, Anisotropy> as SystemData>::setup(world);
+        <Write<'a, MeshRegistry> as SystemData>::setup(world);
+        <Write<'a, MaterialRegistry> as SystemData>::setup(world);
+        <ReadStorage<'static, MeshHandle> as SystemData>::setup(world);
+        <ReadStorage<'static, MaterialHandle> as SystemData>::setup(world);
+        <Write<'a, Loading> as SystemData>::setup(world);
         <ReadStorage<'static, Planet> as SystemData>::setup(world);
         <ReadStorage<'static, Position> as SystemData>::setup(world);
+        <Read<'a, Materials> as SystemData>::setup(world);
+        <ReadStorage<'static, Material> as SystemData>::setup(world);
+        <ReadStorage<'static, LightSource> as SystemData>::setup(world);
+        <ReadStorage<'static, Emissive> as SystemData>::setup(world);
+        <ReadStorage<'static, Impostor> as SystemData>::setup(world);
+        <ReadStorage<'static, Surface> as SystemData>::setup(world);
+        <ReadStorage<'static, Radius> as SystemData>::setup(world);
+        <ReadStorage<'static, Name> as SystemData>::setup(world);
+        <ReadStorage<'static, Trail> as SystemData>::setup(world);
+        <ReadStorage<'static, Rings> as SystemData>::setup(world);
+        <ReadStorage<'static, Atmosphere> as SystemData>::setup(world);
+        <ReadStorage<'static, Clouds> as SystemData>::setup(world);
+        <ReadStorage<'static, Belt> as SystemData>::setup(world);
+        <ReadStorage<'static, Comet> as SystemData>::setup(world);
+        <ReadStorage<'static, Velocity> as SystemData>::setup(world);
+        <ReadStorage<'static, Mass> as SystemData>::setup(world);
+        <Read<'a, SimSpeed> as SystemData>::setup(world);
+        <Read<'a, Delta> as SystemData>::setup(world);
+        <Read<'a, Determinism> as SystemData>::setup(world);
+    }
+}
+
+/// Pipelines drawing with the main shader
+struct MainPipelines {
+    lit: wgpu::RenderPipeline,
+    emissive: wgpu::RenderPipeline,
+    clouds: wgpu::RenderPipeline,
+    debug: DebugPipelines,
+}
+
+/// Create the texture a headless [`Render`] draws to, described by its configuration
+fn create_offscreen(device: &wgpu::Device, config: &SurfaceConfiguration) -> wgpu::Texture {
+    device.create_texture(&wgpu::TextureDescriptor {
+        label: Some("Offscreen Target"),
+        size: wgpu::Extent3d {
+            width: config.width,
+            height: config.height,
+            depth_or_array_layers: 1,
+        },
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: wgpu::TextureDimension::D2,
+        format: config.format,
+        usage: config.usage,
+        view_formats: &[],
+    })
+}
+
+/// Compile the main shader and create the pipelines drawing bodies and their clouds with it
+fn create_main_pipelines(
+    device: &wgpu::Device,
+    layout: &PipelineLayout,
+    source: &str,
+) -> MainPipelines {
+    let shader = device.create_shader_module(ShaderModuleDescriptor {
+        label: Some("Shader"),
+        source: ShaderSource::Wgsl(source.into()),
+    });
+    let create_pipeline = |label, entry_point| {
+        device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point,
+                targets: &[Some(ColorTargetState {
+                    format: Tonemap::FORMAT,
+                    blend: Some(BlendState::REPLACE),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                cull_mode: Some(wgpu::Face::Back),
+                topology: PrimitiveTopology::TriangleList,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        })
+    };
+    MainPipelines {
+        lit: create_pipeline("Render Pipeline", "fs_main"),
+        emissive: create_pipeline("Emissive Pipeline", "fs_emissive"),
+        clouds: CloudPipeline::create_pipeline(device, Tonemap::FORMAT, layout, &shader),
+        debug: DebugPipelines::new(device, Tonemap::FORMAT, layout, &shader),
     }
 }
 
 impl Render {
-    pub async fn new(window: Arc<Window>) -> Result<Self, DynError> {
+    pub async fn new(window: Arc<Window>, assets: &Assets) -> Result<Self, DynError> {
         let size = window.inner_size();
 
         let instance = wgpu::Instance::new(Default::default());
@@ -114,19 +1162,44 @@ impl Render {
         // State owns the arced window so this should be safe.
         let surface = unsafe { instance.create_surface(window.as_ref()) }?;
 
+        Self::with_target(instance, Some((surface, window)), size, assets).await
+    }
+
+    /// Render into an offscreen texture of some size instead of a window
+    ///
+    /// The frames can be saved by setting a [`Recording`] directory or read from [`texture`](Self::texture).
+    pub async fn headless(width: u32, height: u32, assets: &Assets) -> Result<Self, DynError> {
+        let instance = wgpu::Instance::new(Default::default());
+        let size = winit::dpi::PhysicalSize::new(width, height);
+        Self::with_target(instance, None, size, assets).await
+    }
+
+    async fn with_target(
+        instance: wgpu::Instance,
+        window: Option<(wgpu::Surface, Arc<Window>)>,
+        size: winit::dpi::PhysicalSize<u32>,
+        assets: &Assets,
+    ) -> Result<Self, DynError> {
         let adapter = instance
             .request_adapter(&RequestAdapterOptions {
                 power_preference: Default::default(),
-                compatible_surface: Some(&surface),
+                compatible_surface: window.as_ref().map(|(surface, _)| surface),
                 force_fallback_adapter: false,
             })
             .await
             .ok_or(CustomError::from("Failed to request adapter"))?;
+        #[cfg(not(target_arch = "wasm32"))]
+        crate::crash::set_adapter_info(&adapter.get_info());
         let (device, queue) = adapter
             .request_device(
                 &DeviceDescriptor {
-                    label: None,
-                    features: Features::empty(),
+                    label: Some("Device"),
+                    // Optional, compressed textures fall back to their encoded images,
+                    // the wireframe view and gpu timings are unavailable without them
+                    features: adapter.features()
+                        & (Features::TEXTURE_COMPRESSION_BC
+                            | Features::POLYGON_MODE_LINE
+                            | Features::TIMESTAMP_QUERY),
                     // WebGL doesn't support all of wgpu's features, so if
                     // we're building for the web we'll have to disable some.
                     limits: if cfg!(target_arch = "wasm32") {
@@ -138,31 +1211,68 @@ impl Render {
                 None,
             )
             .await?;
+        let lost = Arc::new(AtomicBool::new(false));
+        device.on_uncaptured_error(Box::new({
+            let lost = Arc::clone(&lost);
+            move |error| {
+                // Everything fails once the device is lost, until it's recreated
+                if lost.load(Ordering::Relaxed) || is_device_lost(&error) {
+                    lost.store(true, Ordering::Relaxed);
+                } else {
+                    // Like wgpu's default handler
+                    panic!("wgpu error: {error}");
+                }
+            }
+        }));
 
-        let surface_caps = surface.get_capabilities(&adapter);
-        // Shader code in this tutorial assumes an Srgb surface texture. Using a different
-        // one will result all the colors comming out darker. If you want to support non
-        // Srgb surfaces, you'll need to account for that when drawing to the frame.
-        let surface_format = surface_caps
-            .formats
-            .iter()
-            .copied()
-            .find(|f| f.is_srgb())
-            .unwrap_or(surface_caps.formats[0]);
-        let config = SurfaceConfiguration {
-            usage: TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.width,
-            height: size.height,
-            present_mode: surface_caps.present_modes[0],
-            alpha_mode: surface_caps.alpha_modes[0],
-            view_formats: vec![],
+        let (target, config, present_modes) = match window {
+            Some((surface, window)) => {
+                let surface_caps = surface.get_capabilities(&adapter);
+                // Prefer an Srgb surface, otherwise the tonemap pass encodes the colors itself
+                let surface_format = surface_caps
+                    .formats
+                    .iter()
+                    .copied()
+                    .find(|f| f.is_srgb())
+                    .unwrap_or(surface_caps.formats[0]);
+                let config = SurfaceConfiguration {
+                    usage: TextureUsages::RENDER_ATTACHMENT,
+                    format: surface_format,
+                    width: size.width,
+                    height: size.height,
+                    // Switched to the PresentMode resource's in the first frame
+                    present_mode: wgpu::PresentMode::Fifo,
+                    alpha_mode: surface_caps.alpha_modes[0],
+                    view_formats: vec![],
+                };
+                surface.configure(&device, &config);
+                (
+                    Target::Window { surface, window },
+                    config,
+                    surface_caps.present_modes,
+                )
+            }
+            None => {
+                // Describes the offscreen texture, there is no surface to configure
+                let config = SurfaceConfiguration {
+                    usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+                    format: wgpu::TextureFormat::Rgba8UnormSrgb,
+                    width: size.width,
+                    height: size.height,
+                    present_mode: wgpu::PresentMode::Fifo,
+                    alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+                    view_formats: vec![],
+                };
+                (
+                    Target::Offscreen(create_offscreen(&device, &config)),
+                    config,
+                    vec![wgpu::PresentMode::Fifo],
+                )
+            }
         };
-        surface.configure(&device, &config);
 
-        let diffuse_bytes = include_bytes!("../happy-tree.png");
-        let diffuse_texture =
-            Texture::from_bytes(&device, &queue, diffuse_bytes, "happy-tree.png")?;
+        // Bodies are drawn with placeholders until the textures are streamed in
+        let pending_diffuse = assets.spawn(FALLBACK_TEXTURE);
 
         let texture_bind_group_layout =
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
@@ -183,34 +1293,66 @@ impl Render {
                         ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
                         count: None,
                     },
+                    BindGroupLayoutEntry {
+                        binding: 2,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 3,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Texture {
+                            multisampled: false,
+                            view_dimension: wgpu::TextureViewDimension::D2,
+                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        },
+                        count: None,
+                    },
+                    BindGroupLayoutEntry {
+                        binding: 4,
+                        visibility: ShaderStages::FRAGMENT,
+                        ty: BindingType::Buffer {
+                            ty: BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    },
                 ],
                 label: Some("texture_bind_group_layout"),
             });
 
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
-            entries: &[
-                BindGroupEntry {
-                    binding: 0,
-                    resource: BindingResource::TextureView(&diffuse_texture.view),
-                },
-                BindGroupEntry {
-                    binding: 1,
-                    resource: BindingResource::Sampler(&diffuse_texture.sampler),
-                },
-            ],
-            label: Some("diffuse_bind_group"),
-        });
+        let fallback_textures = FallbackTextures::new(&device, &queue)?;
+        let bind_groups = BindGroupCache::default();
+        let bind_placeholder = || {
+            bind_material(
+                &device,
+                &bind_groups,
+                &texture_bind_group_layout,
+                &fallback_textures,
+                &fallback_textures.placeholder,
+                None,
+                None,
+                Reflectance::default(),
+            )
+        };
+        let diffuse_bind_group = bind_placeholder();
+        let placeholder_bind_group = bind_placeholder();
 
         let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Camera Buffer"),
-            contents: bytemuck::cast_slice(&[<Matrix4<f32> as Into<[[f32; 4]; 4]>>::into(
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
                 OPENGL_TO_WGPU_MATRIX * Matrix4::identity(),
+                Point3::origin(),
             )]),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
-        // let instances = Instance::generate(NUM_INSTANCES_PER_ROW, INSTANCE_DISPLACEMENT);
         let instances = vec![Instance::default()];
         let instance_data = instances.iter().map(Instance::to_raw).collect::<Vec<_>>();
         let instance_buffer = device.create_buffer_init(&BufferInitDescriptor {
@@ -223,7 +1365,7 @@ impl Render {
             device.create_bind_group_layout(&BindGroupLayoutDescriptor {
                 entries: &[BindGroupLayoutEntry {
                     binding: 0,
-                    visibility: ShaderStages::VERTEX,
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
                     ty: BindingType::Buffer {
                         ty: BufferBindingType::Uniform,
                         has_dynamic_offset: false,
@@ -242,63 +1384,157 @@ impl Render {
             }],
             label: Some("camera_bind_group"),
         });
+        let inset = Inset::new(&device, &config, &camera_bind_group_layout);
+        let gpu_timer = device
+            .features()
+            .contains(Features::TIMESTAMP_QUERY)
+            .then(|| GpuTimer::new(&device, &queue));
+        let split = SplitView::new(&device, &camera_bind_group_layout);
+        let minimap = MinimapPipeline::new(&device, &config, &camera_bind_group_layout);
+
+        let light_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Light Buffer"),
+            contents: bytemuck::cast_slice(&[LightUniform::unlit()]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
 
-        let camera_config = Projection::new(config.width, config.height);
+        let shadow_map = ShadowMap::new(&device);
 
-        let shader = device.create_shader_module(ShaderModuleDescriptor {
-            label: Some("Shader"),
-            source: ShaderSource::Wgsl(include_str!("../shader.wgsl").into()),
+        let light_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX | ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        sample_type: wgpu::TextureSampleType::Depth,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 3,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+                    count: None,
+                },
+            ],
+            label: Some("light_bind_group_layout"),
+        });
+
+        let light_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &light_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: light_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: shadow_map.matrices.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::TextureView(&shadow_map.view),
+                },
+                BindGroupEntry {
+                    binding: 3,
+                    resource: BindingResource::Sampler(&shadow_map.sampler),
+                },
+            ],
+            label: Some("light_bind_group"),
         });
 
         let depth_texture = Texture::create_depth_texture(&device, &config, "depth_texture");
 
         let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
             label: Some("Render Pipeline Layout"),
-            bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+            bind_group_layouts: &[
+                &texture_bind_group_layout,
+                &camera_bind_group_layout,
+                &light_bind_group_layout,
+            ],
             push_constant_ranges: &[],
         });
 
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-            label: Some("Render Pipeline"),
-            layout: Some(&render_pipeline_layout),
-            vertex: VertexState {
-                module: &shader,
-                entry_point: "vs_main",
-                buffers: &[
-                    wgpu::VertexBufferLayout {
-                        array_stride: size_of::<Vertex>() as BufferAddress,
-                        step_mode: VertexStepMode::Vertex,
-                        attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x2],
-                    },
-                    InstanceRaw::desc(),
-                ],
-            },
-            fragment: Some(FragmentState {
-                module: &shader,
-                entry_point: "fs_main",
-                targets: &[Some(ColorTargetState {
-                    format: config.format,
-                    blend: Some(BlendState::REPLACE),
-                    write_mask: Default::default(),
-                })],
-            }),
-            primitive: PrimitiveState {
-                cull_mode: None, // Some(wgpu::Face::Back),
-                topology: PrimitiveTopology::LineStrip,
-                ..Default::default()
-            },
-            depth_stencil: Some(DepthStencilState {
-                format: Texture::DEPTH_FORMAT,
-                depth_write_enabled: true,
-                depth_compare: CompareFunction::Less,
-                stencil: Default::default(),
-                bias: Default::default(),
-            }),
-            multisample: Default::default(),
-            multiview: Default::default(),
-        });
+        let shader_source = assets.load(MAIN_SHADER).await?;
+        // Report mistakes in the loaded shader instead of panicking
+        device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = create_main_pipelines(&device, &render_pipeline_layout, &shader_source);
+        if let Some(error) = device.pop_error_scope().await {
+            return Err(AssetError {
+                path: assets.root.join(MAIN_SHADER.path),
+                error: error.to_string().into(),
+            }
+            .into());
+        }
+        let cloud_pipeline = CloudPipeline::new(&device, pipelines.clouds);
+
+        let texture_generator = TextureGenerator::new(&device);
+
+        let tonemap = Tonemap::new(&device, &config);
+        let bloom = BloomPipeline::new(&device, &config, tonemap.view());
+        let lens_flare = LensFlarePipeline::new(&device, &depth_texture.view);
 
-        let (vertexes, indexes) = octahedron();
+        let stars = Texture::cube_from_images(
+            &device,
+            &queue,
+            &starfield(STARFIELD_SEED, 1024, 20_000),
+            Some("starfield"),
+        )?;
+        let skybox = Skybox::new(&device, Tonemap::FORMAT, stars);
+
+        let line_pipeline = LinePipeline::new(&device, Tonemap::FORMAT, &camera_bind_group_layout);
+        let axes = AxesGizmo::new(&device, config.format, &camera_bind_group_layout);
+        let text_renderer = TextRenderer::new(&device, config.format)?;
+
+        let trail_pipeline =
+            TrailPipeline::new(&device, Tonemap::FORMAT, &camera_bind_group_layout);
+        let impostor_pipeline =
+            ImpostorPipeline::new(&device, Tonemap::FORMAT, &camera_bind_group_layout);
+        let compared = ComparedView::new(&device, &camera_bind_group_layout);
+        let compute = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(DownlevelFlags::COMPUTE_SHADERS);
+        let particle_pipeline = compute
+            .then(|| ParticlePipeline::new(&device, Tonemap::FORMAT, &camera_bind_group_layout));
+        let nbody = compute.then(|| NBodyPipeline::new(&device));
+
+        let atmosphere_pipeline = AtmospherePipeline::new(
+            &device,
+            Tonemap::FORMAT,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        );
+        let ring_pipeline = RingPipeline::new(
+            &device,
+            Tonemap::FORMAT,
+            &camera_bind_group_layout,
+            &light_bind_group_layout,
+        );
+
+        let (vertexes, indexes) = icosphere(3);
         let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Vertex Buffer"),
             contents: bytemuck::cast_slice(vertexes.as_slice()),
@@ -310,60 +1546,607 @@ impl Render {
             usage: BufferUsages::INDEX,
         });
         let num_indices = indexes.len() as u32;
+        let sphere_lods = SphereLods::new(&device);
 
         Ok(Self {
-            surface,
+            target,
             device,
             queue,
             config,
             size,
-            render_pipeline,
+            render_pipeline: pipelines.lit,
+            emissive_pipeline: pipelines.emissive,
+            debug_pipelines: pipelines.debug,
+            debug_view: DebugView::Off,
+            render_pipeline_layout,
             vertex_buffer,
             index_buffer,
             num_indices,
-            diffuse_texture,
+            diffuse_texture: None,
             diffuse_bind_group,
+            pending_diffuse: Some(pending_diffuse),
+            placeholder_bind_group,
+            loading: Loading::default(),
+            fallback_textures,
+            texture_bind_group_layout,
+            bind_groups,
+            texture_generator,
+            textures: HashMap::new(),
+            instance_textures: Vec::new(),
+            instance_lods: Vec::new(),
+            instance_meshes: Vec::new(),
+            instance_emissive: Vec::new(),
+            sphere_lods,
+            broken_materials: HashSet::new(),
+            broken_handles: HashSet::new(),
+            images: HashMap::new(),
+            pending_images: HashMap::new(),
+            assets: assets.clone(),
+            watcher: assets.watch(),
 
             camera_buffer,
             camera_bind_group,
-            camera_config,
+            camera_bind_group_layout,
+            light_buffer,
+            light_bind_group,
+            shadow_map,
+            skybox,
+            tonemap,
+            bloom,
             instances,
             instance_buffer,
+            instance_ids: Vec::new(),
+            id_buffer: None,
+            gpu_timer,
+            recorder: None,
+            lost,
+            present_modes,
+            pending_pick: None,
+            line_pipeline,
+            trail_pipeline,
+            ring_pipeline,
+            atmosphere_pipeline,
+            cloud_pipeline,
+            transparent_draws: Default::default(),
+            split,
+            compared,
+            impostor_pipeline,
+            particle_pipeline,
+            nbody,
+            lens_flare,
+            axes,
+            inset,
+            minimap,
+            detached: Vec::new(),
+            text_renderer,
             depth_texture,
-            window,
+            instance,
+            adapter,
         })
     }
 
+    /// Texture the frames are drawn to, `None` unless [`headless`](Self::headless)
+    pub fn texture(&self) -> Option<&wgpu::Texture> {
+        match &self.target {
+            Target::Window { .. } => None,
+            Target::Offscreen(texture) => Some(texture),
+        }
+    }
+
+    /// Pipelines drawn in the transparent pass, whose items are sorted together
+    fn transparent_layers(&self) -> [&dyn Transparent; 4] {
+        [
+            &self.cloud_pipeline,
+            &self.atmosphere_pipeline,
+            &self.ring_pipeline,
+            &self.trail_pipeline,
+        ]
+    }
+
+    /// Recompile the main shader and recreate the pipelines using it
+    ///
+    /// Mistakes are logged and the previous pipelines kept.
+    fn reload_shader(&mut self) {
+        let source = match pollster::block_on(self.assets.load(MAIN_SHADER)) {
+            Ok(source) => source,
+            Err(error) => {
+                warn!("{error}");
+                return;
+            }
+        };
+        self.device.push_error_scope(wgpu::ErrorFilter::Validation);
+        let pipelines = create_main_pipelines(&self.device, &self.render_pipeline_layout, &source);
+        if let Some(error) = pollster::block_on(self.device.pop_error_scope()) {
+            warn!("Failed to compile {}: {error}", MAIN_SHADER.path);
+            return;
+        }
+        self.render_pipeline = pipelines.lit;
+        self.emissive_pipeline = pipelines.emissive;
+        self.debug_pipelines = pipelines.debug;
+        self.cloud_pipeline.set_pipeline(pipelines.clouds);
+        info!("Reloaded {}", MAIN_SHADER.path);
+    }
+
+    /// Load a material's textures unless they already are
+    ///
+    /// Returns `None` if the material fails to load, so the diffuse texture is used instead.
+    fn load_material(&mut self, material: &Material, registry: &Materials) -> Option<TextureKey> {
+        let key = TextureKey::Material(material.0);
+        if self.textures.contains_key(&key) {
+            return Some(key);
+        }
+        if self.broken_materials.contains(material.0) {
+            return None;
+        }
+        let Some(textures) = registry.0.get(material.0) else {
+            warn!("Unknown material {}", material.0);
+            self.broken_materials.insert(material.0);
+            return None;
+        };
+        let Some(created) = self.create_material(textures, material.0) else {
+            self.broken_materials.insert(material.0);
+            return None;
+        };
+        self.textures.insert(key, created);
+        Some(key)
+    }
+
+    /// Load the textures of a material from the [`MaterialRegistry`] unless they already are
+    ///
+    /// Returns `None` if the material fails to load, so the diffuse texture is used instead.
+    fn load_handle(
+        &mut self,
+        handle: MaterialHandle,
+        registry: &mut MaterialRegistry,
+    ) -> Option<TextureKey> {
+        if registry.bind_group(handle).is_some() {
+            return Some(TextureKey::Handle(handle));
+        }
+        if self.broken_handles.contains(&handle) {
+            return None;
+        }
+        let created = registry
+            .get(handle)
+            .and_then(|textures| self.create_material(textures, &format!("{handle:?}")));
+        let Some((textures, bind_group)) = created else {
+            self.broken_handles.insert(handle);
+            return None;
+        };
+        registry.bind(handle, textures, bind_group);
+        Some(TextureKey::Handle(handle))
+    }
+
+    /// Create a material's textures and the bind group sampling them
+    ///
+    /// Returns `None` if the albedo fails to load, other textures are replaced by fallbacks instead.
+    fn create_material(
+        &mut self,
+        textures: &MaterialTextures,
+        name: &str,
+    ) -> Option<(Vec<Texture>, Arc<wgpu::BindGroup>)> {
+        let texture = match self.load_source(&textures.albedo, name) {
+            Ok(texture) => texture,
+            Err(error) => {
+                warn!("Failed to load material {name}: {error}");
+                self.forget_images(textures);
+                return None;
+            }
+        };
+        let emissive = textures.emissive.as_ref().and_then(|source| {
+            self.load_source(source, name)
+                .map_err(|error| {
+                    warn!("Failed to load emissive texture of material {name}: {error}")
+                })
+                .ok()
+        });
+        let normal = textures.normal.as_ref().and_then(|source| {
+            self.load_normals(source, name)
+                .map_err(|error| warn!("Failed to load normal map of material {name}: {error}"))
+                .ok()
+        });
+        self.forget_images(textures);
+        let bind_group = self.bind_texture(
+            &texture,
+            emissive.as_ref(),
+            normal.as_ref(),
+            textures.reflectance,
+        );
+        let textures = [Some(texture), emissive, normal]
+            .into_iter()
+            .flatten()
+            .collect();
+        Some((textures, bind_group))
+    }
+
+    /// Create a texture from wherever a material says it comes from
+    fn load_source(&self, source: &MaterialSource, label: &str) -> Result<Texture, DynError> {
+        match source {
+            MaterialSource::Image(bytes) => Texture::from_image_with_mipmaps(
+                &self.device,
+                &self.queue,
+                &self.texture_generator.mipmaps,
+                &image::load_from_memory(bytes)?,
+                Some(label),
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+            ),
+            MaterialSource::Asset(handle) => Texture::from_image_with_mipmaps(
+                &self.device,
+                &self.queue,
+                &self.texture_generator.mipmaps,
+                self.image(handle)?,
+                Some(label),
+                wgpu::TextureFormat::Rgba8UnormSrgb,
+            ),
+            MaterialSource::Procedural(surface) => {
+                Ok(self
+                    .texture_generator
+                    .generate(&self.device, &self.queue, surface))
+            }
+            MaterialSource::Compressed { ktx2, fallback } => {
+                if self.supports_ktx2(ktx2)? {
+                    self.load_ktx2(ktx2, label)
+                } else {
+                    self.load_source(&MaterialSource::Image(fallback), label)
+                }
+            }
+        }
+    }
+
+    /// Create a normal map from wherever a material says it comes from
+    ///
+    /// Procedural sources produce the bumps of their noise.
+    fn load_normals(&self, source: &MaterialSource, label: &str) -> Result<Texture, DynError> {
+        match source {
+            MaterialSource::Image(bytes) => Texture::from_image_with_mipmaps(
+                &self.device,
+                &self.queue,
+                &self.texture_generator.mipmaps,
+                &image::load_from_memory(bytes)?,
+                Some(label),
+                wgpu::TextureFormat::Rgba8Unorm,
+            ),
+            MaterialSource::Asset(handle) => Texture::from_image_with_mipmaps(
+                &self.device,
+                &self.queue,
+                &self.texture_generator.mipmaps,
+                self.image(handle)?,
+                Some(label),
+                wgpu::TextureFormat::Rgba8Unorm,
+            ),
+            MaterialSource::Procedural(surface) => {
+                Ok(self
+                    .texture_generator
+                    .generate_normals(&self.device, &self.queue, surface))
+            }
+            MaterialSource::Compressed { ktx2, fallback } => {
+                if self.supports_ktx2(ktx2)? {
+                    self.load_ktx2(ktx2, label)
+                } else {
+                    self.load_normals(&MaterialSource::Image(fallback), label)
+                }
+            }
+        }
+    }
+
+    /// Fetch the images of a material's asset sources in the background
+    ///
+    /// Returns whether all of them are done, successfully or not.
+    fn fetch_images(&mut self, textures: &MaterialTextures) -> bool {
+        let mut done = true;
+        for handle in textures.assets() {
+            if self.images.contains_key(handle.path) {
+                continue;
+            }
+            let pending = self
+                .pending_images
+                .entry(handle.path)
+                .or_insert_with(|| self.assets.spawn(handle));
+            match pending.poll() {
+                Some(result) => {
+                    self.pending_images.remove(handle.path);
+                    self.images.insert(handle.path, result);
+                }
+                None => done = false,
+            }
+        }
+        done
+    }
+
+    /// Drop the fetched images of a material once its textures are created
+    fn forget_images(&mut self, textures: &MaterialTextures) {
+        for handle in textures.assets() {
+            self.images.remove(handle.path);
+        }
+    }
+
+    /// Image of an asset source fetched by [`fetch_images`](Self::fetch_images)
+    fn image(&self, handle: &Handle<DynamicImage>) -> Result<&DynamicImage, DynError> {
+        match self.images.get(handle.path) {
+            Some(Ok(image)) => Ok(image),
+            Some(Err(error)) => Err(CustomError::from(error.to_string()).into()),
+            None => Err(CustomError::from(format!("{} wasn't fetched", handle.path)).into()),
+        }
+    }
+
+    /// Whether the device can sample a KTX2 container's format
+    fn supports_ktx2(&self, bytes: &[u8]) -> Result<bool, DynError> {
+        let format = Texture::ktx2_format(bytes)?;
+        Ok(self.device.features().contains(format.required_features()))
+    }
+
+    fn load_ktx2(&self, bytes: &[u8], label: &str) -> Result<Texture, DynError> {
+        Texture::from_ktx2(
+            &self.device,
+            &self.queue,
+            &self.texture_generator.mipmaps,
+            bytes,
+            Some(label),
+        )
+    }
+
+    /// Create a bind group for sampling a texture in the main pipeline
+    fn bind_texture(
+        &self,
+        texture: &Texture,
+        emissive: Option<&Texture>,
+        normal: Option<&Texture>,
+        reflectance: Reflectance,
+    ) -> Arc<wgpu::BindGroup> {
+        bind_material(
+            &self.device,
+            &self.bind_groups,
+            &self.texture_bind_group_layout,
+            &self.fallback_textures,
+            texture,
+            emissive,
+            normal,
+            reflectance,
+        )
+    }
+
     pub fn resize(&mut self, new_size: winit::dpi::PhysicalSize<u32>) {
         if new_size.width > 0 && new_size.height > 0 {
             self.size = new_size;
             self.config.width = new_size.width;
             self.config.height = new_size.height;
-            self.surface.configure(&self.device, &self.config);
-            // TODO: self.camera.aspect = self.config.width as f32 / self.config.height as f32;
-            // NEW!
+            match &mut self.target {
+                Target::Window { surface, .. } => surface.configure(&self.device, &self.config),
+                Target::Offscreen(texture) => {
+                    *texture = create_offscreen(&self.device, &self.config)
+                }
+            }
             self.depth_texture =
                 texture::Texture::create_depth_texture(&self.device, &self.config, "depth_texture");
+            self.tonemap.resize(&self.device, &self.config);
+            self.bloom
+                .resize(&self.device, &self.config, self.tonemap.view());
+            self.lens_flare
+                .resize(&self.device, &self.depth_texture.view);
+        }
+    }
+
+    /// Recreate everything on the gpu after the device was lost
+    ///
+    /// Everything is built anew like at startup from the window and the assets,
+    /// with meshes and materials being uploaded again from the copies kept in their registries.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn recover(&mut self, world: &World) -> Result<(), DynError> {
+        warn!("The device was lost, recreating it");
+        let window = match &self.target {
+            Target::Window { window, .. } => Some(Arc::clone(window)),
+            Target::Offscreen(_) => None,
+        };
+        // The window only takes one surface at a time, so the old one has to go first.
+        // Its placeholder can't be created on the lost device, which the error handler ignores.
+        self.target = Target::Offscreen(create_offscreen(&self.device, &self.config));
+        // Opened again in the next frame
+        world
+            .fetch_mut::<DetachedWindows>()
+            .opened
+            .extend(self.detached.drain(..).map(DetachedView::into_parts));
+
+        let instance = wgpu::Instance::new(Default::default());
+        let window = match window {
+            // # Safety
+            //
+            // See `Render::new`
+            Some(window) => Some((unsafe { instance.create_surface(window.as_ref()) }?, window)),
+            None => None,
+        };
+        let recorder = self.recorder.take();
+        *self = pollster::block_on(Self::with_target(instance, window, self.size, &self.assets))?;
+        self.recorder = recorder.map(|mut recorder| {
+            recorder.recreate(&self.device, &self.config);
+            recorder
+        });
+        world.fetch_mut::<MaterialRegistry>().unbind_all();
+        world.fetch_mut::<MeshRegistry>().reupload_all();
+        Ok(())
+    }
+
+    /// Configure the surface anew with the window's current size
+    fn reconfigure(&mut self) {
+        let size = match &self.target {
+            Target::Window { window, .. } => window.inner_size(),
+            Target::Offscreen(_) => self.size,
+        };
+        self.resize(size);
+    }
+
+    /// Camera bind group and viewport of the main and, while the screen is split, the second view
+    fn views(&self) -> Vec<(&BindGroup, [f32; 4])> {
+        let size = [self.config.width as f32, self.config.height as f32];
+        self.split.views(&self.camera_bind_group, size)
+    }
+
+    /// Draw the bodies which aren't culled
+    fn draw_bodies<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        camera: &'a BindGroup,
+        meshes: &'a MeshRegistry,
+        materials: &'a MaterialRegistry,
+    ) {
+        let draws = self
+            .instance_textures
+            .iter()
+            .zip(&self.instance_lods)
+            .zip(&self.instance_meshes)
+            .zip(&self.instance_emissive)
+            .map(|(((key, level), mesh), emissive)| (*key, *level, *mesh, *emissive));
+        let instances = &self.instance_buffer;
+        self.draw_instances(render_pass, camera, instances, draws, meshes, materials);
+    }
+
+    /// Draw the visible bodies of the [`Comparison`]
+    fn draw_compared<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        camera: &'a BindGroup,
+        meshes: &'a MeshRegistry,
+        materials: &'a MaterialRegistry,
+    ) {
+        let draws = self.compared.draws.iter().copied();
+        let instances = &self.compared.instance_buffer;
+        self.draw_instances(render_pass, camera, instances, draws, meshes, materials);
+    }
+
+    /// Draw instances with their texture, level of detail, mesh and whether they're emissive,
+    /// bucketed by pipeline to switch it only once
+    fn draw_instances<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        camera: &'a BindGroup,
+        instances: &'a wgpu::Buffer,
+        draws: impl Iterator<Item = (Option<TextureKey>, usize, Option<MeshHandle>, bool)>,
+        meshes: &'a MeshRegistry,
+        materials: &'a MaterialRegistry,
+    ) {
+        render_pass.set_bind_group(1, camera, &[]);
+        render_pass.set_bind_group(2, &self.light_bind_group, &[]);
+        render_pass.set_vertex_buffer(1, instances.slice(..));
+        self.sphere_lods.bind(render_pass);
+        let (emissive, lit): (Vec<_>, Vec<_>) =
+            draws.enumerate().partition(|(_, (.., emissive))| *emissive);
+        for (name, pipeline, draws) in [
+            ("Lit Bodies", &self.render_pipeline, lit),
+            ("Emissive Bodies", &self.emissive_pipeline, emissive),
+        ] {
+            if draws.is_empty() {
+                continue;
+            }
+            render_pass.insert_debug_marker(name);
+            let debug = self.debug_pipelines.get(self.debug_view);
+            render_pass.set_pipeline(debug.unwrap_or(pipeline));
+            for (index, (key, level, mesh, _)) in draws {
+                let bind_group = match key {
+                    Some(TextureKey::Handle(handle)) => materials.bind_group(handle),
+                    Some(key) => self
+                        .textures
+                        .get(&key)
+                        .map(|(.., bind_group)| bind_group.as_ref()),
+                    None => Some(self.diffuse_bind_group.as_ref()),
+                };
+                let index = index as u32;
+                render_pass.set_bind_group(
+                    0,
+                    bind_group.unwrap_or(&self.placeholder_bind_group),
+                    &[],
+                );
+                match mesh.and_then(|mesh| meshes.get(mesh)) {
+                    Some(mesh) => {
+                        mesh.draw(render_pass, index..index + 1);
+                        self.sphere_lods.bind(render_pass);
+                    }
+                    None => self.sphere_lods.draw(render_pass, level, index..index + 1),
+                }
+            }
         }
     }
 
-    pub fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let output = self.surface.get_current_texture()?;
-        let view = output
-            .texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
+    pub fn render(
+        &mut self,
+        meshes: &MeshRegistry,
+        materials: &MaterialRegistry,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let (output, view) = match &self.target {
+            Target::Window { surface, .. } => {
+                let output = surface.get_current_texture()?;
+                let view = output
+                    .texture
+                    .create_view(&wgpu::TextureViewDescriptor::default());
+                (Some(output), view)
+            }
+            Target::Offscreen(texture) => (
+                None,
+                texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            ),
+        };
 
         let mut encoder = self
             .device
             .create_command_encoder(&wgpu::CommandEncoderDescriptor {
                 label: Some("Render Encoder"),
             });
+        // Taken out to be usable while the other fields are borrowed
+        let mut timer = self.gpu_timer.take();
+        if let Some(timer) = &mut timer {
+            timer.begin(&mut encoder);
+        }
+        // Each stage is a debug group in gpu captures, ending the previous one
+        let mut current = None;
+        let mut stage = |encoder: &mut wgpu::CommandEncoder, next: Option<&'static str>| {
+            if let Some(name) = current.take() {
+                encoder.pop_debug_group();
+                if let Some(timer) = &mut timer {
+                    timer.end(encoder, name);
+                }
+            }
+            if let Some(name) = next {
+                encoder.push_debug_group(name);
+            }
+            current = next;
+        };
+
+        stage(&mut encoder, Some("Shadows"));
+        self.shadow_map.render(
+            &mut encoder,
+            &self.vertex_buffer,
+            &self.index_buffer,
+            self.num_indices,
+            &self.instance_buffer,
+            self.instances.len() as u32,
+        );
+        stage(&mut encoder, Some("Picking"));
+
+        if let (Some(id_buffer), Some(pixel)) = (&mut self.id_buffer, self.pending_pick) {
+            if id_buffer.is_idle() {
+                id_buffer.render(
+                    &self.device,
+                    &self.queue,
+                    &self.config,
+                    &mut encoder,
+                    &self.camera_bind_group,
+                    (&self.vertex_buffer, &self.index_buffer, self.num_indices),
+                    &self.instance_buffer,
+                    &self.instance_ids,
+                    pixel,
+                );
+                self.pending_pick = None;
+            }
+        }
+        stage(&mut encoder, Some("Particles"));
+
+        if let Some(particle_pipeline) = &self.particle_pipeline {
+            particle_pipeline.simulate(&mut encoder);
+        }
+        stage(&mut encoder, Some("Opaque"));
 
         {
             let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
                 label: Some("Render Pass"),
                 color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                    view: &view,
+                    view: self.tonemap.view(),
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: wgpu::LoadOp::Clear(BLACK),
@@ -373,24 +2156,144 @@ impl Render {
                 depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                     view: &self.depth_texture.view,
                     depth_ops: Some(wgpu::Operations {
-                        load: wgpu::LoadOp::Clear(1.0),
+                        load: wgpu::LoadOp::Clear(Texture::DEPTH_CLEAR),
                         store: true,
                     }),
                     stencil_ops: None,
                 }),
             });
 
-            render_pass.set_pipeline(&self.render_pipeline);
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_bind_group(1, &self.camera_bind_group, &[]);
-            render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.instance_buffer.slice(..));
-            render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
-            render_pass.draw_indexed(0..self.num_indices, 0, 0..self.instances.len() as u32);
+            for (view, (camera, [x, y, width, height])) in self.views().into_iter().enumerate() {
+                render_pass.push_debug_group(VIEW_NAMES[view]);
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                if view > 0 && self.compared.active {
+                    self.draw_compared(&mut render_pass, camera, meshes, materials);
+                    render_pass.insert_debug_marker("Skybox");
+                    self.skybox.draw(&mut render_pass, view);
+                    render_pass.insert_debug_marker("Impostors");
+                    self.compared.impostors.draw(&mut render_pass, camera);
+                    render_pass.pop_debug_group();
+                    continue;
+                }
+                self.draw_bodies(&mut render_pass, camera, meshes, materials);
+                render_pass.insert_debug_marker("Skybox");
+                self.skybox.draw(&mut render_pass, view);
+                // After the skybox, which would cover them since they don't write depth
+                render_pass.insert_debug_marker("Impostors");
+                self.impostor_pipeline.draw(&mut render_pass, camera);
+                if let Some(particle_pipeline) = &self.particle_pipeline {
+                    render_pass.insert_debug_marker("Particles");
+                    particle_pipeline.draw(&mut render_pass, camera);
+                }
+                render_pass.insert_debug_marker("Lines");
+                self.line_pipeline.draw(&mut render_pass, camera);
+                render_pass.pop_debug_group();
+            }
+        }
+        stage(&mut encoder, Some("Transparent"));
+
+        {
+            // Depth is read-only, so blended geometry can't hide what's behind it
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Transparent Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: self.tonemap.view(),
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &self.depth_texture.view,
+                    depth_ops: None,
+                    stencil_ops: None,
+                }),
+            });
+
+            let layers = self.transparent_layers();
+            for (view, ((camera, [x, y, width, height]), draws)) in self
+                .views()
+                .into_iter()
+                .zip(&self.transparent_draws)
+                .enumerate()
+            {
+                render_pass.insert_debug_marker(VIEW_NAMES[view]);
+                render_pass.set_viewport(x, y, width, height, 0.0, 1.0);
+                transparent::draw(
+                    &mut render_pass,
+                    &layers,
+                    draws,
+                    &Shared {
+                        camera,
+                        light: &self.light_bind_group,
+                        sphere: (&self.vertex_buffer, &self.index_buffer, self.num_indices),
+                    },
+                );
+            }
+        }
+        stage(&mut encoder, Some("Bloom"));
+
+        self.lens_flare.draw(&mut encoder, self.tonemap.view());
+        self.bloom.draw(&mut encoder, self.tonemap.view());
+        stage(&mut encoder, Some("Insets"));
+        if let Some(mut render_pass) = self.inset.begin(&mut encoder) {
+            self.draw_bodies(&mut render_pass, self.inset.camera(), meshes, materials);
+        }
+        self.inset.composite(&mut encoder, &self.tonemap);
+        self.minimap.draw(&mut encoder);
+        self.minimap.composite(&mut encoder, &self.tonemap);
+        stage(&mut encoder, Some("Detached"));
+        let mut outputs = Vec::new();
+        for detached in &self.detached {
+            if let Some(mut render_pass) = detached.begin(&mut encoder) {
+                self.draw_bodies(&mut render_pass, detached.camera(), meshes, materials);
+                self.impostor_pipeline
+                    .draw(&mut render_pass, detached.camera());
+                self.line_pipeline.draw(&mut render_pass, detached.camera());
+            }
+            outputs.extend(detached.finish(&self.device, &mut encoder));
+        }
+        stage(&mut encoder, Some("Tonemap"));
+        self.tonemap.draw(&mut encoder, &view);
+        self.axes.draw(&mut encoder, &view);
+        self.text_renderer.draw(&mut encoder, &view);
+        if let Some(recorder) = &mut self.recorder {
+            stage(&mut encoder, Some("Recording"));
+            let target = recorder.target(&self.device, &self.config);
+            self.tonemap.draw(&mut encoder, target);
+            self.text_renderer.draw(&mut encoder, target);
+            recorder.copy(&mut encoder);
+        }
+        stage(&mut encoder, None);
+        if let Some(timer) = &mut timer {
+            timer.resolve(&mut encoder);
         }
 
-        self.queue.submit([encoder.finish()]);
-        output.present();
+        let commands = encoder.finish();
+        self.gpu_timer = timer;
+        // Submitting to a lost device panics, it's recreated at the start of the next frame instead
+        if self.lost.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        self.queue.submit([commands]);
+        if let Some(timer) = &mut self.gpu_timer {
+            timer.request();
+        }
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.save(&self.device) {
+                warn!("Failed to save recorded frame: {error}");
+            }
+        }
+        if let Some(id_buffer) = &mut self.id_buffer {
+            id_buffer.request();
+        }
+        if let Some(output) = output {
+            output.present();
+        }
+        for output in outputs {
+            output.present();
+        }
 
         Ok(())
     }
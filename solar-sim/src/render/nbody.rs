@@ -0,0 +1,329 @@
+//! Gravity and integration of every body on the GPU, see [`Gpu`](crate::physics::integrator::Gpu)
+
+use std::mem::size_of;
+use std::sync::mpsc::{channel, Receiver};
+
+use cgmath::{Point3, Vector3, Zero};
+use log::warn;
+use specs::join::MaybeJoin;
+use specs::{Entities, Entity, Join, ReadStorage, SystemData, World, Write, WriteStorage};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferAddress, BufferAsyncError, BufferBindingType,
+    BufferDescriptor, BufferUsages, ComputePassDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, Device, PipelineLayoutDescriptor, Queue, ShaderModuleDescriptor,
+    ShaderSource, ShaderStages,
+};
+
+use crate::physics::barycenter::Barycenter;
+use crate::physics::integrator::GpuSteps;
+use crate::physics::{Acceleration, Mass, Position, Velocity};
+
+/// Threads per workgroup, must match the shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Bodies the buffers have room for at first
+const INITIAL_CAPACITY: usize = 1024;
+
+/// A body's state as stored in the storage buffer
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct BodyRaw {
+    /// Relative to the bodies' [`Barycenter`], `w` is the body's mass
+    position: [f32; 4],
+    /// `w` is one for bodies with a [`Velocity`]
+    velocity: [f32; 4],
+    /// `w` is one for bodies attracted by the others, i.e. with a [`Mass`] and [`Acceleration`]
+    acceleration: [f32; 4],
+    /// Distance moved since the upload
+    moved: [f32; 4],
+    /// Change of velocity since the upload
+    kicked: [f32; 4],
+}
+
+/// A raw vector's `xyz` in double precision
+fn vector(raw: [f32; 4]) -> Vector3<f64> {
+    Vector3::new(raw[0], raw[1], raw[2]).cast().unwrap()
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StepUniform {
+    dt: f32,
+    count: u32,
+    _padding: [u32; 2],
+}
+
+/// Where the simulated bodies are in their way back to the cpu
+enum Readback {
+    Idle,
+
+    /// Waiting for the readback buffer to be mapped,
+    /// holding the entities in the order they were uploaded with their uploaded position and velocity
+    Mapping(
+        Vec<(Entity, Point3<f64>, Vector3<f64>)>,
+        Receiver<Result<(), BufferAsyncError>>,
+    ),
+}
+
+/// Advances the bodies by the [`GpuSteps`] in a compute pass
+///
+/// Every body with a [`Position`] is uploaded to a storage buffer,
+/// advanced by leapfrog steps summing the gravity of every pair of bodies
+/// and read back into the components once the buffer is mapped, usually in the next frame.
+/// Steps queued in the meantime are run on the read back state.
+/// The shader only works in single precision, so positions are uploaded relative to the bodies' [`Barycenter`]
+/// and the distance moved and change of velocity are accumulated apart from them,
+/// to be added to the uploaded [`Position`] and [`Velocity`] in double precision.
+/// Otherwise a step's change would be rounded away at the outer planets.
+pub struct NBodyPipeline {
+    drift: ComputePipeline,
+    kick: ComputePipeline,
+    layout: BindGroupLayout,
+    uniform: Buffer,
+    bodies: Buffer,
+    readback: Buffer,
+    bind_group: BindGroup,
+    capacity: usize,
+    state: Readback,
+}
+
+impl NBodyPipeline {
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("N-Body Shader"),
+            source: ShaderSource::Wgsl(include_str!("../nbody.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("nbody_bind_group_layout"),
+        });
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("N-Body Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = |label, entry_point| {
+            device.create_compute_pipeline(&ComputePipelineDescriptor {
+                label: Some(label),
+                layout: Some(&pipeline_layout),
+                module: &shader,
+                entry_point,
+            })
+        };
+        let drift = pipeline("N-Body Drift Pipeline", "cs_drift");
+        let kick = pipeline("N-Body Kick Pipeline", "cs_kick");
+
+        let uniform = device.create_buffer(&BufferDescriptor {
+            label: Some("N-Body Step Buffer"),
+            size: size_of::<StepUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let (bodies, readback, bind_group) =
+            Self::create_buffers(device, &layout, &uniform, INITIAL_CAPACITY);
+        Self {
+            drift,
+            kick,
+            layout,
+            uniform,
+            bodies,
+            readback,
+            bind_group,
+            capacity: INITIAL_CAPACITY,
+            state: Readback::Idle,
+        }
+    }
+
+    fn create_buffers(
+        device: &Device,
+        layout: &BindGroupLayout,
+        uniform: &Buffer,
+        capacity: usize,
+    ) -> (Buffer, Buffer, BindGroup) {
+        let size = (capacity * size_of::<BodyRaw>()) as BufferAddress;
+        let bodies = device.create_buffer(&BufferDescriptor {
+            label: Some("N-Body Buffer"),
+            size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback = device.create_buffer(&BufferDescriptor {
+            label: Some("N-Body Readback Buffer"),
+            size,
+            usage: BufferUsages::MAP_READ | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: bodies.as_entire_binding(),
+                },
+            ],
+            label: Some("nbody_bind_group"),
+        });
+        (bodies, readback, bind_group)
+    }
+
+    /// Write the last steps' results back into the components and start running the queued ones
+    pub fn run(&mut self, device: &Device, queue: &Queue, world: &World) {
+        let (entities, mut steps, mass, mut pos, mut vel, mut acc) = <(
+            Entities,
+            Write<GpuSteps>,
+            ReadStorage<Mass>,
+            WriteStorage<Position>,
+            WriteStorage<Velocity>,
+            WriteStorage<Acceleration>,
+        )>::fetch(world);
+
+        if let Readback::Mapping(uploaded, receiver) = &self.state {
+            device.poll(wgpu::Maintain::Poll);
+            let Ok(result) = receiver.try_recv() else {
+                return;
+            };
+            match result {
+                Ok(()) => {
+                    let size = (uploaded.len() * size_of::<BodyRaw>()) as BufferAddress;
+                    {
+                        let view = self.readback.slice(..size).get_mapped_range();
+                        let bodies: &[BodyRaw] = bytemuck::cast_slice(&view);
+                        for ((entity, position, velocity), body) in uploaded.iter().zip(bodies) {
+                            if !entities.is_alive(*entity) {
+                                continue;
+                            }
+                            if let Some(pos) = pos.get_mut(*entity) {
+                                pos.0 = position + vector(body.moved);
+                            }
+                            if let Some(vel) = vel.get_mut(*entity) {
+                                vel.0 = velocity + vector(body.kicked);
+                            }
+                            if let Some(acc) = acc.get_mut(*entity) {
+                                acc.0 = vector(body.acceleration);
+                            }
+                        }
+                    }
+                    self.readback.unmap();
+                }
+                Err(error) => warn!("Failed to read back the bodies: {error}"),
+            }
+            self.state = Readback::Idle;
+        }
+
+        if steps.steps == 0 {
+            return;
+        }
+        let GpuSteps { steps, seconds } = std::mem::take(&mut *steps);
+        let flag = |set: bool| f32::from(u8::from(set));
+        let joined: Vec<_> = (
+            &entities,
+            &pos,
+            MaybeJoin(&vel),
+            MaybeJoin(&mass),
+            MaybeJoin(&acc),
+        )
+            .join()
+            .collect();
+        if joined.is_empty() {
+            return;
+        }
+        let barycenter =
+            Barycenter::of(joined.iter().filter_map(|(_, pos, _, mass, _)| {
+                Some((mass.as_ref()?.0, pos.0, Vector3::zero()))
+            }));
+        let bodies: Vec<_> = joined
+            .iter()
+            .map(|(_, pos, vel, mass, acc)| BodyRaw {
+                position: (pos.0 - barycenter.position)
+                    .map(|c| c as f32)
+                    .extend(mass.map_or(0.0, |mass| mass.0))
+                    .into(),
+                velocity: vel
+                    .map_or(Vector3::zero(), |vel| vel.0.map(|c| c as f32))
+                    .extend(flag(vel.is_some()))
+                    .into(),
+                acceleration: acc
+                    .map_or(Vector3::zero(), |acc| acc.0.map(|c| c as f32))
+                    .extend(flag(mass.is_some() && acc.is_some()))
+                    .into(),
+                moved: [0.0; 4],
+                kicked: [0.0; 4],
+            })
+            .collect();
+        let uploaded = joined
+            .into_iter()
+            .map(|(entity, pos, vel, ..)| (entity, pos.0, vel.map_or(Vector3::zero(), |vel| vel.0)))
+            .collect();
+
+        if bodies.len() > self.capacity {
+            self.capacity = bodies.len().next_power_of_two();
+            (self.bodies, self.readback, self.bind_group) =
+                Self::create_buffers(device, &self.layout, &self.uniform, self.capacity);
+        }
+        let count = bodies.len() as u32;
+        queue.write_buffer(&self.bodies, 0, bytemuck::cast_slice(&bodies));
+        queue.write_buffer(
+            &self.uniform,
+            0,
+            bytemuck::cast_slice(&[StepUniform {
+                dt: (seconds / f64::from(steps)) as f32,
+                count,
+                _padding: [0; 2],
+            }]),
+        );
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("N-Body Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("N-Body Pass"),
+            });
+            compute_pass.set_bind_group(0, &self.bind_group, &[]);
+            let workgroups = count.div_ceil(WORKGROUP_SIZE);
+            for _ in 0..steps {
+                compute_pass.set_pipeline(&self.drift);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+                compute_pass.set_pipeline(&self.kick);
+                compute_pass.dispatch_workgroups(workgroups, 1, 1);
+            }
+        }
+        let size = (bodies.len() * size_of::<BodyRaw>()) as BufferAddress;
+        encoder.copy_buffer_to_buffer(&self.bodies, 0, &self.readback, 0, size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = channel();
+        self.readback
+            .slice(..size)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.state = Readback::Mapping(uploaded, receiver);
+    }
+}
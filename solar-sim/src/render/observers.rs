@@ -0,0 +1,83 @@
+//! Cameras and selections of the observers watching a hosted simulation
+//!
+//! Only available on native builds
+
+use cgmath::Point3;
+use specs::{Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::net::{RemoteCameras, RemoteSelections};
+use crate::physics::Position;
+use crate::render::camera::{Camera, Projection};
+use crate::render::label::project;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::text::{Align, Text, TextQueue};
+use crate::render::SCALE;
+
+/// Layer in [`Lines`] the observers are drawn to
+const LAYER: &str = "observers";
+
+/// Color of the observers' lines and labels
+const COLOR: [f32; 3] = [0.4, 1.0, 0.6];
+
+/// Length in render space of the line pointing where an observer looks
+const VIEW: f32 = 2.0;
+
+/// Height of the labels in pixels
+const SIZE: f32 = 12.0;
+
+/// System drawing every observer's camera as a line along its view direction labeled with its address,
+/// and a line from the camera to the body it selected
+///
+/// Updates [`Lines`] and [`TextQueue`] resources
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RemoteObservers;
+
+impl<'a> System<'a> for RemoteObservers {
+    type SystemData = (
+        Write<'a, Lines>,
+        Write<'a, TextQueue>,
+        Read<'a, RemoteCameras>,
+        Read<'a, RemoteSelections>,
+        Read<'a, Camera>,
+        ReadExpect<'a, Projection>,
+        ReadStorage<'a, Position>,
+    );
+
+    fn run(
+        &mut self,
+        (mut lines, mut texts, cameras, selections, camera, projection, pos): Self::SystemData,
+    ) {
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let height = projection.height as f32;
+        let screen = [projection.aspect * height, height];
+        let vertex = |position: Point3<f64>| LineVertex {
+            position,
+            color: COLOR,
+        };
+        let meters = |render: Point3<f32>| render.cast::<f64>().unwrap() * f64::from(SCALE);
+
+        let mut vertices = Vec::new();
+        for (addr, remote) in &cameras.0 {
+            let eye = meters(remote.position);
+            vertices.push(vertex(eye));
+            vertices.push(vertex(meters(remote.position + remote.direction() * VIEW)));
+            if let Some(selected) = selections.0.get(addr).and_then(|&entity| pos.get(entity)) {
+                vertices.push(vertex(eye));
+                vertices.push(vertex(selected.0));
+            }
+
+            let Some(position) = project(view_proj, screen, remote.position) else {
+                continue;
+            };
+            let [r, g, b] = COLOR;
+            texts.push(Text {
+                content: addr.to_string(),
+                position,
+                size: SIZE,
+                color: [r, g, b, 1.0],
+                align: Align::Left,
+            });
+        }
+        lines.set(LAYER, vertices);
+    }
+}
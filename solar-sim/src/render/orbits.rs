@@ -0,0 +1,186 @@
+//! Orbit lines with their apoapsis and periapsis, and a panel describing the selected body's orbit
+
+use std::f64::consts::PI;
+
+use cgmath::{Deg, Point3, Rad, Vector3};
+use specs::{Join, Read, ReadExpect, ReadStorage, System, Write};
+
+use crate::control::Selection;
+use crate::physics::orbit::OrbitalElements;
+use crate::physics::{Name, Position, Radius};
+use crate::render::camera::{Camera, Projection};
+use crate::render::label::project;
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::text::{Align, Text, TextQueue};
+use crate::render::SCALE;
+
+/// Layer in [`Lines`] the orbits are drawn to
+const LAYER: &str = "orbits";
+
+/// Number of lines each orbit is made of
+const SEGMENTS: usize = 128;
+
+/// Length of a marker's arms relative to the orbit's periapsis distance
+const MARKER: f64 = 0.03;
+
+/// Color of the orbit lines
+const ORBIT: [f32; 3] = [0.3, 0.35, 0.5];
+
+/// Color of the apoapsis markers and labels
+const APOAPSIS: [f32; 3] = [0.4, 0.6, 1.0];
+
+/// Color of the periapsis markers and labels
+const PERIAPSIS: [f32; 3] = [1.0, 0.7, 0.3];
+
+/// Height of the marker labels in pixels
+const SIZE: f32 = 12.0;
+
+/// Height of the panel's text in pixels
+const PANEL_SIZE: f32 = 16.0;
+
+/// Human readable distance
+fn kilometers(meters: f64) -> String {
+    let km = meters / 1000.0;
+    if km.abs() >= 1e6 {
+        format!("{:.2} million km", km / 1e6)
+    } else {
+        format!("{km:.0} km")
+    }
+}
+
+/// Human readable duration
+fn days(seconds: f64) -> String {
+    let days = seconds / (24.0 * 60.0 * 60.0);
+    if days >= 365.25 {
+        format!("{:.2} years", days / 365.25)
+    } else {
+        format!("{days:.2} days")
+    }
+}
+
+/// System drawing every body's [`OrbitalElements`] as a line around its primary
+/// with markers at the apoapsis and periapsis labeled with their altitude above the primary's surface
+///
+/// Escape orbits only have a periapsis.
+/// The [`Selection`]'s orbit is described by a panel in the bottom left corner.
+///
+/// Updates [`Lines`] and [`TextQueue`] resources
+#[derive(Copy, Clone, Debug, Default)]
+pub struct OrbitLines;
+
+impl<'a> System<'a> for OrbitLines {
+    type SystemData = (
+        Write<'a, Lines>,
+        Write<'a, TextQueue>,
+        Read<'a, Selection>,
+        Read<'a, Camera>,
+        ReadExpect<'a, Projection>,
+        ReadStorage<'a, OrbitalElements>,
+        ReadStorage<'a, Name>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Radius>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut lines,
+            mut texts,
+            selection,
+            camera,
+            projection,
+            elements,
+            name,
+            pos,
+            radius,
+        ): Self::SystemData,
+    ) {
+        let view_proj = projection.reversed_z() * camera.matrix();
+        let height = projection.height as f32;
+        let screen = [projection.aspect * height, height];
+
+        let mut vertices = Vec::new();
+        for orbit in elements.join() {
+            let Some(primary) = pos.get(orbit.primary).map(|pos| pos.0) else {
+                continue;
+            };
+            let surface = radius
+                .get(orbit.primary)
+                .map_or(0.0, |radius| f64::from(radius.0));
+            let point = |true_anomaly: f64| primary + orbit.at(Rad(true_anomaly)).state().0;
+            let vertex = |position: Point3<f64>, color| LineVertex { position, color };
+
+            let path: Vec<_> = orbit.path(SEGMENTS).collect();
+            for pair in path.windows(2) {
+                for r in pair {
+                    vertices.push(vertex(primary + r, ORBIT));
+                }
+            }
+
+            let arm = orbit.periapsis_distance() * MARKER;
+            let mut apsides = vec![("Pe", 0.0, orbit.periapsis_distance(), PERIAPSIS)];
+            if let Some(distance) = orbit.apoapsis_distance() {
+                apsides.push(("Ap", PI, distance, APOAPSIS));
+            }
+            for (label, true_anomaly, distance, color) in apsides {
+                let center = point(true_anomaly);
+                for axis in [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()] {
+                    vertices.push(vertex(center - axis * arm, color));
+                    vertices.push(vertex(center + axis * arm, color));
+                }
+
+                let center: Point3<f32> = center.map(|c| c as f32);
+                let Some(position) = project(view_proj, screen, center / SCALE) else {
+                    continue;
+                };
+                let [r, g, b] = color;
+                texts.push(Text {
+                    content: format!("{label} {}", kilometers(distance - surface)),
+                    position,
+                    size: SIZE,
+                    color: [r, g, b, 1.0],
+                    align: Align::Left,
+                });
+            }
+        }
+        lines.set(LAYER, vertices);
+
+        let Some((entity, orbit)) = selection
+            .0
+            .and_then(|entity| Some((entity, elements.get(entity)?)))
+        else {
+            return;
+        };
+        let name_of = |entity| name.get(entity).map_or("unnamed", |name| name.0.as_str());
+        let surface = radius
+            .get(orbit.primary)
+            .map_or(0.0, |radius| f64::from(radius.0));
+        let mut content = format!(
+            "{}\norbiting {}\nPe: {}",
+            name_of(entity),
+            name_of(orbit.primary),
+            kilometers(orbit.periapsis_distance() - surface),
+        );
+        match (orbit.apoapsis_distance(), orbit.period()) {
+            (Some(apoapsis), Some(period)) => {
+                content += &format!(
+                    "\nAp: {}\nperiod: {}",
+                    kilometers(apoapsis - surface),
+                    days(period)
+                );
+            }
+            _ => content += "\nescaping",
+        }
+        content += &format!(
+            "\neccentricity: {:.4}\ninclination: {:.2}°",
+            orbit.eccentricity,
+            Deg::from(orbit.inclination).0,
+        );
+        let rows = content.lines().count() as f32;
+        texts.push(Text::new(
+            content,
+            [8.0, height - rows * PANEL_SIZE],
+            PANEL_SIZE,
+        ));
+    }
+}
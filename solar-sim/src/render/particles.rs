@@ -0,0 +1,536 @@
+//! Swarms of small bodies simulated and drawn entirely on the GPU
+
+use std::collections::HashMap;
+use std::f32::consts::TAU;
+use std::mem::size_of;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use specs::{Component, Entity, VecStorage};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
+    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingType, BlendComponent, BlendFactor,
+    BlendOperation, BlendState, Buffer, BufferAddress, BufferBindingType, BufferUsages,
+    ColorTargetState, CommandEncoder, ComputePassDescriptor, ComputePipeline,
+    ComputePipelineDescriptor, DepthStencilState, Device, FragmentState, PipelineLayout,
+    PipelineLayoutDescriptor, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModule, ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::render::texture::Texture;
+
+/// Threads per workgroup of the simulation, must match the shader
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Radius in pixels of a single particle
+const PARTICLE_RADIUS: f32 = 1.0;
+
+/// Color of a single particle in a comet's dust tail
+const DUST_COLOR: [f32; 3] = [0.05, 0.045, 0.035];
+
+/// Color of a single particle in a comet's ion tail
+const ION_COLOR: [f32; 3] = [0.015, 0.025, 0.05];
+
+/// Belt component of a body
+///
+/// Surrounds the body with particles orbiting it.
+/// They only feel the body's gravity and are invisible to the rest of the simulation,
+/// which allows tens of thousands of them.
+#[derive(Copy, Clone, Debug, PartialEq, Component)]
+#[storage(VecStorage)]
+pub struct Belt {
+    pub count: u32,
+
+    /// Smallest distance to the body's center in meters
+    pub inner: f32,
+
+    /// Largest distance to the body's center in meters
+    pub outer: f32,
+
+    /// Largest distance to the orbital plane in meters
+    pub thickness: f32,
+
+    pub seed: u64,
+    pub color: [f32; 3],
+}
+
+impl Belt {
+    /// Place the particles on random, roughly circular orbits around a body with the gravitational parameter `gm`
+    fn particles(&self, gm: f32) -> Vec<ParticleRaw> {
+        // xorshift64
+        let mut rng = self.seed | 1;
+        let mut uniform = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32
+        };
+        (0..self.count)
+            .map(|_| {
+                let radius = self.inner + (self.outer - self.inner) * uniform();
+                let (sin, cos) = (uniform() * TAU).sin_cos();
+                let height = (uniform() * 2.0 - 1.0) * self.thickness;
+                // A few percent off the circular speed to spread the orbits' eccentricities
+                let speed = (gm / radius).sqrt() * (0.97 + 0.06 * uniform());
+                ParticleRaw {
+                    position: [cos * radius, height, sin * radius, 0.0],
+                    velocity: [-sin * speed, 0.0, cos * speed, 0.0],
+                }
+            })
+            .collect()
+    }
+}
+
+/// Comet component of a body
+///
+/// Makes the body emit a dust and an ion tail pointing away from the light source.
+/// The dust keeps the comet's velocity, so its tail curves along the orbit,
+/// while the ions are blown straight away from the light.
+#[derive(Copy, Clone, Debug, PartialEq, Component)]
+#[storage(VecStorage)]
+pub struct Comet {
+    /// Particles in each of the tails
+    pub count: u32,
+
+    /// Seconds a particle lives after being emitted, fading out over it
+    pub lifetime: f32,
+
+    /// Speed in meters per second the dust is pushed away from the light with
+    pub dust_speed: f32,
+
+    /// Speed in meters per second the ions are blown away from the light with
+    pub ion_speed: f32,
+}
+
+impl Comet {
+    /// Particles waiting to be emitted one after another over the first lifetime
+    fn particles(&self) -> Vec<ParticleRaw> {
+        (0..self.count)
+            .map(|index| ParticleRaw {
+                position: [
+                    0.0,
+                    0.0,
+                    0.0,
+                    -self.lifetime * index as f32 / self.count as f32,
+                ],
+                velocity: [0.0; 4],
+            })
+            .collect()
+    }
+}
+
+/// A particle's state relative to the swarm's center in meters
+///
+/// The position's `w` is the seconds since the particle has been emitted,
+/// it is negative while the particle is waiting for its first emission.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ParticleRaw {
+    position: [f32; 4],
+    velocity: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
+struct SwarmUniform {
+    /// The swarm's center in render space
+    center: [f32; 3],
+    /// The center's gravitational parameter
+    gm: f32,
+    color: [f32; 3],
+    /// Simulated seconds to advance the particles by
+    dt: f32,
+    /// Half of a particle's width and height in normalized device coordinates
+    size: [f32; 2],
+    count: u32,
+    /// Counter to vary the random numbers between frames
+    frame: u32,
+    /// Where particles are emitted relative to the center in meters
+    emitter: [f32; 3],
+    /// Seconds a particle lives, zero for immortal ones
+    lifetime: f32,
+    /// Direction particles are emitted in
+    direction: [f32; 3],
+    /// Speed particles are emitted with along the direction
+    speed: f32,
+    /// Velocity added to every emitted particle
+    velocity: [f32; 3],
+    /// Random deviation from the direction relative to the speed
+    spread: f32,
+}
+
+/// Swarms belonging to a single entity
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Swarm {
+    Belt,
+    Dust,
+    Ion,
+}
+
+/// Component a swarm was created from, to recreate it when it changes
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Source {
+    Belt(Belt),
+    Comet(Comet),
+}
+
+/// Buffers of a single swarm
+struct SwarmState {
+    source: Source,
+    count: u32,
+    uniform: Buffer,
+    particles: Buffer,
+    simulate: BindGroup,
+    draw: BindGroup,
+}
+
+/// Advances and draws every body's [`Belt`] and [`Comet`] tails
+///
+/// A compute pass integrates each particle in a storage buffer,
+/// which the render pass then reads as instances of a camera facing sprite.
+///
+/// Each frame starts with [`begin`](Self::begin) followed by the `update_*` methods.
+pub struct ParticlePipeline {
+    orbit: ComputePipeline,
+    tail: ComputePipeline,
+    draw: RenderPipeline,
+    simulate_layout: BindGroupLayout,
+    draw_layout: BindGroupLayout,
+    swarms: HashMap<(Entity, Swarm), SwarmState>,
+    /// Swarms updated in the current frame
+    order: Vec<(Entity, Swarm)>,
+    size: [f32; 2],
+    dt: f32,
+    frame: u32,
+}
+
+impl ParticlePipeline {
+    pub fn new(device: &Device, format: TextureFormat, camera_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Particle Shader"),
+            source: ShaderSource::Wgsl(include_str!("../particles.wgsl").into()),
+        });
+
+        let uniform_entry = BindGroupLayoutEntry {
+            binding: 0,
+            visibility: ShaderStages::COMPUTE | ShaderStages::VERTEX,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let simulate_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                uniform_entry,
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+            label: Some("particle_simulate_bind_group_layout"),
+        });
+        // The particles are read as a vertex buffer while drawing,
+        // which mustn't be bound as a writable storage buffer at the same time
+        let draw_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[uniform_entry],
+            label: Some("particle_draw_bind_group_layout"),
+        });
+
+        let simulate_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Particle Simulation Pipeline Layout"),
+            bind_group_layouts: &[&simulate_layout],
+            push_constant_ranges: &[],
+        });
+        let orbit = Self::compute_pipeline(
+            device,
+            &simulate_pipeline_layout,
+            &shader,
+            "Particle Orbit Pipeline",
+            "cs_orbit",
+        );
+        let tail = Self::compute_pipeline(
+            device,
+            &simulate_pipeline_layout,
+            &shader,
+            "Particle Tail Pipeline",
+            "cs_tail",
+        );
+
+        let additive = BlendComponent {
+            src_factor: BlendFactor::One,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let draw = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Particle Pipeline"),
+            layout: Some(&device.create_pipeline_layout(&PipelineLayoutDescriptor {
+                label: Some("Particle Pipeline Layout"),
+                bind_group_layouts: &[camera_layout, &draw_layout],
+                push_constant_ranges: &[],
+            })),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<ParticleRaw>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![0 => Float32x4],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState {
+                        color: additive,
+                        alpha: additive,
+                    }),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        Self {
+            orbit,
+            tail,
+            draw,
+            simulate_layout,
+            draw_layout,
+            swarms: HashMap::new(),
+            order: Vec::new(),
+            size: [1.0; 2],
+            dt: 0.0,
+            frame: 0,
+        }
+    }
+
+    fn compute_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        label: &str,
+        entry_point: &str,
+    ) -> ComputePipeline {
+        device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(layout),
+            module: shader,
+            entry_point,
+        })
+    }
+
+    /// Start a new frame, dropping the swarms which weren't updated in the last one
+    ///
+    /// `size` is the screen's width and height in pixels and `dt` the simulated seconds since the last frame.
+    pub fn begin(&mut self, size: [f32; 2], dt: f32) {
+        self.swarms.retain(|key, _| self.order.contains(key));
+        self.order.clear();
+        self.size = size;
+        self.dt = dt;
+        self.frame = self.frame.wrapping_add(1);
+    }
+
+    /// Upload the belts of the current frame
+    ///
+    /// `belts` yields each belt with its body's position in render space and gravitational parameter.
+    pub fn update_belts<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        belts: impl Iterator<Item = (Entity, &'a Belt, Point3<f32>, f32)>,
+    ) {
+        for (entity, belt, center, gm) in belts {
+            let uniform = SwarmUniform {
+                center: center.into(),
+                gm,
+                color: belt.color,
+                count: belt.count,
+                ..Default::default()
+            };
+            self.upload(
+                device,
+                queue,
+                (entity, Swarm::Belt),
+                Source::Belt(*belt),
+                uniform,
+                || belt.particles(gm),
+            );
+        }
+    }
+
+    /// Upload the comets of the current frame
+    ///
+    /// `comets` yields each comet with its body's offset from the light and velocity in meters,
+    /// `light` is the light source's position in render space the tails point away from.
+    pub fn update_comets<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        light: Point3<f32>,
+        comets: impl Iterator<Item = (Entity, &'a Comet, Vector3<f32>, Vector3<f32>)>,
+    ) {
+        for (entity, comet, emitter, velocity) in comets {
+            let tail = SwarmUniform {
+                center: light.into(),
+                count: comet.count,
+                emitter: emitter.into(),
+                lifetime: comet.lifetime,
+                direction: emitter.normalize().into(),
+                ..Default::default()
+            };
+            let dust = SwarmUniform {
+                color: DUST_COLOR,
+                speed: comet.dust_speed,
+                velocity: velocity.into(),
+                spread: 0.3,
+                ..tail
+            };
+            let ion = SwarmUniform {
+                color: ION_COLOR,
+                speed: comet.ion_speed,
+                spread: 0.03,
+                ..tail
+            };
+            for (swarm, uniform) in [(Swarm::Dust, dust), (Swarm::Ion, ion)] {
+                self.upload(
+                    device,
+                    queue,
+                    (entity, swarm),
+                    Source::Comet(*comet),
+                    uniform,
+                    || comet.particles(),
+                );
+            }
+        }
+    }
+
+    /// Write a swarm's uniform, (re)creating its buffers if its source changed
+    fn upload(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        key: (Entity, Swarm),
+        source: Source,
+        uniform: SwarmUniform,
+        particles: impl FnOnce() -> Vec<ParticleRaw>,
+    ) {
+        if self.swarms.get(&key).map(|state| state.source) != Some(source) {
+            let state = self.create_state(device, source, uniform.count, &particles());
+            self.swarms.insert(key, state);
+        }
+        let uniform = SwarmUniform {
+            dt: self.dt,
+            size: self.size.map(|pixels| 2.0 * PARTICLE_RADIUS / pixels),
+            frame: self.frame,
+            ..uniform
+        };
+        queue.write_buffer(
+            &self.swarms[&key].uniform,
+            0,
+            bytemuck::cast_slice(&[uniform]),
+        );
+        self.order.push(key);
+    }
+
+    fn create_state(
+        &self,
+        device: &Device,
+        source: Source,
+        count: u32,
+        particles: &[ParticleRaw],
+    ) -> SwarmState {
+        let uniform = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Swarm Buffer"),
+            size: size_of::<SwarmUniform>() as BufferAddress,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let particles = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(particles),
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+        });
+        let simulate = device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.simulate_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: uniform.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: particles.as_entire_binding(),
+                },
+            ],
+            label: Some("particle_simulate_bind_group"),
+        });
+        let draw = device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.draw_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform.as_entire_binding(),
+            }],
+            label: Some("particle_draw_bind_group"),
+        });
+        SwarmState {
+            source,
+            count,
+            uniform,
+            particles,
+            simulate,
+            draw,
+        }
+    }
+
+    /// Advance every particle, must be recorded before [`draw`](Self::draw)
+    pub fn simulate(&self, encoder: &mut CommandEncoder) {
+        if self.order.is_empty() {
+            return;
+        }
+        let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+            label: Some("Particle Simulation Pass"),
+        });
+        for key in &self.order {
+            let state = &self.swarms[key];
+            compute_pass.set_pipeline(match key.1 {
+                Swarm::Belt => &self.orbit,
+                Swarm::Dust | Swarm::Ion => &self.tail,
+            });
+            compute_pass.set_bind_group(0, &state.simulate, &[]);
+            compute_pass.dispatch_workgroups(state.count.div_ceil(WORKGROUP_SIZE), 1, 1);
+        }
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, camera: &'a BindGroup) {
+        if self.order.is_empty() {
+            return;
+        }
+        render_pass.set_pipeline(&self.draw);
+        render_pass.set_bind_group(0, camera, &[]);
+        for state in self.order.iter().map(|key| &self.swarms[key]) {
+            render_pass.set_bind_group(1, &state.draw, &[]);
+            render_pass.set_vertex_buffer(0, state.particles.slice(..));
+            render_pass.draw(0..6, 0..state.count);
+        }
+    }
+}
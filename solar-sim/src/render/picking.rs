@@ -0,0 +1,139 @@
+//! Select bodies by clicking on them
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, SquareMatrix, Vector3, Vector4};
+use specs::join::MaybeJoin;
+use specs::shred::PanicHandler;
+use specs::{Entities, Join, Read, ReadStorage, System, Write};
+
+use crate::control::{Controls, Selection};
+use crate::physics::{Planet, Position, Radius};
+use crate::render::camera::{Camera, Projection};
+use crate::render::split::SplitScreen;
+use crate::render::{Exaggeration, SCALE};
+
+/// Resource choosing how bodies under the cursor are found
+///
+/// Defaults to [`Cpu`](PickingMode::Cpu)
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum PickingMode {
+    /// Intersect a ray with every body's sphere in the [`Picking`] system
+    #[default]
+    Cpu,
+
+    /// Read back the id rendered under the cursor by the [`IdBuffer`](crate::render::id_buffer::IdBuffer)
+    ///
+    /// Exact for any shape, but the selection arrives a few frames late.
+    Gpu,
+}
+
+/// Ray from the camera through a point given in normalized device coordinates
+///
+/// Returns the ray's origin and direction in render space.
+pub fn screen_ray(
+    camera: &Camera,
+    projection: &Projection,
+    [x, y]: [f32; 2],
+) -> Option<(Point3<f32>, Vector3<f32>)> {
+    let inverse = (projection.matrix() * camera.matrix()).invert()?;
+    let unproject = |z| {
+        let point: Vector4<f32> = inverse * Vector4::new(x, y, z, 1.0);
+        Point3::from_vec(point.truncate() / point.w)
+    };
+    let direction = (unproject(1.0) - unproject(-1.0)).normalize();
+    Some((camera.position, direction))
+}
+
+/// Distance along a ray to where it enters a sphere
+fn intersect(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    center: Point3<f32>,
+    radius: f32,
+) -> Option<f32> {
+    let to_center = center - origin;
+    let closest = to_center.dot(direction);
+    let distance2 = to_center.magnitude2() - closest * closest;
+    if distance2 > radius * radius {
+        return None;
+    }
+    let half_chord = (radius * radius - distance2).sqrt();
+    let entry = closest - half_chord;
+    let exit = closest + half_chord;
+    if exit < 0.0 {
+        None
+    } else {
+        Some(entry.max(0.0))
+    }
+}
+
+/// System selecting the closest body under the cursor when the user clicks
+///
+/// Bodies are tested against the spheres they are rendered as.
+/// Clicking on nothing clears the selection.
+/// Does nothing unless [`PickingMode::Cpu`] is used.
+///
+/// Updates [`Selection`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Picking;
+
+impl<'a> System<'a> for Picking {
+    type SystemData = (
+        Write<'a, Controls>,
+        Read<'a, Camera>,
+        Read<'a, Projection, PanicHandler>,
+        Read<'a, Exaggeration>,
+        Write<'a, Selection>,
+        Read<'a, PickingMode>,
+        Read<'a, SplitScreen>,
+        Entities<'a>,
+        ReadStorage<'a, Planet>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Radius>,
+    );
+
+    fn run(
+        &mut self,
+        (
+            mut controls,
+            camera,
+            projection,
+            exaggeration,
+            mut selection,
+            mode,
+            split,
+            ent,
+            planet,
+            pos,
+            radius,
+        ): Self::SystemData,
+    ) {
+        if *mode != PickingMode::Cpu || !controls.is_select_clicked {
+            return;
+        }
+        controls.is_select_clicked = false;
+
+        // The cursor is hidden, so fall back to the screen's center
+        let cursor = match (controls.cursor, split.0) {
+            // Relative to the main camera's half of the screen
+            (Some([x, y]), Some(_)) => [x * 2.0 + 1.0, y],
+            (cursor, _) => cursor.unwrap_or([0.0, 0.0]),
+        };
+        let Some((origin, direction)) = screen_ray(&camera, &projection, cursor) else {
+            return;
+        };
+
+        selection.0 = (&ent, &planet, &pos, MaybeJoin(&radius))
+            .join()
+            .filter_map(|(e, _, pos, radius)| {
+                intersect(
+                    origin,
+                    direction,
+                    pos.0.map(|c| c as f32) / SCALE,
+                    exaggeration.render_radius(radius),
+                )
+                .map(|distance| (e, distance))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(e, _)| e);
+    }
+}
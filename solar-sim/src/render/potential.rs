@@ -0,0 +1,145 @@
+//! Visualize the effective potential of two bodies in their co-rotating frame
+
+use cgmath::{InnerSpace, Point3};
+use specs::{Join, ReadStorage, System, Write};
+
+use crate::physics::{Mass, Position, Velocity, G};
+use crate::render::lines::{LineVertex, Lines};
+
+/// Layer in [`Lines`] the contours are drawn to
+const LAYER: &str = "effective_potential";
+
+/// System drawing contour lines of the effective potential around the two most massive bodies
+///
+/// The potential is evaluated on a grid in the pair's orbital plane, rotating with the pair:
+/// `-G m1 / r1 - G m2 / r2 - ω² d² / 2` where `d` is the distance to their barycenter.
+/// Its saddle points and maxima are the pair's Lagrange points.
+///
+/// Updates [`Lines`] resource every few dispatches
+#[derive(Copy, Clone, Debug)]
+pub struct EffectivePotential {
+    /// Number of grid cells along each of the grid's sides
+    pub resolution: usize,
+
+    /// Number of contour lines
+    pub levels: usize,
+
+    /// Number of dispatches between updates
+    pub every: u32,
+
+    frame: u32,
+}
+
+impl Default for EffectivePotential {
+    fn default() -> Self {
+        Self {
+            resolution: 96,
+            levels: 24,
+            every: 10,
+            frame: 0,
+        }
+    }
+}
+
+impl<'a> System<'a> for EffectivePotential {
+    type SystemData = (
+        Write<'a, Lines>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (mut lines, mass, pos, vel): Self::SystemData) {
+        self.frame += 1;
+        if self.frame < self.every {
+            return;
+        }
+        self.frame = 0;
+
+        let mut bodies: Vec<_> = (&mass, &pos, &vel)
+            .join()
+            .map(|(mass, pos, vel)| (mass.0, pos.0.map(|c| c as f32), vel.0.map(|c| c as f32)))
+            .collect();
+        bodies.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let [(m1, p1, v1), (m2, p2, v2), ..] = bodies[..] else {
+            lines.clear(LAYER);
+            return;
+        };
+
+        // Build the co-rotating frame
+        let r = p2 - p1;
+        let normal = r.cross(v2 - v1);
+        if r.magnitude2() == 0.0 || normal.magnitude2() == 0.0 {
+            lines.clear(LAYER);
+            return;
+        }
+        let omega = normal.magnitude() / r.magnitude2();
+        let barycenter = p1 + r * (m2 / (m1 + m2));
+        let x = r.normalize();
+        let y = normal.normalize().cross(x);
+
+        let potential = |point: Point3<f32>| {
+            -G * m1 / (point - p1).magnitude()
+                - G * m2 / (point - p2).magnitude()
+                - 0.5 * omega * omega * (point - barycenter).magnitude2()
+        };
+
+        // Sample the plane around the barycenter
+        let n = self.resolution;
+        let extent = 1.5 * r.magnitude();
+        let cell = 2.0 * extent / n as f32;
+        let point = |i: usize, j: usize| {
+            barycenter + x * (i as f32 * cell - extent) + y * (j as f32 * cell - extent)
+        };
+        let values: Vec<f32> = (0..=n)
+            .flat_map(|j| (0..=n).map(move |i| (i, j)))
+            .map(|(i, j)| potential(point(i, j)))
+            .collect();
+        let value = |i: usize, j: usize| values[j * (n + 1) + i];
+
+        // Spread the levels evenly over the sampled values, ignoring the wells' steep centers
+        let mut sorted: Vec<_> = values.iter().copied().filter(|v| v.is_finite()).collect();
+        sorted.sort_by(f32::total_cmp);
+        if sorted.is_empty() {
+            lines.clear(LAYER);
+            return;
+        }
+        let levels: Vec<f32> = (0..self.levels)
+            .map(|level| {
+                let quantile = 0.1 + 0.85 * (level as f32 + 0.5) / self.levels as f32;
+                sorted[((sorted.len() - 1) as f32 * quantile) as usize]
+            })
+            .collect();
+
+        // Marching squares
+        let mut contours = Vec::new();
+        for (index, &level) in levels.iter().enumerate() {
+            let t = index as f32 / self.levels.max(2).saturating_sub(1) as f32;
+            let color = [0.3 + 0.7 * t, 0.8, 1.0 - 0.7 * t];
+            for j in 0..n {
+                for i in 0..n {
+                    let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)];
+                    let mut crossings = Vec::with_capacity(4);
+                    for edge in 0..4 {
+                        let (a, b) = (corners[edge], corners[(edge + 1) % 4]);
+                        let (va, vb) = (value(a.0, a.1), value(b.0, b.1));
+                        if (va < level) != (vb < level) && va.is_finite() && vb.is_finite() {
+                            let s = (level - va) / (vb - va);
+                            let (pa, pb) = (point(a.0, a.1), point(b.0, b.1));
+                            crossings.push(pa + (pb - pa) * s);
+                        }
+                    }
+                    for pair in crossings.chunks_exact(2) {
+                        for position in pair {
+                            contours.push(LineVertex {
+                                position: position.cast().unwrap(),
+                                color,
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        lines.set(LAYER, contours);
+    }
+}
@@ -0,0 +1,199 @@
+//! Predict where the bodies are headed
+
+use cgmath::{InnerSpace, Point3, Vector3, Zero};
+use specs::{Entities, Entity, Join, Read, ReadStorage, System, Write};
+
+use crate::physics::{Determinism, Mass, Position, SimSpeed, Velocity, G};
+use crate::render::lines::{LineVertex, Lines};
+use crate::timer::Delta;
+
+/// Layer in [`Lines`] the predictions are drawn to
+const LAYER: &str = "prediction";
+
+/// Number of points drawn per trajectory
+const SAMPLES: usize = 200;
+
+/// Advance bodies by one step using semi-implicit euler like [`Mechanics`](crate::physics::Mechanics)
+pub fn step(state: &mut [(Point3<f32>, Vector3<f32>)], masses: &[f32], dt: f32) {
+    let accelerations: Vec<_> = state
+        .iter()
+        .enumerate()
+        .map(|(this, (this_pos, _))| {
+            let mut acc = Vector3::zero();
+            for (other, ((other_pos, _), mass)) in state.iter().zip(masses).enumerate() {
+                let r = other_pos - this_pos;
+                if this != other && r.magnitude2() > 0.0 {
+                    acc += G * mass / r.magnitude2() * r.normalize();
+                }
+            }
+            acc
+        })
+        .collect();
+    for ((pos, vel), acc) in state.iter_mut().zip(accelerations) {
+        *vel += acc * dt;
+        *pos += *vel * dt;
+    }
+}
+
+/// System integrating a copy of the bodies ahead of time and drawing their paths as dashed lines
+///
+/// The live world is never modified.
+/// The prediction is recomputed when bodies appear, disappear or change their mass,
+/// when a body's velocity strays from the predicted one (e.g. due to plugin forces)
+/// and when half the horizon has passed.
+///
+/// Updates [`Lines`] resource whenever the prediction is recomputed
+#[derive(Clone, Debug)]
+pub struct Prediction {
+    /// Simulated seconds to predict
+    pub horizon: f32,
+
+    /// Number of steps to reach the horizon
+    pub steps: usize,
+
+    /// Meters per second a velocity may deviate from its prediction before a new one is made
+    pub tolerance: f32,
+
+    predicted: Option<Predicted>,
+}
+
+impl Default for Prediction {
+    fn default() -> Self {
+        Self {
+            horizon: 365.25 * 24.0 * 60.0 * 60.0,
+            steps: 2000,
+            tolerance: 100.0,
+            predicted: None,
+        }
+    }
+}
+
+/// The last prediction
+#[derive(Clone, Debug)]
+struct Predicted {
+    /// Entity and mass of every predicted body
+    bodies: Vec<(Entity, f32)>,
+
+    /// Simulated seconds since the prediction started
+    elapsed: f32,
+
+    /// Velocity of every body after every step
+    velocities: Vec<Vec<Vector3<f32>>>,
+}
+
+impl Prediction {
+    fn predict(
+        &self,
+        bodies: &[(Entity, f32, Point3<f32>, Vector3<f32>)],
+    ) -> (Predicted, Vec<LineVertex>) {
+        let masses: Vec<_> = bodies.iter().map(|(_, mass, ..)| *mass).collect();
+        let mut state: Vec<_> = bodies.iter().map(|(_, _, pos, vel)| (*pos, *vel)).collect();
+
+        let dt = self.horizon / self.steps as f32;
+        let sample_every = (self.steps / SAMPLES).max(1);
+        let mut trajectories: Vec<_> = state.iter().map(|(pos, _)| vec![*pos]).collect();
+        let mut velocities = Vec::with_capacity(self.steps + 1);
+        velocities.push(state.iter().map(|(_, vel)| *vel).collect());
+        for i in 1..=self.steps {
+            step(&mut state, &masses, dt);
+            velocities.push(state.iter().map(|(_, vel)| *vel).collect());
+            if i % sample_every == 0 {
+                for (trajectory, (pos, _)) in trajectories.iter_mut().zip(&state) {
+                    trajectory.push(*pos);
+                }
+            }
+        }
+
+        let mut lines = Vec::new();
+        for trajectory in &trajectories {
+            // Every other segment leaves a gap
+            for pair in trajectory.windows(2).step_by(2) {
+                for position in pair {
+                    lines.push(LineVertex {
+                        position: position.cast().unwrap(),
+                        color: [0.4, 0.8, 0.4],
+                    });
+                }
+            }
+        }
+
+        let predicted = Predicted {
+            bodies: bodies.iter().map(|(e, mass, ..)| (*e, *mass)).collect(),
+            elapsed: 0.0,
+            velocities,
+        };
+        (predicted, lines)
+    }
+
+    /// Check whether the bodies still behave as predicted
+    fn is_valid(
+        &self,
+        predicted: &Predicted,
+        bodies: &[(Entity, f32, Point3<f32>, Vector3<f32>)],
+    ) -> bool {
+        if predicted.elapsed > self.horizon / 2.0 {
+            return false;
+        }
+        let same_bodies = predicted.bodies.len() == bodies.len()
+            && predicted
+                .bodies
+                .iter()
+                .zip(bodies)
+                .all(|((e, mass), (body, body_mass, ..))| e == body && mass == body_mass);
+        if !same_bodies {
+            return false;
+        }
+        // Interpolate between the steps around the elapsed time
+        let dt = self.horizon / self.steps as f32;
+        let time = predicted.elapsed / dt;
+        let before = &predicted.velocities[time as usize];
+        let after = &predicted.velocities[(time as usize + 1).min(self.steps)];
+        let t = time.fract();
+        before
+            .iter()
+            .zip(after)
+            .zip(bodies)
+            .all(|((before, after), (.., vel))| {
+                let expected = before + (after - before) * t;
+                (vel - expected).magnitude() <= self.tolerance
+            })
+    }
+}
+
+impl<'a> System<'a> for Prediction {
+    type SystemData = (
+        Write<'a, Lines>,
+        Read<'a, SimSpeed>,
+        Read<'a, Delta>,
+        Read<'a, Determinism>,
+        Entities<'a>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+    );
+
+    fn run(
+        &mut self,
+        (mut lines, speed, delta, determinism, ent, mass, pos, vel): Self::SystemData,
+    ) {
+        let bodies: Vec<_> = (&ent, &mass, &pos, &vel)
+            .join()
+            .map(|(e, mass, pos, vel)| {
+                (e, mass.0, pos.0.map(|c| c as f32), vel.0.map(|c| c as f32))
+            })
+            .collect();
+
+        if let Some(predicted) = &mut self.predicted {
+            predicted.elapsed += determinism.0.unwrap_or(**delta).as_secs_f32() * speed.0;
+        }
+        let valid = self
+            .predicted
+            .as_ref()
+            .is_some_and(|predicted| self.is_valid(predicted, &bodies));
+        if !valid {
+            let (predicted, vertices) = self.predict(&bodies);
+            self.predicted = Some(predicted);
+            lines.set(LAYER, vertices);
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Choosing how frames are presented, i.e. whether to wait for vsync
+
+/// Modes which can be chosen and their names on the command line
+const MODES: [(wgpu::PresentMode, &str); 3] = [
+    (wgpu::PresentMode::Fifo, "fifo"),
+    (wgpu::PresentMode::Mailbox, "mailbox"),
+    (wgpu::PresentMode::Immediate, "immediate"),
+];
+
+/// Present mode resource
+///
+/// Defaults to [`Fifo`](wgpu::PresentMode::Fifo), which waits for vsync and is supported everywhere.
+/// [`Render`](crate::render::Render) reconfigures the surface when it changes
+/// and falls back to the current mode if the surface doesn't support it.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PresentMode(pub wgpu::PresentMode);
+
+impl Default for PresentMode {
+    fn default() -> Self {
+        Self(wgpu::PresentMode::Fifo)
+    }
+}
+
+impl PresentMode {
+    /// Look up a mode by its name, one of `fifo`, `mailbox` or `immediate`
+    pub fn parse(name: &str) -> Option<Self> {
+        MODES
+            .iter()
+            .find(|(_, key)| name.eq_ignore_ascii_case(key))
+            .map(|(mode, _)| Self(*mode))
+    }
+
+    /// The mode after this one which is `supported`, wrapping around
+    pub fn next(self, supported: &[wgpu::PresentMode]) -> Self {
+        let modes = MODES
+            .iter()
+            .map(|(mode, _)| *mode)
+            .filter(|mode| supported.contains(mode));
+        let first = modes.clone().next();
+        Self(
+            modes
+                .skip_while(|mode| *mode != self.0)
+                .nth(1)
+                .or(first)
+                .unwrap_or(self.0),
+        )
+    }
+}
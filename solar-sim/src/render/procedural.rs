@@ -0,0 +1,236 @@
+//! Generate surface textures from noise on the GPU
+
+use specs::{Component, VecStorage};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, BufferBindingType, BufferUsages, ColorTargetState,
+    CommandEncoderDescriptor, Device, Extent3d, FragmentState, PipelineLayoutDescriptor, Queue,
+    RenderPipeline, RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
+    VertexState,
+};
+
+use crate::render::mipmap::MipmapGenerator;
+use crate::render::texture::Texture;
+
+/// Procedural surface component
+///
+/// Bodies with it get a texture generated by [`TextureGenerator`] when they are first rendered.
+/// The noise is blended through the palette's colors from first to last.
+#[derive(Copy, Clone, Debug, Component)]
+#[storage(VecStorage)]
+pub struct Surface {
+    pub seed: u32,
+    pub palette: [[f32; 3]; 4],
+    pub pattern: Pattern,
+}
+
+/// Shape of the noise a [`Surface`] is generated from
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Pattern {
+    /// Warped noise of continents, craters and dunes on rocky bodies
+    #[default]
+    Terrain,
+
+    /// Turbulent stripes along the latitudes of gas giants
+    Bands,
+}
+
+impl Surface {
+    /// Gray-brown surface for asteroids and similar bodies
+    pub fn rocky(seed: u32) -> Self {
+        Self {
+            seed,
+            palette: [
+                [0.05, 0.04, 0.035],
+                [0.2, 0.17, 0.14],
+                [0.4, 0.36, 0.3],
+                [0.6, 0.57, 0.52],
+            ],
+            pattern: Pattern::Terrain,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct SurfaceUniform {
+    palette: [[f32; 4]; 4],
+    seed: u32,
+    pattern: u32,
+    _padding: [u32; 2],
+}
+
+/// Renders [`Surface`]s into textures
+pub struct TextureGenerator {
+    pipeline: RenderPipeline,
+    normal_pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    pub mipmaps: MipmapGenerator,
+}
+
+impl TextureGenerator {
+    /// Width and height of generated textures
+    pub const SIZE: (u32, u32) = (512, 256);
+
+    const FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+    /// Normals are vectors instead of colors, so they are stored linearly
+    const NORMAL_FORMAT: TextureFormat = TextureFormat::Rgba8Unorm;
+
+    pub fn new(device: &Device) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Procedural Texture Shader"),
+            source: ShaderSource::Wgsl(include_str!("../procedural.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("surface_bind_group_layout"),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Procedural Texture Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let create_pipeline = |entry_point, format| {
+            device.create_render_pipeline(&RenderPipelineDescriptor {
+                label: Some("Procedural Texture Pipeline"),
+                layout: Some(&pipeline_layout),
+                vertex: VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(ColorTargetState {
+                        format,
+                        blend: None,
+                        write_mask: Default::default(),
+                    })],
+                }),
+                primitive: Default::default(),
+                depth_stencil: None,
+                multisample: Default::default(),
+                multiview: Default::default(),
+            })
+        };
+
+        Self {
+            pipeline: create_pipeline("fs_main", Self::FORMAT),
+            normal_pipeline: create_pipeline("fs_normal", Self::NORMAL_FORMAT),
+            layout,
+            mipmaps: MipmapGenerator::new(device),
+        }
+    }
+
+    /// Generate a surface's texture
+    pub fn generate(&self, device: &Device, queue: &Queue, surface: &Surface) -> Texture {
+        self.render(device, queue, surface, &self.pipeline, Self::FORMAT)
+    }
+
+    /// Generate a tangent space normal map of the bumps of a surface's noise
+    ///
+    /// The palette doesn't matter.
+    pub fn generate_normals(&self, device: &Device, queue: &Queue, surface: &Surface) -> Texture {
+        self.render(
+            device,
+            queue,
+            surface,
+            &self.normal_pipeline,
+            Self::NORMAL_FORMAT,
+        )
+    }
+
+    fn render(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        surface: &Surface,
+        pipeline: &RenderPipeline,
+        format: TextureFormat,
+    ) -> Texture {
+        let uniform = SurfaceUniform {
+            palette: surface.palette.map(|[r, g, b]| [r, g, b, 1.0]),
+            seed: surface.seed,
+            pattern: surface.pattern as u32,
+            _padding: [0; 2],
+        };
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Surface Buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: BufferUsages::UNIFORM,
+        });
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &self.layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("surface_bind_group"),
+        });
+
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Procedural Texture"),
+            size: Extent3d {
+                width: Self::SIZE.0,
+                height: Self::SIZE.1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: MipmapGenerator::level_count(Self::SIZE.0, Self::SIZE.1),
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        // Only the first level, the others are filled from it
+        let view = texture.create_view(&TextureViewDescriptor {
+            mip_level_count: Some(1),
+            ..Default::default()
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Procedural Texture Encoder"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Procedural Texture Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &bind_group, &[]);
+            render_pass.draw(0..3, 0..1);
+        }
+        queue.submit([encoder.finish()]);
+        self.mipmaps.generate(device, queue, &texture);
+
+        Texture {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            sampler: self.mipmaps.sampler(device),
+            texture,
+        }
+    }
+}
@@ -0,0 +1,172 @@
+//! Saving every rendered frame as a numbered image, e.g. to make a video from
+
+use std::path::PathBuf;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use image::RgbaImage;
+use wgpu::{
+    Buffer, BufferAddress, BufferDescriptor, BufferUsages, CommandEncoder, Device, Extent3d,
+    SurfaceConfiguration, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages,
+    TextureView, TextureViewDescriptor,
+};
+
+use crate::error::{CustomError, DynError};
+
+/// Simulated time between two recorded frames at a [`SimSpeed`](crate::physics::SimSpeed) of 1
+///
+/// Set as fixed step of the [`Determinism`](crate::physics::Determinism) resource while recording,
+/// so the frames are evenly spaced no matter how long each took to render.
+pub const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+/// Recording resource
+///
+/// While a directory is set, [`Render`](crate::render::Render) saves every frame to it
+/// as `frame_000000.png`, `frame_000001.png` and so on.
+#[derive(Clone, Debug, Default)]
+pub struct Recording(pub Option<PathBuf>);
+
+/// Offscreen copy of the frame which is read back and saved after rendering
+pub struct Recorder {
+    directory: PathBuf,
+    frame: u32,
+    target: wgpu::Texture,
+    target_view: TextureView,
+    format: TextureFormat,
+    size: (u32, u32),
+    readback: Buffer,
+}
+
+impl Recorder {
+    pub fn new(device: &Device, config: &SurfaceConfiguration, directory: PathBuf) -> Self {
+        let (target, target_view) = Self::create_target(device, config);
+        Self {
+            directory,
+            frame: 0,
+            target,
+            target_view,
+            format: config.format,
+            size: (config.width, config.height),
+            readback: Self::create_readback(device, config),
+        }
+    }
+
+    /// Directory the frames are saved to
+    pub fn directory(&self) -> &PathBuf {
+        &self.directory
+    }
+
+    fn create_target(
+        device: &Device,
+        config: &SurfaceConfiguration,
+    ) -> (wgpu::Texture, TextureView) {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Recording Target"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: config.format,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        (texture, view)
+    }
+
+    fn create_readback(device: &Device, config: &SurfaceConfiguration) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Recording Readback Buffer"),
+            size: (Self::padded_row(config.width) * config.height) as BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Bytes per row in the readback buffer, which have to be aligned for the copy
+    fn padded_row(width: u32) -> u32 {
+        let unpadded = width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        unpadded.div_ceil(align) * align
+    }
+
+    /// Target to draw the frame to, matching the surface's format and size
+    pub fn target(&mut self, device: &Device, config: &SurfaceConfiguration) -> &TextureView {
+        if self.size != (config.width, config.height) || self.format != config.format {
+            self.recreate(device, config);
+        }
+        &self.target_view
+    }
+
+    /// Create the target and readback buffer anew, continuing the numbering of the frames
+    pub fn recreate(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        (self.target, self.target_view) = Self::create_target(device, config);
+        self.readback = Self::create_readback(device, config);
+        self.format = config.format;
+        self.size = (config.width, config.height);
+    }
+
+    /// Record copying the target into the readback buffer
+    pub fn copy(&self, encoder: &mut CommandEncoder) {
+        encoder.copy_texture_to_buffer(
+            self.target.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(Self::padded_row(self.size.0)),
+                    rows_per_image: Some(self.size.1),
+                },
+            },
+            Extent3d {
+                width: self.size.0,
+                height: self.size.1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Wait for the copy and save it as the next frame
+    ///
+    /// Requires the encoder passed to [`Recorder::copy`] to be submitted
+    pub fn save(&mut self, device: &Device) -> Result<(), DynError> {
+        let (sender, receiver) = channel();
+        self.readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        device.poll(wgpu::Maintain::Wait);
+        receiver.recv()??;
+
+        let (width, height) = self.size;
+        let row = Self::padded_row(width) as usize;
+        let mut pixels = Vec::with_capacity((width * height * 4) as usize);
+        {
+            let view = self.readback.slice(..).get_mapped_range();
+            for y in 0..height as usize {
+                pixels.extend_from_slice(&view[y * row..y * row + width as usize * 4]);
+            }
+        }
+        self.readback.unmap();
+        if matches!(
+            self.format,
+            TextureFormat::Bgra8Unorm | TextureFormat::Bgra8UnormSrgb
+        ) {
+            for pixel in pixels.chunks_exact_mut(4) {
+                pixel.swap(0, 2);
+            }
+        }
+
+        let image = RgbaImage::from_raw(width, height, pixels)
+            .ok_or(CustomError::from("Recorded frame doesn't match its size"))?;
+        std::fs::create_dir_all(&self.directory)?;
+        image.save(self.directory.join(format!("frame_{:06}.png", self.frame)))?;
+        self.frame += 1;
+        Ok(())
+    }
+}
@@ -0,0 +1,156 @@
+//! Meshes and materials on the GPU which bodies pick by handle
+
+use std::ops::Range;
+use std::sync::Arc;
+
+use specs::{Component, VecStorage};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{BindGroup, Buffer, BufferUsages, Device, RenderPass};
+
+use crate::render::material::MaterialTextures;
+use crate::render::texture::Texture;
+use crate::render::Vertex;
+
+/// Mesh component choosing an entry of the [`MeshRegistry`] instead of the sphere
+///
+/// The mesh is scaled by the body's radius, so it should fit into the unit sphere.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Component)]
+#[storage(VecStorage)]
+pub struct MeshHandle(usize);
+
+/// Material component choosing an entry of the [`MaterialRegistry`]
+///
+/// Takes precedence over [`Material`](crate::render::material::Material) and
+/// [`Surface`](crate::render::procedural::Surface) components
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Component)]
+#[storage(VecStorage)]
+pub struct MaterialHandle(usize);
+
+/// Vertex and index buffer of a mesh in the [`MeshRegistry`]
+pub struct Mesh {
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+}
+
+impl Mesh {
+    /// Bind the buffers and draw instances, which are expected in vertex buffer slot 1
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, instances: Range<u32>) {
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        render_pass.draw_indexed(0..self.num_indices, 0, instances);
+    }
+}
+
+/// Registry of meshes available to [`MeshHandle`] components
+///
+/// Meshes are uploaded by [`Render`](crate::render::Render) at the start of the next frame.
+#[derive(Default)]
+pub struct MeshRegistry {
+    meshes: Vec<Option<Mesh>>,
+    /// Vertexes and indexes of every mesh, kept to upload them again after losing the device
+    sources: Vec<(Vec<Vertex>, Vec<u32>)>,
+    /// Meshes which aren't uploaded yet
+    pending: Vec<MeshHandle>,
+}
+
+impl MeshRegistry {
+    /// Add a mesh given by triangles of indexes into its vertexes
+    pub fn add(&mut self, vertexes: Vec<Vertex>, indexes: Vec<u32>) -> MeshHandle {
+        let handle = MeshHandle(self.meshes.len());
+        self.meshes.push(None);
+        self.sources.push((vertexes, indexes));
+        self.pending.push(handle);
+        handle
+    }
+
+    /// Drop every uploaded mesh, so they are uploaded again by the next call to [`upload`](Self::upload)
+    pub fn reupload_all(&mut self) {
+        self.pending = (0..self.meshes.len()).map(MeshHandle).collect();
+        self.meshes.iter_mut().for_each(|mesh| *mesh = None);
+    }
+
+    /// Upload the meshes added since the last call
+    pub fn upload(&mut self, device: &Device) {
+        for handle in self.pending.drain(..) {
+            let (vertexes, indexes) = &self.sources[handle.0];
+            self.meshes[handle.0] = Some(Mesh {
+                vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Mesh Vertex Buffer"),
+                    contents: bytemuck::cast_slice(vertexes),
+                    usage: BufferUsages::VERTEX,
+                }),
+                index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Mesh Index Buffer"),
+                    contents: bytemuck::cast_slice(indexes),
+                    usage: BufferUsages::INDEX,
+                }),
+                num_indices: indexes.len() as u32,
+            });
+        }
+    }
+
+    /// Get a mesh once it's uploaded
+    pub fn get(&self, handle: MeshHandle) -> Option<&Mesh> {
+        self.meshes.get(handle.0)?.as_ref()
+    }
+}
+
+/// Registry of materials available to [`MaterialHandle`] components
+///
+/// Unlike the [`Materials`](crate::render::material::Materials) looked up by name,
+/// materials can be added while running.
+/// Their textures are created by [`Render`](crate::render::Render) when they are first used.
+#[derive(Default)]
+pub struct MaterialRegistry {
+    materials: Vec<MaterialTextures>,
+    bind_groups: Vec<Option<(Vec<Texture>, Arc<BindGroup>)>>,
+}
+
+impl MaterialRegistry {
+    pub fn add(&mut self, textures: MaterialTextures) -> MaterialHandle {
+        let handle = MaterialHandle(self.materials.len());
+        self.materials.push(textures);
+        self.bind_groups.push(None);
+        handle
+    }
+
+    /// The textures making up a material
+    pub fn get(&self, handle: MaterialHandle) -> Option<&MaterialTextures> {
+        self.materials.get(handle.0)
+    }
+
+    /// Every material with its handle
+    pub fn iter(&self) -> impl Iterator<Item = (MaterialHandle, &MaterialTextures)> {
+        self.materials
+            .iter()
+            .enumerate()
+            .map(|(index, textures)| (MaterialHandle(index), textures))
+    }
+
+    /// Get a material's bind group for the main pipeline once its textures are created
+    pub fn bind_group(&self, handle: MaterialHandle) -> Option<&BindGroup> {
+        let (_, bind_group) = self.bind_groups.get(handle.0)?.as_ref()?;
+        Some(bind_group.as_ref())
+    }
+
+    /// Store a material's textures and the bind group sampling them
+    pub fn bind(
+        &mut self,
+        handle: MaterialHandle,
+        textures: Vec<Texture>,
+        bind_group: Arc<BindGroup>,
+    ) {
+        self.bind_groups[handle.0] = Some((textures, bind_group));
+    }
+
+    /// Drop a material's textures, so they are created again when it's used next
+    pub fn unbind(&mut self, handle: MaterialHandle) {
+        self.bind_groups[handle.0] = None;
+    }
+
+    /// Drop every material's textures
+    pub fn unbind_all(&mut self) {
+        self.bind_groups.fill_with(|| None);
+    }
+}
@@ -0,0 +1,307 @@
+//! Transparent rings around planets
+
+use std::collections::HashMap;
+
+use cgmath::{Point3, Quaternion, Rad, Rotation3};
+use image::{DynamicImage, Rgba, RgbaImage};
+use log::warn;
+use specs::{Component, Entity, VecStorage};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferUsages,
+    ColorTargetState, DepthStencilState, Device, FragmentState, PipelineLayoutDescriptor,
+    PrimitiveState, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, VertexState,
+};
+
+use crate::render::instance::{Instance, InstanceRaw};
+use crate::render::shapes::annulus;
+use crate::render::texture::Texture;
+use crate::render::transparent::{Shared, Transparent};
+use crate::render::Vertex;
+
+/// Number of segments the rings' meshes are made of
+const SEGMENTS: u16 = 256;
+
+/// Width of the generated ring textures
+const RESOLUTION: u32 = 512;
+
+/// Rings component of a planet
+///
+/// Rendered in the plane perpendicular to the planet's axis.
+#[derive(Copy, Clone, Debug, PartialEq, Component)]
+#[storage(VecStorage)]
+pub struct Rings {
+    /// Distance of the inner edge to the planet's center in meters
+    pub inner: f32,
+
+    /// Distance of the outer edge to the planet's center in meters
+    pub outer: f32,
+
+    /// Angle between the planet's axis and the orbital plane's normal
+    pub tilt: Rad<f32>,
+
+    pub texture: RingTexture,
+}
+
+/// Generated texture of a ring's bands and gaps
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RingTexture {
+    pub seed: u64,
+    pub color: [f32; 3],
+
+    /// Opacity of the densest bands
+    pub opacity: f32,
+}
+
+impl RingTexture {
+    /// Generate the texture from the inner edge on the left to the outer edge on the right
+    pub fn image(&self) -> DynamicImage {
+        // xorshift64
+        let mut rng = self.seed | 1;
+        let mut uniform = || {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            (rng >> 40) as f32 / (1u64 << 24) as f32
+        };
+        let coarse: Vec<f32> = (0..=16).map(|_| uniform()).collect();
+        let fine: Vec<f32> = (0..=128).map(|_| uniform()).collect();
+        let sample = |values: &[f32], x: f32| {
+            let x = x * (values.len() - 1) as f32;
+            let index = (x as usize).min(values.len() - 2);
+            let t = x - index as f32;
+            values[index] * (1.0 - t) + values[index + 1] * t
+        };
+
+        let mut image = RgbaImage::new(RESOLUTION, 1);
+        for (x, _, pixel) in image.enumerate_pixels_mut() {
+            let x = (x as f32 + 0.5) / RESOLUTION as f32;
+            let detail = sample(&fine, x);
+            let density = (sample(&coarse, x) * (0.5 + detail)).clamp(0.0, 1.0);
+            // Fade in and out at the edges instead of ending abruptly
+            let edge = (x.min(1.0 - x) * 20.0).min(1.0);
+            let shade = 0.8 + 0.4 * detail;
+            let [r, g, b] = self.color.map(|c| ((c * shade).min(1.0) * 255.0) as u8);
+            let a = (density * edge * self.opacity * 255.0) as u8;
+            *pixel = Rgba([r, g, b, a]);
+        }
+        DynamicImage::ImageRgba8(image)
+    }
+}
+
+/// Buffers and texture of a single planet's rings
+struct RingMesh {
+    rings: Rings,
+    vertex_buffer: Buffer,
+    index_buffer: Buffer,
+    num_indices: u32,
+    instance_buffer: Buffer,
+    bind_group: BindGroup,
+}
+
+/// Draws every planet's [`Rings`]
+///
+/// They are drawn in the transparent pass, so overlapping rings and atmospheres blend correctly.
+pub struct RingPipeline {
+    pipeline: RenderPipeline,
+    texture_layout: BindGroupLayout,
+    meshes: HashMap<Entity, RingMesh>,
+    order: Vec<Entity>,
+    centers: Vec<Point3<f32>>,
+}
+
+impl RingPipeline {
+    pub fn new(
+        device: &Device,
+        format: TextureFormat,
+        camera_layout: &BindGroupLayout,
+        light_layout: &BindGroupLayout,
+    ) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Ring Shader"),
+            source: ShaderSource::Wgsl(include_str!("../rings.wgsl").into()),
+        });
+
+        let texture_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("ring_texture_bind_group_layout"),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Ring Pipeline Layout"),
+            bind_group_layouts: &[&texture_layout, camera_layout, light_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Ring Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                // Rings are seen from both sides
+                cull_mode: None,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        Self {
+            pipeline,
+            texture_layout,
+            meshes: HashMap::new(),
+            order: Vec::new(),
+            centers: Vec::new(),
+        }
+    }
+
+    /// Upload the rings of the current frame
+    ///
+    /// `rings` yields each planet's rings with its position in render space.
+    /// `scale` converts meters to render space including any exaggeration.
+    pub fn update<'r>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        scale: f32,
+        rings: impl Iterator<Item = (Entity, &'r Rings, Point3<f32>)>,
+    ) {
+        self.order.clear();
+        self.centers.clear();
+        for (entity, rings, position) in rings {
+            if self.meshes.get(&entity).map(|mesh| &mesh.rings) != Some(rings) {
+                match Self::create_mesh(device, queue, &self.texture_layout, rings) {
+                    Some(mesh) => self.meshes.insert(entity, mesh),
+                    None => self.meshes.remove(&entity),
+                };
+            }
+            let Some(mesh) = self.meshes.get_mut(&entity) else {
+                continue;
+            };
+            let instance = Instance {
+                rotation: Quaternion::from_angle_x(rings.tilt),
+                ..Instance::from_position(position, scale)
+            };
+            queue.write_buffer(
+                &mesh.instance_buffer,
+                0,
+                bytemuck::cast_slice(&[instance.to_raw()]),
+            );
+            self.order.push(entity);
+            self.centers.push(position);
+        }
+        self.meshes.retain(|entity, _| self.order.contains(entity));
+    }
+
+    fn create_mesh(
+        device: &Device,
+        queue: &Queue,
+        texture_layout: &BindGroupLayout,
+        rings: &Rings,
+    ) -> Option<RingMesh> {
+        let texture =
+            match Texture::from_image(device, queue, &rings.texture.image(), Some("rings")) {
+                Ok(texture) => texture,
+                Err(error) => {
+                    warn!("Failed to create ring texture: {error}");
+                    return None;
+                }
+            };
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: texture_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+            label: Some("ring_texture_bind_group"),
+        });
+
+        let (vertexes, indexes) = annulus(rings.inner, rings.outer, SEGMENTS);
+        Some(RingMesh {
+            rings: *rings,
+            vertex_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Ring Vertex Buffer"),
+                contents: bytemuck::cast_slice(&vertexes),
+                usage: BufferUsages::VERTEX,
+            }),
+            index_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Ring Index Buffer"),
+                contents: bytemuck::cast_slice(&indexes),
+                usage: BufferUsages::INDEX,
+            }),
+            num_indices: indexes.len() as u32,
+            instance_buffer: device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Ring Instance Buffer"),
+                contents: bytemuck::cast_slice(&[Instance::default().to_raw()]),
+                usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            }),
+            bind_group,
+        })
+    }
+}
+
+impl Transparent for RingPipeline {
+    fn centers(&self) -> &[Point3<f32>] {
+        &self.centers
+    }
+
+    fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(1, shared.camera, &[]);
+        render_pass.set_bind_group(2, shared.light, &[]);
+    }
+
+    fn draw_item<'a>(&'a self, render_pass: &mut RenderPass<'a>, _: &Shared<'a>, index: usize) {
+        let mesh = &self.meshes[&self.order[index]];
+        render_pass.set_bind_group(0, &mesh.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, mesh.vertex_buffer.slice(..));
+        render_pass.set_vertex_buffer(1, mesh.instance_buffer.slice(..));
+        render_pass.set_index_buffer(mesh.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.draw_indexed(0..mesh.num_indices, 0, 0..1);
+    }
+}
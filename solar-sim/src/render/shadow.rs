@@ -0,0 +1,232 @@
+//! Omnidirectional shadow map rendered from the light's position
+//!
+//! The light is a point, so the map has one layer per direction along the axes,
+//! each covering the space where its axis is the largest component of the direction from the light.
+
+use cgmath::{Deg, Matrix4, Point3, Vector3};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingType, Buffer, BufferBindingType, BufferUsages, CommandEncoder,
+    CompareFunction, DepthBiasState, DepthStencilState, Device, Extent3d, FilterMode,
+    PipelineLayoutDescriptor, PrimitiveState, Queue, RenderPipeline, RenderPipelineDescriptor,
+    Sampler, SamplerDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureUsages, TextureView, TextureViewDescriptor,
+    TextureViewDimension, VertexState,
+};
+
+use crate::render::camera::OPENGL_TO_WGPU_MATRIX;
+use crate::render::instance::InstanceRaw;
+use crate::render::texture::Texture;
+use crate::render::Vertex;
+
+/// Directions and up vectors of the layers
+const FACES: [(Vector3<f32>, Vector3<f32>); 6] = [
+    (Vector3::new(1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+    (Vector3::new(-1.0, 0.0, 0.0), Vector3::new(0.0, 1.0, 0.0)),
+    (Vector3::new(0.0, 1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+    (Vector3::new(0.0, -1.0, 0.0), Vector3::new(0.0, 0.0, 1.0)),
+    (Vector3::new(0.0, 0.0, 1.0), Vector3::new(0.0, 1.0, 0.0)),
+    (Vector3::new(0.0, 0.0, -1.0), Vector3::new(0.0, 1.0, 0.0)),
+];
+
+pub struct ShadowMap {
+    pipeline: RenderPipeline,
+    face_views: Vec<TextureView>,
+    face_buffers: Vec<Buffer>,
+    face_bind_groups: Vec<BindGroup>,
+    /// All six view projection matrices for sampling the map
+    pub matrices: Buffer,
+    pub view: TextureView,
+    pub sampler: Sampler,
+}
+
+impl ShadowMap {
+    /// Width and height of each layer
+    pub const SIZE: u32 = 2048;
+
+    /// Closest distance to the light in render space which still casts shadows
+    const NEAR: f32 = 1.0;
+
+    /// Farthest distance to the light in render space which still casts shadows
+    const FAR: f32 = 1000.0;
+
+    pub fn new(device: &Device) -> Self {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("Shadow Map"),
+            size: Extent3d {
+                width: Self::SIZE,
+                height: Self::SIZE,
+                depth_or_array_layers: 6,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Texture::DEPTH_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor {
+            dimension: Some(TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let face_views = (0..6)
+            .map(|layer| {
+                texture.create_view(&TextureViewDescriptor {
+                    dimension: Some(TextureViewDimension::D2),
+                    base_array_layer: layer,
+                    array_layer_count: Some(1),
+                    ..Default::default()
+                })
+            })
+            .collect();
+        let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: AddressMode::ClampToEdge,
+            address_mode_v: AddressMode::ClampToEdge,
+            address_mode_w: AddressMode::ClampToEdge,
+            mag_filter: FilterMode::Linear,
+            min_filter: FilterMode::Linear,
+            mipmap_filter: FilterMode::Nearest,
+            compare: Some(CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let matrices = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Shadow Matrices Buffer"),
+            contents: bytemuck::cast_slice(&[[[0.0f32; 4]; 4]; 6]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let face_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("shadow_face_bind_group_layout"),
+        });
+        let face_buffers: Vec<_> = (0..6)
+            .map(|_| {
+                device.create_buffer_init(&BufferInitDescriptor {
+                    label: Some("Shadow Face Buffer"),
+                    contents: bytemuck::cast_slice(&[[0.0f32; 4]; 4]),
+                    usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                })
+            })
+            .collect();
+        let face_bind_groups = face_buffers
+            .iter()
+            .map(|buffer| {
+                device.create_bind_group(&BindGroupDescriptor {
+                    layout: &face_layout,
+                    entries: &[BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                    label: Some("shadow_face_bind_group"),
+                })
+            })
+            .collect();
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Shadow Shader"),
+            source: ShaderSource::Wgsl(include_str!("../shadow.wgsl").into()),
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Shadow Pipeline Layout"),
+            bind_group_layouts: &[&face_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Shadow Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[Vertex::desc(), InstanceRaw::desc()],
+            },
+            fragment: None,
+            primitive: PrimitiveState {
+                // Also keeps the light's own body, which surrounds it, from covering everything
+                cull_mode: Some(wgpu::Face::Back),
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::Less,
+                stencil: Default::default(),
+                bias: DepthBiasState {
+                    constant: 2,
+                    slope_scale: 2.0,
+                    clamp: 0.0,
+                },
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        Self {
+            pipeline,
+            face_views,
+            face_buffers,
+            face_bind_groups,
+            matrices,
+            view,
+            sampler,
+        }
+    }
+
+    /// Move the light to a position in render space
+    pub fn update(&self, queue: &Queue, light: Point3<f32>) {
+        let projection =
+            OPENGL_TO_WGPU_MATRIX * cgmath::perspective(Deg(90.0), 1.0, Self::NEAR, Self::FAR);
+        let matrices = FACES.map(|(direction, up)| {
+            let matrix: [[f32; 4]; 4] =
+                (projection * Matrix4::look_to_rh(light, direction, up)).into();
+            matrix
+        });
+        for (buffer, matrix) in self.face_buffers.iter().zip(&matrices) {
+            queue.write_buffer(buffer, 0, bytemuck::cast_slice(&[*matrix]));
+        }
+        queue.write_buffer(&self.matrices, 0, bytemuck::cast_slice(&matrices));
+    }
+
+    /// Render the instances' depth into every layer
+    pub fn render(
+        &self,
+        encoder: &mut CommandEncoder,
+        vertex_buffer: &Buffer,
+        index_buffer: &Buffer,
+        num_indices: u32,
+        instance_buffer: &Buffer,
+        num_instances: u32,
+    ) {
+        for (view, bind_group) in self.face_views.iter().zip(&self.face_bind_groups) {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("Shadow Pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: true,
+                    }),
+                    stencil_ops: None,
+                }),
+            });
+            render_pass.set_pipeline(&self.pipeline);
+            render_pass.set_bind_group(0, bind_group, &[]);
+            render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+            render_pass.draw_indexed(0..num_indices, 0, 0..num_instances);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use cgmath::Vector3;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use cgmath::{InnerSpace, Vector3};
 
 use crate::render::Vertex;
 
@@ -9,6 +12,9 @@ pub fn octahedron() -> (Vec<Vertex>, Vec<u16>) {
         .map(|dir| Vertex {
             position: dir.into(),
             tex_coords: [dir.x, dir.z].map(|c| (c + 1.0) / 2.0),
+            normal: dir.into(),
+            // v follows z, which is the bitangent's direction only on the bottom half
+            tangent: [1.0, 0.0, 0.0, if dir.y < 0.0 { 1.0 } else { -1.0 }],
         })
         .collect();
 
@@ -32,3 +38,155 @@ pub fn octahedron() -> (Vec<Vertex>, Vec<u16>) {
 
     (vertexes, indexes)
 }
+
+/// Unit sphere built by subdividing an icosahedron
+///
+/// Each subdivision splits every triangle into four.
+/// The uv coordinates are an equirectangular projection
+/// and vertices along its seam are duplicated, so textures don't get squashed there.
+///
+/// At most 6 subdivisions fit into `u16` indexes.
+pub fn icosphere(subdivisions: u8) -> (Vec<Vertex>, Vec<u16>) {
+    assert!(
+        subdivisions <= 6,
+        "{subdivisions} subdivisions don't fit into u16 indexes"
+    );
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut positions: Vec<Vector3<f32>> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .into_iter()
+    .map(|p| Vector3::from(p).normalize())
+    .collect();
+
+    #[rustfmt::skip]
+    let mut faces: Vec<[u16; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u16, b: u16| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                let position = (positions[a as usize] + positions[b as usize]).normalize();
+                positions.push(position);
+                (positions.len() - 1) as u16
+            })
+        };
+        faces = faces
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let ab = midpoint(a, b);
+                let bc = midpoint(b, c);
+                let ca = midpoint(c, a);
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let mut vertexes: Vec<_> = positions
+        .into_iter()
+        .map(|p| Vertex {
+            position: p.into(),
+            tex_coords: [0.5 + p.z.atan2(p.x) / (2.0 * PI), 0.5 - p.y.asin() / PI],
+            normal: p.into(),
+            tangent: sphere_tangent(p),
+        })
+        .collect();
+
+    // Triangles crossing the seam would interpolate across the whole texture,
+    // so they are cut at the largest gap between their corners' u coordinates instead
+    // and the corners left of the cut get a copy shifted by one.
+    let mut shifted = HashMap::new();
+    for face in &mut faces {
+        let mut corners = *face;
+        corners.sort_by(|a, b| {
+            let u = |index: &u16| vertexes[*index as usize].tex_coords[0];
+            u(a).total_cmp(&u(b))
+        });
+        let [low, mid, high] = corners.map(|index| vertexes[index as usize].tex_coords[0]);
+        let shift: &[u16] = if 1.0 + low - high >= (mid - low).max(high - mid) {
+            &[]
+        } else if mid - low > high - mid {
+            &corners[..1]
+        } else {
+            &corners[..2]
+        };
+        for index in face.iter_mut().filter(|index| shift.contains(index)) {
+            *index = *shifted.entry(*index).or_insert_with(|| {
+                let mut vertex = vertexes[*index as usize];
+                vertex.tex_coords[0] += 1.0;
+                vertexes.push(vertex);
+                (vertexes.len() - 1) as u16
+            });
+        }
+    }
+
+    (vertexes, faces.into_iter().flatten().collect())
+}
+
+/// Tangent of a point on the unit sphere for the uv coordinates of [`icosphere`]
+///
+/// u follows the longitude and v the latitude southwards.
+fn sphere_tangent(p: Vector3<f32>) -> [f32; 4] {
+    let tangent = Vector3::new(-p.z, 0.0, p.x);
+    // The longitude is undefined at the poles, so any direction will do
+    let tangent = if tangent.magnitude2() > f32::EPSILON {
+        tangent.normalize()
+    } else {
+        Vector3::unit_x()
+    };
+    [tangent.x, tangent.y, tangent.z, 1.0]
+}
+
+/// Flat ring in the xz plane between two radii facing up
+///
+/// The u coordinate goes from the inner to the outer edge
+/// and the v coordinate once around the ring.
+///
+/// At most 32767 segments fit into `u16` indexes.
+pub fn annulus(inner: f32, outer: f32, segments: u16) -> (Vec<Vertex>, Vec<u16>) {
+    assert!(
+        segments <= i16::MAX as u16,
+        "{segments} segments don't fit into u16 indexes"
+    );
+    let vertexes = (0..=segments)
+        .flat_map(|segment| {
+            let v = segment as f32 / segments as f32;
+            let (sin, cos) = (v * 2.0 * PI).sin_cos();
+            [(0.0, inner), (1.0, outer)].map(|(u, radius)| Vertex {
+                position: [cos * radius, 0.0, sin * radius],
+                tex_coords: [u, v],
+                normal: [0.0, 1.0, 0.0],
+                // u points outwards, v goes around against the bitangent
+                tangent: [cos, 0.0, sin, -1.0],
+            })
+        })
+        .collect();
+
+    let indexes = (0..segments)
+        .flat_map(|segment| {
+            let inner = segment * 2;
+            let outer = inner + 1;
+            let next_inner = inner + 2;
+            let next_outer = inner + 3;
+            [inner, outer, next_outer, inner, next_outer, next_inner]
+        })
+        .collect();
+
+    (vertexes, indexes)
+}
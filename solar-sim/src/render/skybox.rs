@@ -0,0 +1,222 @@
+//! Background drawn behind everything else
+
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, SquareMatrix, Vector3};
+use image::{DynamicImage, Rgba, RgbaImage};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    ColorTargetState, CompareFunction, DepthStencilState, Device, FragmentState,
+    PipelineLayoutDescriptor, Queue, RenderPass, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModuleDescriptor, ShaderSource, ShaderStages, TextureFormat, VertexState,
+};
+
+use crate::render::camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX};
+use crate::render::split::VIEWS;
+use crate::render::texture::Texture;
+
+/// Draws a cubemap at infinite depth
+pub struct Skybox {
+    pipeline: RenderPipeline,
+    /// Uniform and bind group of each view of a [`SplitScreen`](crate::render::split::SplitScreen)
+    views: [(Buffer, BindGroup); VIEWS],
+    #[allow(dead_code)]
+    texture: Texture,
+}
+
+impl Skybox {
+    /// Create a skybox from a texture created by [`Texture::cube_from_images`]
+    pub fn new(device: &Device, format: TextureFormat, texture: Texture) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Skybox Shader"),
+            source: ShaderSource::Wgsl(include_str!("../skybox.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::Cube,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("skybox_bind_group_layout"),
+        });
+
+        let views = [(); VIEWS].map(|_| {
+            let buffer = device.create_buffer_init(&BufferInitDescriptor {
+                label: Some("Skybox Buffer"),
+                contents: bytemuck::cast_slice(&[<Matrix4<f32> as Into<[[f32; 4]; 4]>>::into(
+                    Matrix4::identity(),
+                )]),
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            });
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                layout: &layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: BindingResource::TextureView(&texture.view),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: BindingResource::Sampler(&texture.sampler),
+                    },
+                ],
+                label: Some("skybox_bind_group"),
+            });
+            (buffer, bind_group)
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Skybox Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Skybox Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                // The triangle lies exactly on the far plane where nothing else has been drawn
+                depth_write_enabled: false,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        Self {
+            pipeline,
+            views,
+            texture,
+        }
+    }
+
+    /// Follow the rotation of the camera of some view
+    pub fn update(&self, queue: &Queue, view: usize, camera: &Camera, projection: &Projection) {
+        let rotation = Matrix4::look_to_rh(Point3::origin(), camera.direction(), Vector3::unit_y());
+        let view_proj = OPENGL_TO_WGPU_MATRIX * projection.matrix() * rotation;
+        let Some(inverse) = view_proj.invert() else {
+            return;
+        };
+        let matrix: [[f32; 4]; 4] = inverse.into();
+        queue.write_buffer(&self.views[view].0, 0, bytemuck::cast_slice(&[matrix]));
+    }
+
+    pub fn draw<'a>(&'a self, render_pass: &mut RenderPass<'a>, view: usize) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.views[view].1, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Generate the faces of a cubemap showing randomly scattered stars
+///
+/// The faces are ordered +x, -x, +y, -y, +z, -z like [`Texture::cube_from_images`] expects.
+pub fn starfield(seed: u64, size: u32, stars: usize) -> [DynamicImage; 6] {
+    let mut faces = [(); 6].map(|_| RgbaImage::from_pixel(size, size, Rgba([0, 0, 0, 255])));
+
+    // xorshift64
+    let mut rng = seed | 1;
+    let mut uniform = || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        (rng >> 40) as f32 / (1u64 << 24) as f32
+    };
+
+    for _ in 0..stars {
+        // Uniformly distributed direction
+        let z = uniform() * 2.0 - 1.0;
+        let angle = uniform() * std::f32::consts::TAU;
+        let r = (1.0 - z * z).sqrt();
+        let direction = Vector3::new(r * angle.cos(), r * angle.sin(), z).normalize();
+
+        // Many faint stars and few bright ones, either reddish or bluish
+        let brightness = uniform().powi(6);
+        let tint = uniform();
+        let color = [
+            brightness * (0.8 + 0.2 * tint),
+            brightness * 0.9,
+            brightness * (1.0 - 0.2 * tint),
+        ];
+
+        let (face, u, v) = cube_coordinates(direction);
+        let x = ((u * size as f32) as u32).min(size - 1);
+        let y = ((v * size as f32) as u32).min(size - 1);
+        let pixel = faces[face].get_pixel_mut(x, y);
+        for (channel, value) in pixel.0.iter_mut().zip(color) {
+            *channel = channel.saturating_add((value * 255.0) as u8);
+        }
+    }
+
+    faces.map(DynamicImage::ImageRgba8)
+}
+
+/// Face and texture coordinates a direction samples from a cubemap
+fn cube_coordinates(d: Vector3<f32>) -> (usize, f32, f32) {
+    let a = Vector3::new(d.x.abs(), d.y.abs(), d.z.abs());
+    let (face, s, t, major) = if a.x >= a.y && a.x >= a.z {
+        if d.x > 0.0 {
+            (0, -d.z, -d.y, a.x)
+        } else {
+            (1, d.z, -d.y, a.x)
+        }
+    } else if a.y >= a.z {
+        if d.y > 0.0 {
+            (2, d.x, d.z, a.y)
+        } else {
+            (3, d.x, -d.z, a.y)
+        }
+    } else if d.z > 0.0 {
+        (4, d.x, -d.y, a.z)
+    } else {
+        (5, -d.x, -d.y, a.z)
+    };
+    (face, (s / major + 1.0) / 2.0, (t / major + 1.0) / 2.0)
+}
@@ -0,0 +1,130 @@
+//! Second camera rendered next to the main one
+
+use cgmath::{Matrix4, Point3, Rad, SquareMatrix};
+use specs::{System, Write};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, Buffer, BufferUsages, Device,
+    Queue,
+};
+
+use crate::control::Controls;
+use crate::render::camera::{Camera, CameraUniform};
+use crate::render::grid::AU;
+use crate::render::SCALE;
+
+/// Number of views the screen is split into at most
+pub const VIEWS: usize = 2;
+
+/// Split screen resource
+///
+/// While set, the screen is split into halves with the [`Camera`] on the left and this one on the right.
+/// Both halves show the same world, unless there's a [`Comparison`](crate::render::compare::Comparison).
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SplitScreen(pub Option<Camera>);
+
+impl SplitScreen {
+    /// Camera looking down onto the ecliptic plane from far enough to see the outer planets
+    pub fn overview() -> Self {
+        Self(Some(Camera {
+            position: Point3::new(0.0, 60.0 * AU / SCALE, 0.0),
+            yaw: Rad(0.0),
+            pitch: Rad(-std::f32::consts::FRAC_PI_2 + 0.0001),
+        }))
+    }
+}
+
+/// System swapping the [`Camera`] with the [`SplitScreen`]'s when tab is pressed,
+/// so either can be flown with the usual controls
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SwapCameras;
+
+impl<'a> System<'a> for SwapCameras {
+    type SystemData = (
+        Write<'a, Controls>,
+        Write<'a, Camera>,
+        Write<'a, SplitScreen>,
+    );
+
+    fn run(&mut self, (mut controls, mut camera, mut split): Self::SystemData) {
+        if !controls.swap_cameras {
+            return;
+        }
+        controls.swap_cameras = false;
+        if let Some(second) = &mut split.0 {
+            std::mem::swap(&mut *camera, second);
+        }
+    }
+}
+
+/// Uniform of the [`SplitScreen`]'s camera and the viewports of both halves
+pub struct SplitView {
+    camera_buffer: Buffer,
+    camera_bind_group: BindGroup,
+    /// Whether the screen is split at all
+    active: bool,
+}
+
+impl SplitView {
+    pub fn new(device: &Device, camera_layout: &BindGroupLayout) -> Self {
+        let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Split Camera Buffer"),
+            contents: bytemuck::cast_slice(&[CameraUniform::new(
+                Matrix4::identity(),
+                Point3::new(0.0, 0.0, 0.0),
+            )]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+        let camera_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: camera_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: camera_buffer.as_entire_binding(),
+            }],
+            label: Some("split_camera_bind_group"),
+        });
+
+        Self {
+            camera_buffer,
+            camera_bind_group,
+            active: false,
+        }
+    }
+
+    /// Upload the second camera's view projection, or give the whole screen to the main camera
+    pub fn update(&mut self, queue: &Queue, camera: Option<(Matrix4<f32>, Point3<f32>)>) {
+        self.active = camera.is_some();
+        if let Some((view_proj, position)) = camera {
+            queue.write_buffer(
+                &self.camera_buffer,
+                0,
+                bytemuck::cast_slice(&[CameraUniform::new(view_proj, position)]),
+            );
+        }
+    }
+
+    /// Size in pixels of the main camera's viewport on a screen of some size
+    fn viewport(&self, [width, height]: [f32; 2]) -> [f32; 2] {
+        if self.active {
+            [width / 2.0, height]
+        } else {
+            [width, height]
+        }
+    }
+
+    /// Camera bind group and viewport as x, y, width and height of each view
+    ///
+    /// The main camera's comes first and is passed in as `main`.
+    pub fn views<'a>(
+        &'a self,
+        main: &'a BindGroup,
+        size: [f32; 2],
+    ) -> Vec<(&'a BindGroup, [f32; 4])> {
+        let [width, height] = self.viewport(size);
+        let mut views = vec![(main, [0.0, 0.0, width, height])];
+        if self.active {
+            views.push((&self.camera_bind_group, [width, 0.0, width, height]));
+        }
+        views
+    }
+}
@@ -0,0 +1,59 @@
+//! Overlay showing how fast frames are drawn
+
+use specs::{Read, System, Write};
+
+use crate::control::Controls;
+use crate::render::text::{Text, TextQueue};
+use crate::render::timestamps::GpuStats;
+use crate::render::tonemap::PostProcess;
+use crate::timer::FrameStats;
+
+/// System drawing the [`FrameStats`] and [`GpuStats`] in the top left corner
+/// while [`Controls::show_stats`] is set
+///
+/// Updates [`TextQueue`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct StatsOverlay;
+
+impl<'a> System<'a> for StatsOverlay {
+    type SystemData = (
+        Read<'a, Controls>,
+        Read<'a, FrameStats>,
+        Read<'a, GpuStats>,
+        Read<'a, PostProcess>,
+        Write<'a, TextQueue>,
+    );
+
+    fn run(&mut self, (controls, stats, gpu, post_process, mut texts): Self::SystemData) {
+        if !controls.show_stats {
+            return;
+        }
+        let fps = |frame: std::time::Duration| match frame.as_secs_f32() {
+            secs if secs > 0.0 => 1.0 / secs,
+            _ => 0.0,
+        };
+        let ms = |duration: std::time::Duration| duration.as_secs_f32() * 1000.0;
+        let average = stats.average();
+        let low = stats.one_percent_low();
+        let mut content = format!(
+            "{:.0} fps ({:.2} ms)\n1% low: {:.0} fps ({:.2} ms)\nphysics: {:.2} ms\nrender: {:.2} ms",
+            fps(average),
+            ms(average),
+            fps(low),
+            ms(low),
+            ms(stats.physics),
+            ms(stats.render),
+        );
+        if !gpu.passes.is_empty() {
+            content += &format!("\ngpu: {:.2} ms", ms(gpu.total()));
+            for (name, duration) in &gpu.passes {
+                content += &format!("\n  {name}: {:.2} ms", ms(*duration));
+            }
+        }
+        content += &format!(
+            "\nexposure: {:.2}, gamma: {:.2}, saturation: {:.2}",
+            post_process.exposure, post_process.gamma, post_process.saturation,
+        );
+        texts.push(Text::new(content, [8.0, 20.0], 16.0));
+    }
+}
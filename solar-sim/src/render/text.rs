@@ -0,0 +1,455 @@
+//! Draw strings on top of everything using a glyph atlas
+//!
+//! Systems push [`Text`]s to the [`TextQueue`] resource and [`Render`](crate::render::Render) draws them.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use ab_glyph::{point, Font, FontRef, GlyphId, ScaleFont};
+use log::warn;
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    vertex_attr_array, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, BlendState, Buffer, BufferAddress,
+    BufferBindingType, BufferDescriptor, BufferUsages, ColorTargetState, CommandEncoder, Device,
+    Extent3d, FragmentState, PipelineLayoutDescriptor, Queue, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, ShaderStages,
+    TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor, VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::error::DynError;
+
+/// How a [`Text`] is placed relative to its position
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Align {
+    /// The text starts at the position
+    #[default]
+    Left,
+
+    /// The text's middle is at the position
+    Center,
+}
+
+/// A string to draw
+#[derive(Clone, Debug)]
+pub struct Text {
+    pub content: String,
+
+    /// Pixels from the window's top left corner to the first line's baseline
+    pub position: [f32; 2],
+
+    /// Height in pixels
+    pub size: f32,
+
+    /// Linear rgba
+    pub color: [f32; 4],
+
+    pub align: Align,
+}
+
+impl Text {
+    /// White left aligned text
+    pub fn new(content: impl Into<String>, position: [f32; 2], size: f32) -> Self {
+        Self {
+            content: content.into(),
+            position,
+            size,
+            color: [1.0; 4],
+            align: Align::Left,
+        }
+    }
+}
+
+/// Resource of texts to draw in the next frame
+///
+/// Emptied whenever a frame is drawn, so systems have to push their texts on every dispatch.
+#[derive(Clone, Debug, Default)]
+pub struct TextQueue(pub Vec<Text>);
+
+impl TextQueue {
+    pub fn push(&mut self, text: Text) {
+        self.0.push(text);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct GlyphInstance {
+    rect: [f32; 4],
+    uv: [f32; 4],
+    color: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct ScreenUniform {
+    size: [f32; 2],
+    _padding: [f32; 2],
+}
+
+/// A glyph rasterized into the atlas
+#[derive(Copy, Clone, Debug)]
+struct CachedGlyph {
+    /// Offset from the pen position to the glyph's top left corner in pixels
+    offset: [f32; 2],
+
+    /// Width and height in pixels
+    size: [f32; 2],
+
+    /// Left, top, right and bottom in the atlas
+    uv: [f32; 4],
+}
+
+/// Packs glyphs into rows from top to bottom
+#[derive(Copy, Clone, Debug, Default)]
+struct Shelf {
+    x: u32,
+    y: u32,
+    height: u32,
+}
+
+/// Renders [`Text`]s onto the surface
+pub struct TextRenderer {
+    font: FontRef<'static>,
+    pipeline: RenderPipeline,
+    atlas: wgpu::Texture,
+    shelf: Shelf,
+    /// Glyphs by character and pixel size, `None` for glyphs without an outline like spaces
+    glyphs: HashMap<(char, u32), Option<CachedGlyph>>,
+    screen: Buffer,
+    bind_group: BindGroup,
+    instances: Buffer,
+    capacity: usize,
+    len: u32,
+}
+
+impl TextRenderer {
+    /// Width and height of the glyph atlas
+    const ATLAS_SIZE: u32 = 1024;
+
+    pub fn new(device: &Device, format: TextureFormat) -> Result<Self, DynError> {
+        let font = FontRef::try_from_slice(include_bytes!("../fonts/DejaVuSans.ttf"))?;
+
+        let atlas = device.create_texture(&TextureDescriptor {
+            label: Some("Glyph Atlas"),
+            size: Extent3d {
+                width: Self::ATLAS_SIZE,
+                height: Self::ATLAS_SIZE,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::R8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = atlas.create_view(&TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let screen = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Screen Buffer"),
+            contents: bytemuck::cast_slice(&[ScreenUniform {
+                size: [1.0; 2],
+                _padding: [0.0; 2],
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::VERTEX,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("text_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout: &layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: screen.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(&sampler),
+                },
+            ],
+            label: Some("text_bind_group"),
+        });
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Text Shader"),
+            source: ShaderSource::Wgsl(include_str!("../text.wgsl").into()),
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Text Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Text Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<GlyphInstance>() as BufferAddress,
+                    step_mode: VertexStepMode::Instance,
+                    attributes: &vertex_attr_array![0 => Float32x4, 1 => Float32x4, 2 => Float32x4],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let capacity = 256;
+        let instances = Self::create_buffer(device, capacity);
+
+        Ok(Self {
+            font,
+            pipeline,
+            atlas,
+            shelf: Shelf::default(),
+            glyphs: HashMap::new(),
+            screen,
+            bind_group,
+            instances,
+            capacity,
+            len: 0,
+        })
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Glyph Buffer"),
+            size: (capacity * size_of::<GlyphInstance>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Width of a single line in pixels
+    pub fn measure(&self, content: &str, size: f32) -> f32 {
+        let font = self.font.as_scaled(size);
+        let mut width = 0.0;
+        let mut previous: Option<GlyphId> = None;
+        for c in content.chars() {
+            let id = font.glyph_id(c);
+            if let Some(previous) = previous {
+                width += font.kern(previous, id);
+            }
+            width += font.h_advance(id);
+            previous = Some(id);
+        }
+        width
+    }
+
+    /// Rasterize a glyph into the atlas unless it already is
+    fn glyph(&mut self, queue: &Queue, c: char, size: u32) -> Option<CachedGlyph> {
+        if let Some(glyph) = self.glyphs.get(&(c, size)) {
+            return *glyph;
+        }
+
+        let glyph = self
+            .font
+            .glyph_id(c)
+            .with_scale_and_position(size as f32, point(0.0, 0.0));
+        let cached = self.font.outline_glyph(glyph).and_then(|outline| {
+            let bounds = outline.px_bounds();
+            let width = bounds.width() as u32;
+            let height = bounds.height() as u32;
+            if width == 0 || height == 0 {
+                return None;
+            }
+
+            if self.shelf.x + width > Self::ATLAS_SIZE {
+                self.shelf.x = 0;
+                self.shelf.y += self.shelf.height + 1;
+                self.shelf.height = 0;
+            }
+            if self.shelf.y + height > Self::ATLAS_SIZE {
+                warn!("Glyph atlas is full, can't draw {c:?}");
+                return None;
+            }
+            let (x, y) = (self.shelf.x, self.shelf.y);
+            self.shelf.x += width + 1;
+            self.shelf.height = self.shelf.height.max(height);
+
+            let mut pixels = vec![0u8; (width * height) as usize];
+            outline.draw(|px, py, coverage| {
+                if px < width && py < height {
+                    pixels[(py * width + px) as usize] = (coverage * 255.0) as u8;
+                }
+            });
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &self.atlas,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d { x, y, z: 0 },
+                },
+                &pixels,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(width),
+                    rows_per_image: Some(height),
+                },
+                Extent3d {
+                    width,
+                    height,
+                    depth_or_array_layers: 1,
+                },
+            );
+
+            let atlas = Self::ATLAS_SIZE as f32;
+            Some(CachedGlyph {
+                offset: [bounds.min.x, bounds.min.y],
+                size: [width as f32, height as f32],
+                uv: [
+                    x as f32 / atlas,
+                    y as f32 / atlas,
+                    (x + width) as f32 / atlas,
+                    (y + height) as f32 / atlas,
+                ],
+            })
+        });
+        self.glyphs.insert((c, size), cached);
+        cached
+    }
+
+    /// Lay out the texts and upload their glyphs
+    pub fn prepare(&mut self, device: &Device, queue: &Queue, size: [f32; 2], texts: &[Text]) {
+        queue.write_buffer(
+            &self.screen,
+            0,
+            bytemuck::cast_slice(&[ScreenUniform {
+                size,
+                _padding: [0.0; 2],
+            }]),
+        );
+
+        let mut instances = Vec::new();
+        for text in texts {
+            let pixels = text.size.round().max(1.0) as u32;
+            let font = self.font.as_scaled(pixels as f32);
+            let line_height = font.height() + font.line_gap();
+
+            let mut advances = Vec::new();
+            for (line, content) in text.content.lines().enumerate() {
+                let mut x = text.position[0];
+                if text.align == Align::Center {
+                    x -= self.measure(content, pixels as f32) / 2.0;
+                }
+                let y = text.position[1] + line as f32 * line_height;
+
+                let mut previous: Option<GlyphId> = None;
+                for c in content.chars() {
+                    let id = font.glyph_id(c);
+                    if let Some(previous) = previous {
+                        x += font.kern(previous, id);
+                    }
+                    advances.push((c, x, y));
+                    x += font.h_advance(id);
+                    previous = Some(id);
+                }
+            }
+
+            for (c, x, y) in advances {
+                if let Some(glyph) = self.glyph(queue, c, pixels) {
+                    instances.push(GlyphInstance {
+                        rect: [
+                            (x + glyph.offset[0]).round(),
+                            (y + glyph.offset[1]).round(),
+                            glyph.size[0],
+                            glyph.size[1],
+                        ],
+                        uv: glyph.uv,
+                        color: text.color,
+                    });
+                }
+            }
+        }
+
+        if instances.len() > self.capacity {
+            self.capacity = instances.len().next_power_of_two();
+            self.instances = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.instances, 0, bytemuck::cast_slice(&instances));
+        self.len = instances.len() as u32;
+    }
+
+    /// Draw the prepared texts onto the output
+    pub fn draw(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        if self.len == 0 {
+            return;
+        }
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.set_vertex_buffer(0, self.instances.slice(..));
+        render_pass.draw(0..6, 0..self.len);
+    }
+}
@@ -1,6 +1,123 @@
 use image::GenericImageView;
 
-use crate::error::DynError;
+use crate::error::{CustomError, DynError};
+use crate::render::mipmap::MipmapGenerator;
+
+/// Bytes every KTX2 container starts with
+const KTX2_IDENTIFIER: [u8; 12] = [
+    0xAB, b'K', b'T', b'X', b' ', b'2', b'0', 0xBB, b'\r', b'\n', 0x1A, b'\n',
+];
+
+/// Size of a KTX2 container's header and index preceding its level index
+const KTX2_HEADER_SIZE: usize = 80;
+
+/// Contents of a KTX2 container
+struct Ktx2<'a> {
+    format: wgpu::TextureFormat,
+    width: u32,
+    height: u32,
+    /// Data of each mip level starting with the largest
+    levels: Vec<&'a [u8]>,
+}
+
+impl<'a> Ktx2<'a> {
+    /// Parse a 2D texture without supercompression
+    fn parse(bytes: &'a [u8]) -> Result<Self, CustomError> {
+        if !bytes.starts_with(&KTX2_IDENTIFIER) {
+            return Err(CustomError::from("Not a KTX2 container"));
+        }
+        let u32_at = |offset: usize| {
+            bytes
+                .get(offset..offset + 4)
+                .map(|field| u32::from_le_bytes(field.try_into().unwrap()))
+                .ok_or(CustomError::from("Truncated KTX2 container"))
+        };
+        let u64_at = |offset: usize| {
+            bytes
+                .get(offset..offset + 8)
+                .map(|field| u64::from_le_bytes(field.try_into().unwrap()) as usize)
+                .ok_or(CustomError::from("Truncated KTX2 container"))
+        };
+
+        let vk_format = u32_at(12)?;
+        let format = Self::format(vk_format)
+            .ok_or_else(|| CustomError::from(format!("Unsupported KTX2 format {vk_format}")))?;
+        let (width, height) = (u32_at(20)?, u32_at(24)?);
+        let (depth, layers, faces) = (u32_at(28)?, u32_at(32)?, u32_at(36)?);
+        if width == 0 || height == 0 || depth != 0 || layers != 0 || faces != 1 {
+            return Err(CustomError::from(
+                "Only plain 2D KTX2 textures are supported",
+            ));
+        }
+        if u32_at(44)? != 0 {
+            return Err(CustomError::from(
+                "Supercompressed KTX2 containers are not supported",
+            ));
+        }
+
+        // Zero asks the loader to generate the mips,
+        // which can't be rendered into compressed formats
+        let level_count = u32_at(40)?.max(1);
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        if level_count > size.max_mips(wgpu::TextureDimension::D2) {
+            return Err(CustomError::from("KTX2 container has too many mip levels"));
+        }
+        let levels = (0..level_count as usize)
+            .map(|level| {
+                let entry = KTX2_HEADER_SIZE + 24 * level;
+                let (offset, length) = (u64_at(entry)?, u64_at(entry + 8)?);
+                let data = bytes
+                    .get(offset..offset.saturating_add(length))
+                    .ok_or(CustomError::from("Truncated KTX2 container"))?;
+
+                // Compressed formats are copied in whole blocks
+                let extent = size
+                    .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+                    .physical_size(format);
+                let (block_width, block_height) = format.block_dimensions();
+                let block_size = format.block_size(None).unwrap_or(0) as usize;
+                let expected = (extent.width / block_width) as usize
+                    * (extent.height / block_height) as usize
+                    * block_size;
+                if data.len() < expected {
+                    return Err(CustomError::from(format!(
+                        "KTX2 mip level {level} is too small"
+                    )));
+                }
+                Ok(data)
+            })
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            levels,
+        })
+    }
+
+    /// Texture format of a `VkFormat`
+    fn format(vk_format: u32) -> Option<wgpu::TextureFormat> {
+        use wgpu::TextureFormat::*;
+        Some(match vk_format {
+            37 => Rgba8Unorm,
+            43 => Rgba8UnormSrgb,
+            131 | 133 => Bc1RgbaUnorm,
+            132 | 134 => Bc1RgbaUnormSrgb,
+            137 => Bc3RgbaUnorm,
+            138 => Bc3RgbaUnormSrgb,
+            139 => Bc4RUnorm,
+            141 => Bc5RgUnorm,
+            145 => Bc7RgbaUnorm,
+            146 => Bc7RgbaUnormSrgb,
+            _ => return None,
+        })
+    }
+}
 
 pub struct Texture {
     pub texture: wgpu::Texture,
@@ -11,6 +128,14 @@ pub struct Texture {
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
+    /// Comparison passing fragments closer to the camera
+    ///
+    /// The camera's depth is reversed, see [`Projection::reversed_z`](crate::render::camera::Projection::reversed_z).
+    pub const DEPTH_COMPARE: wgpu::CompareFunction = wgpu::CompareFunction::Greater;
+
+    /// Depth of a fragment infinitely far away from the camera
+    pub const DEPTH_CLEAR: f32 = 0.0;
+
     pub fn create_depth_texture(
         device: &wgpu::Device,
         config: &wgpu::SurfaceConfiguration,
@@ -110,6 +235,23 @@ impl Texture {
         queue: &wgpu::Queue,
         img: &image::DynamicImage,
         label: Option<&str>,
+    ) -> Result<Self, DynError> {
+        Self::from_image_with_format(
+            device,
+            queue,
+            img,
+            label,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Like [`Texture::from_image`], but with a format other than srgb e.g. for normal maps
+    pub fn from_image_with_format(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
     ) -> Result<Self, DynError> {
         let rgba = img.to_rgba8();
         let dimensions = img.dimensions();
@@ -119,7 +261,6 @@ impl Texture {
             height: dimensions.1,
             depth_or_array_layers: 1,
         };
-        let format = wgpu::TextureFormat::Rgba8UnormSrgb;
         let texture = device.create_texture(&wgpu::TextureDescriptor {
             label,
             size,
@@ -164,4 +305,208 @@ impl Texture {
             sampler,
         })
     }
+
+    /// Like [`Texture::from_image_with_format`], but with a full mip chain
+    /// and the sampler of the [`MipmapGenerator`] for textures wrapped around bodies
+    pub fn from_image_with_mipmaps(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+        img: &image::DynamicImage,
+        label: Option<&str>,
+        format: wgpu::TextureFormat,
+    ) -> Result<Self, DynError> {
+        let rgba = img.to_rgba8();
+        let dimensions = img.dimensions();
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: MipmapGenerator::level_count(dimensions.0, dimensions.1),
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING
+                | wgpu::TextureUsages::COPY_DST
+                | wgpu::TextureUsages::RENDER_ATTACHMENT,
+            view_formats: &[],
+        });
+
+        queue.write_texture(
+            wgpu::ImageCopyTexture {
+                aspect: wgpu::TextureAspect::All,
+                texture: &texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            &rgba,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            size,
+        );
+        mipmaps.generate(device, queue, &texture);
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self {
+            texture,
+            view,
+            sampler: mipmaps.sampler(device),
+        })
+    }
+
+    /// Format of a KTX2 container's texture
+    ///
+    /// Check it against the device's features before calling [`Texture::from_ktx2`].
+    pub fn ktx2_format(bytes: &[u8]) -> Result<wgpu::TextureFormat, DynError> {
+        Ok(Ktx2::parse(bytes)?.format)
+    }
+
+    /// Load a KTX2 container with BC1, BC3, BC4, BC5, BC7 or uncompressed RGBA8 payload
+    ///
+    /// Its mip levels are used as stored with the sampler of the [`MipmapGenerator`].
+    pub fn from_ktx2(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        mipmaps: &MipmapGenerator,
+        bytes: &[u8],
+        label: Option<&str>,
+    ) -> Result<Self, DynError> {
+        let ktx2 = Ktx2::parse(bytes)?;
+        let missing = ktx2.format.required_features() - device.features();
+        if !missing.is_empty() {
+            return Err(CustomError::from(format!(
+                "{:?} requires the missing features {missing:?}",
+                ktx2.format
+            ))
+            .into());
+        }
+
+        let size = wgpu::Extent3d {
+            width: ktx2.width,
+            height: ktx2.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: ktx2.levels.len() as u32,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: ktx2.format,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        let (block_width, block_height) = ktx2.format.block_dimensions();
+        let block_size = ktx2.format.block_size(None).unwrap_or(0);
+        for (level, data) in ktx2.levels.iter().enumerate() {
+            let extent = size
+                .mip_level_size(level as u32, wgpu::TextureDimension::D2)
+                .physical_size(ktx2.format);
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: level as u32,
+                    origin: wgpu::Origin3d::ZERO,
+                },
+                data,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(extent.width / block_width * block_size),
+                    rows_per_image: Some(extent.height / block_height),
+                },
+                extent,
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        Ok(Self {
+            texture,
+            view,
+            sampler: mipmaps.sampler(device),
+        })
+    }
+
+    /// Load a cubemap from six equally sized square images ordered +x, -x, +y, -y, +z, -z
+    pub fn cube_from_images(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        faces: &[image::DynamicImage; 6],
+        label: Option<&str>,
+    ) -> Result<Self, DynError> {
+        let dimensions = faces[0].dimensions();
+        if faces.iter().any(|face| face.dimensions() != dimensions) {
+            return Err(CustomError::from("Cubemap faces differ in size").into());
+        }
+
+        let size = wgpu::Extent3d {
+            width: dimensions.0,
+            height: dimensions.1,
+            depth_or_array_layers: 6,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+
+        for (layer, face) in faces.iter().enumerate() {
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    aspect: wgpu::TextureAspect::All,
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                },
+                &face.to_rgba8(),
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * dimensions.0),
+                    rows_per_image: Some(dimensions.1),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Ok(Self {
+            texture,
+            view,
+            sampler,
+        })
+    }
 }
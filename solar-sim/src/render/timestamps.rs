@@ -0,0 +1,180 @@
+//! Measuring how long the gpu spends on each pass
+
+use std::mem::size_of;
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use log::warn;
+use wgpu::{
+    Buffer, BufferAddress, BufferAsyncError, BufferDescriptor, BufferUsages, CommandEncoder,
+    Device, QuerySet, QuerySetDescriptor, QueryType, Queue,
+};
+
+/// Timestamps written per frame at most, the first one marking the frame's start
+const MAX_TIMESTAMPS: u32 = 16;
+
+/// Resource of the gpu time each pass of the last measured frame took
+///
+/// Updated by [`Render`](crate::render::Render) while the stats are shown,
+/// stays empty if the adapter doesn't support timestamp queries.
+#[derive(Clone, Debug, Default)]
+pub struct GpuStats {
+    /// Name and duration of each pass in the order they ran
+    pub passes: Vec<(&'static str, Duration)>,
+}
+
+impl GpuStats {
+    /// Time of the whole frame
+    pub fn total(&self) -> Duration {
+        self.passes.iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+/// Where the timestamps are in their way back to the cpu
+#[derive(Debug)]
+enum Readback {
+    /// Free to measure the next frame
+    Idle,
+
+    /// Timestamps are being written by the frame being recorded
+    Recording,
+
+    /// Copy into the readback buffer is recorded but not submitted yet
+    Copied,
+
+    /// Waiting for the readback buffer to be mapped
+    Mapping(Receiver<Result<(), BufferAsyncError>>),
+}
+
+/// Writes a timestamp after each pass and reads them back into [`GpuStats`]
+///
+/// Requires [`Features::TIMESTAMP_QUERY`](wgpu::Features::TIMESTAMP_QUERY).
+/// Frames are skipped while the last one is still read back.
+pub struct GpuTimer {
+    query_set: QuerySet,
+    resolve: Buffer,
+    readback: Buffer,
+    /// Nanoseconds per tick of a timestamp
+    period: f32,
+    /// Name of the pass each timestamp but the first ends
+    passes: Vec<&'static str>,
+    state: Readback,
+    /// Whether to measure at all, e.g. only while the stats are shown
+    pub enabled: bool,
+}
+
+impl GpuTimer {
+    pub fn new(device: &Device, queue: &Queue) -> Self {
+        let size = (MAX_TIMESTAMPS as usize * size_of::<u64>()) as BufferAddress;
+        Self {
+            query_set: device.create_query_set(&QuerySetDescriptor {
+                label: Some("Timestamp Query Set"),
+                ty: QueryType::Timestamp,
+                count: MAX_TIMESTAMPS,
+            }),
+            resolve: device.create_buffer(&BufferDescriptor {
+                label: Some("Timestamp Resolve Buffer"),
+                size,
+                usage: BufferUsages::QUERY_RESOLVE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            }),
+            readback: device.create_buffer(&BufferDescriptor {
+                label: Some("Timestamp Readback Buffer"),
+                size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            }),
+            period: queue.get_timestamp_period(),
+            passes: Vec::new(),
+            state: Readback::Idle,
+            enabled: false,
+        }
+    }
+
+    /// Record the frame's first timestamp, unless the last frame is still read back
+    pub fn begin(&mut self, encoder: &mut CommandEncoder) {
+        if !self.enabled || !matches!(self.state, Readback::Idle) {
+            return;
+        }
+        self.passes.clear();
+        encoder.write_timestamp(&self.query_set, 0);
+        self.state = Readback::Recording;
+    }
+
+    /// Record a timestamp ending the pass called `name`, which started at the previous one
+    pub fn end(&mut self, encoder: &mut CommandEncoder, name: &'static str) {
+        let index = self.passes.len() as u32 + 1;
+        if !matches!(self.state, Readback::Recording) || index >= MAX_TIMESTAMPS {
+            return;
+        }
+        encoder.write_timestamp(&self.query_set, index);
+        self.passes.push(name);
+    }
+
+    /// Record copying the frame's timestamps for readback
+    pub fn resolve(&mut self, encoder: &mut CommandEncoder) {
+        if !matches!(self.state, Readback::Recording) {
+            return;
+        }
+        let count = self.passes.len() as u32 + 1;
+        encoder.resolve_query_set(&self.query_set, 0..count, &self.resolve, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve,
+            0,
+            &self.readback,
+            0,
+            (count as usize * size_of::<u64>()) as BufferAddress,
+        );
+        self.state = Readback::Copied;
+    }
+
+    /// Request mapping the readback buffer
+    ///
+    /// Requires the encoder passed to [`GpuTimer::resolve`] to be submitted
+    pub fn request(&mut self) {
+        if let Readback::Copied = self.state {
+            let (sender, receiver) = channel();
+            self.readback
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            self.state = Readback::Mapping(receiver);
+        }
+    }
+
+    /// The measured passes once they arrived
+    pub fn poll(&mut self, device: &Device) -> Option<GpuStats> {
+        let Readback::Mapping(receiver) = &self.state else {
+            return None;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let result = receiver.try_recv().ok()?;
+        self.state = Readback::Idle;
+        if let Err(error) = result {
+            warn!("Failed to read back timestamps: {error}");
+            return None;
+        }
+        let timestamps: Vec<u64> = {
+            let view = self.readback.slice(..).get_mapped_range();
+            view.chunks_exact(size_of::<u64>())
+                .take(self.passes.len() + 1)
+                .map(|bytes| u64::from_ne_bytes(bytes.try_into().unwrap()))
+                .collect()
+        };
+        self.readback.unmap();
+        let passes = self
+            .passes
+            .iter()
+            .zip(timestamps.windows(2))
+            .map(|(name, pair)| {
+                let ticks = pair[1].saturating_sub(pair[0]);
+                (
+                    *name,
+                    Duration::from_nanos((ticks as f64 * self.period as f64) as u64),
+                )
+            })
+            .collect();
+        Some(GpuStats { passes })
+    }
+}
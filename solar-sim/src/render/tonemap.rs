@@ -0,0 +1,289 @@
+//! Map the high dynamic range scene to the surface's colors
+
+use specs::{System, Write};
+use wgpu::util::{BufferInitDescriptor, DeviceExt};
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Buffer, BufferBindingType, BufferUsages,
+    ColorTargetState, CommandEncoder, Device, Extent3d, FragmentState, PipelineLayoutDescriptor,
+    Queue, RenderPipeline, RenderPipelineDescriptor, Sampler, ShaderModuleDescriptor, ShaderSource,
+    ShaderStages, SurfaceConfiguration, Texture, TextureDescriptor, TextureDimension,
+    TextureFormat, TextureUsages, TextureView, TextureViewDescriptor, VertexState,
+};
+
+use crate::control::Controls;
+
+/// Factor the exposure changes by per step, a quarter stop
+const EXPOSURE_STEP: f32 = 1.189_207;
+
+/// Amount the gamma and saturation change by per step
+const STEP: f32 = 0.05;
+
+/// Resource of the adjustments applied while the scene is mapped to the surface
+///
+/// Defaults to leaving the tonemapped colors unchanged
+#[derive(Copy, Clone, Debug)]
+pub struct PostProcess {
+    /// Factor scaling the scene's brightness before it is tonemapped
+    pub exposure: f32,
+    /// Gamma the tonemapped colors are corrected by, higher values brighten the dark parts
+    pub gamma: f32,
+    /// How colorful the tonemapped colors are, `0.0` being grayscale
+    pub saturation: f32,
+}
+
+impl Default for PostProcess {
+    fn default() -> Self {
+        Self {
+            exposure: 1.0,
+            gamma: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+/// System applying the steps queued in [`Controls`] to the [`PostProcess`] resource
+#[derive(Copy, Clone, Debug, Default)]
+pub struct AdjustPostProcess;
+
+impl<'a> System<'a> for AdjustPostProcess {
+    type SystemData = (Write<'a, Controls>, Write<'a, PostProcess>);
+
+    fn run(&mut self, (mut controls, mut post_process): Self::SystemData) {
+        let exposure = std::mem::take(&mut controls.exposure_steps);
+        let gamma = std::mem::take(&mut controls.gamma_steps);
+        let saturation = std::mem::take(&mut controls.saturation_steps);
+        if exposure == 0 && gamma == 0 && saturation == 0 {
+            return;
+        }
+        post_process.exposure =
+            (post_process.exposure * EXPOSURE_STEP.powi(exposure)).clamp(1.0 / 256.0, 256.0);
+        post_process.gamma = (post_process.gamma + gamma as f32 * STEP).clamp(0.2, 5.0);
+        post_process.saturation =
+            (post_process.saturation + saturation as f32 * STEP).clamp(0.0, 2.0);
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TonemapUniform {
+    exposure: f32,
+    gamma: f32,
+    saturation: f32,
+    /// Non-zero if the surface doesn't encode its colors as sRGB, so the shader has to
+    encode_srgb: u32,
+}
+
+/// Offscreen target the scene is rendered to and the pass copying it to the surface
+pub struct Tonemap {
+    pipeline: RenderPipeline,
+    layout: BindGroupLayout,
+    sampler: Sampler,
+    buffer: Buffer,
+    texture: Texture,
+    view: TextureView,
+    bind_group: BindGroup,
+    /// Whether the output is linear and the colors have to be converted to sRGB in the shader
+    encode_srgb: bool,
+}
+
+impl Tonemap {
+    /// Format of the offscreen target every scene pipeline has to render to
+    pub const FORMAT: TextureFormat = TextureFormat::Rgba16Float;
+
+    pub fn new(device: &Device, config: &SurfaceConfiguration) -> Self {
+        let encode_srgb = !config.format.is_srgb();
+        let post_process = PostProcess::default();
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Tonemap Shader"),
+            source: ShaderSource::Wgsl(include_str!("../tonemap.wgsl").into()),
+        });
+
+        let layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+            label: Some("tonemap_bind_group_layout"),
+        });
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("Tonemap Buffer"),
+            contents: bytemuck::cast_slice(&[TonemapUniform {
+                exposure: post_process.exposure,
+                gamma: post_process.gamma,
+                saturation: post_process.saturation,
+                encode_srgb: encode_srgb.into(),
+            }]),
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Nearest,
+            min_filter: wgpu::FilterMode::Nearest,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Tonemap Pipeline Layout"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Tonemap Pipeline"),
+            layout: Some(&pipeline_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format: config.format,
+                    blend: None,
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: Default::default(),
+            depth_stencil: None,
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let (texture, view, bind_group) =
+            Self::create_target(device, &layout, &buffer, &sampler, config);
+
+        Self {
+            pipeline,
+            layout,
+            sampler,
+            buffer,
+            texture,
+            view,
+            bind_group,
+            encode_srgb,
+        }
+    }
+
+    fn create_target(
+        device: &Device,
+        layout: &BindGroupLayout,
+        buffer: &Buffer,
+        sampler: &Sampler,
+        config: &SurfaceConfiguration,
+    ) -> (Texture, TextureView, BindGroup) {
+        let texture = device.create_texture(&TextureDescriptor {
+            label: Some("HDR Target"),
+            size: Extent3d {
+                width: config.width,
+                height: config.height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: Self::FORMAT,
+            // Insets are copied into it
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::TextureView(&view),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: BindingResource::Sampler(sampler),
+                },
+            ],
+            label: Some("tonemap_bind_group"),
+        });
+        (texture, view, bind_group)
+    }
+
+    /// Match the offscreen target to the surface's new size
+    pub fn resize(&mut self, device: &Device, config: &SurfaceConfiguration) {
+        (self.texture, self.view, self.bind_group) =
+            Self::create_target(device, &self.layout, &self.buffer, &self.sampler, config);
+    }
+
+    /// The offscreen target to render the scene to
+    pub fn view(&self) -> &TextureView {
+        &self.view
+    }
+
+    /// Texture of the offscreen target, e.g. to copy into
+    pub fn texture(&self) -> &Texture {
+        &self.texture
+    }
+
+    pub fn update(&self, queue: &Queue, post_process: &PostProcess) {
+        let uniform = TonemapUniform {
+            exposure: post_process.exposure,
+            gamma: post_process.gamma,
+            saturation: post_process.saturation,
+            encode_srgb: self.encode_srgb.into(),
+        };
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[uniform]));
+    }
+
+    /// Tonemap the offscreen target into the output
+    pub fn draw(&self, encoder: &mut CommandEncoder, output: &TextureView) {
+        let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("Tonemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: output,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                    store: true,
+                },
+            })],
+            depth_stencil_attachment: None,
+        });
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
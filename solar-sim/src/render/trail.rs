@@ -0,0 +1,229 @@
+//! Fading lines behind moving bodies
+
+use std::collections::VecDeque;
+use std::mem::size_of;
+use std::ops::Range;
+
+use cgmath::Point3;
+use specs::{Component, DenseVecStorage, Entities, Join, ReadStorage, System, WriteStorage};
+use wgpu::{
+    vertex_attr_array, BindGroupLayout, BlendState, Buffer, BufferAddress, BufferDescriptor,
+    BufferUsages, ColorTargetState, DepthStencilState, Device, FragmentState,
+    PipelineLayoutDescriptor, PrimitiveState, PrimitiveTopology, Queue, RenderPass, RenderPipeline,
+    RenderPipelineDescriptor, ShaderModuleDescriptor, ShaderSource, TextureFormat,
+    VertexBufferLayout, VertexState, VertexStepMode,
+};
+
+use crate::physics::Position;
+use crate::render::texture::Texture;
+use crate::render::transparent::{Shared, Transparent};
+use crate::render::Origin;
+
+/// Trail component storing a body's recent positions in meters
+///
+/// Updated by [`RecordTrails`] system which also adds it to every body without one
+#[derive(Clone, Debug, Component)]
+pub struct Trail {
+    /// Oldest position first
+    pub points: VecDeque<Point3<f64>>,
+
+    /// Number of positions to keep
+    pub length: usize,
+
+    pub color: [f32; 3],
+}
+
+impl Trail {
+    pub fn new(length: usize, color: [f32; 3]) -> Self {
+        Self {
+            points: VecDeque::with_capacity(length),
+            length,
+            color,
+        }
+    }
+
+    /// Append a position, dropping the oldest one if the trail is full
+    pub fn push(&mut self, point: Point3<f64>) {
+        if self.length == 0 {
+            return;
+        }
+        while self.points.len() >= self.length {
+            self.points.pop_front();
+        }
+        self.points.push_back(point);
+    }
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Self::new(1000, [0.5, 0.6, 0.8])
+    }
+}
+
+/// System appending every body's position to its [`Trail`]
+///
+/// Updates [`Trail`] components
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RecordTrails;
+
+impl<'a> System<'a> for RecordTrails {
+    type SystemData = (
+        Entities<'a>,
+        ReadStorage<'a, Position>,
+        WriteStorage<'a, Trail>,
+    );
+
+    fn run(&mut self, (ent, pos, mut trail): Self::SystemData) {
+        let missing: Vec<_> = (&ent, &pos, !&trail).join().map(|(e, ..)| e).collect();
+        for e in missing {
+            trail
+                .insert(e, Trail::default())
+                .expect("The entity was just joined so it is alive");
+        }
+
+        for (pos, trail) in (&pos, &mut trail).join() {
+            trail.push(pos.0);
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TrailVertexRaw {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+/// Draws every [`Trail`] as a line strip fading out towards its oldest position
+pub struct TrailPipeline {
+    pipeline: RenderPipeline,
+    buffer: Buffer,
+    capacity: usize,
+    strips: Vec<Range<u32>>,
+    /// Newest position of each strip, i.e. where its body is
+    centers: Vec<Point3<f32>>,
+}
+
+impl TrailPipeline {
+    pub fn new(device: &Device, format: TextureFormat, camera_layout: &BindGroupLayout) -> Self {
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Trail Shader"),
+            source: ShaderSource::Wgsl(include_str!("../trail.wgsl").into()),
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Trail Pipeline Layout"),
+            bind_group_layouts: &[camera_layout],
+            push_constant_ranges: &[],
+        });
+
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Trail Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[VertexBufferLayout {
+                    array_stride: size_of::<TrailVertexRaw>() as BufferAddress,
+                    step_mode: VertexStepMode::Vertex,
+                    attributes: &vertex_attr_array![0 => Float32x3, 1 => Float32x4],
+                }],
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: Some(BlendState::ALPHA_BLENDING),
+                    write_mask: Default::default(),
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineStrip,
+                ..Default::default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                // Transparent, so they mustn't hide what is drawn after them
+                depth_write_enabled: false,
+                depth_compare: Texture::DEPTH_COMPARE,
+                stencil: Default::default(),
+                bias: Default::default(),
+            }),
+            multisample: Default::default(),
+            multiview: Default::default(),
+        });
+
+        let capacity = 1024;
+        let buffer = Self::create_buffer(device, capacity);
+
+        Self {
+            pipeline,
+            buffer,
+            capacity,
+            strips: Vec::new(),
+            centers: Vec::new(),
+        }
+    }
+
+    fn create_buffer(device: &Device, capacity: usize) -> Buffer {
+        device.create_buffer(&BufferDescriptor {
+            label: Some("Trail Buffer"),
+            size: (capacity * size_of::<TrailVertexRaw>()) as BufferAddress,
+            usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Upload all trails, growing the buffer if necessary
+    pub fn update<'a>(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        origin: &Origin,
+        trails: impl IntoIterator<Item = &'a Trail>,
+    ) {
+        let mut vertices = Vec::new();
+        self.strips.clear();
+        self.centers.clear();
+        for trail in trails {
+            if trail.points.len() < 2 {
+                continue;
+            }
+            let start = vertices.len() as u32;
+            let [r, g, b] = trail.color;
+            let newest = (trail.points.len() - 1) as f32;
+            for (age, point) in trail.points.iter().enumerate() {
+                vertices.push(TrailVertexRaw {
+                    position: origin.render(*point).into(),
+                    color: [r, g, b, age as f32 / newest],
+                });
+            }
+            self.strips.push(start..vertices.len() as u32);
+            let newest = trail.points[trail.points.len() - 1];
+            self.centers.push(origin.render(newest));
+        }
+
+        if vertices.len() > self.capacity {
+            self.capacity = vertices.len().next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity);
+        }
+        queue.write_buffer(&self.buffer, 0, bytemuck::cast_slice(&vertices));
+    }
+}
+
+impl Transparent for TrailPipeline {
+    fn centers(&self) -> &[Point3<f32>] {
+        &self.centers
+    }
+
+    fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>) {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, shared.camera, &[]);
+        render_pass.set_vertex_buffer(0, self.buffer.slice(..));
+    }
+
+    fn draw_item<'a>(&'a self, render_pass: &mut RenderPass<'a>, _: &Shared<'a>, index: usize) {
+        render_pass.draw(self.strips[index].clone(), 0..1);
+    }
+}
@@ -0,0 +1,72 @@
+//! Blended geometry drawn back to front after everything opaque
+
+use cgmath::{MetricSpace, Point3};
+use wgpu::{BindGroup, Buffer, RenderPass};
+
+/// Resources shared by the [`Transparent`] pipelines
+pub struct Shared<'a> {
+    pub camera: &'a BindGroup,
+    pub light: &'a BindGroup,
+    /// Vertex buffer, index buffer and number of indices of the planets' sphere mesh
+    pub sphere: (&'a Buffer, &'a Buffer, u32),
+}
+
+/// Pipeline drawing blended items, which have to be sorted together with every other such pipeline's
+///
+/// None of them may write depth, since the transparent pass only reads it.
+pub trait Transparent {
+    /// Center in render space of each item, in the order [`draw_item`](Self::draw_item) takes them
+    fn centers(&self) -> &[Point3<f32>];
+
+    /// Set the pipeline and everything its items share
+    fn bind<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>);
+
+    /// Draw a single item after [`bind`](Self::bind) was called
+    fn draw_item<'a>(&'a self, render_pass: &mut RenderPass<'a>, shared: &Shared<'a>, index: usize);
+}
+
+/// Item of a [`Transparent`] pipeline, which is given by its index in the slice passed to [`sort`]
+#[derive(Copy, Clone, Debug)]
+pub struct TransparentDraw {
+    pub layer: usize,
+    pub index: usize,
+}
+
+/// Every item of the pipelines from the farthest to the nearest to the camera
+pub fn sort(layers: &[&dyn Transparent], camera: Point3<f32>) -> Vec<TransparentDraw> {
+    let mut draws: Vec<_> = layers
+        .iter()
+        .enumerate()
+        .flat_map(|(layer, pipeline)| {
+            pipeline
+                .centers()
+                .iter()
+                .enumerate()
+                .map(move |(index, center)| {
+                    (TransparentDraw { layer, index }, camera.distance2(*center))
+                })
+        })
+        .collect();
+    draws.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+    draws.into_iter().map(|(draw, _)| draw).collect()
+}
+
+/// Draw the items in the order [`sort`] returned them, switching pipelines only between layers
+pub fn draw<'a>(
+    render_pass: &mut RenderPass<'a>,
+    layers: &[&'a dyn Transparent],
+    draws: &[TransparentDraw],
+    shared: &Shared<'a>,
+) {
+    let mut bound = None;
+    for draw in draws {
+        let Some(pipeline) = layers.get(draw.layer) else {
+            continue;
+        };
+        if bound != Some(draw.layer) {
+            pipeline.bind(render_pass, shared);
+            bound = Some(draw.layer);
+        }
+        pipeline.draw_item(render_pass, shared, draw.index);
+    }
+}
@@ -0,0 +1,218 @@
+//! Visualize how sensitive a body's trajectory is to errors in its initial state
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use specs::{Entities, Join, ReadStorage, System, Write};
+
+use crate::physics::{Mass, Position, Velocity};
+use crate::render::lines::{LineVertex, Lines};
+use crate::render::prediction::step;
+
+/// Layer in [`Lines`] the trajectories are drawn to
+const LAYER: &str = "trajectory_uncertainty";
+
+/// Number of points drawn per trajectory
+const SAMPLES: usize = 200;
+
+/// System propagating an ensemble of randomly perturbed copies of the simulation
+/// and drawing the resulting fan of trajectories for one body
+///
+/// The propagation is spread over many dispatches.
+/// Once an ensemble reaches the horizon, its trajectories replace the previous ones
+/// and a new ensemble is started from the current state.
+///
+/// The unperturbed trajectory is white,
+/// the others are shaded by how far they end up from the ensemble's mean:
+/// the closest half bright, up to the 90th percentile dimmer and the rest darkest.
+///
+/// Updates [`Lines`] resource whenever an ensemble is done
+#[derive(Clone, Debug)]
+pub struct TrajectoryUncertainty {
+    /// Id of the body's entity
+    pub body: u32,
+
+    /// Number of perturbed copies
+    pub members: usize,
+
+    /// Simulated seconds to propagate
+    pub horizon: f32,
+
+    /// Number of steps to reach the horizon
+    pub steps: usize,
+
+    /// Number of steps to take per dispatch
+    pub steps_per_dispatch: usize,
+
+    /// Standard deviation of the error added to each of the body's position's components in meters
+    pub position_error: f32,
+
+    /// Standard deviation of the error added to each of the body's velocity's components in meters per second
+    pub velocity_error: f32,
+
+    ensemble: Option<Ensemble>,
+    rng: u64,
+}
+
+impl TrajectoryUncertainty {
+    /// The same seed perturbs the copies the same way every run
+    pub fn new(body: u32, seed: u64) -> Self {
+        Self {
+            body,
+            members: 32,
+            horizon: 365.25 * 24.0 * 60.0 * 60.0,
+            steps: 2000,
+            steps_per_dispatch: 50,
+            position_error: 1e7,
+            velocity_error: 30.0,
+            ensemble: None,
+            // xorshift gets stuck at zero
+            rng: (seed ^ 0x2545_f491_4f6c_dd1d).max(1),
+        }
+    }
+
+    /// Pseudo random number uniformly distributed in `(0, 1]` (xorshift64)
+    fn uniform(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        ((self.rng >> 40) as f32 + 1.0) / (1u64 << 24) as f32
+    }
+
+    /// Pseudo random vector whose components are normally distributed (Box-Muller)
+    fn normal(&mut self, deviation: f32) -> Vector3<f32> {
+        let mut normal = || {
+            let (u, v) = (self.uniform(), self.uniform());
+            (-2.0 * u.ln()).sqrt() * (std::f32::consts::TAU * v).cos() * deviation
+        };
+        Vector3::new(normal(), normal(), normal())
+    }
+}
+
+/// Copies of the simulation being propagated
+#[derive(Clone, Debug)]
+struct Ensemble {
+    masses: Vec<f32>,
+    target: usize,
+    step: usize,
+
+    /// Position and velocity of every body for every member, the first member is unperturbed
+    states: Vec<Vec<(Point3<f32>, Vector3<f32>)>>,
+
+    /// Sampled positions of the target for every member
+    trajectories: Vec<Vec<Point3<f32>>>,
+}
+
+impl Ensemble {
+    /// Advance every member by one step
+    fn step(&mut self, dt: f32) {
+        for state in &mut self.states {
+            step(state, &self.masses, dt);
+        }
+        self.step += 1;
+    }
+
+    /// Convert the trajectories to shaded lines
+    fn lines(&self) -> Vec<LineVertex> {
+        let ends: Vec<_> = self
+            .trajectories
+            .iter()
+            .filter_map(|trajectory| trajectory.last().copied())
+            .collect();
+        if ends.is_empty() {
+            return Vec::new();
+        }
+        let mean = Point3::centroid(&ends);
+        let deviation = |member: usize| {
+            self.trajectories[member]
+                .last()
+                .map_or(0.0, |end| (end - mean).magnitude())
+        };
+
+        let mut ranked: Vec<_> = (1..self.trajectories.len()).collect();
+        ranked.sort_by(|a, b| deviation(*a).total_cmp(&deviation(*b)));
+
+        let mut lines = Vec::new();
+        let mut push = |trajectory: &[Point3<f32>], color: [f32; 3]| {
+            for pair in trajectory.windows(2) {
+                for position in pair {
+                    lines.push(LineVertex {
+                        position: position.cast().unwrap(),
+                        color,
+                    });
+                }
+            }
+        };
+        for (rank, &member) in ranked.iter().enumerate() {
+            let percentile = (rank + 1) as f32 / ranked.len() as f32;
+            let color = if percentile <= 0.5 {
+                [1.0, 0.6, 0.1]
+            } else if percentile <= 0.9 {
+                [0.6, 0.35, 0.05]
+            } else {
+                [0.3, 0.15, 0.03]
+            };
+            push(&self.trajectories[member], color);
+        }
+        push(&self.trajectories[0], [1.0, 1.0, 1.0]);
+        lines
+    }
+}
+
+impl<'a> System<'a> for TrajectoryUncertainty {
+    type SystemData = (
+        Write<'a, Lines>,
+        Entities<'a>,
+        ReadStorage<'a, Mass>,
+        ReadStorage<'a, Position>,
+        ReadStorage<'a, Velocity>,
+    );
+
+    fn run(&mut self, (mut lines, ent, mass, pos, vel): Self::SystemData) {
+        let mut ensemble = match self.ensemble.take() {
+            Some(ensemble) => ensemble,
+            None => {
+                let bodies: Vec<_> = (&ent, &mass, &pos, &vel).join().collect();
+                let Some(target) = bodies.iter().position(|(e, ..)| e.id() == self.body) else {
+                    lines.clear(LAYER);
+                    return;
+                };
+                let state: Vec<_> = bodies
+                    .iter()
+                    .map(|(_, _, pos, vel)| (pos.0.map(|c| c as f32), vel.0.map(|c| c as f32)))
+                    .collect();
+                let mut states = vec![state; self.members + 1];
+                for state in states.iter_mut().skip(1) {
+                    state[target].0 += self.normal(self.position_error);
+                    state[target].1 += self.normal(self.velocity_error);
+                }
+                Ensemble {
+                    masses: bodies.iter().map(|(_, mass, ..)| mass.0).collect(),
+                    target,
+                    step: 0,
+                    trajectories: states.iter().map(|state| vec![state[target].0]).collect(),
+                    states,
+                }
+            }
+        };
+
+        let dt = self.horizon / self.steps as f32;
+        let sample_every = (self.steps / SAMPLES).max(1);
+        for _ in 0..self.steps_per_dispatch {
+            if ensemble.step >= self.steps {
+                break;
+            }
+            ensemble.step(dt);
+            if ensemble.step % sample_every == 0 {
+                let target = ensemble.target;
+                for (trajectory, state) in ensemble.trajectories.iter_mut().zip(&ensemble.states) {
+                    trajectory.push(state[target].0);
+                }
+            }
+        }
+
+        if ensemble.step >= self.steps {
+            lines.set(LAYER, ensemble.lines());
+        } else {
+            self.ensemble = Some(ensemble);
+        }
+    }
+}
@@ -0,0 +1,115 @@
+//! A self-contained simulation i.e. a world and the systems running on it
+
+use specs::{Dispatcher, DispatcherBuilder, World, WorldExt};
+
+#[cfg(not(target_arch = "wasm32"))]
+use crate::crash::CrashSnapshot;
+use crate::physics::barycenter::{FindBarycenter, Recenter};
+use crate::physics::integrator::Integrator;
+use crate::physics::kepler::FollowRails;
+use crate::physics::maneuver::ExecuteManeuvers;
+use crate::physics::planets::build_planets;
+use crate::physics::radiation::RadiationPressure;
+use crate::physics::tides::TidalTorque;
+use crate::physics::timestep::FixedTimestep;
+use crate::physics::{Gravity, Spin};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::plugin::{PluginAnalysis, PluginForces};
+use crate::timer::Timer;
+
+/// A world together with its dispatcher
+///
+/// Simulations don't share any state, so several of them can run side by side in one process,
+/// e.g. to compare integrators.
+/// [`Render`] draws the world it runs in, the bodies of a second one captured into a [`Comparison`]
+/// are drawn in the right half of a [`SplitScreen`].
+///
+/// [`Render`]: crate::render::Render
+/// [`Comparison`]: crate::render::compare::Comparison
+/// [`SplitScreen`]: crate::render::split::SplitScreen
+pub struct Simulation {
+    pub world: World,
+    dispatcher: Dispatcher<'static, 'static>,
+}
+
+impl Simulation {
+    /// Dispatcher builder containing the systems every simulation needs
+    ///
+    /// Add rendering, controls, etc. on top and pass it to [`Simulation::new`].
+    ///
+    /// The physics runs after the parallel systems in fixed ticks, see [`FixedTimestep`],
+    /// followed by the [`CrashSnapshot`] of its result.
+    pub fn systems() -> DispatcherBuilder<'static, 'static> {
+        let builder = Self::compared_systems();
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = builder.with_thread_local(CrashSnapshot::default());
+        builder
+    }
+
+    /// Dispatcher builder of the systems running once per physics tick using some integrator
+    ///
+    /// The stages are separated by barriers, so the integrator's systems don't need to know the others.
+    fn physics(integrator: &dyn Integrator) -> DispatcherBuilder<'static, 'static> {
+        let mut builder = integrator
+            .before_forces(DispatcherBuilder::new())
+            .with_barrier();
+        builder.add(Spin, "spin", &[]);
+        if integrator.cpu_forces() {
+            builder.add(Gravity, "gravity", &[]);
+            builder.add(TidalTorque, "tides", &["gravity"]);
+            builder.add(RadiationPressure, "radiation", &["gravity"]);
+            #[cfg(not(target_arch = "wasm32"))]
+            builder.add(PluginForces, "plugin_forces", &["gravity"]);
+        }
+        let builder = integrator
+            .after_forces(builder.with_barrier())
+            .with_barrier()
+            .with(FollowRails, "rails", &[])
+            .with(ExecuteManeuvers, "maneuvers", &["rails"])
+            .with_barrier()
+            .with(FindBarycenter, "barycenter", &[])
+            .with(Recenter, "recenter", &["barycenter"]);
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = builder
+            .with_barrier()
+            .with(PluginAnalysis, "plugin_analysis", &[]);
+        builder
+    }
+
+    /// Dispatcher builder for a second simulation running next to the main one
+    ///
+    /// Same as [`Simulation::systems`] without the [`CrashSnapshot`], which records the main simulation only.
+    pub fn compared_systems() -> DispatcherBuilder<'static, 'static> {
+        DispatcherBuilder::new()
+            .with(Timer::default(), "timer", &[])
+            .with_thread_local(FixedTimestep::new(Self::physics))
+    }
+
+    /// Dispatcher builder for a simulation whose state is computed elsewhere
+    ///
+    /// Same as [`Simulation::systems`] without the physics,
+    /// the [`CrashSnapshot`] runs after the parallel systems receiving the state.
+    pub fn observer_systems() -> DispatcherBuilder<'static, 'static> {
+        let builder = DispatcherBuilder::new().with(Timer::default(), "timer", &[]);
+        #[cfg(not(target_arch = "wasm32"))]
+        let builder = builder.with_thread_local(CrashSnapshot::default());
+        builder
+    }
+
+    /// Create a new simulation populated with our planets
+    pub fn new(systems: DispatcherBuilder<'static, 'static>) -> Self {
+        let mut world = World::new();
+        let mut dispatcher = systems.build();
+        dispatcher.setup(&mut world);
+
+        build_planets(&mut world);
+
+        Self { world, dispatcher }
+    }
+
+    /// Run all systems once
+    pub fn step(&mut self) {
+        self.dispatcher.dispatch(&self.world);
+        self.world.maintain();
+    }
+}
@@ -0,0 +1,38 @@
+// Vertex shader
+
+struct SkyboxUniform {
+    // Inverse of the camera's view projection without its translation
+    inverse_view_proj: mat4x4<f32>,
+}
+@group(0) @binding(0)
+var<uniform> skybox: SkyboxUniform;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+}
+
+// Single triangle covering the whole target infinitely far away i.e. at a depth of 0
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = vec2<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0);
+    var out: VertexOutput;
+    out.ndc = ndc;
+    out.clip_position = vec4<f32>(ndc, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+@group(0) @binding(1)
+var t_sky: texture_cube<f32>;
+@group(0) @binding(2)
+var s_sky: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let world = skybox.inverse_view_proj * vec4<f32>(in.ndc, 1.0, 1.0);
+    let direction = world.xyz / world.w;
+    return vec4<f32>(textureSample(t_sky, s_sky, direction).rgb, 1.0);
+}
@@ -0,0 +1,60 @@
+// Vertex shader
+
+struct ScreenUniform {
+    size: vec2<f32>,
+}
+@group(0) @binding(0)
+var<uniform> screen: ScreenUniform;
+
+struct GlyphInput {
+    // Left, top, width and height in pixels
+    @location(0) rect: vec4<f32>,
+    // Left, top, right and bottom in the atlas
+    @location(1) uv: vec4<f32>,
+    @location(2) color: vec4<f32>,
+}
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+}
+
+// Two triangles per glyph
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32, glyph: GlyphInput) -> VertexOutput {
+    var corners = array<vec2<f32>, 6>(
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(0.0, 1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(0.0, 0.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(1.0, 0.0),
+    );
+    let corner = corners[index];
+    let pixel = glyph.rect.xy + corner * glyph.rect.zw;
+
+    var out: VertexOutput;
+    out.uv = mix(glyph.uv.xy, glyph.uv.zw, corner);
+    out.color = glyph.color;
+    out.clip_position = vec4<f32>(
+        pixel.x / screen.size.x * 2.0 - 1.0,
+        1.0 - pixel.y / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    return out;
+}
+
+// Fragment shader
+
+@group(0) @binding(1)
+var t_atlas: texture_2d<f32>;
+@group(0) @binding(2)
+var s_atlas: sampler;
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let coverage = textureSample(t_atlas, s_atlas, in.uv).r;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
@@ -1,5 +1,6 @@
 //! System and resource to track the time passed between dispatches
 
+use std::collections::VecDeque;
 use std::ops::Deref;
 use std::time::Duration;
 
@@ -31,9 +32,75 @@ impl Deref for Delta {
     }
 }
 
+/// Resource of statistics about the last frames
+///
+/// Updated by [`Timer`] system, the render time by [`Render`](crate::render::Render)
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    /// Durations of the last frames, oldest first
+    frames: VecDeque<Duration>,
+    started: Instant,
+    render_started: Instant,
+
+    /// Time from the start of the last dispatch until rendering started
+    ///
+    /// This is spent in the physics and other parallel systems.
+    pub physics: Duration,
+
+    /// Time the last frame took to render
+    pub render: Duration,
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        Self {
+            frames: VecDeque::with_capacity(Self::FRAMES),
+            started: Instant::now(),
+            render_started: Instant::now(),
+            physics: Duration::ZERO,
+            render: Duration::ZERO,
+        }
+    }
+}
+
+impl FrameStats {
+    /// Number of frames the statistics are taken over
+    pub const FRAMES: usize = 240;
+
+    /// Average duration of a frame
+    pub fn average(&self) -> Duration {
+        match self.frames.len() {
+            0 => Duration::ZERO,
+            len => self.frames.iter().sum::<Duration>() / len as u32,
+        }
+    }
+
+    /// Average duration of the slowest percent of frames
+    pub fn one_percent_low(&self) -> Duration {
+        let mut frames: Vec<_> = self.frames.iter().copied().collect();
+        frames.sort_unstable_by(|a, b| b.cmp(a));
+        let slowest = &frames[..(frames.len() / 100).max(1).min(frames.len())];
+        match slowest.len() {
+            0 => Duration::ZERO,
+            len => slowest.iter().sum::<Duration>() / len as u32,
+        }
+    }
+
+    /// Mark the start of rendering a frame
+    pub fn begin_render(&mut self) {
+        self.render_started = Instant::now();
+        self.physics = self.render_started.duration_since(self.started);
+    }
+
+    /// Mark the end of rendering a frame
+    pub fn end_render(&mut self) {
+        self.render = self.render_started.elapsed();
+    }
+}
+
 /// System tracking time passed between dispatches
 ///
-/// Updates [`Delta`] resource
+/// Updates [`Delta`] and [`FrameStats`] resources
 #[derive(Copy, Clone, Debug)]
 pub struct Timer(Instant);
 
@@ -44,11 +111,53 @@ impl Default for Timer {
 }
 
 impl<'a> System<'a> for Timer {
-    type SystemData = Write<'a, Delta>;
+    type SystemData = (Write<'a, Delta>, Write<'a, FrameStats>);
 
-    fn run(&mut self, mut delta: Self::SystemData) {
+    fn run(&mut self, (mut delta, mut stats): Self::SystemData) {
         let last = self.0;
         self.0 = Instant::now();
         delta.0 = self.0.duration_since(last);
+
+        if stats.frames.len() >= FrameStats::FRAMES {
+            stats.frames.pop_front();
+        }
+        stats.frames.push_back(delta.0);
+        stats.started = self.0;
+    }
+}
+
+/// Limits how often frames are drawn, independent of the present mode
+///
+/// Keeps the gpu from being pegged with [`Immediate`](wgpu::PresentMode::Immediate)
+/// or [`Mailbox`](wgpu::PresentMode::Mailbox), e.g. on battery.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Copy, Clone, Debug)]
+pub struct FrameLimiter {
+    frame_time: Duration,
+    last: Instant,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FrameLimiter {
+    pub fn new(fps: f32) -> Self {
+        Self {
+            frame_time: Duration::from_secs_f32(1.0 / fps),
+            last: Instant::now(),
+        }
+    }
+
+    /// When the next frame is due, `None` if it already is
+    pub fn next_frame(&self) -> Option<Instant> {
+        let next = self.last + self.frame_time;
+        (next > Instant::now()).then_some(next)
+    }
+
+    /// Mark the start of a frame
+    pub fn frame(&mut self) {
+        let now = Instant::now();
+        // Catch up on a late frame without drawing the next ones early
+        self.last = (self.last + self.frame_time)
+            .max(now - self.frame_time)
+            .min(now);
     }
 }
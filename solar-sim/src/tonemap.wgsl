@@ -0,0 +1,62 @@
+// Vertex shader
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+}
+
+// Single triangle covering the whole target
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.uv = uv;
+    out.clip_position = vec4<f32>(uv.x * 2.0 - 1.0, 1.0 - uv.y * 2.0, 0.0, 1.0);
+    return out;
+}
+
+// Fragment shader
+
+struct TonemapUniform {
+    exposure: f32,
+    gamma: f32,
+    saturation: f32,
+    // Non-zero if the target is linear and doesn't convert to sRGB on its own
+    encode_srgb: u32,
+}
+@group(0) @binding(0)
+var<uniform> tonemap: TonemapUniform;
+@group(0) @binding(1)
+var t_hdr: texture_2d<f32>;
+@group(0) @binding(2)
+var s_hdr: sampler;
+
+// Narkowicz's fit of the ACES filmic curve
+fn aces(x: vec3<f32>) -> vec3<f32> {
+    let a = 2.51;
+    let b = 0.03;
+    let c = 2.43;
+    let d = 0.59;
+    let e = 0.14;
+    return clamp((x * (a * x + b)) / (x * (c * x + d) + e), vec3<f32>(0.0), vec3<f32>(1.0));
+}
+
+// Linear to sRGB transfer function, which sRGB targets apply when they are written
+fn encode_srgb(linear: vec3<f32>) -> vec3<f32> {
+    let low = linear * 12.92;
+    let high = 1.055 * pow(linear, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, linear <= vec3<f32>(0.0031308));
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    let hdr = textureSample(t_hdr, s_hdr, in.uv).rgb;
+    var color = aces(hdr * tonemap.exposure);
+    let luminance = dot(color, vec3<f32>(0.2126, 0.7152, 0.0722));
+    color = clamp(mix(vec3<f32>(luminance), color, tonemap.saturation), vec3<f32>(0.0), vec3<f32>(1.0));
+    color = pow(color, vec3<f32>(1.0 / tonemap.gamma));
+    if tonemap.encode_srgb != 0u {
+        color = encode_srgb(color);
+    }
+    return vec4<f32>(color, 1.0);
+}
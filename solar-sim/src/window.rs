@@ -0,0 +1,77 @@
+//! Configuration of the main window
+
+use std::path::PathBuf;
+
+use winit::dpi::LogicalSize;
+use winit::window::{Icon, WindowBuilder};
+
+use crate::error::DynError;
+use crate::physics::{SimSpeed, SimTime};
+
+/// Seconds in a day, the unit of the title's time
+const DAY: f64 = 24.0 * 60.0 * 60.0;
+
+/// How the main window is created, see [`Options::window`](crate::Options::window)
+#[derive(Clone, Debug)]
+pub struct WindowConfig {
+    pub title: String,
+
+    /// Append the simulated days and the [`SimSpeed`] to the title while running
+    pub dynamic_title: bool,
+
+    /// Initial size in logical pixels, left to the platform if `None`
+    pub size: Option<(u32, u32)>,
+
+    /// Size in logical pixels the window can't be shrunk below
+    pub min_size: Option<(u32, u32)>,
+
+    /// Image to use as the window's icon
+    pub icon: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        Self {
+            title: "Solar Sim".to_string(),
+            dynamic_title: true,
+            size: None,
+            min_size: None,
+            icon: None,
+        }
+    }
+}
+
+impl WindowConfig {
+    /// Builder for a window as configured, failing if the icon can't be loaded
+    pub fn builder(&self) -> Result<WindowBuilder, DynError> {
+        let mut builder = WindowBuilder::new().with_title(&self.title);
+        if let Some((width, height)) = self.size {
+            builder = builder.with_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some((width, height)) = self.min_size {
+            builder = builder.with_min_inner_size(LogicalSize::new(width, height));
+        }
+        if let Some(path) = &self.icon {
+            let image = image::open(path)?.into_rgba8();
+            let (width, height) = image.dimensions();
+            builder =
+                builder.with_window_icon(Some(Icon::from_rgba(image.into_raw(), width, height)?));
+        }
+        Ok(builder)
+    }
+
+    /// Title to show for the simulation's current state
+    pub fn title(&self, time: SimTime, speed: SimSpeed) -> String {
+        if self.dynamic_title {
+            format!("{} - day {:.1} at {}x", self.title, time.0 / DAY, speed.0)
+        } else {
+            self.title.clone()
+        }
+    }
+}
+
+/// Parse a size given as `<width>x<height>`
+pub fn parse_size(size: &str) -> Option<(u32, u32)> {
+    let (width, height) = size.split_once('x')?;
+    Some((width.parse().ok()?, height.parse().ok()?))
+}